@@ -0,0 +1,134 @@
+//! Jumlah alokasi jalur /search sebelum dan sesudah snapshot dibagi lewat `Arc`.
+//!
+//! Jalankan dengan `cargo bench --bench search_snapshot`. Crate ini hanya binary, jadi bench
+//! tidak bisa memanggil `search_books` langsung; dua fungsi di bawah meniru bentuk handler
+//! lama (chunk dan query di-clone per task, buku yang cocok di-clone ke hasil) dan handler
+//! sekarang (satu snapshot `Arc`, task mengembalikan indeks, buku dipindahkan ke hasil).
+//! Yang diukur hanya alokasi, lewat global allocator yang menghitung.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const BOOKS: usize = 5_000;
+const WORKERS: usize = 8;
+
+/// Bentuk `book::Book` yang relevan untuk alokasi: field String ikut di-clone.
+#[derive(Clone)]
+#[allow(dead_code)]
+struct Book {
+    id: i32,
+    public_id: String,
+    title: String,
+    author: String,
+    category: String,
+    location: Option<String>,
+    year: Option<i32>,
+    total_copies: i32,
+    available_copies: i32,
+    version: i32,
+}
+
+/// Separuh katalog cocok dengan query "rust".
+fn catalogue() -> Vec<Book> {
+    (0..BOOKS as i32)
+        .map(|i| Book {
+            id: i,
+            public_id: format!("bk_{i:016}"),
+            title: if i % 2 == 0 {
+                format!("Rust Programming vol {i}")
+            } else {
+                format!("Python Basics {i}")
+            },
+            author: "Some Author".into(),
+            category: "Komputer".into(),
+            location: Some("A-03".into()),
+            year: Some(2020),
+            total_copies: 3,
+            available_copies: 2,
+            version: 1,
+        })
+        .collect()
+}
+
+fn matches(book: &Book, q: &str) -> bool {
+    book.title.to_lowercase().contains(q)
+}
+
+/// Sebelum: tiap task menerima salinan chunk dan query, lalu meng-clone buku yang cocok.
+fn before(books: &[Book], query: &str) -> Vec<Book> {
+    let chunk_size = books.len().div_ceil(WORKERS);
+    let mut results = Vec::new();
+    for chunk in books.chunks(chunk_size) {
+        let chunk = chunk.to_vec();
+        let q = query.to_lowercase();
+        let mut partial: Vec<Book> = chunk.iter().filter(|b| matches(b, &q)).cloned().collect();
+        results.append(&mut partial);
+    }
+    results
+}
+
+/// Sesudah: satu snapshot `Arc`, task mengembalikan indeks, buku dipindahkan ke hasil.
+fn after(books: Vec<Book>, query: &str) -> Vec<Book> {
+    let len = books.len();
+    let chunk_size = len.div_ceil(WORKERS);
+    let snapshot = Arc::new(books);
+    let query: Arc<str> = Arc::from(query.to_lowercase());
+    let mut matched = Vec::new();
+    for start in (0..len).step_by(chunk_size) {
+        let end = (start + chunk_size).min(len);
+        let books = Arc::clone(&snapshot);
+        let q = Arc::clone(&query);
+        let mut partial: Vec<usize> = (start..end).filter(|&i| matches(&books[i], &q)).collect();
+        matched.append(&mut partial);
+    }
+    let books = Arc::try_unwrap(snapshot).unwrap_or_else(|shared| (*shared).clone());
+    let mut slots: Vec<Option<Book>> = books.into_iter().map(Some).collect();
+    matched.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+/// Jalankan `f` dan kembalikan (jumlah hasil, alokasi, KiB) selama `f` berjalan.
+fn measure(f: impl FnOnce() -> Vec<Book>) -> (usize, usize, usize) {
+    let allocs = ALLOCS.load(Ordering::Relaxed);
+    let bytes = BYTES.load(Ordering::Relaxed);
+    let results = f();
+    let count = results.len();
+    let allocs = ALLOCS.load(Ordering::Relaxed) - allocs;
+    let kib = (BYTES.load(Ordering::Relaxed) - bytes) / 1024;
+    drop(results);
+    (count, allocs, kib)
+}
+
+fn main() {
+    let books = catalogue();
+    let (old_count, old_allocs, old_kib) = measure(|| before(&books, "rust"));
+    drop(books);
+
+    let books = catalogue();
+    let (new_count, new_allocs, new_kib) = measure(|| after(books, "rust"));
+
+    assert_eq!(old_count, new_count);
+    println!("{BOOKS} buku, {WORKERS} chunk, {new_count} cocok");
+    println!("sebelum: {old_allocs} alokasi, {old_kib} KiB");
+    println!("sesudah: {new_allocs} alokasi, {new_kib} KiB");
+}
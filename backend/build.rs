@@ -0,0 +1,32 @@
+// Metadata build yang ditanam ke binary untuk `GET /version`.
+// GIT_COMMIT / BUILD_TIMESTAMP dari environment menang (mis. build di CI tanpa folder .git).
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let commit = std::env::var("GIT_COMMIT").ok().or_else(git_commit).unwrap_or_default();
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={commit}");
+
+    let timestamp = std::env::var("BUILD_TIMESTAMP").ok().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs().to_string())
+            .unwrap_or_default()
+    });
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={timestamp}");
+
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=BUILD_TIMESTAMP");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+}
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git").args(["rev-parse", "HEAD"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!commit.is_empty()).then_some(commit)
+}
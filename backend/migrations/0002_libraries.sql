@@ -0,0 +1,28 @@
+-- Multi-tenancy: setiap buku, anggota, dan peminjaman milik satu perpustakaan.
+-- Data lama otomatis masuk ke perpustakaan id = 1.
+
+CREATE TABLE libraries (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO libraries (id, name) VALUES (1, 'Perpustakaan Utama');
+
+CREATE TABLE api_keys (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    key_hash CHAR(64) NOT NULL,
+    label VARCHAR(100) NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_api_keys_hash (key_hash),
+    CONSTRAINT fk_api_keys_library FOREIGN KEY (library_id) REFERENCES libraries (id)
+);
+
+ALTER TABLE books ADD COLUMN library_id INT NOT NULL DEFAULT 1 AFTER id;
+ALTER TABLE members ADD COLUMN library_id INT NOT NULL DEFAULT 1 AFTER id;
+ALTER TABLE loans ADD COLUMN library_id INT NOT NULL DEFAULT 1 AFTER id;
+
+CREATE INDEX idx_books_library ON books (library_id);
+CREATE INDEX idx_members_library ON members (library_id);
+CREATE INDEX idx_loans_library ON loans (library_id);
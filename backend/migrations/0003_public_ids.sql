@@ -0,0 +1,17 @@
+-- Public id yang tidak bisa di-enumerate (mis. bk_8f3k...), disimpan di samping primary key integer.
+
+ALTER TABLE books ADD COLUMN public_id VARCHAR(32) NULL AFTER id;
+ALTER TABLE members ADD COLUMN public_id VARCHAR(32) NULL AFTER id;
+ALTER TABLE loans ADD COLUMN public_id VARCHAR(32) NULL AFTER id;
+
+UPDATE books SET public_id = CONCAT('bk_', LOWER(HEX(RANDOM_BYTES(8)))) WHERE public_id IS NULL;
+UPDATE members SET public_id = CONCAT('mb_', LOWER(HEX(RANDOM_BYTES(8)))) WHERE public_id IS NULL;
+UPDATE loans SET public_id = CONCAT('ln_', LOWER(HEX(RANDOM_BYTES(8)))) WHERE public_id IS NULL;
+
+ALTER TABLE books MODIFY public_id VARCHAR(32) NOT NULL;
+ALTER TABLE members MODIFY public_id VARCHAR(32) NOT NULL;
+ALTER TABLE loans MODIFY public_id VARCHAR(32) NOT NULL;
+
+CREATE UNIQUE INDEX uq_books_public_id ON books (public_id);
+CREATE UNIQUE INDEX uq_members_public_id ON members (public_id);
+CREATE UNIQUE INDEX uq_loans_public_id ON loans (public_id);
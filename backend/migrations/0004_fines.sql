@@ -0,0 +1,14 @@
+-- Buku besar denda: satu baris per denda (keterlambatan, buku hilang, dll).
+
+CREATE TABLE fines (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    loan_id INT NOT NULL,
+    member_id INT NOT NULL,
+    amount BIGINT NOT NULL,
+    reason VARCHAR(20) NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    paid_at DATETIME NULL,
+    INDEX idx_fines_member (library_id, member_id),
+    INDEX idx_fines_loan (loan_id)
+);
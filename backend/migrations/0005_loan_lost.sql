@@ -0,0 +1,3 @@
+-- Status akhir "hilang": pinjaman ditutup (returned_at diisi) dan lost_at ikut diisi.
+
+ALTER TABLE loans ADD COLUMN lost_at DATETIME NULL AFTER returned_at;
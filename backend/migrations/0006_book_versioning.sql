@@ -0,0 +1,5 @@
+-- Optimistic concurrency untuk buku: version naik di setiap perubahan, updated_at dicatat handler.
+
+ALTER TABLE books
+    ADD COLUMN version INT NOT NULL DEFAULT 1,
+    ADD COLUMN updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP;
@@ -0,0 +1,10 @@
+-- Jejak aksi admin/pemeliharaan yang mengubah data secara massal.
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    action VARCHAR(64) NOT NULL,
+    details TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    INDEX idx_audit_log_library (library_id, created_at)
+);
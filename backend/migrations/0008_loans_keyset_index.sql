@@ -0,0 +1,3 @@
+-- Index untuk paging keyset GET /loans (urut borrowed_at, id menurun).
+
+CREATE INDEX idx_loans_library_borrowed ON loans (library_id, borrowed_at, id);
@@ -0,0 +1,13 @@
+-- API key dikelola lewat /admin/keys: hash KDF (argon2) + prefix untuk identifikasi.
+-- Baris lama (hanya key_hash SHA-256) tetap berlaku sampai dicabut.
+-- library_id NULL = kunci operator.
+
+ALTER TABLE api_keys
+    MODIFY library_id INT NULL,
+    MODIFY key_hash CHAR(64) NULL,
+    ADD COLUMN prefix VARCHAR(16) NULL AFTER library_id,
+    ADD COLUMN kdf_hash VARCHAR(255) NULL AFTER key_hash,
+    ADD COLUMN last_used_at DATETIME NULL,
+    ADD COLUMN revoked_at DATETIME NULL;
+
+CREATE UNIQUE INDEX uq_api_keys_prefix ON api_keys (prefix);
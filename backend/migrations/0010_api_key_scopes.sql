@@ -0,0 +1,6 @@
+-- Scope per API key (nama dipisah koma). Key yang sudah ada mendapat semua scope
+-- supaya aksesnya tidak berubah.
+
+ALTER TABLE api_keys
+    ADD COLUMN scopes VARCHAR(255) NOT NULL
+        DEFAULT 'books:read,books:write,members:read,members:write,loans:read,loans:create,loans:write,admin';
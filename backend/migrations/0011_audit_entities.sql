@@ -0,0 +1,8 @@
+-- Audit log per entitas: siapa (actor) melakukan apa pada baris mana.
+
+ALTER TABLE audit_log
+    ADD COLUMN entity VARCHAR(32) NULL AFTER action,
+    ADD COLUMN entity_id INT NULL AFTER entity,
+    ADD COLUMN actor VARCHAR(64) NOT NULL DEFAULT 'system' AFTER entity_id;
+
+CREATE INDEX idx_audit_log_entity ON audit_log (library_id, entity, entity_id);
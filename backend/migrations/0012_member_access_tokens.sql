@@ -0,0 +1,3 @@
+-- Generasi token self-service anggota; menaikkan angka ini membatalkan semua link lama.
+
+ALTER TABLE members ADD COLUMN token_generation INT NOT NULL DEFAULT 0;
@@ -0,0 +1,26 @@
+-- Donasi buku: satu batch per donatur, item-itemnya direview sebelum masuk katalog.
+
+CREATE TABLE donations (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    donor_name VARCHAR(255) NOT NULL,
+    donated_on DATE NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_donations_library (library_id)
+);
+
+CREATE TABLE donation_items (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    donation_id INT NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    author VARCHAR(255) NULL,
+    copies INT NOT NULL DEFAULT 1,
+    -- pending / accepted / rejected
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    reject_reason TEXT NULL,
+    -- Buku yang dibuat/ditambah eksemplarnya saat item diterima (provenance).
+    book_id INT NULL,
+    decided_at DATETIME NULL,
+    INDEX idx_donation_items_donation (donation_id),
+    INDEX idx_donation_items_book (book_id)
+);
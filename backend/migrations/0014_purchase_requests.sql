@@ -0,0 +1,32 @@
+-- Usulan pengadaan judul yang belum dimiliki perpustakaan.
+-- Usulan dengan judul sama (setelah normalisasi) digabung; peminatnya dicatat di
+-- purchase_request_interests (termasuk pengusul pertama).
+
+CREATE TABLE purchase_requests (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    -- normalize::search_key(title), kunci penggabungan usulan duplikat.
+    title_key VARCHAR(255) NOT NULL,
+    author VARCHAR(255) NULL,
+    requester_member_id INT NOT NULL,
+    note TEXT NULL,
+    -- pending / approved / rejected / received
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    expected_copies INT NULL,
+    reject_reason TEXT NULL,
+    book_id INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    decided_at DATETIME NULL,
+    received_at DATETIME NULL,
+    INDEX idx_purchase_requests_status (library_id, status),
+    INDEX idx_purchase_requests_title (library_id, title_key)
+);
+
+CREATE TABLE purchase_request_interests (
+    request_id INT NOT NULL,
+    member_id INT NOT NULL,
+    note TEXT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (request_id, member_id)
+);
@@ -0,0 +1,26 @@
+-- Daftar reserve mata kuliah: buku di daftar aktif dipinjamkan dengan periode lebih pendek.
+
+CREATE TABLE reserve_lists (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    course_code VARCHAR(32) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    semester VARCHAR(32) NOT NULL,
+    owner VARCHAR(255) NOT NULL,
+    -- Periode pinjam maksimum (hari) untuk buku di daftar ini.
+    loan_days INT NOT NULL,
+    renewal_limit INT NOT NULL DEFAULT 0,
+    -- Akhir semester; setelah lewat, daftar dinonaktifkan oleh job latar belakang.
+    ends_on DATE NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_reserve_lists_library (library_id, active)
+);
+
+CREATE TABLE reserve_list_books (
+    list_id INT NOT NULL,
+    book_id INT NOT NULL,
+    added_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (list_id, book_id),
+    INDEX idx_reserve_list_books_book (book_id)
+);
@@ -0,0 +1,20 @@
+-- Program membaca (mis. program liburan anak): peserta mengumpulkan buku yang selesai dibaca.
+
+CREATE TABLE programs (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    -- Jumlah buku berbeda yang harus dikembalikan untuk menyelesaikan program.
+    target_count INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_programs_library (library_id)
+);
+
+CREATE TABLE program_enrollments (
+    program_id INT NOT NULL,
+    member_id INT NOT NULL,
+    enrolled_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (program_id, member_id)
+);
@@ -0,0 +1,4 @@
+-- Perubahan email anggota menunggu konfirmasi lewat token (kedaluwarsa 48 jam).
+ALTER TABLE members
+    ADD COLUMN pending_email VARCHAR(255) NULL,
+    ADD COLUMN pending_email_expires_at DATETIME NULL;
@@ -0,0 +1,4 @@
+-- Kode rak/lokasi fisik buku (opsional), untuk inventaris per rak.
+ALTER TABLE books
+    ADD COLUMN location VARCHAR(64) NULL,
+    ADD INDEX idx_books_location (library_id, location);
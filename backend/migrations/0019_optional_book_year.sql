@@ -0,0 +1,5 @@
+-- Tahun terbit boleh kosong (mis. buku donasi tanpa keterangan tahun).
+-- Nilai pengganti yang selama ini diisi staf (0 dan 9999) diubah jadi NULL.
+ALTER TABLE books MODIFY year INT NULL;
+
+UPDATE books SET year = NULL WHERE year IN (0, 9999);
@@ -0,0 +1,25 @@
+-- Kategori jadi entitas sendiri (maksimal dua tingkat lewat parent_id).
+-- books.category tetap menyimpan nama kanonik supaya API lama tidak berubah;
+-- books.category_id yang jadi acuan, dan rename kategori ikut memperbarui nama di books.
+
+CREATE TABLE categories (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    parent_id INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_categories_name (library_id, name),
+    INDEX idx_categories_parent (parent_id)
+);
+
+ALTER TABLE books
+    ADD COLUMN category_id INT NULL,
+    ADD INDEX idx_books_category (category_id);
+
+-- Nama yang hanya beda huruf besar/kecil dianggap satu kategori (collation case-insensitive).
+INSERT IGNORE INTO categories (library_id, name)
+SELECT library_id, MIN(category) FROM books GROUP BY library_id, category;
+
+UPDATE books b
+JOIN categories c ON c.library_id = b.library_id AND c.name = b.category
+SET b.category_id = c.id, b.category = c.name;
@@ -0,0 +1,6 @@
+-- Kunci urut judul/pengarang (lihat collation::sort_key), diisi aplikasi saat tulis dan
+-- disinkronkan saat startup. NULL = belum dihitung.
+ALTER TABLE books
+    ADD COLUMN title_sort_key VARCHAR(255) NULL,
+    ADD COLUMN author_sort_key VARCHAR(255) NULL,
+    ADD INDEX idx_books_title_sort (library_id, title_sort_key);
@@ -0,0 +1,22 @@
+-- Buku besar stok: setiap perubahan available_copies dicatat sebagai satu baris append-only,
+-- ditulis di transaksi yang sama dengan perubahannya. SUM(delta) per buku = available_copies.
+
+CREATE TABLE stock_movements (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    library_id INT NOT NULL,
+    book_id INT NOT NULL,
+    delta INT NOT NULL,
+    -- loan / return / restock / lost / repair / adjustment
+    reason VARCHAR(16) NOT NULL,
+    -- Id pinjaman, item donasi, atau usulan pembelian penyebabnya (kalau ada).
+    reference_id INT NULL,
+    created_at DATETIME NOT NULL,
+    INDEX idx_stock_movements_book (book_id, id),
+    INDEX idx_stock_movements_library (library_id)
+);
+
+-- Saldo awal: stok yang sudah ada sebelum buku besar dicatat.
+INSERT INTO stock_movements (library_id, book_id, delta, reason, reference_id, created_at)
+SELECT library_id, id, available_copies, 'adjustment', NULL, UTC_TIMESTAMP()
+FROM books
+WHERE available_copies <> 0;
@@ -0,0 +1,16 @@
+-- Arsip anggota yang sudah digabung ke anggota lain (POST /members/:id/merge). Barisnya
+-- dipindah dari members ke sini, bukan dibuang, supaya data aslinya masih bisa dilacak.
+
+CREATE TABLE merged_members (
+    id INT PRIMARY KEY,
+    library_id INT NOT NULL,
+    public_id VARCHAR(32) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    joined_at DATETIME NOT NULL,
+    -- Anggota tujuan penggabungan.
+    merged_into INT NOT NULL,
+    merged_at DATETIME NOT NULL,
+    INDEX idx_merged_members_library (library_id),
+    INDEX idx_merged_members_target (merged_into)
+);
@@ -0,0 +1,44 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::scope::Scopes;
+
+/// Satu API key seperti yang ditampilkan ke operator (tanpa hash).
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ApiKeyInfo {
+    pub id: i32,
+    /// Awalan key untuk identifikasi; NULL untuk key lama sebelum /admin/keys.
+    pub prefix: Option<String>,
+    pub label: String,
+    /// NULL = kunci operator.
+    pub library_id: Option<i32>,
+    /// Kosong untuk kunci operator (akses penuh ke /admin/*).
+    #[sqlx(try_from = "String")]
+    pub scopes: Scopes,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+/// Payload `POST /admin/keys`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewApiKey {
+    pub label: String,
+    /// Wajib untuk key perpustakaan; harus kosong kalau `operator` true.
+    pub library_id: Option<i32>,
+    #[serde(default)]
+    pub operator: bool,
+    /// Nama scope, mis. `["books:read", "loans:create"]`. Default: semua scope.
+    /// Tidak boleh diisi untuk kunci operator.
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Key yang baru dibuat; `api_key` (plaintext) hanya dikirim sekali di sini.
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+    pub api_key: String,
+}
@@ -0,0 +1,153 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{Executor, FromRow, MySql, MySqlPool};
+
+use crate::auth::Tenant;
+use crate::public_id::Entity;
+
+/// Nama aksi yang disimpan di kolom `audit_log.action`.
+pub const ACTION_CREATE: &str = "create";
+pub const ACTION_UPDATE: &str = "update";
+pub const ACTION_DELETE: &str = "delete";
+pub const ACTION_MERGE: &str = "merge";
+pub const ACTION_CATEGORY_CREATE: &str = "category.create";
+pub const ACTION_CATEGORY_UPDATE: &str = "category.update";
+pub const ACTION_CATEGORY_DELETE: &str = "category.delete";
+pub const ACTION_CATEGORY_MERGE: &str = "category.merge";
+pub const ACTION_RETURN: &str = "return";
+pub const ACTION_MARK_LOST: &str = "mark_lost";
+pub const ACTION_PURGE_ORPHANS: &str = "purge_orphans";
+pub const ACTION_BOOKS_RECOUNT: &str = "books.recount";
+pub const ACTION_LOANS_EXTEND: &str = "loans.extend";
+pub const ACTION_ACCESS_LINK: &str = "access_link";
+pub const ACTION_REVOKE_ACCESS_LINKS: &str = "revoke_access_links";
+pub const ACTION_EMAIL_CONFIRM: &str = "email_confirm";
+pub const ACTION_DONATION_RECEIVE: &str = "donation.receive";
+pub const ACTION_DONATION_ACCEPT: &str = "donation.accept";
+pub const ACTION_DONATION_REJECT: &str = "donation.reject";
+pub const ACTION_PURCHASE_REQUEST_SUBMIT: &str = "purchase_request.submit";
+pub const ACTION_PURCHASE_REQUEST_APPROVE: &str = "purchase_request.approve";
+pub const ACTION_PURCHASE_REQUEST_REJECT: &str = "purchase_request.reject";
+pub const ACTION_PURCHASE_REQUEST_RECEIVE: &str = "purchase_request.receive";
+pub const ACTION_RESERVE_LIST_CREATE: &str = "reserve_list.create";
+pub const ACTION_RESERVE_LIST_ADD_BOOK: &str = "reserve_list.add_book";
+pub const ACTION_RESERVE_LIST_REMOVE_BOOK: &str = "reserve_list.remove_book";
+pub const ACTION_PROGRAM_CREATE: &str = "program.create";
+pub const ACTION_PROGRAM_ENROLL: &str = "program.enroll";
+pub const ACTION_RESTORE: &str = "restore";
+pub const ACTION_MAINTENANCE: &str = "maintenance";
+
+/// Actor untuk aksi lewat rute operator (`Operator`), yang tidak memakai API key tenant.
+pub const ACTOR_OPERATOR: &str = "operator";
+
+/// Satu entri audit sebelum ditulis. Semua handler yang mengubah data memakai ini
+/// supaya bentuk entrinya seragam.
+pub struct AuditEntry<D> {
+    pub library_id: i32,
+    pub actor: String,
+    pub action: &'static str,
+    pub entity: Option<Entity>,
+    pub entity_id: Option<i32>,
+    pub details: D,
+}
+
+impl<D: Serialize> AuditEntry<D> {
+    /// Entri untuk satu baris entitas, dilakukan oleh pemilik request `tenant`.
+    pub fn new(
+        tenant: &Tenant,
+        action: &'static str,
+        entity: Entity,
+        entity_id: i32,
+        details: D,
+    ) -> Self {
+        Self {
+            library_id: tenant.library_id,
+            actor: tenant.actor(),
+            action,
+            entity: Some(entity),
+            entity_id: Some(entity_id),
+            details,
+        }
+    }
+
+    /// Entri untuk aksi massal yang tidak menunjuk satu baris.
+    pub fn bulk(tenant: &Tenant, action: &'static str, entity: Option<Entity>, details: D) -> Self {
+        Self {
+            library_id: tenant.library_id,
+            actor: tenant.actor(),
+            action,
+            entity,
+            entity_id: None,
+            details,
+        }
+    }
+
+    /// Entri untuk aksi operator terhadap satu perpustakaan (restore, mode pemeliharaan).
+    pub fn operator(library_id: i32, action: &'static str, details: D) -> Self {
+        Self {
+            library_id,
+            actor: ACTOR_OPERATOR.to_string(),
+            action,
+            entity: None,
+            entity_id: None,
+            details,
+        }
+    }
+
+    /// Tulis entri; dipakai di dalam transaksi supaya audit ikut rollback.
+    pub async fn write<'e, E>(&self, executor: E, now: NaiveDateTime) -> Result<(), sqlx::Error>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        let details = serde_json::to_string(&self.details).unwrap_or_else(|_| "null".to_string());
+
+        sqlx::query(
+            "INSERT INTO audit_log (library_id, action, entity, entity_id, actor, details, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(self.library_id)
+        .bind(self.action)
+        .bind(self.entity.map(Entity::name))
+        .bind(self.entity_id)
+        .bind(&self.actor)
+        .bind(details)
+        .bind(now)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Seperti `write`, tapi untuk handler tanpa transaksi: gagal menulis audit
+    /// hanya dicatat ke log, tidak menggagalkan request yang sudah berhasil.
+    pub async fn write_logged(&self, pool: &MySqlPool, now: NaiveDateTime) {
+        if let Err(e) = self.write(pool, now).await {
+            eprintln!("DB error on audit_log ({}): {e}", self.action);
+        }
+    }
+}
+
+/// Satu baris `audit_log` untuk `GET /admin/audit`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditRecord {
+    pub id: i32,
+    pub action: String,
+    pub entity: Option<String>,
+    pub entity_id: Option<i32>,
+    pub actor: String,
+    /// JSON mentah seperti yang disimpan.
+    #[sqlx(try_from = "String")]
+    pub details: JsonText,
+    pub created_at: NaiveDateTime,
+}
+
+/// Kolom TEXT berisi JSON, dikirim ke client sebagai JSON (bukan string).
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct JsonText(serde_json::Value);
+
+impl From<String> for JsonText {
+    fn from(text: String) -> Self {
+        Self(serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)))
+    }
+}
@@ -0,0 +1,508 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, MatchedPath},
+    http::request::Parts,
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::mysql::MySqlRow;
+use sqlx::{MySqlConnection, Row};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use crate::i18n::Message;
+use crate::scope::{required_scope, Scopes};
+use crate::AppState;
+
+/// Header tempat client mengirim API key.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Perpustakaan (tenant) pemilik request, di-resolve dari API key.
+/// Semua query data wajib memfilter dengan `library_id` ini.
+#[derive(Debug, Clone, Copy)]
+pub struct Tenant {
+    pub library_id: i32,
+    /// API key pemilik request; None kalau jatuh ke DEFAULT_LIBRARY_ID tanpa key.
+    pub key_id: Option<i32>,
+}
+
+impl Tenant {
+    /// Pelaku untuk audit log: `api_key:<id>` atau `anonymous`.
+    pub fn actor(&self) -> String {
+        match self.key_id {
+            Some(id) => format!("api_key:{id}"),
+            None => "anonymous".to_string(),
+        }
+    }
+}
+
+/// Penanda bahwa request membawa kunci operator (ADMIN_API_KEY atau key operator di tabel).
+#[derive(Debug, Clone, Copy)]
+pub struct Operator;
+
+/// Awalan semua key yang dibuat lewat `issue_api_key`; key tanpa awalan ini adalah key lama (SHA-256).
+const KEY_MARKER: &str = "sb_";
+/// Panjang bagian acak di prefix (hex).
+const PREFIX_HEX_LEN: usize = 12;
+/// `last_used_at` paling sering ditulis sekali per interval ini per key.
+const LAST_USED_WRITE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Buat API key acak baru (64 karakter hex).
+pub fn generate_api_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Hash SHA-256 dari API key. Dipakai untuk key lama dan sebagai kunci cache;
+/// key baru disimpan dengan argon2 (`kdf_hash`).
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Bagian prefix (`sb_<12 hex>`) dari key format baru.
+fn key_prefix(key: &str) -> Option<&str> {
+    let rest = key.strip_prefix(KEY_MARKER)?;
+    let (random, _) = rest.split_once('_')?;
+    (random.len() == PREFIX_HEX_LEN).then(|| &key[..KEY_MARKER.len() + PREFIX_HEX_LEN])
+}
+
+/// Buat key baru, simpan hash argon2 + prefix-nya, lalu kembalikan `(id, plaintext)`.
+/// `library_id` None = kunci operator.
+pub async fn issue_api_key(
+    conn: &mut MySqlConnection,
+    library_id: Option<i32>,
+    label: &str,
+    scopes: Scopes,
+) -> Result<(i32, String), ApiError> {
+    let mut random = [0u8; PREFIX_HEX_LEN / 2];
+    rand::thread_rng().fill_bytes(&mut random);
+    let prefix = format!("{KEY_MARKER}{}", hex::encode(random));
+    let api_key = format!("{prefix}_{}", generate_api_key());
+
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt)
+        .map_err(|e| ApiError::internal(format!("key salt failed: {e}")))?;
+
+    let plaintext = api_key.clone();
+    let kdf_hash = tokio::task::spawn_blocking(move || {
+        Argon2::default()
+            .hash_password(plaintext.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    })
+    .await
+    .map_err(|e| ApiError::internal(format!("key hashing task failed: {e}")))?
+    .map_err(|e| ApiError::internal(format!("key hashing failed: {e}")))?;
+
+    let res = sqlx::query(
+        "INSERT INTO api_keys (library_id, prefix, kdf_hash, label, scopes) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(library_id)
+    .bind(&prefix)
+    .bind(kdf_hash)
+    .bind(label)
+    .bind(scopes.to_column())
+    .execute(conn)
+    .await?;
+
+    Ok((res.last_insert_id() as i32, api_key))
+}
+
+/// Pemilik dan izin satu key yang sudah terverifikasi.
+#[derive(Debug, Clone, Copy)]
+struct KeyGrant {
+    id: i32,
+    /// None = kunci operator.
+    library_id: Option<i32>,
+    scopes: Scopes,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedKey {
+    grant: KeyGrant,
+    last_used_written: Option<Instant>,
+}
+
+/// Cache key yang sudah lolos verifikasi (argon2 mahal), di-index hash SHA-256 key.
+/// Dikosongkan setiap kali key dibuat/dicabut supaya pencabutan langsung berlaku.
+#[derive(Debug, Default)]
+pub struct KeyCache {
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    /// Naik setiap `invalidate`; hasil lookup DB yang dimulai sebelum invalidate
+    /// tidak boleh dimasukkan lagi (bisa saja key-nya baru dicabut).
+    generation: u64,
+    map: HashMap<String, CachedKey>,
+}
+
+impl KeyCache {
+    pub fn invalidate(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.generation += 1;
+        entries.map.clear();
+    }
+
+    /// Entri cache plus generasi saat dibaca.
+    fn get(&self, cache_key: &str) -> (Option<CachedKey>, u64) {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        (entries.map.get(cache_key).copied(), entries.generation)
+    }
+
+    /// Simpan hasil lookup DB, kecuali cache sudah di-invalidate sejak `generation` dibaca.
+    fn insert(&self, cache_key: String, entry: CachedKey, generation: u64) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.generation == generation {
+            entries.map.insert(cache_key, entry);
+        }
+    }
+
+    /// Tandai `last_used_at` sudah ditulis sekarang; True kalau memang sudah waktunya menulis.
+    /// Entri yang sudah hilang dari cache (dicabut) tidak dibuat ulang.
+    fn claim_last_used_write(&self, cache_key: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(entry) = entries.map.get_mut(cache_key) else {
+            return false;
+        };
+        let due = entry
+            .last_used_written
+            .is_none_or(|at| at.elapsed() >= LAST_USED_WRITE_INTERVAL);
+        if due {
+            entry.last_used_written = Some(Instant::now());
+        }
+        due
+    }
+}
+
+/// Cari key di cache atau DB; `None` kalau tidak dikenal/sudah dicabut.
+async fn authenticate(state: &AppState, key: &str) -> Result<Option<KeyGrant>, ApiError> {
+    let cache_key = hash_api_key(key);
+    let (cached, generation) = state.keys.get(&cache_key);
+
+    let grant = match cached {
+        Some(entry) => entry.grant,
+        None => match lookup_key(state, key, &cache_key).await? {
+            Some(grant) => {
+                let entry = CachedKey {
+                    grant,
+                    last_used_written: None,
+                };
+                state.keys.insert(cache_key.clone(), entry, generation);
+                grant
+            }
+            None => return Ok(None),
+        },
+    };
+
+    // Catat last_used_at tanpa menahan request, paling sering sekali per interval.
+    if state.keys.claim_last_used_write(&cache_key) {
+        let pool = state.pool.clone();
+        let now = state.clock.now_naive();
+        tokio::spawn(async move {
+            if let Err(e) = sqlx::query("UPDATE api_keys SET last_used_at = ? WHERE id = ?")
+                .bind(now)
+                .bind(grant.id)
+                .execute(&pool)
+                .await
+            {
+                eprintln!("DB error on api key last_used_at: {e}");
+            }
+        });
+    }
+
+    Ok(Some(grant))
+}
+
+/// Verifikasi key ke DB: format baru lewat prefix + argon2, key lama lewat SHA-256.
+async fn lookup_key(
+    state: &AppState,
+    key: &str,
+    sha256: &str,
+) -> Result<Option<KeyGrant>, ApiError> {
+    let Some(prefix) = key_prefix(key) else {
+        let row = sqlx::query(
+            "SELECT id, library_id, scopes FROM api_keys WHERE key_hash = ? AND revoked_at IS NULL",
+        )
+        .bind(sha256)
+        .fetch_optional(&state.pool)
+        .await?;
+        return Ok(row.as_ref().map(grant_from));
+    };
+
+    let row = sqlx::query(
+        "SELECT id, library_id, scopes, kdf_hash FROM api_keys
+         WHERE prefix = ? AND revoked_at IS NULL",
+    )
+    .bind(prefix)
+    .fetch_optional(&state.pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let stored: String = row.get("kdf_hash");
+    let candidate = key.to_string();
+    let valid = tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&stored)
+            .map(|hash| Argon2::default().verify_password(candidate.as_bytes(), &hash).is_ok())
+            .unwrap_or(false)
+    })
+    .await
+    .unwrap_or(false);
+
+    Ok(valid.then(|| grant_from(&row)))
+}
+
+fn grant_from(row: &MySqlRow) -> KeyGrant {
+    KeyGrant {
+        id: row.get("id"),
+        library_id: row.get("library_id"),
+        scopes: Scopes::from(row.get::<String, _>("scopes")),
+    }
+}
+
+/// Tolak request kalau key tidak punya scope yang dibutuhkan route ini (lihat `ROUTES` di main).
+fn check_scope(parts: &Parts, scopes: Scopes) -> Result<(), ApiError> {
+    let path = parts
+        .extensions
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or_else(|| parts.uri.path());
+
+    match required_scope(&parts.method, path) {
+        Some(scope) if scopes.contains(scope) => Ok(()),
+        Some(scope) => Err(ApiError::forbidden(
+            Message::new("auth.missing_scope").param("scope", scope),
+        )
+        .with_details(serde_json::json!({ "missing_scope": scope.name() }))),
+        None => Err(ApiError::forbidden(format!(
+            "no scope is defined for {} {path}",
+            parts.method
+        ))),
+    }
+}
+
+/// Deployment satu perpustakaan boleh jalan tanpa API key, tapi hanya dengan
+/// ANONYMOUS_SCOPES (tanpa `admin`), supaya tanpa key tidak lebih leluasa dari key sempit.
+fn anonymous_tenant(parts: &Parts, config: &AppConfig) -> Result<Tenant, ApiError> {
+    let library_id = config
+        .default_library_id
+        .ok_or_else(|| ApiError::unauthorized(Message::new("auth.missing_key")))?;
+    check_scope(parts, config.anonymous_scopes)?;
+    Ok(Tenant {
+        library_id,
+        key_id: None,
+    })
+}
+
+fn api_key_from(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Tenant {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(key) = api_key_from(parts) else {
+            return anonymous_tenant(parts, &state.config);
+        };
+
+        match authenticate(state, key).await? {
+            Some(KeyGrant {
+                id,
+                library_id: Some(library_id),
+                scopes,
+            }) => {
+                check_scope(parts, scopes)?;
+                Ok(Tenant {
+                    library_id,
+                    key_id: Some(id),
+                })
+            }
+            Some(_) => Err(ApiError::forbidden(Message::new("auth.operator_key_on_tenant_route"))),
+            None => Err(ApiError::unauthorized(Message::new("auth.invalid_key"))),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Operator {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(key) = api_key_from(parts) else {
+            return Err(ApiError::unauthorized(Message::new("auth.missing_key")));
+        };
+
+        // ADMIN_API_KEY dari env tetap berlaku sebagai kunci bootstrap,
+        // supaya kunci operator pertama bisa dibuat lewat /admin/keys.
+        // Bandingkan hash supaya waktu perbandingan tidak bocor per karakter.
+        if let Some(expected) = state.config.admin_api_key.as_deref() {
+            if hash_api_key(key) == hash_api_key(expected) {
+                return Ok(Operator);
+            }
+        }
+
+        match authenticate(state, key).await? {
+            Some(KeyGrant { library_id: None, .. }) => Ok(Operator),
+            Some(_) => Err(ApiError::forbidden(Message::new("auth.not_admin"))),
+            None => Err(ApiError::unauthorized(Message::new("auth.invalid_key"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scope::Scope;
+    use axum::http::{Method, Request, StatusCode};
+
+    fn parts(method: Method, path: &str) -> Parts {
+        Request::builder().method(method).uri(path).body(()).unwrap().into_parts().0
+    }
+
+    fn scopes(names: &[&str]) -> Scopes {
+        Scopes::parse(names).unwrap()
+    }
+
+    fn entry() -> CachedKey {
+        CachedKey {
+            grant: KeyGrant {
+                id: 7,
+                library_id: Some(1),
+                scopes: Scopes::all(),
+            },
+            last_used_written: None,
+        }
+    }
+
+    #[test]
+    fn lookup_started_before_invalidate_is_not_cached() {
+        let cache = KeyCache::default();
+        let (cached, generation) = cache.get("k");
+        assert!(cached.is_none());
+
+        // revoke_key terjadi selama lookup DB berjalan.
+        cache.invalidate();
+        cache.insert("k".into(), entry(), generation);
+        assert!(cache.get("k").0.is_none());
+
+        let (_, generation) = cache.get("k");
+        cache.insert("k".into(), entry(), generation);
+        assert_eq!(cache.get("k").0.map(|e| e.grant.id), Some(7));
+    }
+
+    #[test]
+    fn last_used_write_does_not_recreate_revoked_entry() {
+        let cache = KeyCache::default();
+        let (_, generation) = cache.get("k");
+        cache.insert("k".into(), entry(), generation);
+        assert!(cache.claim_last_used_write("k"));
+        assert!(!cache.claim_last_used_write("k"));
+
+        cache.invalidate();
+        assert!(!cache.claim_last_used_write("k"));
+        assert!(cache.get("k").0.is_none());
+    }
+
+    #[test]
+    fn narrow_key_is_rejected_outside_its_scope() {
+        let read_only = scopes(&["books:read"]);
+        assert!(check_scope(&parts(Method::GET, "/books"), read_only).is_ok());
+        assert!(check_scope(&parts(Method::GET, "/search"), read_only).is_ok());
+
+        for (method, path) in [
+            (Method::POST, "/books"),
+            (Method::GET, "/members"),
+            (Method::POST, "/loans"),
+            (Method::GET, "/admin/stats"),
+        ] {
+            let err = check_scope(&parts(method.clone(), path), read_only).unwrap_err();
+            assert_eq!(err.status, StatusCode::FORBIDDEN, "{method} {path}");
+            let needed = required_scope(&method, path).unwrap();
+            assert_eq!(err.details, Some(serde_json::json!({ "missing_scope": needed.name() })));
+        }
+    }
+
+    #[test]
+    fn loans_create_does_not_grant_other_loan_routes() {
+        let create_only = scopes(&["loans:create"]);
+        assert!(check_scope(&parts(Method::POST, "/loans"), create_only).is_ok());
+        assert!(check_scope(&parts(Method::GET, "/loans"), create_only).is_err());
+    }
+
+    #[test]
+    fn admin_routes_need_admin_even_with_every_other_scope() {
+        let names: Vec<&str> =
+            Scope::ALL.into_iter().filter(|s| *s != Scope::Admin).map(Scope::name).collect();
+        let key = scopes(&names);
+        assert!(check_scope(&parts(Method::GET, "/admin/stats"), key).is_err());
+        assert!(check_scope(&parts(Method::GET, "/admin/stats"), scopes(&["admin"])).is_ok());
+    }
+
+    fn keyless_config(anonymous_scopes: &str) -> AppConfig {
+        let mut config = AppConfig::from_env();
+        config.default_library_id = Some(1);
+        config.anonymous_scopes = Scopes::anonymous(anonymous_scopes);
+        config
+    }
+
+    #[test]
+    fn keyless_request_cannot_reach_admin_routes() {
+        // Walaupun ANONYMOUS_SCOPES menyebut admin, tanpa key /admin/* tetap ditolak.
+        let config = keyless_config("admin,books:read");
+        assert!(!config.anonymous_scopes.contains(Scope::Admin));
+        for (method, path) in [
+            (Method::DELETE, "/admin/orphaned-loans"),
+            (Method::GET, "/admin/audit"),
+            (Method::GET, "/admin/stats"),
+        ] {
+            let err = anonymous_tenant(&parts(method.clone(), path), &config).unwrap_err();
+            assert_eq!(err.status, StatusCode::FORBIDDEN, "{method} {path}");
+        }
+        let tenant = anonymous_tenant(&parts(Method::GET, "/books"), &config).unwrap();
+        assert_eq!((tenant.library_id, tenant.key_id), (1, None));
+    }
+
+    #[test]
+    fn keyless_request_is_read_only_by_default() {
+        let config = keyless_config(crate::scope::ANONYMOUS_DEFAULT);
+        assert!(anonymous_tenant(&parts(Method::GET, "/loans"), &config).is_ok());
+        assert!(anonymous_tenant(&parts(Method::POST, "/books"), &config).is_err());
+        assert!(anonymous_tenant(&parts(Method::POST, "/loans"), &config).is_err());
+    }
+
+    #[test]
+    fn keyless_request_without_default_library_is_unauthorized() {
+        let mut config = keyless_config(crate::scope::ANONYMOUS_DEFAULT);
+        config.default_library_id = None;
+        let err = anonymous_tenant(&parts(Method::GET, "/books"), &config).unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn unregistered_route_is_rejected_even_for_full_key() {
+        let err = check_scope(&parts(Method::GET, "/not-registered"), Scopes::all()).unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,339 @@
+use std::io::{self, Write};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use zstd::stream::raw::{Decoder, InBuffer, Operation, OutBuffer};
+
+use crate::book::Book;
+use crate::fine::Fine;
+use crate::loan::Loan;
+use crate::member::Member;
+
+/// Versi format backup; restore menolak payload dengan versi lain.
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Jumlah baris per INSERT saat restore.
+pub const RESTORE_CHUNK_SIZE: usize = 500;
+
+/// Snapshot seluruh data satu perpustakaan (`GET /admin/backup`, `POST /admin/restore`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    pub library_id: i32,
+    pub created_at: NaiveDateTime,
+    pub books: Vec<Book>,
+    pub members: Vec<Member>,
+    pub loans: Vec<Loan>,
+    pub fines: Vec<Fine>,
+}
+
+/// Jumlah baris yang dipulihkan per tabel.
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub library_id: i32,
+    pub books: usize,
+    pub members: usize,
+    pub loans: usize,
+    pub fines: usize,
+}
+
+/// Nilai `?format=` untuk backup/restore NDJSON terkompresi zstd.
+pub const FORMAT_NDJSON_ZST: &str = "ndjson.zst";
+pub const ZST_CONTENT_TYPE: &str = "application/zstd";
+
+/// Level kompresi zstd untuk backup (default library = 3).
+const ZSTD_LEVEL: i32 = 3;
+
+/// Output terkompresi dikirim ke client per ~64 KB.
+const ZST_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Satu baris NDJSON yang lebih panjang dari ini ditolak saat restore (bukan backup yang sah).
+pub const MAX_LINE_BYTES: usize = 1024 * 1024;
+
+/// Satu baris backup NDJSON: `header` dulu, lalu books, members, loans, fines (urutan ini
+/// wajib karena foreign key; restore menolak record yang mundur urutannya).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackupRecord {
+    Header {
+        version: u32,
+        library_id: i32,
+        created_at: NaiveDateTime,
+    },
+    Book(Book),
+    Member(Member),
+    Loan(Loan),
+    Fine(Fine),
+}
+
+impl BackupRecord {
+    /// Posisi record di urutan backup.
+    pub fn rank(&self) -> u8 {
+        match self {
+            Self::Header { .. } => 0,
+            Self::Book(_) => 1,
+            Self::Member(_) => 2,
+            Self::Loan(_) => 3,
+            Self::Fine(_) => 4,
+        }
+    }
+}
+
+/// Encoder backup NDJSON → zstd. Output yang sudah terkompresi diambil per chunk supaya
+/// bisa langsung dikirim tanpa menahan seluruh file di memori.
+pub struct ZstLineWriter {
+    encoder: zstd::stream::write::Encoder<'static, Vec<u8>>,
+}
+
+impl ZstLineWriter {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            encoder: zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)?,
+        })
+    }
+
+    /// Tulis satu record; hasilnya chunk terkompresi kalau buffer sudah cukup besar.
+    pub fn push(&mut self, record: &BackupRecord) -> io::Result<Option<Vec<u8>>> {
+        serde_json::to_writer(&mut self.encoder, record)?;
+        self.encoder.write_all(b"\n")?;
+        if self.encoder.get_ref().len() < ZST_CHUNK_BYTES {
+            return Ok(None);
+        }
+        Ok(Some(std::mem::take(self.encoder.get_mut())))
+    }
+
+    /// Tutup frame zstd; hasilnya sisa output terakhir.
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        self.encoder.finish()
+    }
+}
+
+/// Decoder zstd → baris NDJSON untuk restore. Input diumpankan per chunk body request lewat
+/// `push`, lalu dikuras dengan `next_lines` yang mendekompresi paling banyak ZST_CHUNK_BYTES
+/// per panggilan; jadi chunk kecil yang mengembang sangat besar (zip bomb) tidak pernah
+/// didekompresi sekaligus. Yang ditahan di memori hanya chunk input, satu buffer output,
+/// dan baris yang belum lengkap.
+pub struct ZstLineReader {
+    decoder: Decoder<'static>,
+    input: Vec<u8>,
+    input_pos: usize,
+    output: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl ZstLineReader {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            decoder: Decoder::new()?,
+            input: Vec::new(),
+            input_pos: 0,
+            output: vec![0; ZST_CHUNK_BYTES],
+            pending: Vec::new(),
+        })
+    }
+
+    /// Tambah input terkompresi; kuras dengan `next_lines` sebelum `push` berikutnya.
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.input.drain(..self.input_pos);
+        self.input_pos = 0;
+        self.input.extend_from_slice(chunk);
+    }
+
+    /// Dekompresi satu langkah (output paling banyak ZST_CHUNK_BYTES) lalu kembalikan baris
+    /// yang sudah lengkap. None kalau input sudah habis dan decoder tidak punya output lagi.
+    pub fn next_lines(&mut self) -> io::Result<Option<Vec<Vec<u8>>>> {
+        let mut input = InBuffer::around(&self.input[self.input_pos..]);
+        let mut output = OutBuffer::around(&mut self.output[..]);
+        self.decoder.run(&mut input, &mut output)?;
+        let (consumed, produced) = (input.pos(), output.pos());
+        self.input_pos += consumed;
+        if consumed == 0 && produced == 0 {
+            return Ok(None);
+        }
+        self.pending.extend_from_slice(&self.output[..produced]);
+        self.take_lines().map(Some)
+    }
+
+    /// Akhir input: baris terakhir boleh tanpa `\n`.
+    pub fn finish(mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut lines = Vec::new();
+        while let Some(more) = self.next_lines()? {
+            lines.extend(more);
+        }
+        if !self.pending.iter().all(u8::is_ascii_whitespace) {
+            lines.push(std::mem::take(&mut self.pending));
+        }
+        Ok(lines)
+    }
+
+    fn take_lines(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let buffer = &mut self.pending;
+        let Some(last_newline) = buffer.iter().rposition(|b| *b == b'\n') else {
+            if buffer.len() > MAX_LINE_BYTES {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "backup line too long"));
+            }
+            return Ok(Vec::new());
+        };
+        let rest = buffer.split_off(last_newline + 1);
+        let complete = std::mem::replace(buffer, rest);
+        Ok(complete
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .map(<[u8]>::to_vec)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{BookId, LoanId, MemberId};
+
+    fn at(day: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2025, 1, day)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap()
+    }
+
+    fn fixture(books: i32) -> Vec<BackupRecord> {
+        let mut records = vec![BackupRecord::Header {
+            version: BACKUP_VERSION,
+            library_id: 7,
+            created_at: at(1),
+        }];
+        records.extend((1..=books).map(|i| {
+            BackupRecord::Book(Book {
+                id: BookId(i),
+                public_id: format!("B{i}"),
+                title: format!("Buku ke-{i} — édition spéciale"),
+                author: "Pramoedya".into(),
+                category: "Novel".into(),
+                year: (i % 2 == 0).then_some(1980),
+                total_copies: 2,
+                available_copies: 1,
+                version: 1,
+                updated_at: at(2),
+                location: None,
+            })
+        }));
+        records.push(BackupRecord::Member(Member {
+            id: MemberId(1),
+            public_id: "M1".into(),
+            name: "Siti".into(),
+            email: "siti@example.com".into(),
+            joined_at: at(3),
+        }));
+        records.push(BackupRecord::Loan(Loan {
+            id: LoanId(1),
+            public_id: "L1".into(),
+            book_id: BookId(1),
+            member_id: MemberId(1),
+            borrowed_at: at(4),
+            due_at: at(11),
+            returned_at: Some(at(12)),
+            lost_at: None,
+        }));
+        records.push(BackupRecord::Fine(Fine {
+            id: 1,
+            loan_id: LoanId(1),
+            member_id: MemberId(1),
+            amount: 1000,
+            reason: "late".into(),
+            created_at: at(12),
+            paid_at: None,
+            amount_formatted: String::new(),
+        }));
+        records
+    }
+
+    fn compress(records: &[BackupRecord]) -> Vec<u8> {
+        let mut writer = ZstLineWriter::new().unwrap();
+        let mut out = Vec::new();
+        for record in records {
+            if let Some(chunk) = writer.push(record).unwrap() {
+                out.extend(chunk);
+            }
+        }
+        out.extend(writer.finish().unwrap());
+        out
+    }
+
+    fn decompress(bytes: &[u8], chunk_size: usize) -> io::Result<Vec<BackupRecord>> {
+        let mut reader = ZstLineReader::new()?;
+        let mut lines = Vec::new();
+        for chunk in bytes.chunks(chunk_size) {
+            reader.push(chunk);
+            while let Some(more) = reader.next_lines()? {
+                let size: usize = more.iter().map(Vec::len).sum();
+                assert!(size <= ZST_CHUNK_BYTES + MAX_LINE_BYTES);
+                lines.extend(more);
+            }
+        }
+        lines.extend(reader.finish()?);
+        Ok(lines.iter().map(|l| serde_json::from_slice(l).unwrap()).collect())
+    }
+
+    fn counts(records: &[BackupRecord]) -> [usize; 5] {
+        let mut counts = [0; 5];
+        records.iter().for_each(|r| counts[r.rank() as usize] += 1);
+        counts
+    }
+
+    #[test]
+    fn round_trip_preserves_records() {
+        let records = fixture(2_000);
+        let bytes = compress(&records);
+        for chunk_size in [1, 7, 4096, bytes.len()] {
+            let restored = decompress(&bytes, chunk_size).unwrap();
+            assert_eq!(counts(&restored), [1, 2_000, 1, 1, 1]);
+            assert_eq!(
+                serde_json::to_value(&restored[0]).unwrap(),
+                serde_json::to_value(&records[0]).unwrap()
+            );
+            for i in [1, 1_000, 2_000, 2_001, 2_002, 2_003] {
+                assert_eq!(
+                    serde_json::to_value(&restored[i]).unwrap(),
+                    serde_json::to_value(&records[i]).unwrap(),
+                    "record {i}, chunk size {chunk_size}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn highly_compressible_chunk_is_decoded_in_bounded_steps() {
+        // ~32 MB baris pendek yang terkompresi jadi beberapa KB.
+        let line = b"{\"type\":\"header\"}\n";
+        let plain: Vec<u8> = line.repeat(32 * 1024 * 1024 / line.len());
+        let bytes = zstd::encode_all(&plain[..], ZSTD_LEVEL).unwrap();
+        assert!(bytes.len() < ZST_CHUNK_BYTES);
+
+        let mut reader = ZstLineReader::new().unwrap();
+        reader.push(&bytes);
+        let mut steps = 0;
+        let mut total = 0;
+        while let Some(lines) = reader.next_lines().unwrap() {
+            let size: usize = lines.iter().map(|l| l.len() + 1).sum();
+            assert!(size <= ZST_CHUNK_BYTES + line.len());
+            total += lines.len();
+            steps += 1;
+        }
+        total += reader.finish().unwrap().len();
+        assert_eq!(total, plain.len() / line.len());
+        assert!(steps >= plain.len() / ZST_CHUNK_BYTES);
+    }
+
+    #[test]
+    fn overlong_line_is_rejected() {
+        let plain = vec![b'a'; MAX_LINE_BYTES * 2];
+        let bytes = zstd::encode_all(&plain[..], ZSTD_LEVEL).unwrap();
+        let err = decompress(&bytes, bytes.len()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn garbage_is_not_a_zstd_stream() {
+        assert!(decompress(b"definitely not zstd", 4).is_err());
+    }
+}
@@ -1,22 +1,210 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use chrono::NaiveDateTime;
+
+use crate::i18n::Message;
+use crate::json_body::nullable;
+use crate::ids::BookId;
+use crate::normalize;
+
+/// Rentang tahun terbit yang diterima; di luar ini hampir pasti salah ketik atau nilai pengganti.
+const MIN_YEAR: i32 = 1000;
+const MAX_YEAR: i32 = 2100;
+
+/// Tahun kosong boleh, tapi kalau diisi harus masuk akal.
+fn validate_year(year: Option<i32>) -> Result<Option<i32>, Message> {
+    match year {
+        Some(year) if !(MIN_YEAR..=MAX_YEAR).contains(&year) => Err(Message::new("validation.year")
+            .param("field", "year")
+            .param("min", MIN_YEAR)
+            .param("max", MAX_YEAR)),
+        other => Ok(other),
+    }
+}
+
+/// Nama field `Book` yang boleh dipilih lewat `?fields=`.
+pub const FIELDS: &[&str] = &[
+    "id",
+    "public_id",
+    "title",
+    "author",
+    "category",
+    "year",
+    "total_copies",
+    "available_copies",
+    "version",
+    "updated_at",
+    "location",
+];
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Book {
-    pub id: i32,
+    pub id: BookId,
+    pub public_id: String,
     pub title: String,
     pub author: String,
     pub category: String,
-    pub year: i32,
+    /// None = tahun terbit tidak diketahui.
+    pub year: Option<i32>,
     pub total_copies: i32,
     pub available_copies: i32,
+    /// Naik setiap kali baris berubah; dipakai untuk If-Match / expected_version.
+    pub version: i32,
+    pub updated_at: NaiveDateTime,
+    /// Kode rak, mis. `A-03`. None = belum ditempatkan.
+    pub location: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct NewBook {
     pub title: String,
     pub author: String,
     pub category: String,
-    pub year: i32,
+    #[serde(default)]
+    pub year: Option<i32>,
     pub total_copies: i32, // input dari user
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+impl NewBook {
+    /// Trim + ringkas whitespace semua field teks; tolak yang jadi kosong.
+    pub fn normalized(self) -> Result<Self, Message> {
+        Ok(Self {
+            title: normalize::required("title", &self.title)?,
+            author: normalize::required("author", &self.author)?,
+            category: normalize::required("category", &self.category)?,
+            year: validate_year(self.year)?,
+            location: self
+                .location
+                .map(|l| normalize::collapse_whitespace(&l))
+                .filter(|l| !l.is_empty()),
+            ..self
+        })
+    }
+}
+
+/// Payload PUT/PATCH /books/:id. Field yang tidak dikirim tidak diubah.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateBook {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub category: Option<String>,
+    /// `null` mengosongkan tahun terbit.
+    #[serde(default, deserialize_with = "nullable")]
+    pub year: Option<Option<i32>>,
+    pub total_copies: Option<i32>,
+    /// String kosong menghapus lokasi.
+    pub location: Option<String>,
+    /// Versi terakhir yang dilihat client (alternatif header If-Match).
+    pub expected_version: Option<i32>,
+}
+
+impl UpdateBook {
+    /// Sama seperti `NewBook::normalized`, hanya untuk field yang dikirim.
+    pub fn normalized(self) -> Result<Self, Message> {
+        let field = |name: &str, value: Option<String>| {
+            value.map(|v| normalize::required(name, &v)).transpose()
+        };
+        Ok(Self {
+            title: field("title", self.title)?,
+            author: field("author", self.author)?,
+            category: field("category", self.category)?,
+            year: self.year.map(validate_year).transpose()?,
+            location: self.location.map(|l| normalize::collapse_whitespace(&l)),
+            ..self
+        })
+    }
+}
+
+/// Hasil `GET /books/recategorize/preview`: buku yang akan terkena recategorize.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecategorizePreview {
+    pub from_category: String,
+    pub count: usize,
+    pub books: Vec<Book>,
+}
+
+/// Satu saran pembelian dari `GET /books/reorder-suggestions`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReorderSuggestion {
+    pub book: Book,
+    pub suggested_additional_copies: i32,
+    pub reason: String,
+}
+
+/// Heuristik sederhana: buku yang dipinjam minimal `min_loans` kali dalam jendela waktu
+/// dianggap laris, lalu ditambah eksemplarnya sampai rasio tersedia (dengan jumlah yang
+/// sedang dipinjam sekarang) minimal `min_available_ratio`. `None` kalau tidak perlu tambahan.
+pub fn reorder_suggestion(
+    book: Book,
+    recent_loans: i64,
+    window_days: u32,
+    min_loans: i64,
+    min_available_ratio: f64,
+) -> Option<ReorderSuggestion> {
+    if recent_loans < min_loans {
+        return None;
+    }
+    let on_loan = (book.total_copies - book.available_copies).max(0);
+    // available / total >= ratio  <=>  total >= on_loan / (1 - ratio)
+    let needed_total = (f64::from(on_loan) / (1.0 - min_available_ratio)).ceil() as i32;
+    let additional = needed_total - book.total_copies;
+    if additional <= 0 {
+        return None;
+    }
+    let reason = format!(
+        "{recent_loans} loans in the last {window_days} days, {} of {} copies available",
+        book.available_copies, book.total_copies
+    );
+    Some(ReorderSuggestion {
+        book,
+        suggested_additional_copies: additional,
+        reason,
+    })
+}
+
+/// Respons `GET /books/:id/next-available`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NextAvailable {
+    pub book_id: BookId,
+    pub available_copies: i32,
+    /// Jatuh tempo pinjaman aktif paling awal; null kalau stok masih ada (atau tidak ada
+    /// pinjaman aktif sama sekali).
+    pub next_available_at: Option<NaiveDateTime>,
+}
+
+/// Satu hasil `GET /books/:id/similar`: data buku plus skor cosine (0..1).
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarBook {
+    #[serde(flatten)]
+    pub book: Book,
+    pub similarity: f64,
+}
+
+/// Satu kode rak beserta jumlah judul dan eksemplarnya, hasil `GET /locations`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct LocationCount {
+    pub location: String,
+    pub books: i64,
+    pub total_copies: i64,
+    pub available_copies: i64,
+}
+
+/// Payload `POST /books/availability`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AvailabilityQuery {
+    pub ids: Vec<BookId>,
+}
+
+/// Stok tersedia satu buku, urut sesuai `ids` di request.
+#[derive(Debug, Clone, Serialize)]
+pub struct BookAvailability {
+    pub id: BookId,
+    /// False kalau id tidak ada di perpustakaan ini; `available_copies` jadi null.
+    pub found: bool,
+    pub available_copies: Option<i32>,
 }
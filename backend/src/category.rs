@@ -0,0 +1,114 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySqlConnection};
+
+use crate::i18n::Message;
+use crate::json_body::nullable;
+use crate::normalize;
+
+/// Kolom untuk `query_as::<_, Category>`; `books` dihitung dari tabel books.
+pub const SELECT: &str = "SELECT c.id, c.name, c.parent_id, c.created_at,
+            (SELECT COUNT(*) FROM books b WHERE b.category_id = c.id) AS books
+     FROM categories c";
+
+/// Satu baris di tabel `categories`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    /// Kategori induk; hanya kategori tingkat atas yang boleh jadi induk.
+    pub parent_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+    /// Jumlah buku di kategori ini (tidak termasuk sub-kategori).
+    pub books: i64,
+}
+
+/// Payload `POST /categories`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewCategory {
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+}
+
+impl NewCategory {
+    pub fn normalized(self) -> Result<Self, Message> {
+        Ok(Self {
+            name: normalize::required("name", &self.name)?,
+            ..self
+        })
+    }
+}
+
+/// Payload `PUT /categories/:id`. Ganti nama ikut memperbarui semua buku;
+/// `parent_id: null` menjadikannya kategori tingkat atas.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateCategory {
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub parent_id: Option<Option<i32>>,
+}
+
+impl UpdateCategory {
+    pub fn normalized(self) -> Result<Self, Message> {
+        Ok(Self {
+            name: self.name.map(|n| normalize::required("name", &n)).transpose()?,
+            ..self
+        })
+    }
+}
+
+/// Payload `POST /categories/:id/merge`: kategori `:id` dilebur ke `into_id`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MergeCategory {
+    pub into_id: i32,
+}
+
+/// Id dan nama kanonik kategori untuk nama dari payload buku. Kategori yang belum ada
+/// dibuat kalau `auto_create` (AUTO_CREATE_CATEGORIES), selain itu `Ok(None)`.
+/// Perbandingan nama mengikuti collation tabel (tidak peka huruf besar/kecil).
+pub async fn resolve(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    name: &str,
+    auto_create: bool,
+) -> Result<Option<(i32, String)>, sqlx::Error> {
+    if auto_create {
+        sqlx::query("INSERT IGNORE INTO categories (library_id, name) VALUES (?, ?)")
+            .bind(library_id)
+            .bind(name)
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query_as("SELECT id, name FROM categories WHERE library_id = ? AND name = ?")
+        .bind(library_id)
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await
+}
+
+/// Samakan tabel categories dengan nama kategori di books (dipakai setelah restore backup,
+/// yang hanya membawa nama). Sama dengan langkah migrasi 0020.
+pub async fn sync_from_books(conn: &mut MySqlConnection, library_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT IGNORE INTO categories (library_id, name)
+         SELECT library_id, MIN(category) FROM books WHERE library_id = ?
+         GROUP BY library_id, category",
+    )
+    .bind(library_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(
+        "UPDATE books b
+         JOIN categories c ON c.library_id = b.library_id AND c.name = b.category
+         SET b.category_id = c.id, b.category = c.name
+         WHERE b.library_id = ?",
+    )
+    .bind(library_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// Sumber waktu "sekarang". Semua logika yang bergantung pada waktu
+/// (jatuh tempo, keterlambatan, denda) membaca waktu lewat trait ini,
+/// bukan `Utc::now()` langsung, supaya bisa diuji di batas hari.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Waktu sekarang dalam UTC tanpa zona, sesuai kolom DATETIME di DB.
+    fn now_naive(&self) -> NaiveDateTime {
+        self.now().naive_utc()
+    }
+}
+
+/// Jam sistem (dipakai di produksi).
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Jam yang berhenti di satu instan dan bisa digeser manual, mis. untuk uji
+/// "tengah malam di hari jatuh tempo". Hanya ada di build test.
+#[cfg(test)]
+pub struct FixedClock {
+    instant: std::sync::Mutex<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+impl FixedClock {
+    pub fn new(instant: DateTime<Utc>) -> Self {
+        Self { instant: std::sync::Mutex::new(instant) }
+    }
+
+    pub fn set(&self, instant: DateTime<Utc>) {
+        *self.instant.lock().unwrap_or_else(|e| e.into_inner()) = instant;
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.instant.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.instant.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn fixed_clock_stays_put_until_moved() {
+        let start = Utc.with_ymd_and_hms(2025, 6, 20, 23, 59, 59).unwrap();
+        let clock = FixedClock::new(start);
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::seconds(1));
+        assert_eq!(clock.now_naive().to_string(), "2025-06-21 00:00:00");
+
+        let later = Utc.with_ymd_and_hms(2025, 7, 1, 8, 0, 0).unwrap();
+        clock.set(later);
+        assert_eq!(clock.now(), later);
+    }
+}
@@ -0,0 +1,211 @@
+// Urutan judul/pengarang "seperti manusia": huruf besar/kecil dan diakritik diabaikan
+// ("Émile" di dekat "Emil", bukan setelah "Z"), tanda baca di depan dilewati, dan kata
+// sandang di awal (COLLATION_IGNORED_ARTICLES, mis. "The", "Sang", "Si") tidak ikut dihitung.
+// Dipakai langsung saat mengurutkan di Rust, dan disimpan sebagai kolom `*_sort_key`
+// (dihitung saat tulis) untuk ORDER BY di SQL.
+
+use sqlx::{MySql, MySqlConnection, QueryBuilder, Row};
+
+/// Kata sandang default yang dilewati di awal judul/nama.
+pub const DEFAULT_ARTICLES: &str = "the,a,an,sang,si";
+
+/// Panjang maksimum kunci yang disimpan, sama dengan kolom VARCHAR(255).
+const MAX_KEY_CHARS: usize = 255;
+
+/// Jumlah buku per UPDATE saat menyinkronkan kunci urut.
+const SYNC_BATCH_SIZE: usize = 500;
+
+/// Huruf Latin beraksen dan pasangan dasarnya (huruf kecil; input sudah di-lowercase).
+const FOLDS: &[(&str, &str)] = &[
+    ("àáâãäåāăą", "a"),
+    ("çćĉċč", "c"),
+    ("ďđ", "d"),
+    ("èéêëēĕėęě", "e"),
+    ("ĝğġģ", "g"),
+    ("ĥħ", "h"),
+    ("ìíîïĩīĭįı", "i"),
+    ("ĵ", "j"),
+    ("ķ", "k"),
+    ("ĺļľŀł", "l"),
+    ("ñńņňŉ", "n"),
+    ("òóôõöøōŏő", "o"),
+    ("ŕŗř", "r"),
+    ("śŝşš", "s"),
+    ("ţťŧ", "t"),
+    ("ùúûüũūŭůűų", "u"),
+    ("ŵ", "w"),
+    ("ýÿŷ", "y"),
+    ("źżž", "z"),
+    ("ß", "ss"),
+    ("æ", "ae"),
+    ("œ", "oe"),
+];
+
+fn fold_char(c: char, out: &mut String) {
+    match FOLDS.iter().find(|(accented, _)| accented.contains(c)) {
+        Some((_, base)) => out.push_str(base),
+        None => out.push(c),
+    }
+}
+
+/// Kunci urut: lowercase, diakritik dilepas, tanda baca di depan dan kata sandang pertama
+/// dibuang, whitespace diringkas. Teks yang isinya hanya kata sandang dibiarkan.
+pub fn sort_key(s: &str, articles: &[String]) -> String {
+    let mut folded = String::with_capacity(s.len());
+    for c in s.to_lowercase().chars() {
+        fold_char(c, &mut folded);
+    }
+    let folded = folded.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let words: Vec<&str> = folded.split_whitespace().collect();
+
+    let skip = match words.split_first() {
+        Some((first, rest)) if !rest.is_empty() && articles.iter().any(|a| a == first) => 1,
+        _ => 0,
+    };
+    let key = words[skip..].join(" ");
+    match key.char_indices().nth(MAX_KEY_CHARS) {
+        Some((end, _)) => key[..end].to_string(),
+        None => key,
+    }
+}
+
+/// Parse daftar kata sandang dipisah koma (env COLLATION_IGNORED_ARTICLES).
+pub fn parse_articles(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|a| a.trim().to_lowercase())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/// Hitung ulang `title_sort_key`/`author_sort_key` yang kosong atau basi (mis. setelah restore
+/// atau daftar kata sandang diganti). `library_id` None = semua perpustakaan.
+/// Mengembalikan jumlah buku yang diperbarui.
+pub async fn sync_sort_keys(
+    conn: &mut MySqlConnection,
+    library_id: Option<i32>,
+    articles: &[String],
+) -> Result<u64, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT id, title, author, title_sort_key, author_sort_key FROM books
+         WHERE ? IS NULL OR library_id = ?",
+    )
+    .bind(library_id)
+    .bind(library_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut stale: Vec<(i32, String, String)> = Vec::new();
+    for row in rows {
+        let title_key = sort_key(row.try_get("title")?, articles);
+        let author_key = sort_key(row.try_get("author")?, articles);
+        let stored: (Option<String>, Option<String>) =
+            (row.try_get("title_sort_key")?, row.try_get("author_sort_key")?);
+        if stored.0.as_deref() != Some(title_key.as_str())
+            || stored.1.as_deref() != Some(author_key.as_str())
+        {
+            stale.push((row.try_get("id")?, title_key, author_key));
+        }
+    }
+
+    let mut updated = 0;
+    for batch in stale.chunks(SYNC_BATCH_SIZE) {
+        updated += update_batch(batch).build().execute(&mut *conn).await?.rows_affected();
+    }
+    Ok(updated)
+}
+
+/// Satu UPDATE untuk sekelompok buku: `SET kolom = CASE id WHEN .. THEN .. END`.
+fn update_batch(batch: &[(i32, String, String)]) -> QueryBuilder<'_, MySql> {
+    let mut qb = QueryBuilder::new("UPDATE books SET title_sort_key = CASE id");
+    for (id, title_key, _) in batch {
+        qb.push(" WHEN ").push_bind(id).push(" THEN ").push_bind(title_key);
+    }
+    qb.push(" END, author_sort_key = CASE id");
+    for (id, _, author_key) in batch {
+        qb.push(" WHEN ").push_bind(id).push(" THEN ").push_bind(author_key);
+    }
+    qb.push(" END WHERE id IN (");
+    let mut ids = qb.separated(", ");
+    for (id, _, _) in batch {
+        ids.push_bind(id);
+    }
+    qb.push(")");
+    qb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn articles() -> Vec<String> {
+        parse_articles(DEFAULT_ARTICLES)
+    }
+
+    #[test]
+    fn sort_key_folds_case_accents_and_leading_articles() {
+        let a = articles();
+        assert_eq!(sort_key("The Hobbit", &a), "hobbit");
+        assert_eq!(sort_key("  ...Émile  ou   de l'Éducation", &a), "emile ou de l'education");
+        assert_eq!(sort_key("Sang Pemimpi", &a), "pemimpi");
+        assert_eq!(sort_key("Straße", &a), "strasse");
+        // Judul yang hanya berisi kata sandang tidak dikosongkan.
+        assert_eq!(sort_key("The", &a), "the");
+        // Kata sandang hanya di awal.
+        assert_eq!(sort_key("Into the Wild", &a), "into the wild");
+    }
+
+    #[test]
+    fn fixture_titles_sort_like_a_catalogue() {
+        let a = articles();
+        let mut titles = vec![
+            "Zorro",
+            "The Zebra",
+            "Émile",
+            "an Apple a Day",
+            "Ëmma",
+            "Emil and the Detectives",
+            "A Bridge Too Far",
+            "Si Kancil",
+            "¡Ay, Carmela!",
+            "edgar",
+        ];
+        titles.sort_by_key(|t| sort_key(t, &a));
+        assert_eq!(
+            titles,
+            vec![
+                "an Apple a Day",
+                "¡Ay, Carmela!",
+                "A Bridge Too Far",
+                "edgar",
+                "Emil and the Detectives",
+                "Émile",
+                "Ëmma",
+                "Si Kancil",
+                "The Zebra",
+                "Zorro",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_articles_trims_and_lowercases() {
+        assert_eq!(parse_articles(" The, ,LE,la "), vec!["the", "le", "la"]);
+        assert!(parse_articles("").is_empty());
+    }
+
+    #[test]
+    fn sort_key_is_capped_to_column_length() {
+        let long = "é".repeat(300);
+        assert_eq!(sort_key(&long, &[]).chars().count(), MAX_KEY_CHARS);
+    }
+
+    #[test]
+    fn update_batch_sets_both_keys_in_one_statement() {
+        let batch = vec![(1, "a".to_string(), "x".to_string()), (2, "b".into(), "y".into())];
+        assert_eq!(
+            update_batch(&batch).sql(),
+            "UPDATE books SET title_sort_key = CASE id WHEN ? THEN ? WHEN ? THEN ? \
+             END, author_sort_key = CASE id WHEN ? THEN ? WHEN ? THEN ? END WHERE id IN (?, ?)"
+        );
+    }
+}
@@ -1,5 +1,200 @@
-use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+use sqlx::{migrate::Migrator, mysql::MySqlPoolOptions, Connection, MySqlPool};
 use std::env;
+use std::str::FromStr;
+
+use crate::collation;
+use crate::json_case::JsonCase;
+use crate::loan::OpenHours;
+use crate::scope::{Scopes, ANONYMOUS_DEFAULT};
+use crate::search::SearchSort;
+use crate::timestamps::{TimestampFormat, UtcOffset};
+
+/// Konfigurasi aplikasi yang dibaca sekali dari environment saat startup.
+/// Diserialisasi apa adanya oleh `GET /admin/config`, jadi field rahasia
+/// WAJIB diberi `#[serde(skip)]`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppConfig {
+    /// Kunci operator untuk endpoint `/admin/*` (ADMIN_API_KEY).
+    #[serde(skip)]
+    pub admin_api_key: Option<String>,
+    /// Kunci HMAC untuk link self-service anggota (MEMBER_LINK_SECRET). Kosong = fitur mati.
+    #[serde(skip)]
+    pub member_link_secret: Option<String>,
+    /// Masa berlaku link self-service dalam jam (MEMBER_LINK_TTL_HOURS, default 72).
+    pub member_link_ttl_hours: i64,
+    /// Alamat halaman self-service yang dikirim di link (MEMBER_LINK_URL).
+    pub member_link_url: String,
+    /// Alamat halaman konfirmasi perubahan email anggota (EMAIL_CONFIRM_URL).
+    pub email_confirm_url: String,
+    /// Perpustakaan yang dipakai kalau request tidak membawa API key
+    /// (DEFAULT_LIBRARY_ID). Kosong = API key wajib.
+    pub default_library_id: Option<i32>,
+    /// Scope untuk request tanpa API key di DEFAULT_LIBRARY_ID (ANONYMOUS_SCOPES, nama
+    /// dipisah koma, default baca saja). `admin` tidak pernah diberikan.
+    pub anonymous_scopes: Scopes,
+    /// Denda keterlambatan per hari dalam rupiah (FINE_PER_DAY, default 1000).
+    pub fine_per_day: i64,
+    /// Biaya penggantian buku hilang dalam rupiah (LOST_BOOK_FEE, default 50000).
+    pub lost_book_fee: i64,
+    /// Pinjaman yang lewat jatuh tempo lebih dari sekian hari otomatis ditutup sebagai hilang
+    /// oleh job per jam (AUTO_LOST_DAYS). Kosong = job mati.
+    pub auto_lost_days: Option<i64>,
+    /// Kode mata uang untuk format nominal denda (CURRENCY, default IDR).
+    pub currency: String,
+    /// Batas jumlah anggota per perpustakaan (MAX_MEMBERS). Kosong = tanpa batas.
+    pub max_members: Option<i64>,
+    /// Batas pinjaman aktif per anggota (MAX_ACTIVE_LOANS). Kosong = tanpa batas.
+    pub max_active_loans: Option<i64>,
+    /// Masa orientasi anggota baru dalam hari sejak `joined_at` (NEW_MEMBER_GRACE_DAYS).
+    /// Kosong = tidak ada masa orientasi.
+    pub new_member_grace_days: Option<i64>,
+    /// Batas pinjaman aktif selama masa orientasi (NEW_MEMBER_MAX_ACTIVE_LOANS).
+    /// Kosong = tanpa batas selama masa orientasi.
+    pub new_member_max_active_loans: Option<i64>,
+    /// Ukuran halaman default dan maksimum (DEFAULT_PAGE_SIZE / MAX_PAGE_SIZE).
+    pub default_page_size: u32,
+    pub max_page_size: u32,
+    /// Batas lunak /books, /members, dan /loans tanpa paging (LIST_RESULT_CAP). Kelebihannya
+    /// dipotong dengan header `X-Result-Truncated: true`; `?all=true` melewatinya.
+    /// Kosong = tanpa batas.
+    pub list_result_cap: Option<usize>,
+    /// Kalau true, buku dengan total_copies 0 (rekaman katalog saja) tidak muncul di
+    /// /books dan /search (HIDE_ZERO_COPY_BOOKS, default false).
+    pub hide_zero_copy_books: bool,
+    /// Kategori yang belum ada dibuat otomatis saat buku dibuat/diubah
+    /// (AUTO_CREATE_CATEGORIES, default true). False = nama kategori tak dikenal ditolak.
+    pub auto_create_categories: bool,
+    /// Query yang lebih lambat dari ini (ms) dicatat ke log (SLOW_QUERY_MS, default 250).
+    pub slow_query_ms: u64,
+    /// Urutan /search kalau client tidak mengirim `?sort=` (SEARCH_DEFAULT_SORT:
+    /// relevance|title|year, default relevance).
+    pub search_default_sort: SearchSort,
+    /// Kalau true, snapshot /search dibaca di transaksi REPEATABLE READ read-only dengan
+    /// consistent snapshot (SEARCH_CONSISTENT_SNAPSHOT, default false).
+    pub search_consistent_snapshot: bool,
+    /// Ukuran chunk minimum saat /search diproses paralel (SEARCH_MIN_CHUNK_SIZE, default 256).
+    pub search_min_chunk_size: usize,
+    /// Casing field response JSON kalau client tidak mengirim `X-Json-Case`
+    /// (JSON_CASE: snake|camel, default snake).
+    pub json_case: JsonCase,
+    /// Format field waktu di response kalau client tidak mengirim `X-Timestamp-Format`
+    /// (TIMESTAMP_FORMAT: naive|utc|local, default naive = bentuk lama tanpa zona).
+    pub timestamp_format: TimestampFormat,
+    /// Offset zona waktu perpustakaan untuk format `local` (LIBRARY_UTC_OFFSET, mis. +07:00).
+    pub library_utc_offset: UtcOffset,
+    /// Jam buka peminjaman di zona LIBRARY_UTC_OFFSET (OPEN_HOURS, mis. `08:00-20:00`);
+    /// di luar jam ini POST /loans ditolak 403. Kosong = tanpa batas jam;
+    /// format salah = server gagal start.
+    pub open_hours: Option<OpenHours>,
+    /// Kata sandang yang dilewati di awal judul/nama saat mengurutkan
+    /// (COLLATION_IGNORED_ARTICLES, dipisah koma, default `the,a,an,sang,si`).
+    pub collation_articles: Vec<String>,
+}
+
+impl AppConfig {
+    pub fn from_env() -> Self {
+        Self {
+            admin_api_key: env::var("ADMIN_API_KEY").ok().filter(|k| !k.is_empty()),
+            member_link_secret: env::var("MEMBER_LINK_SECRET").ok().filter(|k| !k.is_empty()),
+            member_link_ttl_hours: env_or("MEMBER_LINK_TTL_HOURS", 72),
+            member_link_url: env::var("MEMBER_LINK_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:8000/me/loans".to_string()),
+            email_confirm_url: env::var("EMAIL_CONFIRM_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:8000/members/confirm-email".to_string()),
+            default_library_id: env::var("DEFAULT_LIBRARY_ID")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            anonymous_scopes: Scopes::anonymous(
+                &env::var("ANONYMOUS_SCOPES").unwrap_or_else(|_| ANONYMOUS_DEFAULT.to_string()),
+            ),
+            fine_per_day: env_or("FINE_PER_DAY", 1000),
+            lost_book_fee: env_or("LOST_BOOK_FEE", 50000),
+            auto_lost_days: env::var("AUTO_LOST_DAYS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|days| *days > 0),
+            currency: env::var("CURRENCY")
+                .ok()
+                .map(|v| v.trim().to_uppercase())
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| "IDR".to_string()),
+            max_members: env::var("MAX_MEMBERS").ok().and_then(|v| v.trim().parse().ok()),
+            max_active_loans: env::var("MAX_ACTIVE_LOANS").ok().and_then(|v| v.trim().parse().ok()),
+            new_member_grace_days: env::var("NEW_MEMBER_GRACE_DAYS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|days| *days > 0),
+            new_member_max_active_loans: env::var("NEW_MEMBER_MAX_ACTIVE_LOANS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok()),
+            default_page_size: env_or("DEFAULT_PAGE_SIZE", 50),
+            max_page_size: env_or("MAX_PAGE_SIZE", 500),
+            list_result_cap: env::var("LIST_RESULT_CAP")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|cap| *cap > 0),
+            hide_zero_copy_books: env_or("HIDE_ZERO_COPY_BOOKS", false),
+            auto_create_categories: env_or("AUTO_CREATE_CATEGORIES", true),
+            slow_query_ms: env_or("SLOW_QUERY_MS", 250),
+            search_default_sort: env::var("SEARCH_DEFAULT_SORT")
+                .ok()
+                .and_then(|v| SearchSort::from_str(&v))
+                .unwrap_or_default(),
+            search_consistent_snapshot: env_or("SEARCH_CONSISTENT_SNAPSHOT", false),
+            search_min_chunk_size: env_or("SEARCH_MIN_CHUNK_SIZE", 256).max(1),
+            json_case: env::var("JSON_CASE")
+                .ok()
+                .and_then(|v| JsonCase::from_str(&v))
+                .unwrap_or_default(),
+            timestamp_format: env::var("TIMESTAMP_FORMAT")
+                .ok()
+                .and_then(|v| TimestampFormat::from_str(&v))
+                .unwrap_or_default(),
+            library_utc_offset: env::var("LIBRARY_UTC_OFFSET")
+                .ok()
+                .and_then(|v| UtcOffset::from_str(&v))
+                .unwrap_or_default(),
+            open_hours: env::var("OPEN_HOURS")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(|v| {
+                    // Salah ketik di sini diam-diam membuka peminjaman 24 jam, jadi gagal start.
+                    OpenHours::from_str(&v).unwrap_or_else(|| {
+                        panic!("invalid OPEN_HOURS '{v}' (expected HH:MM-HH:MM)")
+                    })
+                }),
+            collation_articles: collation::parse_articles(
+                &env::var("COLLATION_IGNORED_ARTICLES")
+                    .unwrap_or_else(|_| collation::DEFAULT_ARTICLES.to_string()),
+            ),
+        }
+    }
+
+    /// Batas pinjaman aktif untuk anggota yang bergabung pada `joined_at`: selama
+    /// NEW_MEMBER_GRACE_DAYS berlaku NEW_MEMBER_MAX_ACTIVE_LOANS, setelahnya MAX_ACTIVE_LOANS.
+    pub fn max_active_loans_for(
+        &self,
+        joined_at: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> Option<i64> {
+        match self.new_member_grace_days {
+            Some(days) if now < joined_at + Duration::days(days) => {
+                self.new_member_max_active_loans
+            }
+            _ => self.max_active_loans,
+        }
+    }
+}
+
+/// Baca env `key` dan parse ke `T`; pakai `default` kalau kosong atau tidak valid.
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
 
 /// Membuat connection pool ke MySQL.
 /// Dipanggil sekali di awal aplikasi, lalu disimpan di AppState.
@@ -7,9 +202,53 @@ pub async fn create_pool() -> MySqlPool {
     // DATABASE_URL diambil dari .env
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 
-    MySqlPoolOptions::new()
+    // DB_MIN_CONNECTIONS > 0 membuka koneksi di depan supaya request pertama tidak
+    // menanggung biaya handshake.
+    let min_connections: u32 = env_or("DB_MIN_CONNECTIONS", 0).min(10);
+
+    let pool = MySqlPoolOptions::new()
         .max_connections(10)
+        .min_connections(min_connections)
         .connect(&database_url)
         .await
-        .expect("Failed to connect to database")
+        .expect("Failed to connect to database");
+
+    if min_connections > 0 {
+        warm_up(&pool, min_connections).await;
+    }
+    pool
+}
+
+/// Buka dan ping `count` koneksi sekaligus, lalu kembalikan ke pool.
+/// Gagal warm-up tidak fatal; pool tetap dipakai dan koneksi dibuka saat dibutuhkan.
+async fn warm_up(pool: &MySqlPool, count: u32) {
+    let mut held = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        match pool.acquire().await {
+            Ok(mut conn) => {
+                if let Err(e) = conn.ping().await {
+                    eprintln!("Pool warm-up ping failed: {e}");
+                    break;
+                }
+                held.push(conn);
+            }
+            Err(e) => {
+                eprintln!("Pool warm-up failed: {e}");
+                break;
+            }
+        }
+    }
+    println!("Database pool ready: {} of {count} connections warmed up", held.len());
+}
+
+/// Migrasi di folder `migrations/`, ditanam saat kompilasi. Versi tertingginya adalah skema
+/// yang diharapkan binary ini (lihat `GET /health/schema`).
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Menjalankan migrasi di folder `migrations/` yang belum diterapkan.
+pub async fn run_migrations(pool: &MySqlPool) {
+    MIGRATOR
+        .run(pool)
+        .await
+        .expect("Failed to run database migrations");
 }
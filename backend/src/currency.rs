@@ -0,0 +1,30 @@
+// Format nominal uang (denda) untuk ditampilkan apa adanya oleh client.
+// Nominal di DB selalu bilangan bulat dalam satuan utama mata uang (rupiah utuh untuk IDR).
+
+/// Format `amount` sesuai kode mata uang CURRENCY, mis. IDR 3000 → "Rp 3.000".
+/// Kode yang tidak dikenal ditulis sebagai awalan dengan pemisah ribuan koma.
+pub fn format(currency: &str, amount: i64) -> String {
+    let (prefix, separator) = match currency {
+        "IDR" => ("Rp ".to_string(), '.'),
+        "USD" => ("$".to_string(), ','),
+        "EUR" => ("€".to_string(), '.'),
+        "SGD" => ("S$".to_string(), ','),
+        "MYR" => ("RM ".to_string(), ','),
+        other => (format!("{other} "), ','),
+    };
+    let sign = if amount < 0 { "-" } else { "" };
+    format!("{sign}{prefix}{}", group(amount.unsigned_abs(), separator))
+}
+
+/// 1234567 → "1.234.567" (dengan `separator` = '.').
+fn group(value: u64, separator: char) -> String {
+    let digits = value.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(separator);
+        }
+        out.push(c);
+    }
+    out
+}
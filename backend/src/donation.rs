@@ -0,0 +1,149 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::book::Book;
+use crate::i18n::Message;
+use crate::ids::BookId;
+use crate::normalize;
+use crate::timestamps;
+
+/// Status item donasi yang disimpan di kolom `donation_items.status`.
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_ACCEPTED: &str = "accepted";
+pub const STATUS_REJECTED: &str = "rejected";
+
+/// Satu baris di tabel `donations`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Donation {
+    pub id: i32,
+    pub donor_name: String,
+    pub donated_on: NaiveDate,
+    pub created_at: NaiveDateTime,
+}
+
+/// Satu judul dalam batch donasi.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DonationItem {
+    pub id: i32,
+    pub title: String,
+    pub author: Option<String>,
+    pub copies: i32,
+    pub status: String,
+    pub reject_reason: Option<String>,
+    pub book_id: Option<BookId>,
+    pub decided_at: Option<NaiveDateTime>,
+}
+
+/// Respons `GET /donations/:id` (dan `POST /donations`).
+#[derive(Debug, Clone, Serialize)]
+pub struct DonationDetail {
+    #[serde(flatten)]
+    pub donation: Donation,
+    pub items: Vec<DonationItem>,
+}
+
+/// Judul tentatif di payload `POST /donations`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewDonationItem {
+    pub title: String,
+    pub author: Option<String>,
+    #[serde(default = "one")]
+    pub copies: i32,
+}
+
+fn one() -> i32 {
+    1
+}
+
+/// Payload `POST /donations`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewDonation {
+    pub donor_name: String,
+    /// Tanggal donasi "YYYY-MM-DD" (atau RFC 3339).
+    #[serde(deserialize_with = "timestamps::date")]
+    pub donated_on: NaiveDate,
+    pub items: Vec<NewDonationItem>,
+}
+
+impl NewDonation {
+    /// Normalisasi teks dan tolak batch kosong/jumlah eksemplar tidak masuk akal.
+    pub fn normalized(self) -> Result<Self, Message> {
+        if self.items.is_empty() {
+            return Err(Message::new("validation.required").param("field", "items"));
+        }
+        let items = self
+            .items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| {
+                if item.copies < 1 {
+                    return Err(Message::new("validation.invalid_value")
+                        .param("field", format!("items[{i}].copies"))
+                        .param("reason", "must be at least 1"));
+                }
+                Ok(NewDonationItem {
+                    title: normalize::required(&format!("items[{i}].title"), &item.title)?,
+                    author: item
+                        .author
+                        .map(|a| normalize::collapse_whitespace(&a))
+                        .filter(|a| !a.is_empty()),
+                    copies: item.copies,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            donor_name: normalize::required("donor_name", &self.donor_name)?,
+            donated_on: self.donated_on,
+            items,
+        })
+    }
+}
+
+/// Payload `POST /donations/:id/items/:item_id/accept`. Data buku yang belum ada di item
+/// donasi dilengkapi di sini; kalau judul+pengarang sudah ada di katalog, eksemplarnya ditambah.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AcceptDonationItem {
+    /// Pengarang, wajib kalau item donasi tidak mencatatnya.
+    pub author: Option<String>,
+    /// Dipakai hanya kalau buku baru dibuat.
+    pub category: Option<String>,
+    pub year: Option<i32>,
+}
+
+/// Payload `POST /donations/:id/items/:item_id/reject`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RejectDonationItem {
+    pub reason: String,
+}
+
+/// Hasil menerima satu item: buku yang terbentuk dan apakah buku itu baru.
+#[derive(Debug, Clone, Serialize)]
+pub struct AcceptedDonationItem {
+    pub item: DonationItem,
+    pub book: Book,
+    /// false = eksemplar ditambahkan ke buku yang sudah ada.
+    pub created: bool,
+}
+
+/// Asal-usul donasi sebuah buku, ditampilkan di `GET /books/:id`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DonationProvenance {
+    pub donation_id: i32,
+    pub donor_name: String,
+    pub donated_on: NaiveDate,
+    pub copies: i32,
+    pub accepted_at: Option<NaiveDateTime>,
+}
+
+/// Respons `GET /books/:id`: buku beserta asal-usul donasinya.
+#[derive(Debug, Clone, Serialize)]
+pub struct BookDetail {
+    #[serde(flatten)]
+    pub book: Book,
+    pub donations: Vec<DonationProvenance>,
+}
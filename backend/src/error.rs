@@ -0,0 +1,439 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::mysql::MySqlDatabaseError;
+
+use crate::i18n::{Lang, Message, Text};
+use crate::loan::LoanError;
+use crate::member_token::TokenError;
+use crate::search::SearchError;
+
+/// Error API yang dikirim ke client sebagai
+/// `{ "error": { "code": "...", "message": "..." } }`.
+/// `message` diterjemahkan sesuai Accept-Language (lihat `i18n`), `code` tidak.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: Text,
+    /// Data tambahan untuk client, mis. versi terbaru saat konflik.
+    pub details: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a Value>,
+}
+
+#[derive(Serialize)]
+struct ErrorEnvelope<'a> {
+    error: ErrorBody<'a>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<Text>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn bad_request(message: impl Into<Text>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn unauthorized(message: impl Into<Text>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+    }
+
+    pub fn forbidden(message: impl Into<Text>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+
+    pub fn not_found(message: impl Into<Text>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn conflict(message: impl Into<Text>) -> Self {
+        Self::new(StatusCode::CONFLICT, "conflict", message)
+    }
+
+    pub fn precondition_failed(message: impl Into<Text>) -> Self {
+        Self::new(StatusCode::PRECONDITION_FAILED, "precondition_failed", message)
+    }
+
+    pub fn internal(message: impl Into<Text>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", message)
+    }
+
+    /// 400 untuk field wajib yang kosong.
+    pub fn required(field: &str) -> Self {
+        Self::bad_request(Message::new("validation.required").param("field", field))
+    }
+
+    /// 400 untuk parameter wajib yang tidak dikirim.
+    pub fn missing(field: &str) -> Self {
+        Self::bad_request(Message::new("validation.missing").param("field", field))
+    }
+
+    /// 400 untuk limit/per_page di luar 1..=max.
+    pub fn out_of_range(field: &str, max: impl std::fmt::Display) -> Self {
+        Self::bad_request(
+            Message::new("validation.range")
+                .param("field", field)
+                .param("max", max),
+        )
+    }
+
+    pub fn invalid_cursor() -> Self {
+        Self::bad_request(Message::new("validation.invalid_cursor"))
+    }
+
+    /// 404 dengan pesan per jenis resource, mis. `not_found.book`.
+    pub fn missing_resource(key: &'static str, id: impl std::fmt::Display) -> Self {
+        Self::not_found(Message::new(key).param("id", id))
+    }
+
+    /// Body JSON dalam bahasa tertentu.
+    pub fn render(&self, lang: Lang) -> Response {
+        let message = self.message.render(lang);
+        let body = ErrorEnvelope {
+            error: ErrorBody {
+                code: self.code,
+                message: &message,
+                details: self.details.as_ref(),
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<SearchError> for ApiError {
+    fn from(e: SearchError) -> Self {
+        let message = match e {
+            SearchError::QueryTooLong { len, max } => Message::new("search.query_too_long")
+                .param("len", len)
+                .param("max", max),
+            SearchError::InvalidPattern(reason) => {
+                Message::new("search.invalid_pattern").param("reason", reason)
+            }
+        };
+        Self::new(StatusCode::BAD_REQUEST, "invalid_search", message)
+    }
+}
+
+impl From<TokenError> for ApiError {
+    fn from(e: TokenError) -> Self {
+        let (code, key) = match e {
+            TokenError::Invalid => ("token_invalid", "token.invalid"),
+            TokenError::Expired => ("token_expired", "token.expired"),
+            TokenError::Revoked => ("token_revoked", "token.revoked"),
+        };
+        Self::new(StatusCode::UNAUTHORIZED, code, Message::new(key))
+    }
+}
+
+impl From<LoanError> for ApiError {
+    fn from(e: LoanError) -> Self {
+        match e {
+            LoanError::InvalidDueDate(raw) => Self::bad_request(
+                Message::new("validation.invalid_value")
+                    .param("field", "due_date")
+                    .param("reason", format!("'{raw}' is not a YYYY-MM-DD or RFC 3339 date")),
+            ),
+            LoanError::DueDateInPast { due, today } => Self::bad_request(
+                Message::new("loan.due_date_past").param("due", due).param("today", today),
+            ),
+            LoanError::MemberNotFound(id) => Self::missing_resource("not_found.member", id),
+            LoanError::BookNotFound(id) => Self::missing_resource("not_found.book", id),
+            LoanError::LoanNotFound(id) => Self::missing_resource("not_found.loan", id),
+            LoanError::LimitReached { member_id, active, cap } => Self::new(
+                StatusCode::CONFLICT,
+                "loan_limit_reached",
+                Message::new("loan.limit_reached").param("id", member_id).param("cap", cap),
+            )
+            .with_details(serde_json::json!({ "active_loans": active, "max_active_loans": cap })),
+            LoanError::OutOfStock(id) => Self::new(
+                StatusCode::CONFLICT,
+                "out_of_stock",
+                Message::new("loan.out_of_stock").param("id", id),
+            ),
+            LoanError::NotActive(id) => {
+                Self::conflict(Message::new("loan.not_active").param("id", id))
+            }
+            LoanError::OutsideOpenHours { now, hours } => Self::new(
+                StatusCode::FORBIDDEN,
+                "outside_open_hours",
+                Message::new("loan.outside_open_hours")
+                    .param("now", now.format("%H:%M"))
+                    .param("hours", hours),
+            ),
+            LoanError::Storage(e) => Self::from(&*e.0),
+        }
+    }
+}
+
+// Nomor error MySQL yang diterjemahkan jadi error client (lihat `constraint_error`).
+const ER_DUP_ENTRY: u16 = 1062;
+const ER_DATA_TOO_LONG: u16 = 1406;
+const ER_ROW_IS_REFERENCED_2: u16 = 1451;
+const ER_NO_REFERENCED_ROW_2: u16 = 1452;
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::from(&e)
+    }
+}
+
+impl From<&sqlx::Error> for ApiError {
+    fn from(e: &sqlx::Error) -> Self {
+        if let Some(error) = constraint_error(e) {
+            eprintln!("DB constraint rejected request: {e}");
+            return error;
+        }
+        eprintln!("DB error: {e}");
+        Self::internal(Message::new("db.error"))
+    }
+}
+
+/// Pelanggaran constraint (foreign key, unique, panjang kolom) adalah kesalahan data dari
+/// client, bukan error server: 409 untuk konflik dengan data lain, 422 untuk nilai yang
+/// tidak bisa disimpan. `details` menyebut tabel/constraint/kolom yang terlibat.
+fn constraint_error(e: &sqlx::Error) -> Option<ApiError> {
+    let db = e.as_database_error()?;
+    let number = db.try_downcast_ref::<MySqlDatabaseError>()?.number();
+    map_constraint(number, db.message())
+}
+
+/// Pure function: nomor + pesan error MySQL → ApiError, None kalau bukan pelanggaran constraint.
+fn map_constraint(number: u16, message: &str) -> Option<ApiError> {
+    let error = match number {
+        ER_ROW_IS_REFERENCED_2 => {
+            // "... a foreign key constraint fails (`db`.`loans`, CONSTRAINT `fk` FOREIGN KEY
+            // (`member_id`) REFERENCES `members` (`id`))": baris induk masih dipakai `loans`.
+            let table = between(message, "`.`", "`");
+            let referenced = between(message, "REFERENCES `", "`");
+            ApiError::new(
+                StatusCode::CONFLICT,
+                "still_referenced",
+                Message::new("db.still_referenced")
+                    .param("entity", referenced.unwrap_or("row"))
+                    .param("table", table.unwrap_or("another table")),
+            )
+            .with_details(serde_json::json!({
+                "table": table,
+                "references": referenced,
+                "constraint": between(message, "CONSTRAINT `", "`"),
+            }))
+        }
+        ER_NO_REFERENCED_ROW_2 => {
+            let referenced = between(message, "REFERENCES `", "`");
+            let column = between(message, "FOREIGN KEY (`", "`");
+            ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_reference",
+                Message::new("db.invalid_reference")
+                    .param("field", column.unwrap_or("id"))
+                    .param("entity", referenced.unwrap_or("row")),
+            )
+            .with_details(serde_json::json!({
+                "field": column,
+                "references": referenced,
+                "constraint": between(message, "CONSTRAINT `", "`"),
+            }))
+        }
+        ER_DUP_ENTRY => {
+            // "Duplicate entry 'x' for key 'categories.uq_categories_name'"
+            let key = between(message, "for key '", "'");
+            ApiError::new(
+                StatusCode::CONFLICT,
+                "duplicate",
+                Message::new("db.duplicate").param("key", key.unwrap_or("unique key")),
+            )
+            .with_details(serde_json::json!({ "constraint": key }))
+        }
+        ER_DATA_TOO_LONG => {
+            // "Data too long for column 'title' at row 1"
+            let column = between(message, "column '", "'");
+            ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "too_long",
+                Message::new("db.too_long").param("field", column.unwrap_or("value")),
+            )
+            .with_details(serde_json::json!({ "field": column }))
+        }
+        _ => return None,
+    };
+    Some(error)
+}
+
+/// Teks di antara `start` dan `end` pertama sesudahnya.
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let rest = &text[text.find(start)? + start.len()..];
+    rest.find(end).map(|i| &rest[..i])
+}
+
+/// Dirender dalam bahasa default; error aslinya dititipkan di extension supaya
+/// `i18n::localize` bisa merender ulang sesuai Accept-Language.
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = self.render(Lang::default());
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::MySqlPool;
+
+    // Pesan asli MySQL 8 untuk tiap nomor error.
+    const FK_PARENT: &str = "Cannot delete or update a parent row: a foreign key constraint fails \
+        (`perpustakaan`.`loans`, CONSTRAINT `loans_ibfk_2` FOREIGN KEY (`member_id`) \
+        REFERENCES `members` (`id`))";
+    const FK_CHILD: &str = "Cannot add or update a child row: a foreign key constraint fails \
+        (`perpustakaan`.`loans`, CONSTRAINT `loans_ibfk_1` FOREIGN KEY (`book_id`) \
+        REFERENCES `books` (`id`))";
+    const DUPLICATE: &str =
+        "Duplicate entry '1-Fiksi' for key 'categories.uq_categories_library_name'";
+    const TOO_LONG: &str = "Data too long for column 'title' at row 1";
+
+    #[test]
+    fn between_extracts_names_from_mysql_messages() {
+        assert_eq!(between(FK_PARENT, "`.`", "`"), Some("loans"));
+        assert_eq!(between(FK_PARENT, "REFERENCES `", "`"), Some("members"));
+        assert_eq!(between(FK_PARENT, "CONSTRAINT `", "`"), Some("loans_ibfk_2"));
+        assert_eq!(between(FK_CHILD, "FOREIGN KEY (`", "`"), Some("book_id"));
+        assert_eq!(
+            between(DUPLICATE, "for key '", "'"),
+            Some("categories.uq_categories_library_name")
+        );
+        assert_eq!(between(TOO_LONG, "column '", "'"), Some("title"));
+        assert_eq!(between(TOO_LONG, "FOREIGN KEY (`", "`"), None);
+        assert_eq!(between("column 'unterminated", "column '", "'"), None);
+    }
+
+    #[test]
+    fn parent_row_still_referenced_is_conflict() {
+        let e = map_constraint(ER_ROW_IS_REFERENCED_2, FK_PARENT).unwrap();
+        assert_eq!((e.status, e.code), (StatusCode::CONFLICT, "still_referenced"));
+        assert_eq!(
+            e.details,
+            Some(serde_json::json!({
+                "table": "loans",
+                "references": "members",
+                "constraint": "loans_ibfk_2",
+            }))
+        );
+    }
+
+    #[test]
+    fn missing_referenced_row_is_unprocessable() {
+        let e = map_constraint(ER_NO_REFERENCED_ROW_2, FK_CHILD).unwrap();
+        assert_eq!(
+            (e.status, e.code),
+            (StatusCode::UNPROCESSABLE_ENTITY, "invalid_reference")
+        );
+        assert_eq!(e.details.unwrap()["field"], "book_id");
+    }
+
+    #[test]
+    fn duplicate_entry_is_conflict() {
+        let e = map_constraint(ER_DUP_ENTRY, DUPLICATE).unwrap();
+        assert_eq!((e.status, e.code), (StatusCode::CONFLICT, "duplicate"));
+        assert_eq!(
+            e.details.unwrap()["constraint"],
+            "categories.uq_categories_library_name"
+        );
+    }
+
+    #[test]
+    fn data_too_long_is_unprocessable() {
+        let e = map_constraint(ER_DATA_TOO_LONG, TOO_LONG).unwrap();
+        assert_eq!((e.status, e.code), (StatusCode::UNPROCESSABLE_ENTITY, "too_long"));
+        assert_eq!(e.details.unwrap()["field"], "title");
+    }
+
+    #[test]
+    fn unexpected_message_shape_still_maps() {
+        let e = map_constraint(ER_DUP_ENTRY, "Duplicate entry").unwrap();
+        assert_eq!(e.code, "duplicate");
+        assert_eq!(e.details, Some(serde_json::json!({ "constraint": null })));
+    }
+
+    #[test]
+    fn other_errors_are_not_constraints() {
+        assert!(map_constraint(1213, "Deadlock found when trying to get lock").is_none());
+    }
+
+    // Sisanya butuh MySQL: `DATABASE_URL=mysql://... cargo test -- --ignored`. Error-nya
+    // datang dari skema asli, jadi pesan MySQL yang di-parse `map_constraint` juga asli.
+
+    async fn insert_book(pool: &MySqlPool, public_id: &str, title: &str) -> Result<(), ApiError> {
+        sqlx::query(
+            "INSERT INTO books (library_id, public_id, title, author, category, year,
+                                total_copies, available_copies)
+             VALUES (1, ?, ?, 'Andrea Hirata', 'Novel', 2005, 1, 1)",
+        )
+        .bind(public_id)
+        .bind(title)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::config::MIGRATOR")]
+    #[ignore = "butuh DATABASE_URL (MySQL)"]
+    async fn real_duplicate_key_is_conflict(pool: MySqlPool) {
+        insert_book(&pool, "bk_dup", "Laskar Pelangi").await.unwrap();
+        let e = insert_book(&pool, "bk_dup", "Sang Pemimpi").await.unwrap_err();
+        assert_eq!((e.status, e.code), (StatusCode::CONFLICT, "duplicate"));
+        assert_eq!(e.details.unwrap()["constraint"], "books.uq_books_public_id");
+    }
+
+    #[sqlx::test(migrator = "crate::config::MIGRATOR")]
+    #[ignore = "butuh DATABASE_URL (MySQL)"]
+    async fn real_data_too_long_is_unprocessable(pool: MySqlPool) {
+        let e = insert_book(&pool, "bk_long", &"x".repeat(256)).await.unwrap_err();
+        assert_eq!((e.status, e.code), (StatusCode::UNPROCESSABLE_ENTITY, "too_long"));
+        assert_eq!(e.details.unwrap()["field"], "title");
+    }
+
+    #[sqlx::test(migrator = "crate::config::MIGRATOR")]
+    #[ignore = "butuh DATABASE_URL (MySQL)"]
+    async fn real_foreign_key_violations_map_to_409_and_422(pool: MySqlPool) {
+        let insert_key = |library_id: i32| {
+            sqlx::query("INSERT INTO api_keys (library_id, label) VALUES (?, 'test')")
+                .bind(library_id)
+                .execute(&pool)
+        };
+
+        let e = ApiError::from(insert_key(999).await.unwrap_err());
+        assert_eq!((e.status, e.code), (StatusCode::UNPROCESSABLE_ENTITY, "invalid_reference"));
+        let details = e.details.unwrap();
+        assert_eq!(details["field"], "library_id");
+        assert_eq!(details["references"], "libraries");
+
+        insert_key(1).await.unwrap();
+        let e = ApiError::from(
+            sqlx::query("DELETE FROM libraries WHERE id = 1").execute(&pool).await.unwrap_err(),
+        );
+        assert_eq!((e.status, e.code), (StatusCode::CONFLICT, "still_referenced"));
+        assert_eq!(e.details.unwrap()["table"], "api_keys");
+    }
+}
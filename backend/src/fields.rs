@@ -0,0 +1,108 @@
+// Sparse fieldsets: `?fields=id,title,available_copies` membatasi field yang diserialisasi.
+// Relasi yang di-embed (mis. `book` di LoanDetail) dipilih dengan path bertitik `book.title`;
+// `book` saja berarti seluruh object. Nama field boleh camelCase (dinormalisasi ke snake_case).
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::error::ApiError;
+use crate::i18n::Message;
+use crate::json_case::camel_to_snake;
+use crate::pagination::CursorPage;
+
+/// Field yang dipilih; `Some(sub)` untuk relasi yang dibatasi lagi lewat path bertitik.
+#[derive(Debug, Clone, Default)]
+pub struct FieldSet {
+    fields: BTreeMap<String, Option<FieldSet>>,
+}
+
+impl FieldSet {
+    /// Parse daftar dipisah koma. `known` = field object utama, `nested` = relasi yang boleh
+    /// dipilih beserta field-nya. Field yang tidak dikenal ditolak dengan 400.
+    pub fn parse(
+        raw: &str,
+        known: &[&str],
+        nested: &[(&str, &[&str])],
+    ) -> Result<Self, ApiError> {
+        let unknown = |field: &str| {
+            ApiError::bad_request(Message::new("validation.unknown_field").param("field", field))
+        };
+
+        let mut set = Self::default();
+        for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let path = camel_to_snake(part);
+            match path.split_once('.') {
+                None if known.contains(&path.as_str()) => {
+                    set.fields.insert(path, None);
+                }
+                None if nested.iter().any(|(name, _)| *name == path) => {
+                    // Seluruh relasi menimpa pilihan `relasi.field` sebelumnya.
+                    set.fields.insert(path, None);
+                }
+                None => return Err(unknown(part)),
+                Some((relation, field)) => {
+                    let Some((_, fields)) = nested.iter().find(|(name, _)| *name == relation)
+                    else {
+                        return Err(unknown(part));
+                    };
+                    if !fields.contains(&field) {
+                        return Err(unknown(part));
+                    }
+                    let entry = set
+                        .fields
+                        .entry(relation.to_string())
+                        .or_insert_with(|| Some(Self::default()));
+                    if let Some(sub) = entry {
+                        sub.fields.insert(field.to_string(), None);
+                    }
+                }
+            }
+        }
+
+        if set.fields.is_empty() {
+            return Err(ApiError::bad_request(
+                Message::new("validation.invalid_value")
+                    .param("field", "fields")
+                    .param("reason", "must name at least one field"),
+            ));
+        }
+        Ok(set)
+    }
+
+    /// Parse `?fields=` opsional; tanpa parameter hasilnya None (semua field).
+    pub fn parse_opt(
+        raw: Option<&str>,
+        known: &[&str],
+        nested: &[(&str, &[&str])],
+    ) -> Result<Option<Self>, ApiError> {
+        raw.map(|raw| Self::parse(raw, known, nested)).transpose()
+    }
+
+    /// Buang key object yang tidak dipilih; array diproses per elemen.
+    pub fn apply(&self, value: Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .filter_map(|(key, value)| match self.fields.get(&key)? {
+                        Some(sub) => Some((key, sub.apply(value))),
+                        None => Some((key, value)),
+                    })
+                    .collect::<Map<_, _>>(),
+            ),
+            Value::Array(items) => Value::Array(items.into_iter().map(|v| self.apply(v)).collect()),
+            other => other,
+        }
+    }
+
+    /// Serialisasi `value` lalu batasi field-nya.
+    pub fn select<T: Serialize>(&self, value: &T) -> Value {
+        self.apply(serde_json::to_value(value).unwrap_or(Value::Null))
+    }
+
+    /// Untuk `CursorPage`: hanya item yang dibatasi, `next_cursor` tetap ada.
+    pub fn select_page<T: Serialize>(&self, page: &CursorPage<T>) -> Value {
+        json!({ "items": self.select(&page.items), "next_cursor": page.next_cursor })
+    }
+}
@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::currency;
+use crate::ids::{LoanId, MemberId};
+
+/// Alasan denda yang disimpan di kolom `fines.reason`.
+pub const REASON_LATE: &str = "late";
+pub const REASON_LOST: &str = "lost";
+
+/// Satu baris di tabel `fines`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Fine {
+    pub id: i32,
+    pub loan_id: LoanId,
+    pub member_id: MemberId,
+    pub amount: i64,
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+    pub paid_at: Option<NaiveDateTime>,
+    /// `amount` yang sudah diformat sesuai CURRENCY, mis. "Rp 3.000". Diisi oleh `with_currency`.
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub amount_formatted: String,
+}
+
+impl Fine {
+    pub fn with_currency(self, currency: &str) -> Self {
+        Self {
+            amount_formatted: currency::format(currency, self.amount),
+            ..self
+        }
+    }
+}
+
+/// Pure function: denda keterlambatan = jumlah hari lewat jatuh tempo × tarif per hari.
+/// Dihitung per tanggal kalender, jadi kembali di hari jatuh tempo tidak kena denda.
+pub fn late_fine(due_at: NaiveDateTime, returned_at: NaiveDateTime, per_day: i64) -> i64 {
+    let days_late = (returned_at.date() - due_at.date()).num_days();
+    days_late.max(0) * per_day
+}
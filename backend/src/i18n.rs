@@ -0,0 +1,475 @@
+// Katalog pesan error/validasi dalam bahasa Indonesia (default) dan Inggris.
+// Field `code` di envelope error tidak pernah diterjemahkan; hanya `message`.
+
+use axum::{
+    extract::Request,
+    http::header::{ACCEPT_LANGUAGE, CONTENT_LENGTH},
+    middleware::Next,
+    response::Response,
+};
+use std::fmt;
+
+use crate::error::ApiError;
+
+/// Bahasa yang didukung katalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    Id,
+    En,
+}
+
+impl Lang {
+    /// Pilih bahasa dari header Accept-Language (mis. `en-US,en;q=0.9,id;q=0.8`).
+    /// Tag dengan q tertinggi yang dikenal menang; kalau tidak ada yang dikenal, pakai default.
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut best: Option<(Lang, f32)> = None;
+        for part in header.split(',') {
+            let mut pieces = part.trim().split(';');
+            let tag = pieces.next().unwrap_or("").trim().to_ascii_lowercase();
+            let q = pieces
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let primary = tag.split('-').next().unwrap_or("");
+            let lang = match primary {
+                "id" | "in" => Lang::Id,
+                "en" => Lang::En,
+                _ => continue,
+            };
+            if q > 0.0 && best.is_none_or(|(_, bq)| q > bq) {
+                best = Some((lang, q));
+            }
+        }
+        best.map(|(lang, _)| lang).unwrap_or_default()
+    }
+}
+
+/// (key, id, en). Placeholder `{nama}` diisi dari parameter [`Message`].
+const CATALOGUE: &[(&str, &str, &str)] = &[
+    ("validation.required", "{field} tidak boleh kosong", "{field} must not be empty"),
+    ("validation.missing", "{field} wajib diisi", "{field} is required"),
+    (
+        "validation.range",
+        "{field} harus antara 1 dan {max}",
+        "{field} must be between 1 and {max}",
+    ),
+    (
+        "validation.year",
+        "{field} harus antara {min} dan {max}, atau null kalau tidak diketahui",
+        "{field} must be between {min} and {max}, or null if unknown",
+    ),
+    ("validation.unknown_field", "field '{field}' tidak dikenal", "unknown field '{field}'"),
+    ("validation.invalid_value", "{field} tidak valid: {reason}", "{field} is invalid: {reason}"),
+    ("validation.invalid_cursor", "cursor tidak valid", "invalid cursor"),
+    (
+        "validation.unknown_value",
+        "{field} '{value}' tidak dikenal, pilihan: {expected}",
+        "unknown {field} '{value}', expected {expected}",
+    ),
+    (
+        "validation.not_streamable",
+        "{param} tidak didukung pada listing streaming",
+        "{param} is not supported on streamed listings",
+    ),
+    (
+        "validation.conflicting_params",
+        "{a} tidak bisa digabung dengan {b}",
+        "{a} cannot be combined with {b}",
+    ),
+    ("validation.requires", "{field} harus disertai {other}", "{field} requires {other}"),
+    ("validation.min", "{field} minimal {min}", "{field} must be at least {min}"),
+    (
+        "validation.ratio",
+        "{field} harus di antara 0 (inklusif) dan 1 (eksklusif)",
+        "{field} must be in [0, 1)",
+    ),
+    (
+        "validation.date_in_past",
+        "{field} {date} sudah lewat",
+        "{field} {date} is already in the past",
+    ),
+    (
+        "validation.date_order",
+        "{start_field} {start} jatuh setelah {end_field} {end}",
+        "{start_field} {start} is after {end_field} {end}",
+    ),
+    ("validation.invalid_if_match", "header If-Match tidak valid", "invalid If-Match header"),
+    (
+        "validation.unknown_category",
+        "kategori '{name}' belum terdaftar",
+        "category '{name}' does not exist",
+    ),
+    ("not_found.book", "buku {id} tidak ditemukan", "book {id} not found"),
+    ("not_found.member", "anggota {id} tidak ditemukan", "member {id} not found"),
+    ("not_found.loan", "peminjaman {id} tidak ditemukan", "loan {id} not found"),
+    ("not_found.library", "perpustakaan {id} tidak ditemukan", "library {id} not found"),
+    ("not_found.api_key", "API key {id} tidak ditemukan", "api key {id} not found"),
+    ("not_found.id", "{id} tidak ditemukan", "{id} not found"),
+    ("not_found.category", "kategori {id} tidak ditemukan", "category {id} not found"),
+    ("not_found.donation", "donasi {id} tidak ditemukan", "donation {id} not found"),
+    (
+        "not_found.donation_item",
+        "item {id} tidak ada di donasi {donation_id}",
+        "donation item {id} not found in donation {donation_id}",
+    ),
+    (
+        "not_found.reserve_list",
+        "daftar reserve {id} tidak ditemukan",
+        "reserve list {id} not found",
+    ),
+    (
+        "not_found.reserve_entry",
+        "buku {book} tidak ada di daftar reserve {id}",
+        "book {book} is not on reserve list {id}",
+    ),
+    ("not_found.program", "program {id} tidak ditemukan", "program {id} not found"),
+    (
+        "not_found.enrollment",
+        "anggota {member} tidak terdaftar di program {id}",
+        "member {member} is not enrolled in program {id}",
+    ),
+    (
+        "not_found.purchase_request",
+        "usulan pembelian {id} tidak ditemukan",
+        "purchase request {id} not found",
+    ),
+    (
+        "book.stale_version",
+        "buku {id} sudah diubah (versi yang diharapkan {expected}, sekarang {current})",
+        "book {id} was modified (expected version {expected}, current {current})",
+    ),
+    (
+        "book.below_on_loan",
+        "total_copies {total} lebih kecil dari {on_loan} eksemplar yang sedang dipinjam",
+        "total_copies {total} is below the {on_loan} copies currently on loan",
+    ),
+    (
+        "category.exists",
+        "kategori '{name}' sudah ada",
+        "category '{name}' already exists",
+    ),
+    (
+        "category.rename_taken",
+        "kategori '{name}' sudah ada; gabungkan kategorinya",
+        "category '{name}' already exists; merge the categories instead",
+    ),
+    (
+        "category.own_parent",
+        "kategori tidak bisa jadi induknya sendiri",
+        "a category cannot be its own parent",
+    ),
+    (
+        "category.too_deep",
+        "kategori {id} sendiri adalah sub-kategori; hanya dua tingkat yang didukung",
+        "category {id} is itself a subcategory; only two levels are supported",
+    ),
+    (
+        "category.has_children",
+        "kategori {id} punya sub-kategori dan tidak bisa dipindah ke kategori lain",
+        "category {id} has subcategories and cannot be moved under another category",
+    ),
+    (
+        "category.not_empty",
+        "kategori {id} masih punya {books} buku dan {children} sub-kategori",
+        "category {id} still has {books} books and {children} subcategories",
+    ),
+    (
+        "category.merge_self",
+        "kategori tidak bisa digabung ke dirinya sendiri",
+        "cannot merge a category into itself",
+    ),
+    (
+        "category.merge_into_subcategory",
+        "kategori {id} punya sub-kategori, sedangkan {into_id} sendiri sub-kategori",
+        "category {id} has subcategories but {into_id} is itself a subcategory",
+    ),
+    (
+        "category.merge_into_child",
+        "kategori {into_id} adalah sub-kategori dari {id}; pindahkan dulu",
+        "category {into_id} is a subcategory of {id}; move it first",
+    ),
+    ("loan.not_active", "peminjaman {id} sudah tidak aktif", "loan {id} is not active"),
+    (
+        "loan.due_date_past",
+        "due_date {due} sudah lewat (hari ini {today})",
+        "due_date {due} is before today ({today})",
+    ),
+    (
+        "loan.limit_reached",
+        "anggota {id} sudah mencapai batas {cap} pinjaman aktif",
+        "member {id} already has the maximum of {cap} active loans",
+    ),
+    ("loan.out_of_stock", "stok buku {id} habis", "book {id} has no available copies"),
+    (
+        "loan.outside_open_hours",
+        "peminjaman hanya dilayani pada jam buka {hours} (sekarang {now})",
+        "loans can only be created during opening hours {hours} (now {now})",
+    ),
+    (
+        "member.limit_reached",
+        "batas {cap} anggota sudah tercapai",
+        "member limit of {cap} reached",
+    ),
+    (
+        "member.merge_self",
+        "anggota tidak bisa digabung ke dirinya sendiri",
+        "cannot merge a member into itself",
+    ),
+    (
+        "member.primary_in_duplicates",
+        "primary_id tidak boleh ada di duplicate_ids",
+        "primary_id must not be listed in duplicate_ids",
+    ),
+    (
+        "member.reassign_self",
+        "pinjaman tidak bisa dipindah ke anggota yang sedang dihapus",
+        "cannot reassign loans to the member being deleted",
+    ),
+    (
+        "member.reassign_over_limit",
+        "anggota {id} akan punya {after} pinjaman aktif, batasnya {cap}",
+        "member {id} would have {after} active loans, limit is {cap}",
+    ),
+    (
+        "member.has_active_loans",
+        "anggota {id} masih punya {count} pinjaman aktif; pakai ?reassign_to= atau ?force=true",
+        "member {id} has {count} active loans; use ?reassign_to= or ?force=true",
+    ),
+    (
+        "member.self_service_disabled",
+        "layanan mandiri anggota nonaktif (MEMBER_LINK_SECRET belum diisi)",
+        "member self-service is disabled (MEMBER_LINK_SECRET not set)",
+    ),
+    (
+        "donation.item_decided",
+        "item donasi {id} sudah {status}",
+        "donation item {id} is already {status}",
+    ),
+    (
+        "purchase.wrong_status",
+        "usulan pembelian {id} berstatus {status}, seharusnya {expected}",
+        "purchase request {id} is {status}, expected {expected}",
+    ),
+    (
+        "api_key.operator_library",
+        "key operator tidak boleh punya library_id",
+        "operator keys must not have a library_id",
+    ),
+    (
+        "api_key.operator_scopes",
+        "key operator tidak memakai scope",
+        "operator keys do not take scopes",
+    ),
+    (
+        "backup.unsupported_version",
+        "versi backup {version} tidak didukung, seharusnya {expected}",
+        "unsupported backup version {version}, expected {expected}",
+    ),
+    (
+        "backup.foreign_database",
+        "backup berasal dari database lain: {table} id {id} sudah dipakai perpustakaan lain",
+        "backup comes from another database: {table} id {id} belongs to another library",
+    ),
+    ("auth.missing_key", "API key tidak dikirim", "missing API key"),
+    ("auth.invalid_key", "API key tidak valid", "invalid API key"),
+    ("auth.not_admin", "bukan API key admin", "not an admin key"),
+    (
+        "auth.operator_key_on_tenant_route",
+        "key operator tidak terikat ke perpustakaan; pakai API key perpustakaan",
+        "operator keys are not bound to a library; use a library API key",
+    ),
+    (
+        "auth.missing_scope",
+        "API key tidak punya scope '{scope}'",
+        "API key is missing scope '{scope}'",
+    ),
+    ("token.invalid", "link akses tidak valid", "access link is invalid"),
+    ("token.expired", "link akses sudah kedaluwarsa", "access link has expired"),
+    ("token.revoked", "link akses sudah dicabut", "access link has been revoked"),
+    (
+        "search.query_too_long",
+        "query sepanjang {len} karakter, maksimal {max}",
+        "query is {len} characters long, at most {max} allowed",
+    ),
+    (
+        "search.invalid_pattern",
+        "regex tidak valid: {reason}",
+        "invalid regex: {reason}",
+    ),
+    (
+        "maintenance.active",
+        "server sedang dalam mode pemeliharaan, perubahan data dinonaktifkan",
+        "server is in maintenance mode, writes are disabled",
+    ),
+    (
+        "maintenance.required",
+        "restore hanya bisa dalam mode pemeliharaan (PUT /admin/maintenance)",
+        "restore requires maintenance mode (PUT /admin/maintenance)",
+    ),
+    ("db.error", "terjadi kesalahan database", "database error"),
+    (
+        "db.still_referenced",
+        "{entity} masih dipakai oleh data di {table}",
+        "{entity} is still referenced by {table}",
+    ),
+    (
+        "db.invalid_reference",
+        "{field} menunjuk ke {entity} yang tidak ada",
+        "{field} refers to a {entity} row that does not exist",
+    ),
+    ("db.duplicate", "data sudah ada ({key})", "duplicate value for {key}"),
+    ("db.too_long", "{field} terlalu panjang", "{field} is too long"),
+];
+
+/// Pesan dari katalog beserta parameternya; dirender sesuai bahasa request.
+#[derive(Debug, Clone)]
+pub struct Message {
+    key: &'static str,
+    params: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    pub fn new(key: &'static str) -> Self {
+        Self { key, params: Vec::new() }
+    }
+
+    pub fn param(mut self, name: &'static str, value: impl fmt::Display) -> Self {
+        self.params.push((name, value.to_string()));
+        self
+    }
+
+    /// Key yang tidak ada di katalog dirender apa adanya, supaya kelihatan saat development.
+    pub fn render(&self, lang: Lang) -> String {
+        let Some(&(_, id, en)) = CATALOGUE.iter().find(|(key, _, _)| *key == self.key) else {
+            return self.key.to_string();
+        };
+        let template = match lang {
+            Lang::Id => id,
+            Lang::En => en,
+        };
+        self.params
+            .iter()
+            .fold(template.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{name}}}"), value)
+            })
+    }
+}
+
+/// Pesan error: dari katalog, atau teks bebas yang belum punya entri katalog.
+#[derive(Debug, Clone)]
+pub enum Text {
+    Plain(String),
+    Catalogue(Message),
+}
+
+impl Text {
+    pub fn render(&self, lang: Lang) -> String {
+        match self {
+            Text::Plain(text) => text.clone(),
+            Text::Catalogue(message) => message.render(lang),
+        }
+    }
+}
+
+/// Dipakai untuk log server: selalu bahasa Inggris.
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(Lang::En))
+    }
+}
+
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        Text::Plain(text)
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        Text::Plain(text.to_string())
+    }
+}
+
+impl From<Message> for Text {
+    fn from(message: Message) -> Self {
+        Text::Catalogue(message)
+    }
+}
+
+/// Render ulang body error sesuai Accept-Language. ApiError selalu dirender dalam
+/// bahasa default dan menitipkan dirinya di extension response, jadi middleware ini
+/// cukup merender ulang kalau client minta bahasa lain.
+pub async fn localize(req: Request, next: Next) -> Response {
+    let lang = req
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(Lang::from_accept_language)
+        .unwrap_or_default();
+
+    let response = next.run(req).await;
+    if lang == Lang::default() {
+        return response;
+    }
+    let Some(error) = response.extensions().get::<ApiError>().cloned() else {
+        return response;
+    };
+    // Header asli (mis. ETag, Retry-After) dipertahankan; hanya body yang diganti.
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    let (_, body) = error.render(lang).into_parts();
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+
+    #[test]
+    fn validation_error_is_translated() {
+        let e = ApiError::bad_request(
+            Message::new("validation.unknown_value")
+                .param("field", "sort")
+                .param("value", "harga")
+                .param("expected", "relevance, title"),
+        );
+        assert_eq!(
+            e.message.render(Lang::Id),
+            "sort 'harga' tidak dikenal, pilihan: relevance, title"
+        );
+        assert_eq!(
+            e.message.render(Lang::En),
+            "unknown sort 'harga', expected relevance, title"
+        );
+    }
+
+    #[test]
+    fn not_found_error_is_translated() {
+        let e = ApiError::missing_resource("not_found.category", 12);
+        assert_eq!(e.message.render(Lang::Id), "kategori 12 tidak ditemukan");
+        assert_eq!(e.message.render(Lang::En), "category 12 not found");
+    }
+
+    #[test]
+    fn catalogue_keys_are_unique_and_use_the_same_placeholders() {
+        let placeholders = |text: &str| {
+            let mut names: Vec<String> = text
+                .split('{')
+                .skip(1)
+                .filter_map(|rest| rest.split_once('}').map(|(name, _)| name.to_string()))
+                .collect();
+            names.sort();
+            names.dedup();
+            names
+        };
+        for (i, (key, id, en)) in CATALOGUE.iter().enumerate() {
+            assert!(
+                CATALOGUE[i + 1..].iter().all(|(other, _, _)| other != key),
+                "duplicate key {key}"
+            );
+            assert_eq!(placeholders(id), placeholders(en), "{key}");
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// Builder iCalendar (RFC 5545) minimal untuk feed batas kembali pinjaman.
+// Hanya VEVENT sepanjang hari; baris dipisah CRLF dan dilipat di 75 oktet.
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+pub const CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Panjang baris maksimal (oktet, tanpa CRLF) sebelum dilipat.
+const LINE_LIMIT: usize = 75;
+
+/// Satu event sepanjang hari.
+pub struct Event {
+    pub uid: String,
+    pub date: NaiveDate,
+    pub summary: String,
+    pub description: String,
+}
+
+/// Kalender berisi beberapa event; `render()` menghasilkan teks .ics.
+pub struct Calendar {
+    name: String,
+    stamp: NaiveDateTime,
+    events: Vec<Event>,
+}
+
+impl Calendar {
+    /// `stamp` dipakai sebagai DTSTAMP semua event (waktu feed dibuat, UTC).
+    pub fn new(name: impl Into<String>, stamp: NaiveDateTime) -> Self {
+        Self { name: name.into(), stamp, events: Vec::new() }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        line(&mut out, "BEGIN:VCALENDAR");
+        line(&mut out, "VERSION:2.0");
+        line(&mut out, "PRODID:-//Perpustakaan//Loans//ID");
+        line(&mut out, "CALSCALE:GREGORIAN");
+        line(&mut out, &format!("X-WR-CALNAME:{}", escape(&self.name)));
+        let stamp = self.stamp.format("%Y%m%dT%H%M%SZ");
+        for event in &self.events {
+            let end = event.date.succ_opt().unwrap_or(event.date);
+            line(&mut out, "BEGIN:VEVENT");
+            line(&mut out, &format!("UID:{}", escape(&event.uid)));
+            line(&mut out, &format!("DTSTAMP:{stamp}"));
+            line(&mut out, &format!("DTSTART;VALUE=DATE:{}", event.date.format("%Y%m%d")));
+            line(&mut out, &format!("DTEND;VALUE=DATE:{}", end.format("%Y%m%d")));
+            line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+            line(&mut out, &format!("DESCRIPTION:{}", escape(&event.description)));
+            line(&mut out, "END:VEVENT");
+        }
+        line(&mut out, "END:VCALENDAR");
+        out
+    }
+}
+
+/// Escape karakter khusus di nilai TEXT: `\`, `;`, `,`, dan newline.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Tulis satu content line, dilipat (CRLF + spasi) tanpa memotong karakter UTF-8.
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+    for c in content.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::public_id::{Entity, HasPublicId};
+
+// Newtype untuk primary key supaya `member_id` tidak bisa ter-bind di tempat `book_id`.
+// Di JSON dan di DB tetap angka biasa (serde/sqlx transparent).
+macro_rules! entity_id {
+    ($(#[$doc:meta])* $name:ident, $entity:expr) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+        #[serde(transparent)]
+        #[sqlx(transparent)]
+        pub struct $name(pub i32);
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl HasPublicId for $name {
+            const ENTITY: Entity = $entity;
+
+            fn from_raw(id: i32) -> Self {
+                Self(id)
+            }
+        }
+    };
+}
+
+entity_id!(
+    /// Primary key tabel `books`.
+    BookId,
+    Entity::Book
+);
+
+entity_id!(
+    /// Primary key tabel `members`.
+    MemberId,
+    Entity::Member
+);
+
+entity_id!(
+    /// Primary key tabel `loans`.
+    LoanId,
+    Entity::Loan
+);
@@ -0,0 +1,531 @@
+use serde::Serialize;
+use sqlx::FromRow;
+
+use crate::ids::{BookId, LoanId, MemberId};
+
+// Invarian yang diperiksa /admin/integrity:
+//   buku:     0 <= available_copies <= total_copies
+//             total_copies - available_copies == jumlah pinjaman aktif
+//             available_copies == SUM(delta) di buku besar stok (stock_movements)
+//   pinjaman: buku dan anggotanya masih ada
+//   anggota:  pinjaman aktif untuk satu buku <= total_copies buku itu
+
+/// Angka stok satu buku beserta jumlah pinjaman aktifnya.
+#[derive(Debug, Clone, FromRow)]
+pub struct StockSnapshot {
+    pub book_id: BookId,
+    pub total_copies: i32,
+    pub available_copies: i32,
+    pub active_loans: i64,
+    /// Jumlah `delta` buku ini di `stock_movements`.
+    pub ledger_balance: i64,
+}
+
+/// Satu pelanggaran invarian.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Violation {
+    NegativeAvailable { available_copies: i32 },
+    AvailableExceedsTotal { available_copies: i32, total_copies: i32 },
+    OnLoanMismatch { on_loan: i64, active_loans: i64 },
+    LedgerMismatch { available_copies: i32, ledger_balance: i64 },
+    OrphanedLoan { book_missing: bool, member_missing: bool },
+    ImpossibleLoanCount { book_id: BookId, active_loans: i64, total_copies: i32 },
+}
+
+/// Pure function: semua invarian yang dilanggar oleh satu buku (kosong = sehat).
+pub fn check_book(stock: &StockSnapshot) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if stock.available_copies < 0 {
+        violations.push(Violation::NegativeAvailable {
+            available_copies: stock.available_copies,
+        });
+    }
+    if stock.available_copies > stock.total_copies {
+        violations.push(Violation::AvailableExceedsTotal {
+            available_copies: stock.available_copies,
+            total_copies: stock.total_copies,
+        });
+    }
+
+    let on_loan = i64::from(stock.total_copies) - i64::from(stock.available_copies);
+    if on_loan != stock.active_loans {
+        violations.push(Violation::OnLoanMismatch {
+            on_loan,
+            active_loans: stock.active_loans,
+        });
+    }
+
+    if i64::from(stock.available_copies) != stock.ledger_balance {
+        violations.push(Violation::LedgerMismatch {
+            available_copies: stock.available_copies,
+            ledger_balance: stock.ledger_balance,
+        });
+    }
+
+    violations
+}
+
+/// Pinjaman beserta status keberadaan buku/anggotanya.
+#[derive(Debug, Clone, FromRow)]
+pub struct LoanRefs {
+    pub loan_id: LoanId,
+    pub existing_book_id: Option<BookId>,
+    pub existing_member_id: Option<MemberId>,
+}
+
+/// Pure function: pinjaman yang menunjuk ke buku/anggota yang sudah tidak ada.
+pub fn check_loan(refs: &LoanRefs) -> Option<Violation> {
+    let book_missing = refs.existing_book_id.is_none();
+    let member_missing = refs.existing_member_id.is_none();
+    (book_missing || member_missing).then_some(Violation::OrphanedLoan {
+        book_missing,
+        member_missing,
+    })
+}
+
+/// Jumlah pinjaman aktif satu anggota untuk satu buku.
+#[derive(Debug, Clone, FromRow)]
+pub struct MemberHolding {
+    pub member_id: MemberId,
+    pub book_id: BookId,
+    pub active_loans: i64,
+    pub total_copies: i32,
+}
+
+/// Pure function: anggota tidak mungkin memegang lebih banyak eksemplar daripada yang dimiliki buku.
+pub fn check_holding(holding: &MemberHolding) -> Option<Violation> {
+    (holding.active_loans > i64::from(holding.total_copies)).then_some(
+        Violation::ImpossibleLoanCount {
+            book_id: holding.book_id,
+            active_loans: holding.active_loans,
+            total_copies: holding.total_copies,
+        },
+    )
+}
+
+/// Kelompokkan pelanggaran per anggota (input diurutkan per `member_id`).
+pub fn member_violations(holdings: &[MemberHolding]) -> Vec<MemberViolations> {
+    let mut out: Vec<MemberViolations> = Vec::new();
+    for holding in holdings {
+        let Some(violation) = check_holding(holding) else {
+            continue;
+        };
+        match out.last_mut() {
+            Some(last) if last.member_id == holding.member_id => last.violations.push(violation),
+            _ => out.push(MemberViolations {
+                member_id: holding.member_id,
+                violations: vec![violation],
+            }),
+        }
+    }
+    out
+}
+
+/// Nilai available_copies yang benar menurut tabel loans, dijepit ke [0, total].
+pub fn expected_available(stock: &StockSnapshot) -> i32 {
+    let expected = i64::from(stock.total_copies) - stock.active_loans;
+    expected.clamp(0, i64::from(stock.total_copies.max(0))) as i32
+}
+
+/// Movement koreksi supaya SUM(delta) buku besar sama dengan `after`; None kalau sudah sama.
+pub fn ledger_correction(stock: &StockSnapshot, after: i32) -> Option<i32> {
+    let delta = i64::from(after) - stock.ledger_balance;
+    (delta != 0).then_some(delta as i32)
+}
+
+/// Pure function: perubahan yang dilakukan recount untuk satu buku, None kalau counter
+/// dan buku besar sudah cocok dengan tabel loans.
+pub fn plan_recount(stock: &StockSnapshot) -> Option<RecountChange> {
+    let after = expected_available(stock);
+    let ledger_correction = ledger_correction(stock, after);
+    (after != stock.available_copies || ledger_correction.is_some()).then_some(RecountChange {
+        book_id: stock.book_id,
+        before: stock.available_copies,
+        after,
+        ledger_balance: stock.ledger_balance,
+        ledger_correction,
+    })
+}
+
+/// Pelanggaran untuk satu buku di laporan integritas.
+#[derive(Debug, Clone, Serialize)]
+pub struct BookViolations {
+    pub book_id: BookId,
+    pub violations: Vec<Violation>,
+}
+
+/// Pelanggaran untuk satu pinjaman di laporan integritas.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoanViolations {
+    pub loan_id: LoanId,
+    pub violations: Vec<Violation>,
+}
+
+/// Pelanggaran untuk satu anggota di laporan integritas.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberViolations {
+    pub member_id: MemberId,
+    pub violations: Vec<Violation>,
+}
+
+/// Laporan `GET`/`POST /admin/integrity`.
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    /// True kalau tidak ada satu pun pelanggaran.
+    pub healthy: bool,
+    pub books_checked: usize,
+    pub books: Vec<BookViolations>,
+    pub loans: Vec<LoanViolations>,
+    pub members: Vec<MemberViolations>,
+    /// Jumlah buku yang available_copies-nya dihitung ulang (hanya di `POST /admin/integrity`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repaired: Option<u64>,
+}
+
+/// Satu buku yang available_copies-nya dan/atau buku besarnya berubah (atau akan berubah)
+/// saat recount.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecountChange {
+    pub book_id: BookId,
+    pub before: i32,
+    pub after: i32,
+    /// SUM(delta) buku besar sebelum recount.
+    pub ledger_balance: i64,
+    /// Delta movement `repair` yang ditulis; None kalau buku besar sudah sama dengan `after`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledger_correction: Option<i32>,
+}
+
+/// Laporan `POST /admin/books/recount`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecountReport {
+    pub dry_run: bool,
+    pub books_checked: usize,
+    pub changed: Vec<RecountChange>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock(total: i32, available: i32, active_loans: i64, ledger_balance: i64) -> StockSnapshot {
+        StockSnapshot {
+            book_id: BookId(1),
+            total_copies: total,
+            available_copies: available,
+            active_loans,
+            ledger_balance,
+        }
+    }
+
+    #[test]
+    fn consistent_book_needs_no_recount() {
+        let s = stock(3, 2, 1, 2);
+        assert!(check_book(&s).is_empty());
+        assert_eq!(plan_recount(&s), None);
+    }
+
+    #[test]
+    fn ledger_correction_targets_new_counter_not_old_one() {
+        // Counter 5 (salah), buku besar 4, seharusnya 3: movement harus -1, bukan -2.
+        let s = stock(3, 5, 0, 4);
+        let change = plan_recount(&s).unwrap();
+        assert_eq!(change.after, 3);
+        assert_eq!(change.ledger_correction, Some(-1));
+        assert_eq!(change.ledger_balance + i64::from(change.ledger_correction.unwrap()), 3);
+    }
+
+    #[test]
+    fn ledger_only_drift_is_repaired() {
+        let s = stock(3, 2, 1, 7);
+        assert_eq!(
+            check_book(&s),
+            vec![Violation::LedgerMismatch {
+                available_copies: 2,
+                ledger_balance: 7,
+            }]
+        );
+        let change = plan_recount(&s).unwrap();
+        assert_eq!((change.before, change.after), (2, 2));
+        assert_eq!(change.ledger_correction, Some(-5));
+    }
+
+    #[test]
+    fn counter_only_drift_leaves_ledger_alone() {
+        let s = stock(3, 1, 1, 2);
+        let change = plan_recount(&s).unwrap();
+        assert_eq!((change.before, change.after), (1, 2));
+        assert_eq!(change.ledger_correction, None);
+    }
+
+    #[test]
+    fn recount_result_satisfies_every_stock_invariant() {
+        for total in 0..4 {
+            for available in -2..6 {
+                for active in 0..5 {
+                    for ledger in -2..6 {
+                        let s = stock(total, available, active, ledger);
+                        let after = match plan_recount(&s) {
+                            Some(change) => StockSnapshot {
+                                available_copies: change.after,
+                                ledger_balance: change.ledger_balance
+                                    + i64::from(change.ledger_correction.unwrap_or(0)),
+                                ..s.clone()
+                            },
+                            None => s.clone(),
+                        };
+                        // OnLoanMismatch tetap muncul kalau pinjaman aktif > total (tidak bisa
+                        // diperbaiki dari angka stok saja); sisanya harus bersih.
+                        let left: Vec<_> = check_book(&after)
+                            .into_iter()
+                            .filter(|v| !matches!(v, Violation::OnLoanMismatch { .. }))
+                            .collect();
+                        assert!(left.is_empty(), "{s:?} -> {left:?}");
+                        if active <= i64::from(total) {
+                            assert!(check_book(&after).is_empty(), "{s:?}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_book_reports_each_broken_invariant() {
+        assert_eq!(
+            check_book(&stock(2, -1, 3, -1)),
+            vec![Violation::NegativeAvailable { available_copies: -1 }]
+        );
+        assert_eq!(
+            check_book(&stock(2, 3, 0, 3)),
+            vec![
+                Violation::AvailableExceedsTotal { available_copies: 3, total_copies: 2 },
+                Violation::OnLoanMismatch { on_loan: -1, active_loans: 0 },
+            ]
+        );
+        assert_eq!(
+            check_book(&stock(3, 3, 1, 3)),
+            vec![Violation::OnLoanMismatch { on_loan: 0, active_loans: 1 }]
+        );
+    }
+
+    #[test]
+    fn expected_available_is_clamped_to_stock_range() {
+        assert_eq!(expected_available(&stock(3, 0, 1, 0)), 2);
+        assert_eq!(expected_available(&stock(3, 0, 0, 0)), 3);
+        // Pinjaman aktif melebihi total: tidak boleh negatif.
+        assert_eq!(expected_available(&stock(2, 1, 5, 0)), 0);
+        // Total negatif (data rusak) tetap menghasilkan 0, bukan panic di clamp.
+        assert_eq!(expected_available(&stock(-1, 0, 0, 0)), 0);
+    }
+
+    #[test]
+    fn check_loan_flags_missing_book_or_member() {
+        let refs = |book: Option<i32>, member: Option<i32>| LoanRefs {
+            loan_id: LoanId(1),
+            existing_book_id: book.map(BookId),
+            existing_member_id: member.map(MemberId),
+        };
+        assert_eq!(check_loan(&refs(Some(1), Some(2))), None);
+        assert_eq!(
+            check_loan(&refs(None, Some(2))),
+            Some(Violation::OrphanedLoan { book_missing: true, member_missing: false })
+        );
+        assert_eq!(
+            check_loan(&refs(None, None)),
+            Some(Violation::OrphanedLoan { book_missing: true, member_missing: true })
+        );
+    }
+
+    fn holding(member: i32, book: i32, active_loans: i64, total_copies: i32) -> MemberHolding {
+        MemberHolding {
+            member_id: MemberId(member),
+            book_id: BookId(book),
+            active_loans,
+            total_copies,
+        }
+    }
+
+    #[test]
+    fn check_holding_allows_up_to_total_copies() {
+        assert_eq!(check_holding(&holding(1, 1, 2, 2)), None);
+        assert_eq!(
+            check_holding(&holding(1, 7, 3, 2)),
+            Some(Violation::ImpossibleLoanCount {
+                book_id: BookId(7),
+                active_loans: 3,
+                total_copies: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn member_violations_groups_by_member() {
+        let grouped = member_violations(&[
+            holding(1, 1, 2, 1),
+            holding(1, 2, 1, 1),
+            holding(1, 3, 4, 2),
+            holding(2, 1, 1, 5),
+            holding(3, 1, 2, 0),
+        ]);
+        let summary: Vec<(MemberId, Vec<BookId>)> = grouped
+            .iter()
+            .map(|m| {
+                let books = m
+                    .violations
+                    .iter()
+                    .map(|v| match v {
+                        Violation::ImpossibleLoanCount { book_id, .. } => *book_id,
+                        other => panic!("unexpected {other:?}"),
+                    })
+                    .collect();
+                (m.member_id, books)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(MemberId(1), vec![BookId(1), BookId(3)]), (MemberId(3), vec![BookId(1)])]
+        );
+        assert!(member_violations(&[]).is_empty());
+    }
+
+    /// Model test: urutan acak pinjam, kembali, dan restock lewat `loan::create_loan` /
+    /// `loan::return_loan` terhadap `repo::memory::MemoryRepo`; semua invarian stok dan
+    /// anggota harus tetap bersih setelah setiap langkah.
+    mod model {
+        use super::*;
+        use crate::clock::FixedClock;
+        use crate::config::AppConfig;
+        use crate::loan::{create_loan, return_loan, LoanError, NewLoan};
+        use crate::repo::memory::{MemoryRepo, LIBRARY_ID};
+        use chrono::{Duration, TimeZone, Utc};
+        use futures_util::FutureExt;
+        use proptest::prelude::*;
+
+        const BOOKS: i32 = 3;
+        const MEMBERS: i32 = 3;
+
+        #[derive(Debug, Clone)]
+        enum Op {
+            Borrow { book: i32, member: i32 },
+            /// Id pinjaman mana pun (yang sudah kembali atau belum ada juga).
+            Return { loan: i32 },
+            Restock { book: i32, copies: i32 },
+        }
+
+        fn op() -> impl Strategy<Value = Op> {
+            prop_oneof![
+                (1..=BOOKS, 1..=MEMBERS).prop_map(|(book, member)| Op::Borrow { book, member }),
+                (1..16i32).prop_map(|loan| Op::Return { loan }),
+                (1..=BOOKS, 1..4i32).prop_map(|(book, copies)| Op::Restock { book, copies }),
+            ]
+        }
+
+        fn snapshots(repo: &MemoryRepo) -> Vec<StockSnapshot> {
+            repo.books
+                .iter()
+                .map(|b| StockSnapshot {
+                    book_id: b.id,
+                    total_copies: b.total_copies,
+                    available_copies: b.available_copies,
+                    active_loans: repo.active_loans_for(|l| l.book_id == b.id),
+                    ledger_balance: repo.ledger_balance(b.id),
+                })
+                .collect()
+        }
+
+        fn holdings(repo: &MemoryRepo) -> Vec<MemberHolding> {
+            let mut out = Vec::new();
+            for m in &repo.members {
+                for b in &repo.books {
+                    out.push(MemberHolding {
+                        member_id: m.id,
+                        book_id: b.id,
+                        active_loans: repo
+                            .active_loans_for(|l| l.member_id == m.id && l.book_id == b.id),
+                        total_copies: b.total_copies,
+                    });
+                }
+            }
+            out
+        }
+
+        /// Jalankan satu langkah; penolakan yang wajar (stok habis, batas pinjaman, pinjaman
+        /// sudah kembali atau tidak ada) bukan error. MemoryRepo tidak pernah menunggu, jadi
+        /// future-nya selesai saat pertama di-poll.
+        fn apply(
+            repo: &mut MemoryRepo,
+            clock: &FixedClock,
+            config: &AppConfig,
+            op: &Op,
+        ) -> Result<(), LoanError> {
+            let result = match *op {
+                Op::Borrow { book, member } => {
+                    let request = NewLoan {
+                        book_id: BookId(book),
+                        member_id: MemberId(member),
+                        due_date: "2099-01-01".into(),
+                    };
+                    let created = create_loan(repo, clock, config, LIBRARY_ID, &request);
+                    created.now_or_never().unwrap().map(drop)
+                }
+                Op::Return { loan } => {
+                    let id = LoanId(loan);
+                    let returned = return_loan(repo, clock, config, LIBRARY_ID, id, false);
+                    returned.now_or_never().unwrap().map(drop)
+                }
+                Op::Restock { book, copies } => {
+                    repo.restock(BookId(book), copies);
+                    Ok(())
+                }
+            };
+            match result {
+                Err(
+                    LoanError::OutOfStock(_)
+                    | LoanError::LimitReached { .. }
+                    | LoanError::NotActive(_)
+                    | LoanError::LoanNotFound(_),
+                ) => Ok(()),
+                other => other,
+            }
+        }
+
+        proptest! {
+            #[test]
+            fn invariants_hold_after_every_step(
+                copies in prop::collection::vec(0..3i32, BOOKS as usize),
+                ops in prop::collection::vec(op(), 0..60),
+            ) {
+                let clock = FixedClock::new(Utc.with_ymd_and_hms(2025, 6, 1, 10, 0, 0).unwrap());
+                let mut config = AppConfig::from_env();
+                config.open_hours = None;
+                config.max_active_loans = Some(2);
+                config.new_member_grace_days = None;
+
+                let mut repo = MemoryRepo::default();
+                let joined = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap().naive_utc();
+                for _ in 0..MEMBERS {
+                    repo.add_member(joined);
+                }
+                for &c in &copies {
+                    repo.add_book(c, joined);
+                }
+
+                for op in &ops {
+                    clock.advance(Duration::days(1));
+                    if let Err(e) = apply(&mut repo, &clock, &config, op) {
+                        prop_assert!(false, "{:?}: unexpected {:?}", op, e);
+                    }
+
+                    for stock in snapshots(&repo) {
+                        prop_assert!(check_book(&stock).is_empty(), "{:?}: {:?}", op, stock);
+                        prop_assert_eq!(plan_recount(&stock), None);
+                    }
+                    prop_assert!(member_violations(&holdings(&repo)).is_empty(), "{:?}", op);
+                }
+            }
+        }
+    }
+}
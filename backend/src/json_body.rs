@@ -0,0 +1,113 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+};
+use serde::de::{Deserialize, DeserializeOwned, Deserializer};
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::i18n::Message;
+use crate::json_case::{camel_to_snake, rename_keys};
+
+/// Pengganti `axum::Json` untuk body request. Error deserialisasi dikirim sebagai
+/// envelope error biasa (422 `invalid_field`) beserta path field-nya, bukan teks polos dari serde.
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json(req.headers()) {
+            return Err(ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                "expected Content-Type: application/json",
+            ));
+        }
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| ApiError::new(e.status(), "invalid_body", e.body_text()))?;
+
+        let value: Value = serde_json::from_slice(&bytes).map_err(|e| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_json",
+                format!("invalid JSON at line {} column {}", e.line(), e.column()),
+            )
+        })?;
+        // Frontend boleh mengirim camelCase; struct kita memakai snake_case.
+        let value = rename_keys(value, camel_to_snake);
+        serde_path_to_error::deserialize(value)
+            .map(JsonBody)
+            .map_err(field_error)
+    }
+}
+
+pub fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| {
+            let mime = mime.trim();
+            mime == "application/json" || mime.ends_with("+json")
+        })
+}
+
+/// Untuk field `Option<Option<T>>` dengan `#[serde(default, deserialize_with = "nullable")]`:
+/// field yang tidak dikirim jadi `None`, `null` eksplisit jadi `Some(None)`.
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// JSON valid tapi tidak cocok dengan struct → 422 `invalid_field` dengan `details.field`
+/// (path, mis. `year` atau `scopes[1]`) dan `details.expected` kalau ada.
+fn field_error(err: serde_path_to_error::Error<serde_json::Error>) -> ApiError {
+    let path = err.path().to_string();
+    let reason = err.into_inner().to_string();
+    let reason = reason.as_str();
+
+    let expected = reason.split_once("expected ").map(|(_, e)| e.to_string());
+    let (message, field) = if reason.starts_with("unknown field `") {
+        // Untuk field asing path-nya sudah memuat nama field itu sendiri.
+        (Message::new("validation.unknown_field").param("field", &path), path)
+    } else if let Some(name) = quoted(reason, "missing field `") {
+        let field = join_path(&path, name);
+        (Message::new("validation.missing").param("field", &field), field)
+    } else {
+        let message = Message::new("validation.invalid_value")
+            .param("field", &path)
+            .param("reason", reason);
+        (message, path)
+    };
+
+    ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_field", message).with_details(
+        serde_json::json!({ "field": field, "expected": expected, "reason": reason }),
+    )
+}
+
+/// Ambil nama di antara `prefix` dan backtick penutup, mis. "missing field `title`".
+fn quoted<'a>(reason: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = reason.strip_prefix(prefix)?;
+    rest.split_once('`').map(|(name, _)| name)
+}
+
+/// Untuk field yang hilang, path dari serde_path_to_error menunjuk ke objek induk (`.` untuk root).
+fn join_path(parent: &str, name: &str) -> String {
+    if parent == "." {
+        name.to_string()
+    } else {
+        format!("{parent}.{name}")
+    }
+}
@@ -0,0 +1,242 @@
+// Dukungan camelCase untuk frontend: body request boleh memakai snake_case atau camelCase
+// (dinormalisasi ke snake_case sebelum deserialisasi), dan body response JSON
+// diubah ke camelCase kalau diminta lewat header `X-Json-Case` atau env JSON_CASE.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header::CONTENT_LENGTH, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::ApiError;
+use crate::json_body::is_json;
+use crate::stream::{RowStyle, Streamed};
+use crate::AppState;
+
+/// Header request untuk memilih casing response (`snake` atau `camel`).
+pub const JSON_CASE_HEADER: &str = "x-json-case";
+
+/// Casing nama field di body response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonCase {
+    #[default]
+    Snake,
+    Camel,
+}
+
+impl JsonCase {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "snake" => Some(Self::Snake),
+            "camel" => Some(Self::Camel),
+            _ => None,
+        }
+    }
+}
+
+/// `totalCopies` → `total_copies`; key yang sudah snake_case tidak berubah.
+pub fn camel_to_snake(key: &str) -> String {
+    let mut out = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            if !out.is_empty() {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// `total_copies` → `totalCopies`. Key yang diawali `_` dibiarkan.
+pub fn snake_to_camel(key: &str) -> String {
+    if key.starts_with('_') {
+        return key.to_string();
+    }
+    let mut out = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Field yang isinya data bebas (key-nya nilai, bukan nama field), mis. `details` audit log
+/// dan error, atau `queries` di /admin/metrics: nama field-nya ikut di-rename, isinya tidak.
+const DATA_KEYS: &[&str] = &["details", "queries"];
+
+/// Ganti semua key object (rekursif) dengan `rename`. Nilai tidak disentuh, begitu juga
+/// isi field di `DATA_KEYS`.
+pub fn rename_keys(value: Value, rename: fn(&str) -> String) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = if DATA_KEYS.contains(&k.as_str()) {
+                        v
+                    } else {
+                        rename_keys(v, rename)
+                    };
+                    (rename(&k), v)
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|v| rename_keys(v, rename)).collect())
+        }
+        other => other,
+    }
+}
+
+/// Response JSON diubah ke camelCase kalau header `X-Json-Case` (atau default JSON_CASE) meminta.
+/// Response non-JSON (CSV) dilewatkan apa adanya; body streaming (`?stream=true`, NDJSON)
+/// tidak di-buffer, casing-nya dipasang per baris lewat `RowStyle`.
+pub async fn convert_response(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let case = req
+        .headers()
+        .get(JSON_CASE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(JsonCase::from_str)
+        .unwrap_or(state.config.json_case);
+    let extensions = req.extensions_mut();
+    let style = extensions.get::<RowStyle>().copied().unwrap_or_default();
+    extensions.insert(RowStyle { case, ..style });
+
+    let response = next.run(req).await;
+    if case == JsonCase::Snake
+        || !is_json(response.headers())
+        || response.extensions().get::<Streamed>().is_some()
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("json_case: failed to buffer response: {e}");
+            return ApiError::internal("failed to read response body").into_response();
+        }
+    };
+    let Ok(value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let converted = match serde_json::to_vec(&rename_keys(value, snake_to_camel)) {
+        Ok(converted) => converted,
+        Err(_) => return Response::from_parts(parts, Body::from(bytes)),
+    };
+    parts.headers.insert(CONTENT_LENGTH, HeaderValue::from(converted.len()));
+    Response::from_parts(parts, Body::from(converted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::{spawn_rows, StreamFormat};
+    use serde_json::json;
+
+    fn snake_row() -> Value {
+        json!({
+            "id": 1,
+            "total_copies": 3,
+            "updated_at": "2025-01-01T00:00:00",
+            "book": { "public_id": "B1", "available_copies": 2 },
+            "items": [{ "due_at": null, "lost_at": null }],
+            "_links": { "self_href": "/books/1" },
+        })
+    }
+
+    #[test]
+    fn key_conversion_round_trips() {
+        for key in ["id", "total_copies", "available_copies", "borrowed_at", "max_active_loans"] {
+            assert_eq!(camel_to_snake(&snake_to_camel(key)), key);
+        }
+        for key in ["id", "totalCopies", "publicId", "maxActiveLoans"] {
+            assert_eq!(snake_to_camel(&camel_to_snake(key)), key);
+        }
+        assert_eq!(snake_to_camel("_links"), "_links");
+    }
+
+    #[test]
+    fn response_round_trips_through_both_casings() {
+        let camel = rename_keys(snake_row(), snake_to_camel);
+        assert_eq!(camel["totalCopies"], 3);
+        assert_eq!(camel["book"]["availableCopies"], 2);
+        assert_eq!(camel["items"][0]["dueAt"], Value::Null);
+        // Key yang diawali `_` tidak diubah, isinya tetap di-rename.
+        assert_eq!(camel["_links"]["selfHref"], "/books/1");
+
+        assert_eq!(rename_keys(camel.clone(), camel_to_snake), snake_row());
+        // Snake → snake tidak mengubah apa pun.
+        assert_eq!(rename_keys(snake_row(), camel_to_snake), snake_row());
+    }
+
+    #[test]
+    fn data_keyed_maps_are_left_alone() {
+        let entry = json!({
+            "entity_id": 4,
+            "details": { "renamed_books": 2, "before": { "parent_id": null } },
+            "queries": { "list_books": { "slow_count": 1 } },
+        });
+        let camel = rename_keys(entry, snake_to_camel);
+        assert_eq!(
+            camel,
+            json!({
+                "entityId": 4,
+                "details": { "renamed_books": 2, "before": { "parent_id": null } },
+                "queries": { "list_books": { "slow_count": 1 } },
+            })
+        );
+    }
+
+    async fn stream_body(format: StreamFormat, case: JsonCase) -> (Response, String) {
+        let rows = vec![Ok(snake_row()), Ok(snake_row())];
+        let style = RowStyle {
+            case,
+            ..RowStyle::default()
+        };
+        let response = spawn_rows(format, style, "t".into(), move |sink| async move {
+            sink.drain(futures_util::stream::iter(rows)).await
+        });
+        let (parts, body) = response.into_parts();
+        let bytes = to_bytes(body, usize::MAX).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        (Response::from_parts(parts, Body::empty()), text)
+    }
+
+    #[tokio::test]
+    async fn streamed_rows_are_converted_per_row() {
+        let (response, body) = stream_body(StreamFormat::JsonArray, JsonCase::Camel).await;
+        assert!(response.extensions().get::<Streamed>().is_some());
+        let rows: Vec<Value> = serde_json::from_str(&body).unwrap();
+        assert_eq!(rows, vec![rename_keys(snake_row(), snake_to_camel); 2]);
+
+        let (_, body) = stream_body(StreamFormat::NdJson, JsonCase::Camel).await;
+        for line in body.lines() {
+            let row: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(row["book"]["publicId"], "B1");
+        }
+
+        let (_, body) = stream_body(StreamFormat::NdJson, JsonCase::Snake).await;
+        let first: Value = serde_json::from_str(body.lines().next().unwrap()).unwrap();
+        assert_eq!(first, snake_row());
+    }
+}
@@ -0,0 +1,26 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Satu perpustakaan (tenant) di tabel `libraries`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Library {
+    pub id: i32,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Payload untuk mendaftarkan perpustakaan baru.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewLibrary {
+    pub name: String,
+}
+
+/// Hasil provisioning: perpustakaan baru + API key pertamanya.
+/// `api_key` hanya dikirim sekali di sini, yang disimpan di DB cuma hash-nya.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProvisionedLibrary {
+    pub library: Library,
+    pub api_key: String,
+}
@@ -1,23 +1,1019 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use sqlx::FromRow;
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::book::{self, Book};
+use crate::clock::Clock;
+use crate::config::AppConfig;
+use crate::fine::{late_fine, Fine};
+use crate::ids::{BookId, LoanId, MemberId};
+use crate::member::{self, Member};
+use crate::pagination::{decode_cursor, encode_cursor};
+use crate::public_id::{self, Entity};
+use crate::repo::LoanRepo;
+use crate::stock::{Movement, StockReason};
+use crate::timestamps;
+
+/// Nama field `Loan` yang boleh dipilih lewat `?fields=`.
+pub const FIELDS: &[&str] = &[
+    "id",
+    "public_id",
+    "book_id",
+    "member_id",
+    "borrowed_at",
+    "due_at",
+    "returned_at",
+    "lost_at",
+];
 
 /// Baris peminjaman di tabel `loans`.
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Loan {
-    pub id: i32,
-    pub book_id: i32,
-    pub member_id: i32,
+    pub id: LoanId,
+    pub public_id: String,
+    pub book_id: BookId,
+    pub member_id: MemberId,
     pub borrowed_at: NaiveDateTime,
     pub due_at: NaiveDateTime,
     pub returned_at: Option<NaiveDateTime>,
+    /// Terisi kalau pinjaman ditutup karena bukunya hilang (returned_at ikut terisi).
+    pub lost_at: Option<NaiveDateTime>,
+}
+
+/// Filter `GET /loans`: jendela tanggal pinjam (inklusif, YYYY-MM-DD atau RFC 3339).
+#[derive(Debug, Clone, Default)]
+pub struct LoanFilter {
+    pub borrowed_from: Option<NaiveDate>,
+    pub borrowed_to: Option<NaiveDate>,
+}
+
+impl LoanFilter {
+    pub fn is_empty(&self) -> bool {
+        self.borrowed_from.is_none() && self.borrowed_to.is_none()
+    }
+}
+
+/// Posisi paging keyset `GET /loans`: `(borrowed_at, id)` pinjaman terakhir di halaman.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoanCursor {
+    pub borrowed_at: NaiveDateTime,
+    pub id: LoanId,
+}
+
+impl LoanCursor {
+    const TIME_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S%.f";
+
+    pub fn of(loan: &Loan) -> Self {
+        Self {
+            borrowed_at: loan.borrowed_at,
+            id: loan.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        encode_cursor(&format!("{}|{}", self.borrowed_at.format(Self::TIME_FORMAT), self.id))
+    }
+
+    /// `None` kalau cursor rusak/diubah client.
+    pub fn decode(raw: &str) -> Option<Self> {
+        let text = decode_cursor(raw)?;
+        let (borrowed_at, id) = text.split_once('|')?;
+        let id: i32 = id.parse().ok()?;
+        if id <= 0 {
+            return None;
+        }
+        Some(Self {
+            borrowed_at: NaiveDateTime::parse_from_str(borrowed_at, Self::TIME_FORMAT).ok()?,
+            id: LoanId(id),
+        })
+    }
+}
+
+/// Respons `GET /loans/by-status`: pinjaman dikelompokkan untuk papan meja layanan.
+/// Pinjaman hilang (lost) ikut di `returned` karena sudah ditutup.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LoansByStatus {
+    pub active: Vec<Loan>,
+    pub overdue: Vec<Loan>,
+    pub returned: Vec<Loan>,
+}
+
+/// Respons `GET /loans/by-status?counts_only=true`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LoanStatusCounts {
+    pub active: usize,
+    pub overdue: usize,
+    pub returned: usize,
+}
+
+impl LoansByStatus {
+    /// Pure function: kelompokkan berdasarkan `returned_at` dan `due_at` terhadap `now`.
+    pub fn group(loans: Vec<Loan>, now: NaiveDateTime) -> Self {
+        let mut grouped = Self::default();
+        for loan in loans {
+            match loan.returned_at {
+                Some(_) => grouped.returned.push(loan),
+                None if loan.due_at < now => grouped.overdue.push(loan),
+                None => grouped.active.push(loan),
+            }
+        }
+        grouped
+    }
 }
 
 /// Payload untuk membuat peminjaman baru.
 /// Kita kirim tanggal jatuh tempo sebagai string "YYYY-MM-DD" dari frontend.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct NewLoan {
-    pub book_id: i32,
-    pub member_id: i32,
+    pub book_id: BookId,
+    pub member_id: MemberId,
     pub due_date: String, // contoh: "2025-12-01"
 }
+
+// Aturan peminjaman sebagai pure function (`plan_*`): fakta dibaca dari repositori (di dalam
+// transaksi, baris yang relevan dikunci), lalu keputusan diambil tanpa I/O. `create_loan` dan
+// `return_loan` merangkai keduanya di atas `repo::LoanRepo`, jadi handler HTTP maupun jalur
+// lain memakai aturan yang sama.
+
+/// Alasan peminjaman/pengembalian ditolak; dipetakan ke ApiError di `error.rs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoanError {
+    /// `due_date` bukan tanggal `YYYY-MM-DD`.
+    InvalidDueDate(String),
+    DueDateInPast { due: NaiveDate, today: NaiveDate },
+    MemberNotFound(MemberId),
+    BookNotFound(BookId),
+    LoanNotFound(LoanId),
+    /// Anggota sudah mencapai MAX_ACTIVE_LOANS.
+    LimitReached { member_id: MemberId, active: i64, cap: i64 },
+    OutOfStock(BookId),
+    /// Pinjaman sudah dikembalikan (atau ditutup karena hilang).
+    NotActive(LoanId),
+    /// Peminjaman di luar OPEN_HOURS; `now` = jam lokal perpustakaan.
+    OutsideOpenHours { now: NaiveTime, hours: OpenHours },
+    /// Repositori gagal (error DB), bukan penolakan aturan.
+    Storage(StorageError),
+}
+
+/// Error repositori di dalam `LoanError::Storage`. Dibungkus `Arc` supaya `LoanError` tetap
+/// `Clone`; dua error storage hanya sama kalau keduanya error yang sama persis.
+#[derive(Debug, Clone)]
+pub struct StorageError(pub Arc<sqlx::Error>);
+
+impl PartialEq for StorageError {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for StorageError {}
+
+impl From<sqlx::Error> for LoanError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Storage(StorageError(Arc::new(e)))
+    }
+}
+
+/// Jam buka untuk peminjaman (OPEN_HOURS), ditulis `08:00-20:00` di zona perpustakaan.
+/// Jam buka ikut dihitung, jam tutup tidak; tutup lebih awal dari buka berarti lewat tengah
+/// malam (`20:00-02:00`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenHours {
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+}
+
+impl OpenHours {
+    pub fn from_str(s: &str) -> Option<Self> {
+        let (open, close) = s.trim().split_once('-')?;
+        let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").ok();
+        let (open, close) = (parse(open)?, parse(close)?);
+        (open != close).then_some(Self { open, close })
+    }
+
+    pub fn contains(self, time: NaiveTime) -> bool {
+        if self.open < self.close {
+            self.open <= time && time < self.close
+        } else {
+            time >= self.open || time < self.close
+        }
+    }
+}
+
+impl fmt::Display for OpenHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.open.format("%H:%M"), self.close.format("%H:%M"))
+    }
+}
+
+impl Serialize for OpenHours {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Pure function: tolak peminjaman kalau OPEN_HOURS diisi dan `local_now` di luarnya.
+pub fn check_open_hours(hours: Option<OpenHours>, local_now: NaiveTime) -> Result<(), LoanError> {
+    match hours {
+        Some(hours) if !hours.contains(local_now) => {
+            Err(LoanError::OutsideOpenHours { now: local_now, hours })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fakta untuk memutuskan satu peminjaman baru.
+#[derive(Debug, Clone, Copy)]
+pub struct LoanContext {
+    pub member_exists: bool,
+    /// Pinjaman aktif anggota saat ini.
+    pub active_loans: i64,
+    /// None = buku tidak ada di perpustakaan ini.
+    pub available_copies: Option<i32>,
+    /// Periode pinjam maksimal dari daftar reserve aktif yang memuat buku ini.
+    pub reserve_loan_days: Option<i32>,
+}
+
+/// Pure function: parse `due_date` dari payload; tanggal yang sudah lewat ditolak.
+/// Boleh `YYYY-MM-DD` atau RFC 3339; untuk RFC 3339 yang dipakai tanggal di offset-nya sendiri.
+pub fn parse_due_date(raw: &str, today: NaiveDate) -> Result<NaiveDate, LoanError> {
+    let due =
+        timestamps::parse_date(raw).ok_or_else(|| LoanError::InvalidDueDate(raw.to_string()))?;
+    if due < today {
+        return Err(LoanError::DueDateInPast { due, today });
+    }
+    Ok(due)
+}
+
+/// Pure function: cek anggota, batas pinjaman aktif, dan stok, lalu tentukan `due_at`
+/// (dipotong ke periode daftar reserve kalau bukunya sedang di-reserve).
+pub fn plan_new_loan(
+    request: &NewLoan,
+    due: NaiveDate,
+    ctx: &LoanContext,
+    today: NaiveDate,
+    max_active_loans: Option<i64>,
+) -> Result<NaiveDateTime, LoanError> {
+    if !ctx.member_exists {
+        return Err(LoanError::MemberNotFound(request.member_id));
+    }
+    if let Some(cap) = max_active_loans {
+        if ctx.active_loans >= cap {
+            return Err(LoanError::LimitReached {
+                member_id: request.member_id,
+                active: ctx.active_loans,
+                cap,
+            });
+        }
+    }
+    let Some(available) = ctx.available_copies else {
+        return Err(LoanError::BookNotFound(request.book_id));
+    };
+    if available <= 0 {
+        return Err(LoanError::OutOfStock(request.book_id));
+    }
+
+    let due_at = due.and_time(NaiveTime::MIN);
+    Ok(match ctx.reserve_loan_days {
+        Some(days) => {
+            let cap = (today + Duration::days(i64::from(days))).and_time(NaiveTime::MIN);
+            due_at.min(cap)
+        }
+        None => due_at,
+    })
+}
+
+/// Pure function: pengembalian pinjaman `loan` pada `now`; hasilnya denda keterlambatan
+/// (0 kalau tepat waktu).
+pub fn plan_return(loan: &Loan, now: NaiveDateTime, fine_per_day: i64) -> Result<i64, LoanError> {
+    if loan.returned_at.is_some() {
+        return Err(LoanError::NotActive(loan.id));
+    }
+    Ok(late_fine(loan.due_at, now, fine_per_day))
+}
+
+/// Buat peminjaman `request` di perpustakaan `library_id`: cek jam buka dan tanggal jatuh
+/// tempo, kumpulkan fakta (baris buku dikunci supaya dua peminjaman bersamaan tidak sama-sama
+/// melihat stok terakhir), putuskan lewat `plan_new_loan`, lalu insert, kurangi stok, dan
+/// catat di buku besar stok. Commit dan audit urusan pemanggil.
+pub async fn create_loan(
+    repo: &mut impl LoanRepo,
+    clock: &dyn Clock,
+    config: &AppConfig,
+    library_id: i32,
+    request: &NewLoan,
+) -> Result<Loan, LoanError> {
+    let now = clock.now_naive();
+    let today = clock.now().date_naive();
+    // OPEN_HOURS berlaku di jam lokal perpustakaan (LIBRARY_UTC_OFFSET).
+    let local_now = clock.now().with_timezone(&config.library_utc_offset.0);
+    check_open_hours(config.open_hours, local_now.time())?;
+    let due = parse_due_date(&request.due_date, today)?;
+
+    let joined_at = repo
+        .find_member(library_id, request.member_id)
+        .await?
+        .map(|member| member.joined_at);
+    // Anggota baru dalam masa orientasi (NEW_MEMBER_GRACE_DAYS) memakai batas tersendiri.
+    let max_active_loans = match joined_at {
+        Some(joined_at) => config.max_active_loans_for(joined_at, now),
+        None => config.max_active_loans,
+    };
+    let context = LoanContext {
+        member_exists: joined_at.is_some(),
+        active_loans: repo.active_loans(library_id, request.member_id).await?,
+        reserve_loan_days: repo.reserve_loan_days(library_id, request.book_id, today).await?,
+        available_copies: repo
+            .lock_book(library_id, request.book_id)
+            .await?
+            .map(|book| book.available_copies),
+    };
+    let due_at = plan_new_loan(request, due, &context, today, max_active_loans)?;
+
+    let mut attempts = 1;
+    let id = loop {
+        let public_id = public_id::generate(Entity::Loan);
+        match repo
+            .insert_loan(library_id, &public_id, request.book_id, request.member_id, due_at)
+            .await
+        {
+            Err(e) if public_id::is_collision(&e) && attempts < public_id::MAX_INSERT_ATTEMPTS => {
+                attempts += 1;
+            }
+            other => break other?,
+        }
+    };
+
+    let movement = Movement {
+        library_id,
+        book_id: request.book_id,
+        delta: -1,
+        reason: StockReason::Loan,
+        reference_id: Some(id.0),
+    };
+    repo.move_stock(movement, now).await?;
+
+    repo.find_loan(library_id, id).await?.ok_or(LoanError::LoanNotFound(id))
+}
+
+/// Hasil `return_loan`.
+#[derive(Debug, Clone)]
+pub struct ReturnedLoan {
+    /// Pinjaman setelah `returned_at` diisi.
+    pub loan: Loan,
+    /// Denda keterlambatan yang dicatat (0 = tepat waktu).
+    pub fine: i64,
+}
+
+/// Kembalikan pinjaman `id`: kunci barisnya (pengembalian kedua yang datang bersamaan
+/// menunggu di sini lalu ditolak `plan_return`), set returned_at, kembalikan stok lewat buku
+/// besar, dan catat denda keterlambatan (langsung lunas kalau `pay_fine`). Commit dan audit
+/// urusan pemanggil.
+pub async fn return_loan(
+    repo: &mut impl LoanRepo,
+    clock: &dyn Clock,
+    config: &AppConfig,
+    library_id: i32,
+    id: LoanId,
+    pay_fine: bool,
+) -> Result<ReturnedLoan, LoanError> {
+    let now = clock.now_naive();
+    let loan = repo.lock_loan(library_id, id).await?.ok_or(LoanError::LoanNotFound(id))?;
+    let fine = plan_return(&loan, now, config.fine_per_day)?;
+
+    repo.mark_returned(library_id, id, now).await?;
+    let movement = Movement {
+        library_id,
+        book_id: loan.book_id,
+        delta: 1,
+        reason: StockReason::Return,
+        reference_id: Some(id.0),
+    };
+    repo.move_stock(movement, now).await?;
+    if fine > 0 {
+        repo.insert_late_fine(library_id, &loan, fine, pay_fine.then_some(now)).await?;
+    }
+
+    let loan = repo.find_loan(library_id, id).await?.ok_or(LoanError::LoanNotFound(id))?;
+    Ok(ReturnedLoan { loan, fine })
+}
+
+/// Payload `POST /loans/:id/return-and-pay`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReturnAndPay {
+    /// True = denda keterlambatan langsung dicatat lunas.
+    #[serde(default)]
+    pub pay_fine: bool,
+}
+
+/// Respons `POST /loans/:id/return` dan `/return-and-pay`: pinjaman yang sudah ditutup dan dendanya
+/// (None kalau tepat waktu).
+#[derive(Debug, Clone, Serialize)]
+pub struct LoanReturn {
+    pub loan: Loan,
+    pub fine: Option<Fine>,
+}
+
+/// Batas `days` untuk perpanjangan massal; lebih dari setahun hampir pasti salah ketik.
+pub const MAX_EXTEND_DAYS: i32 = 365;
+
+/// Payload `POST /admin/extend-all-loans` (mis. saat perpustakaan tutup/libur).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtendLoans {
+    pub days: i32,
+}
+
+/// Peminjaman beserta buku dan/atau anggotanya.
+/// Field yang tidak diminta (atau datanya sudah tidak ada) tidak ikut diserialisasi.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoanDetail {
+    #[serde(flatten)]
+    pub loan: Loan,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub book: Option<Book>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member: Option<Member>,
+}
+
+/// Relasi yang di-embed lewat `?include=book,member`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoanInclude {
+    pub book: bool,
+    pub member: bool,
+}
+
+impl LoanInclude {
+    /// Parse daftar dipisah koma; nilai yang tidak dikenal ditolak.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let mut include = Self::default();
+        for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part {
+                "book" => include.book = true,
+                "member" => include.member = true,
+                other => return Err(format!("unknown include '{other}', expected book or member")),
+            }
+        }
+        Ok(include)
+    }
+
+    pub fn any(&self) -> bool {
+        self.book || self.member
+    }
+
+    /// Relasi yang boleh dipilih dengan path bertitik di `?fields=` (`book.title`, ...).
+    pub fn nested_fields(&self) -> Vec<(&'static str, &'static [&'static str])> {
+        let mut nested = Vec::new();
+        if self.book {
+            nested.push(("book", book::FIELDS));
+        }
+        if self.member {
+            nested.push(("member", member::FIELDS));
+        }
+        nested
+    }
+}
+
+/// Satu baris `GET /stats/busiest-days`: jumlah pinjaman per hari (dan jam) dalam seminggu.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct BusyDay {
+    /// Hasil `DAYOFWEEK()` MySQL: 1 = Minggu … 7 = Sabtu.
+    pub day_of_week: i64,
+    #[sqlx(skip)]
+    pub day_name: &'static str,
+    /// Jam 0–23; hanya ada kalau `?by_hour=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hour: Option<i64>,
+    pub loans: i64,
+}
+
+impl BusyDay {
+    /// Nama hari untuk `DAYOFWEEK()`.
+    pub fn day_name(day_of_week: i64) -> &'static str {
+        match day_of_week {
+            1 => "sunday",
+            2 => "monday",
+            3 => "tuesday",
+            4 => "wednesday",
+            5 => "thursday",
+            6 => "friday",
+            7 => "saturday",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Peminjaman yang `book_id` atau `member_id`-nya sudah tidak ada di DB
+/// (sisa hard delete sebelum ada guard referensial).
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanedLoan {
+    #[serde(flatten)]
+    pub loan: Loan,
+    pub book_missing: bool,
+    pub member_missing: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn request() -> NewLoan {
+        NewLoan {
+            book_id: BookId(3),
+            member_id: MemberId(5),
+            due_date: "2025-06-20".into(),
+        }
+    }
+
+    fn ctx() -> LoanContext {
+        LoanContext {
+            member_exists: true,
+            active_loans: 0,
+            available_copies: Some(1),
+            reserve_loan_days: None,
+        }
+    }
+
+    fn loan(due_at: &str, returned_at: Option<&str>) -> Loan {
+        let at = |s: &str| date(s).and_time(NaiveTime::MIN);
+        Loan {
+            id: LoanId(9),
+            public_id: "L9".into(),
+            book_id: BookId(3),
+            member_id: MemberId(5),
+            borrowed_at: at("2025-06-01"),
+            due_at: at(due_at),
+            returned_at: returned_at.map(at),
+            lost_at: None,
+        }
+    }
+
+    const TODAY: &str = "2025-06-10";
+
+    #[test]
+    fn parse_due_date_accepts_plain_date_and_rfc3339() {
+        let today = date(TODAY);
+        assert_eq!(parse_due_date("2025-06-20", today), Ok(date("2025-06-20")));
+        assert_eq!(parse_due_date("2025-06-10", today), Ok(today));
+        // Tanggal diambil di offset-nya sendiri, bukan dikonversi ke UTC.
+        assert_eq!(parse_due_date("2025-06-20T23:30:00+07:00", today), Ok(date("2025-06-20")));
+    }
+
+    #[test]
+    fn parse_due_date_rejects_garbage() {
+        for raw in ["", "20-06-2025", "2025-13-01", "besok"] {
+            assert_eq!(
+                parse_due_date(raw, date(TODAY)),
+                Err(LoanError::InvalidDueDate(raw.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_due_date_rejects_past() {
+        assert_eq!(
+            parse_due_date("2025-06-09", date(TODAY)),
+            Err(LoanError::DueDateInPast {
+                due: date("2025-06-09"),
+                today: date(TODAY),
+            })
+        );
+    }
+
+    #[test]
+    fn plan_new_loan_uses_due_date_at_midnight() {
+        let due = date("2025-06-20");
+        assert_eq!(
+            plan_new_loan(&request(), due, &ctx(), date(TODAY), Some(3)),
+            Ok(due.and_time(NaiveTime::MIN))
+        );
+    }
+
+    #[test]
+    fn plan_new_loan_rejects_missing_member() {
+        let ctx = LoanContext {
+            member_exists: false,
+            ..ctx()
+        };
+        assert_eq!(
+            plan_new_loan(&request(), date("2025-06-20"), &ctx, date(TODAY), None),
+            Err(LoanError::MemberNotFound(MemberId(5)))
+        );
+    }
+
+    #[test]
+    fn plan_new_loan_enforces_active_loan_cap() {
+        let ctx = LoanContext {
+            active_loans: 3,
+            ..ctx()
+        };
+        assert_eq!(
+            plan_new_loan(&request(), date("2025-06-20"), &ctx, date(TODAY), Some(3)),
+            Err(LoanError::LimitReached {
+                member_id: MemberId(5),
+                active: 3,
+                cap: 3,
+            })
+        );
+        // Tanpa MAX_ACTIVE_LOANS tidak ada batas.
+        assert!(plan_new_loan(&request(), date("2025-06-20"), &ctx, date(TODAY), None).is_ok());
+    }
+
+    #[test]
+    fn plan_new_loan_rejects_missing_book_and_empty_stock() {
+        let missing = LoanContext {
+            available_copies: None,
+            ..ctx()
+        };
+        assert_eq!(
+            plan_new_loan(&request(), date("2025-06-20"), &missing, date(TODAY), None),
+            Err(LoanError::BookNotFound(BookId(3)))
+        );
+        let empty = LoanContext {
+            available_copies: Some(0),
+            ..ctx()
+        };
+        assert_eq!(
+            plan_new_loan(&request(), date("2025-06-20"), &empty, date(TODAY), None),
+            Err(LoanError::OutOfStock(BookId(3)))
+        );
+    }
+
+    #[test]
+    fn plan_new_loan_caps_due_at_to_reserve_period() {
+        let reserved = LoanContext {
+            reserve_loan_days: Some(2),
+            ..ctx()
+        };
+        assert_eq!(
+            plan_new_loan(&request(), date("2025-06-20"), &reserved, date(TODAY), None),
+            Ok(date("2025-06-12").and_time(NaiveTime::MIN))
+        );
+        // Tanggal yang lebih awal dari batas reserve tidak diperpanjang.
+        assert_eq!(
+            plan_new_loan(&request(), date("2025-06-11"), &reserved, date(TODAY), None),
+            Ok(date("2025-06-11").and_time(NaiveTime::MIN))
+        );
+    }
+
+    #[test]
+    fn plan_return_computes_late_fine() {
+        let now = date("2025-06-23").and_hms_opt(10, 0, 0).unwrap();
+        assert_eq!(plan_return(&loan("2025-06-20", None), now, 1000), Ok(3000));
+        assert_eq!(plan_return(&loan("2025-06-25", None), now, 1000), Ok(0));
+    }
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn open_hours_parse() {
+        let hours = OpenHours::from_str(" 08:00 - 20:00 ").unwrap();
+        assert_eq!((hours.open, hours.close), (time("08:00"), time("20:00")));
+        assert_eq!(hours.to_string(), "08:00-20:00");
+        for bad in ["", "08:00", "8-20", "08:00-25:00", "09:00-09:00"] {
+            assert_eq!(OpenHours::from_str(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn open_hours_open_inclusive_close_exclusive() {
+        let hours = OpenHours::from_str("08:00-20:00").unwrap();
+        assert!(!hours.contains(time("07:59")));
+        assert!(hours.contains(time("08:00")));
+        assert!(hours.contains(time("19:59")));
+        assert!(!hours.contains(time("20:00")));
+        assert!(!hours.contains(time("00:00")));
+    }
+
+    #[test]
+    fn open_hours_past_midnight() {
+        let hours = OpenHours::from_str("20:00-02:00").unwrap();
+        assert!(!hours.contains(time("19:59")));
+        assert!(hours.contains(time("20:00")));
+        assert!(hours.contains(time("23:59")));
+        assert!(hours.contains(time("00:00")));
+        assert!(hours.contains(time("01:59")));
+        assert!(!hours.contains(time("02:00")));
+        assert!(!hours.contains(time("12:00")));
+    }
+
+    #[test]
+    fn check_open_hours_rejects_outside_window() {
+        let hours = OpenHours::from_str("08:00-20:00").unwrap();
+        assert_eq!(check_open_hours(Some(hours), time("08:00")), Ok(()));
+        assert_eq!(
+            check_open_hours(Some(hours), time("20:00")),
+            Err(LoanError::OutsideOpenHours {
+                now: time("20:00"),
+                hours,
+            })
+        );
+        // Tanpa OPEN_HOURS selalu boleh.
+        assert_eq!(check_open_hours(None, time("03:00")), Ok(()));
+    }
+
+    #[test]
+    fn plan_return_rejects_closed_loan() {
+        let now = date("2025-06-23").and_time(NaiveTime::MIN);
+        assert_eq!(
+            plan_return(&loan("2025-06-20", Some("2025-06-21")), now, 1000),
+            Err(LoanError::NotActive(LoanId(9)))
+        );
+    }
+
+    #[test]
+    fn concurrent_returns_increment_stock_once() {
+        use std::sync::{Arc, Barrier, Mutex};
+
+        // Mutex = baris pinjaman yang dikunci `SELECT ... FOR UPDATE` di return_loan:
+        // keputusan plan_return dan update stok terjadi selama kunci dipegang.
+        let row = Arc::new(Mutex::new((loan("2025-06-20", None), 0_i32)));
+        let barrier = Arc::new(Barrier::new(2));
+        let now = date("2025-06-21").and_time(NaiveTime::MIN);
+
+        let returns: Vec<_> = (0..2)
+            .map(|_| {
+                let (row, barrier) = (Arc::clone(&row), Arc::clone(&barrier));
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let mut guard = row.lock().unwrap();
+                    let (loan, available) = &mut *guard;
+                    let planned = plan_return(loan, now, 1000);
+                    if planned.is_ok() {
+                        loan.returned_at = Some(now);
+                        *available += 1;
+                    }
+                    planned
+                })
+            })
+            .collect();
+        let results: Vec<_> = returns.into_iter().map(|t| t.join().unwrap()).collect();
+
+        assert_eq!(results.iter().filter(|r| **r == Ok(1000)).count(), 1);
+        assert!(results.contains(&Err(LoanError::NotActive(LoanId(9)))));
+        let (loan, available) = &*row.lock().unwrap();
+        assert_eq!(*available, 1);
+        assert_eq!(loan.returned_at, Some(now));
+    }
+
+    #[test]
+    fn due_day_boundary_with_fixed_clock() {
+        use crate::clock::{Clock, FixedClock};
+        use chrono::{Duration, TimeZone, Utc};
+
+        // Jatuh tempo 2025-06-20 00:00; jam berhenti tepat sebelum tengah malam berikutnya.
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2025, 6, 20, 23, 59, 59).unwrap());
+        let loan = loan("2025-06-20", None);
+
+        assert_eq!(plan_return(&loan, clock.now_naive(), 1000), Ok(0));
+        let grouped = LoansByStatus::group(vec![loan.clone()], clock.now_naive());
+        assert_eq!(grouped.overdue.len(), 1);
+
+        clock.advance(Duration::seconds(1));
+        assert_eq!(plan_return(&loan, clock.now_naive(), 1000), Ok(1000));
+
+        clock.set(Utc.with_ymd_and_hms(2025, 6, 19, 12, 0, 0).unwrap());
+        let grouped = LoansByStatus::group(vec![loan.clone()], clock.now_naive());
+        assert_eq!((grouped.active.len(), grouped.overdue.len()), (1, 0));
+        assert_eq!(plan_return(&loan, clock.now_naive(), 1000), Ok(0));
+    }
+
+    /// `create_loan` / `return_loan` terhadap `repo::memory::MemoryRepo`, tanpa HTTP dan DB.
+    mod domain {
+        use super::*;
+        use crate::clock::FixedClock;
+        use crate::repo::memory::{MemoryRepo, LIBRARY_ID};
+        use crate::timestamps::UtcOffset;
+        use chrono::{FixedOffset, TimeZone, Utc};
+
+        /// Jam 2025-06-10 10:00 UTC; zona perpustakaan UTC supaya OPEN_HOURS mudah dibaca.
+        fn fixed_clock() -> FixedClock {
+            FixedClock::new(Utc.with_ymd_and_hms(2025, 6, 10, 10, 0, 0).unwrap())
+        }
+
+        fn base_config() -> AppConfig {
+            let mut config = AppConfig::from_env();
+            config.library_utc_offset = UtcOffset(FixedOffset::east_opt(0).unwrap());
+            config.open_hours = None;
+            config.max_active_loans = Some(2);
+            config.new_member_grace_days = None;
+            config.new_member_max_active_loans = None;
+            config.fine_per_day = 1000;
+            config
+        }
+
+        /// Repositori dengan satu anggota lama dan satu buku ber-`copies` eksemplar.
+        fn repo(copies: i32) -> (MemoryRepo, MemberId, BookId) {
+            let mut repo = MemoryRepo::default();
+            let joined = date("2024-01-01").and_time(NaiveTime::MIN);
+            let member = repo.add_member(joined);
+            let book = repo.add_book(copies, joined);
+            (repo, member, book)
+        }
+
+        fn borrow(book_id: BookId, member_id: MemberId, due_date: &str) -> NewLoan {
+            NewLoan { book_id, member_id, due_date: due_date.into() }
+        }
+
+        #[tokio::test]
+        async fn create_loan_takes_one_copy_through_the_ledger() {
+            let (clock, config) = (fixed_clock(), base_config());
+            let (mut repo, member, book) = repo(2);
+            let request = borrow(book, member, "2025-06-20");
+
+            let loan = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request)
+                .await
+                .unwrap();
+
+            assert_eq!((loan.book_id, loan.member_id), (book, member));
+            assert_eq!(loan.due_at, date("2025-06-20").and_time(NaiveTime::MIN));
+            assert!(loan.returned_at.is_none());
+            assert_eq!(repo.book(book).available_copies, 1);
+            assert_eq!(repo.ledger_balance(book), 1);
+            let last = repo.movements.last().unwrap();
+            assert_eq!((last.delta, last.reason), (-1, StockReason::Loan));
+            assert_eq!(last.reference_id, Some(loan.id.0));
+        }
+
+        #[tokio::test]
+        async fn create_loan_rejects_bad_requests_without_writing() {
+            let (clock, config) = (fixed_clock(), base_config());
+            let (mut repo, member, book) = repo(1);
+            let cases = [
+                (borrow(book, member, "besok"), LoanError::InvalidDueDate("besok".into())),
+                (
+                    borrow(book, member, "2025-06-09"),
+                    LoanError::DueDateInPast { due: date("2025-06-09"), today: date("2025-06-10") },
+                ),
+                (borrow(book, MemberId(99), "2025-06-20"), LoanError::MemberNotFound(MemberId(99))),
+                (borrow(BookId(99), member, "2025-06-20"), LoanError::BookNotFound(BookId(99))),
+            ];
+            for (request, expected) in cases {
+                let result = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request);
+                assert_eq!(result.await.unwrap_err(), expected);
+            }
+            // Id dari perpustakaan lain sama dengan id yang tidak ada.
+            let request = borrow(book, member, "2025-06-20");
+            let result = create_loan(&mut repo, &clock, &config, 2, &request).await;
+            assert_eq!(result.unwrap_err(), LoanError::MemberNotFound(member));
+
+            assert!(repo.loans.is_empty());
+            assert_eq!(repo.book(book).available_copies, 1);
+            assert_eq!(repo.movements.len(), 1);
+        }
+
+        #[tokio::test]
+        async fn create_loan_enforces_open_hours_in_library_time() {
+            let clock = fixed_clock();
+            let (mut repo, member, book) = repo(1);
+            let request = borrow(book, member, "2025-06-20");
+            let mut config = base_config();
+            config.open_hours = OpenHours::from_str("08:00-16:00");
+
+            // 10:00 UTC = 17:00 di UTC+7, sudah tutup.
+            config.library_utc_offset = UtcOffset(FixedOffset::east_opt(7 * 3600).unwrap());
+            let result = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request).await;
+            assert!(matches!(result, Err(LoanError::OutsideOpenHours { .. })));
+            assert!(repo.loans.is_empty());
+
+            config.library_utc_offset = UtcOffset(FixedOffset::east_opt(0).unwrap());
+            assert!(create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request).await.is_ok());
+        }
+
+        #[tokio::test]
+        async fn create_loan_enforces_member_limit_and_grace_period() {
+            let (clock, config) = (fixed_clock(), base_config());
+            let (mut repo, member, book) = repo(5);
+            let request = borrow(book, member, "2025-06-20");
+            for _ in 0..2 {
+                create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request).await.unwrap();
+            }
+            let result = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request).await;
+            assert_eq!(
+                result.unwrap_err(),
+                LoanError::LimitReached { member_id: member, active: 2, cap: 2 }
+            );
+
+            // Anggota yang baru bergabung memakai NEW_MEMBER_MAX_ACTIVE_LOANS.
+            let mut config = base_config();
+            config.new_member_grace_days = Some(30);
+            config.new_member_max_active_loans = Some(1);
+            let newcomer = repo.add_member(date("2025-06-01").and_time(NaiveTime::MIN));
+            let request = borrow(book, newcomer, "2025-06-20");
+            create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request).await.unwrap();
+            let result = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request).await;
+            assert_eq!(
+                result.unwrap_err(),
+                LoanError::LimitReached { member_id: newcomer, active: 1, cap: 1 }
+            );
+            assert_eq!(repo.book(book).available_copies, 2);
+        }
+
+        #[tokio::test]
+        async fn create_loan_refuses_empty_stock() {
+            let (clock, config) = (fixed_clock(), base_config());
+            let (mut repo, member, book) = repo(1);
+            let other = repo.add_member(date("2024-01-01").and_time(NaiveTime::MIN));
+            let first = borrow(book, member, "2025-06-20");
+            create_loan(&mut repo, &clock, &config, LIBRARY_ID, &first).await.unwrap();
+
+            let second = borrow(book, other, "2025-06-20");
+            let result = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &second).await;
+            assert_eq!(result.unwrap_err(), LoanError::OutOfStock(book));
+            assert_eq!(repo.loans.len(), 1);
+            assert_eq!(repo.book(book).available_copies, 0);
+            assert_eq!(repo.ledger_balance(book), 0);
+        }
+
+        #[tokio::test]
+        async fn create_loan_caps_due_date_to_reserve_period() {
+            let (clock, config) = (fixed_clock(), base_config());
+            let (mut repo, member, book) = repo(1);
+            repo.reserve_loan_days.insert(book, 3);
+            let request = borrow(book, member, "2025-06-30");
+
+            let loan = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request)
+                .await
+                .unwrap();
+            assert_eq!(loan.due_at, date("2025-06-13").and_time(NaiveTime::MIN));
+        }
+
+        #[tokio::test]
+        async fn create_loan_retries_public_id_collisions() {
+            let (clock, config) = (fixed_clock(), base_config());
+            let (mut repo, member, book) = repo(2);
+            let request = borrow(book, member, "2025-06-20");
+
+            repo.collisions = public_id::MAX_INSERT_ATTEMPTS - 1;
+            create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request).await.unwrap();
+            assert_eq!(repo.loans.len(), 1);
+
+            // Bentrok terus sampai batas percobaan: error DB diteruskan, stok tidak berubah.
+            repo.collisions = public_id::MAX_INSERT_ATTEMPTS;
+            let result = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request).await;
+            match result {
+                Err(LoanError::Storage(e)) => assert!(public_id::is_collision(&e.0)),
+                other => panic!("unexpected {other:?}"),
+            }
+            assert_eq!(repo.loans.len(), 1);
+            assert_eq!(repo.book(book).available_copies, 1);
+        }
+
+        #[tokio::test]
+        async fn return_loan_restores_stock_and_records_fine() {
+            let config = base_config();
+            let (mut repo, member, book) = repo(1);
+            let clock = fixed_clock();
+            let request = borrow(book, member, "2025-06-12");
+            let loan = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request)
+                .await
+                .unwrap();
+
+            // Dua hari lewat jatuh tempo, denda langsung dibayar.
+            clock.set(Utc.with_ymd_and_hms(2025, 6, 14, 9, 0, 0).unwrap());
+            let returned = return_loan(&mut repo, &clock, &config, LIBRARY_ID, loan.id, true)
+                .await
+                .unwrap();
+
+            assert_eq!(returned.fine, 2000);
+            assert_eq!(returned.loan.returned_at, Some(clock.now_naive()));
+            assert_eq!(repo.book(book).available_copies, 1);
+            assert_eq!(repo.ledger_balance(book), 1);
+            assert_eq!(repo.fines, vec![(loan.id, 2000, Some(clock.now_naive()))]);
+        }
+
+        #[tokio::test]
+        async fn return_loan_on_time_has_no_fine_and_runs_once() {
+            let (clock, config) = (fixed_clock(), base_config());
+            let (mut repo, member, book) = repo(1);
+            let request = borrow(book, member, "2025-06-12");
+            let loan = create_loan(&mut repo, &clock, &config, LIBRARY_ID, &request)
+                .await
+                .unwrap();
+
+            let returned = return_loan(&mut repo, &clock, &config, LIBRARY_ID, loan.id, false)
+                .await
+                .unwrap();
+            assert_eq!(returned.fine, 0);
+            assert!(repo.fines.is_empty());
+
+            let again = return_loan(&mut repo, &clock, &config, LIBRARY_ID, loan.id, false);
+            assert_eq!(again.await.unwrap_err(), LoanError::NotActive(loan.id));
+            assert_eq!(repo.book(book).available_copies, 1);
+            assert_eq!(repo.ledger_balance(book), 1);
+
+            let missing = return_loan(&mut repo, &clock, &config, LIBRARY_ID, LoanId(99), true);
+            assert_eq!(missing.await.unwrap_err(), LoanError::LoanNotFound(LoanId(99)));
+        }
+    }
+}
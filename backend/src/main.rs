@@ -1,52 +1,311 @@
 mod config;
+mod clock;
+mod error;
+mod json_body;
+mod json_case;
+mod timestamps;
+mod i18n;
+mod auth;
+mod api_key;
+mod scope;
+mod library;
+mod public_id;
+mod ids;
 mod book;
+mod category;
+mod normalize;
+mod collation;
 mod search;
+mod stock;
+mod similar;
 mod member;
+mod member_token;
 mod loan;
+mod fine;
+mod currency;
+mod ical;
+mod donation;
+mod purchase_request;
+mod reserve_list;
+mod program;
+mod stream;
+mod pagination;
+mod repo;
+mod invariants;
+mod audit;
+mod backup;
+mod maintenance;
+mod metrics;
+mod version;
+mod fields;
 
 use axum::{
-    extract::{Path, Query, State},
-    routing::{delete, get, post},
+    body::Body,
+    extract::{DefaultBodyLimit, FromRequest, Path, Query, Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post, put, MethodRouter},
     Json, Router,
 };
-use chrono::{NaiveDate, NaiveDateTime, Utc};
-use serde::Deserialize;
-use sqlx::{MySqlPool, Row};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, FromRow, MySql, MySqlConnection, MySqlPool, QueryBuilder, Row, Transaction};
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tower_http::cors::{Any, CorsLayer};
+use bytes::Bytes;
+use futures_util::{StreamExt, TryStreamExt};
 
-use crate::book::{Book, NewBook};
-use crate::config::create_pool;
-use crate::member::{Member, NewMember};
-use crate::loan::{Loan, NewLoan};
-use crate::search::{search_books as search_books_fn, SearchMode};
+use crate::audit::{
+    AuditEntry, AuditRecord, ACTION_BOOKS_RECOUNT, ACTION_CREATE, ACTION_DELETE, ACTION_MARK_LOST,
+    ACTION_PURGE_ORPHANS, ACTION_RETURN, ACTION_UPDATE, ACTION_ACCESS_LINK,
+    ACTION_EMAIL_CONFIRM, ACTION_MERGE, ACTION_CATEGORY_CREATE,
+    ACTION_CATEGORY_DELETE, ACTION_CATEGORY_MERGE, ACTION_CATEGORY_UPDATE, ACTION_LOANS_EXTEND,
+    ACTION_REVOKE_ACCESS_LINKS, ACTION_DONATION_ACCEPT, ACTION_DONATION_RECEIVE,
+    ACTION_DONATION_REJECT, ACTION_PURCHASE_REQUEST_APPROVE, ACTION_PURCHASE_REQUEST_RECEIVE,
+    ACTION_PURCHASE_REQUEST_REJECT, ACTION_PURCHASE_REQUEST_SUBMIT, ACTION_RESERVE_LIST_ADD_BOOK,
+    ACTION_RESERVE_LIST_CREATE, ACTION_RESERVE_LIST_REMOVE_BOOK, ACTION_PROGRAM_CREATE,
+    ACTION_PROGRAM_ENROLL, ACTION_RESTORE, ACTION_MAINTENANCE,
+};
+use crate::backup::{
+    Backup, BackupRecord, RestoreReport, ZstLineReader, ZstLineWriter, BACKUP_VERSION,
+    RESTORE_CHUNK_SIZE,
+};
+use crate::api_key::{ApiKeyInfo, IssuedApiKey, NewApiKey};
+use crate::auth::{issue_api_key, KeyCache, Operator, Tenant};
+use crate::scope::{Scope, Scopes};
+use crate::book::{
+    reorder_suggestion, AvailabilityQuery, Book, BookAvailability, LocationCount, NewBook,
+    NextAvailable, RecategorizePreview, ReorderSuggestion, SimilarBook,
+    UpdateBook,
+};
+use crate::category::{Category, MergeCategory, NewCategory, UpdateCategory};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{create_pool, run_migrations, AppConfig, MIGRATOR};
+use crate::error::ApiError;
+use crate::i18n::Message;
+use crate::json_body::JsonBody;
+use crate::library::{Library, NewLibrary, ProvisionedLibrary};
+use crate::donation::{
+    AcceptDonationItem, AcceptedDonationItem, BookDetail, Donation, DonationDetail, DonationItem,
+    DonationProvenance, NewDonation, RejectDonationItem,
+};
+use crate::purchase_request::{
+    ApprovePurchaseRequest, NewPurchaseRequest, PurchaseRequest, ReceivePurchaseRequest,
+    ReceivedPurchaseRequest, RejectPurchaseRequest, SubmittedPurchaseRequest,
+};
+use crate::reserve_list::{AddReserveBook, NewReserveList, ReserveList, ReserveListDetail};
+use crate::program::{LeaderboardEntry, NewEnrollment, NewProgram, Program, ProgramProgress};
+use crate::fine::{Fine, REASON_LATE, REASON_LOST};
+use crate::maintenance::MaintenanceMode;
+use crate::metrics::{QueryMetrics, Stats};
+use crate::ids::{BookId, LoanId, MemberId};
+use crate::invariants::{
+    check_book, check_loan, member_violations, plan_recount, BookViolations, IntegrityReport,
+    LoanRefs, LoanViolations, MemberHolding, RecountReport, StockSnapshot,
+};
+use crate::member::{
+    DuplicateCandidate, DuplicateEmailGroup, InactiveMember, Member, MemberAccessLink, MemberDetail,
+    MemberMerge, MemberSummary, MergeMember, MergeMembers, NewMember, PendingEmail, UpdateMember,
+};
+use crate::member_token::{EmailChangeClaims, MemberClaims, TokenError};
+use crate::public_id::Entity;
+use crate::loan::{BusyDay, ExtendLoans, Loan, LoanReturn, ReturnAndPay, LoanStatusCounts, LoansByStatus, LoanCursor, LoanFilter, LoanInclude, NewLoan, OrphanedLoan};
+use crate::pagination::{CursorPage, Page, PageParams};
+use crate::fields::FieldSet;
+use crate::search::{
+    scored_matches, top_matches, Dedupe, GlobalSearch, Matcher, SearchCursor, SearchHit,
+    SearchSnapshot,
+    SearchMode, SearchSort, SortKey,
+};
+use crate::similar::SimilarityIndex;
+use crate::stock::{Movement, StockLedger, StockMovement, StockReason};
+use crate::version::{Readiness, SchemaStatus, VersionInfo};
+use crate::stream::{RowStyle, StreamFormat};
 
 #[derive(Clone)]
 struct AppState {
     pool: MySqlPool,
+    config: Arc<AppConfig>,
+    clock: Arc<dyn Clock>,
+    /// Durasi query per nama, untuk /admin/stats dan log query lambat.
+    metrics: Arc<QueryMetrics>,
+    /// Cache API key yang sudah diverifikasi.
+    keys: Arc<KeyCache>,
+    /// Mode pemeliharaan: kalau aktif, request tulis non-admin ditolak (lihat `maintenance`).
+    maintenance: Arc<AtomicBool>,
+    /// Vektor judul+kategori untuk /books/:id/similar, dibangun ulang oleh job latar.
+    similar: Arc<SimilarityIndex>,
 }
 
 async fn health_check() -> &'static str {
     "OK - Sudut Buku backend (DB)"
 }
 
+/// GET /version – versi crate, commit git, waktu build, dan backend DB.
+async fn version_info() -> Json<VersionInfo> {
+    Json(VersionInfo::current())
+}
+
+/// GET /health/ready – siap menerima trafik kalau DB bisa di-ping; 503 kalau tidak.
+/// Membawa blok versi yang sama dengan /version.
+async fn readiness(State(state): State<AppState>) -> Response {
+    let database_ok = match sqlx::query("SELECT 1").execute(&state.pool).await {
+        Ok(_) => true,
+        Err(e) => {
+            eprintln!("Readiness check failed: {e}");
+            false
+        }
+    };
+    let body = Readiness {
+        status: if database_ok { "ready" } else { "unavailable" },
+        database_ok,
+        build: VersionInfo::current(),
+    };
+    let status = if database_ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body)).into_response()
+}
+
+/// GET /health/schema – versi migrasi di DB dibandingkan dengan yang dibawa binary ini, untuk
+/// mendeteksi binary baru yang jalan di atas skema lama. 503 kalau tidak cocok.
+async fn schema_status(State(state): State<AppState>) -> Response {
+    let expected: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+    let applied = sqlx::query_as::<_, (i64, bool)>(
+        "SELECT version, success FROM _sqlx_migrations ORDER BY version",
+    )
+    .fetch_all(&state.pool)
+    .await;
+
+    let body = match applied {
+        Ok(applied) => SchemaStatus::compare(&expected, &applied),
+        Err(e) => {
+            eprintln!("Schema check failed: {e}");
+            SchemaStatus::unavailable(&expected)
+        }
+    };
+    let status = if body.up_to_date {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Query string untuk endpoint daftar: `?stream=true` untuk respons streaming.
+/// Header `Accept: application/x-ndjson` juga otomatis streaming (NDJSON).
+#[derive(Deserialize)]
+struct ListParams {
+    #[serde(default)]
+    stream: bool,
+    /// Daftar lengkap tanpa LIST_RESULT_CAP (streaming, wajib API key).
+    #[serde(default)]
+    all: bool,
+}
+
 //
 // ---------------------- BOOKS ----------------------
 //
 
+/// Query string GET /books: seperti `ListParams`, plus `?fields=id,title,...`.
+#[derive(Deserialize)]
+struct BookListParams {
+    #[serde(default)]
+    stream: bool,
+    fields: Option<String>,
+    /// Daftar lengkap tanpa LIST_RESULT_CAP (streaming, wajib API key).
+    #[serde(default)]
+    all: bool,
+}
+
 /// GET /books – ambil semua buku dari tabel `books`.
-async fn list_books(State(state): State<AppState>) -> Json<Vec<Book>> {
-    let result = sqlx::query_as::<_, Book>(
-        "SELECT id, title, author, category, year, total_copies, available_copies FROM books",
+/// Buku 0 eksemplar dilewati kalau HIDE_ZERO_COPY_BOOKS aktif.
+async fn list_books(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
+    style: RowStyle,
+    Query(params): Query<BookListParams>,
+) -> Response {
+    const SQL: &str = "SELECT id, public_id, title, author, category, year, total_copies,
+                              available_copies, version, updated_at, location
+                       FROM books WHERE library_id = ? AND (total_copies > 0 OR NOT ?)";
+    let hide_zero_copy = state.config.hide_zero_copy_books;
+    let fields = match FieldSet::parse_opt(params.fields.as_deref(), book::FIELDS, &[]) {
+        Ok(fields) => fields,
+        Err(e) => return e.into_response(),
+    };
+
+    if params.all {
+        if let Err(e) = pagination::allow_full_listing(&tenant, "/books") {
+            return e.into_response();
+        }
+    }
+
+    if let Some(format) = StreamFormat::negotiate(&headers, params.stream || params.all) {
+        if fields.is_some() {
+            let message = Message::new("validation.not_streamable").param("param", "fields");
+            return ApiError::bad_request(message).into_response();
+        }
+        let pool = state.pool.clone();
+        let library_id = tenant.library_id;
+        let response =
+            stream::spawn_rows(format, style, stream::request_id(&headers), move |sink| async move {
+                let rows = sqlx::query_as::<_, Book>(SQL)
+                    .bind(library_id)
+                    .bind(hide_zero_copy)
+                    .fetch(&pool);
+                sink.drain(rows).await;
+            });
+        return if params.all { pagination::mark_complete(response) } else { response };
+    }
+
+    let limit = pagination::cap_limit(state.config.list_result_cap);
+    let capped = match limit {
+        Some(_) => format!("{SQL} LIMIT ?"),
+        None => SQL.to_string(),
+    };
+    let query = sqlx::query_as::<_, Book>(&capped).bind(tenant.library_id).bind(hide_zero_copy);
+    let query = match limit {
+        Some(limit) => query.bind(limit),
+        None => query,
+    };
+    let result = state.metrics.time("books.list", query.fetch_all(&state.pool)).await;
+    let count = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM books WHERE library_id = ? AND (total_copies > 0 OR NOT ?)",
     )
-    .fetch_all(&state.pool)
-    .await;
+    .bind(tenant.library_id)
+    .bind(hide_zero_copy)
+    .fetch_one(&state.pool);
+
+    let result = match result {
+        Ok(mut books) => pagination::soft_cap(&mut books, state.config.list_result_cap, count)
+            .await
+            .map(|cap| (books, cap)),
+        Err(e) => Err(e),
+    };
 
     match result {
-        Ok(books) => Json(books),
+        Ok((books, cap)) => {
+            let response = match fields {
+                Some(fields) => Json(fields.select(&books)).into_response(),
+                None => Json(books).into_response(),
+            };
+            match cap {
+                Some(cap) => cap.apply(response),
+                None => response,
+            }
+        }
         Err(e) => {
             eprintln!("DB error on list_books: {e}");
-            Json(Vec::new())
+            Json(Vec::<Book>::new()).into_response()
         }
     }
 }
@@ -54,441 +313,5383 @@ async fn list_books(State(state): State<AppState>) -> Json<Vec<Book>> {
 /// POST /books – insert buku baru ke DB.
 async fn create_book(
     State(state): State<AppState>,
-    Json(payload): Json<NewBook>,
-) -> Json<Book> {
-    let result = sqlx::query(
-        "INSERT INTO books (title, author, category, year, total_copies, available_copies)
-         VALUES (?, ?, ?, ?, ?, ?)",
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<NewBook>,
+) -> Result<Json<Book>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let mut tx = state.pool.begin().await?;
+    let (category_id, category) =
+        book_category(&mut tx, &state, tenant.library_id, &payload.category).await?;
+
+    let mut attempts = 1;
+    let result = loop {
+        let res = sqlx::query(
+            "INSERT INTO books (library_id, public_id, title, author, category, year, total_copies, available_copies,
+                                location, category_id, title_sort_key, author_sort_key)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(tenant.library_id)
+        .bind(public_id::generate(Entity::Book))
+        .bind(&payload.title)
+        .bind(&payload.author)
+        .bind(&category)
+        .bind(payload.year)
+        .bind(payload.total_copies)
+        .bind(payload.total_copies) // awalnya stok tersedia = total
+        .bind(&payload.location)
+        .bind(category_id)
+        .bind(collation::sort_key(&payload.title, &state.config.collation_articles))
+        .bind(collation::sort_key(&payload.author, &state.config.collation_articles))
+        .execute(&mut *tx)
+        .await;
+
+        match res {
+            Err(e) if public_id::is_collision(&e) && attempts < public_id::MAX_INSERT_ATTEMPTS => {
+                attempts += 1;
+            }
+            other => break other,
+        }
+    };
+
+    // Gagal = tx di-drop dan di-rollback, kategori yang mungkin baru dibuat ikut batal;
+    // errornya dipetakan `From<sqlx::Error>` (409 duplikat, 422 terlalu panjang, ...).
+    let res = result?;
+    let new_id = BookId(res.last_insert_id() as i32);
+    Movement {
+        library_id: tenant.library_id,
+        book_id: new_id,
+        delta: payload.total_copies,
+        reason: StockReason::Restock,
+        reference_id: None,
+    }
+    .write(&mut *tx, state.clock.now_naive())
+    .await?;
+    tx.commit().await?;
+
+    let fetched = repo::find_book(&state.pool, tenant.library_id, new_id)
+        .await?
+        .ok_or_else(|| ApiError::internal("newly inserted book not found"))?;
+
+    AuditEntry::new(&tenant, ACTION_CREATE, Entity::Book, new_id.0, &fetched)
+        .write_logged(&state.pool, state.clock.now_naive())
+        .await;
+    state.similar.upsert(tenant.library_id, &fetched);
+
+    Ok(Json(fetched))
+}
+
+/// GET /books/:id – satu buku beserta asal-usul donasinya (kalau ada).
+async fn get_book(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<BookDetail>, ApiError> {
+    let id: BookId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+
+    let book = repo::find_book(&state.pool, tenant.library_id, id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.book", &raw_id))?;
+
+    let donations = sqlx::query_as::<_, DonationProvenance>(
+        "SELECT d.id AS donation_id, d.donor_name, d.donated_on, i.copies, i.decided_at AS accepted_at
+         FROM donation_items i
+         JOIN donations d ON d.id = i.donation_id
+         WHERE i.book_id = ? AND d.library_id = ? AND i.status = ?
+         ORDER BY i.decided_at, i.id",
     )
-    .bind(&payload.title)
-    .bind(&payload.author)
-    .bind(&payload.category)
-    .bind(payload.year)
-    .bind(payload.total_copies)
-    .bind(payload.total_copies) // awalnya stok tersedia = total
-    .execute(&state.pool)
+    .bind(id)
+    .bind(tenant.library_id)
+    .bind(donation::STATUS_ACCEPTED)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(BookDetail { book, donations }))
+}
+
+/// GET /books/:id/next-available – perkiraan kapan eksemplar berikutnya kembali:
+/// `due_at` paling awal dari pinjaman aktif buku ini. Null kalau stok masih ada.
+async fn book_next_available(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<NextAvailable>, ApiError> {
+    let id: BookId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+
+    let available_copies = repo::find_book(&state.pool, tenant.library_id, id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.book", &raw_id))?
+        .available_copies;
+
+    let next_available_at = if available_copies > 0 {
+        None
+    } else {
+        sqlx::query_scalar::<_, Option<NaiveDateTime>>(
+            "SELECT MIN(due_at) FROM loans
+             WHERE book_id = ? AND library_id = ? AND returned_at IS NULL",
+        )
+        .bind(id)
+        .bind(tenant.library_id)
+        .fetch_one(&state.pool)
+        .await?
+    };
+
+    Ok(Json(NextAvailable {
+        book_id: id,
+        available_copies,
+        next_available_at,
+    }))
+}
+
+/// GET /books/:id/stock-movements – buku besar stok satu buku (lama ke baru) beserta saldonya.
+async fn book_stock_movements(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<StockLedger>, ApiError> {
+    let id: BookId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+
+    let available_copies = repo::find_book(&state.pool, tenant.library_id, id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.book", &raw_id))?
+        .available_copies;
+
+    let movements = sqlx::query_as::<_, StockMovement>(
+        "SELECT id, delta, reason, reference_id, created_at FROM stock_movements
+         WHERE book_id = ? AND library_id = ?
+         ORDER BY id",
+    )
+    .bind(id)
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(StockLedger {
+        book_id: id,
+        available_copies,
+        balance: movements.iter().map(|m| i64::from(m.delta)).sum(),
+        movements,
+    }))
+}
+
+/// Query string untuk /books/:id/similar?limit=5
+#[derive(Deserialize)]
+struct SimilarParams {
+    limit: Option<u32>,
+}
+
+/// Jumlah buku serupa kalau `limit` tidak diisi.
+const SIMILAR_BOOKS_LIMIT: u32 = 5;
+
+/// GET /books/:id/similar – buku dengan judul/kategori paling mirip (cosine TF-IDF), termasuk
+/// buku yang belum pernah dipinjam. Buku yang belum masuk indeks (mis. ditambah lewat donasi
+/// sebelum job berikutnya) dimasukkan saat itu juga.
+async fn similar_books(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+    Query(params): Query<SimilarParams>,
+) -> Result<Json<Vec<SimilarBook>>, ApiError> {
+    let limit = params.limit.unwrap_or(SIMILAR_BOOKS_LIMIT);
+    if limit == 0 || limit > state.config.max_page_size {
+        return Err(ApiError::out_of_range("limit", state.config.max_page_size));
+    }
+    let id: BookId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+
+    let neighbours = match state.similar.similar(tenant.library_id, id.0, limit as usize) {
+        Some(neighbours) => neighbours,
+        None => {
+            let books = repo::get_books_by_ids(&state.pool, tenant.library_id, &[id]).await?;
+            let book = books
+                .get(&id)
+                .ok_or_else(|| ApiError::missing_resource("not_found.book", &raw_id))?;
+            state.similar.upsert(tenant.library_id, book);
+            state
+                .similar
+                .similar(tenant.library_id, id.0, limit as usize)
+                .unwrap_or_default()
+        }
+    };
+
+    let ids: Vec<BookId> = neighbours.iter().map(|(id, _)| BookId(*id)).collect();
+    let mut books = repo::get_books_by_ids(&state.pool, tenant.library_id, &ids).await?;
+    // Buku yang sudah dihapus tapi masih ada di indeks dilewati saja.
+    let similar = neighbours
+        .into_iter()
+        .filter_map(|(id, similarity)| {
+            books.remove(&BookId(id)).map(|book| SimilarBook { book, similarity })
+        })
+        .collect();
+    Ok(Json(similar))
+}
+
+/// Hitung ulang kunci urut semua buku (semua perpustakaan); mengembalikan jumlah buku.
+async fn sync_all_sort_keys(state: &AppState) -> Result<u64, sqlx::Error> {
+    let mut conn = state.pool.acquire().await?;
+    collation::sync_sort_keys(&mut conn, None, &state.config.collation_articles).await
+}
+
+/// Bangun ulang indeks buku serupa dari seluruh katalog; mengembalikan jumlah buku.
+async fn rebuild_similarity_index(state: &AppState) -> Result<usize, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT library_id, id, public_id, title, author, category, year, total_copies,
+                available_copies, version, updated_at, location
+         FROM books",
+    )
+    .fetch_all(&state.pool)
+    .await?;
+    let books = rows
+        .iter()
+        .map(|row| Ok((row.try_get("library_id")?, Book::from_row(row)?)))
+        .collect::<Result<Vec<(i32, Book)>, sqlx::Error>>()?;
+    let count = books.len();
+    state.similar.rebuild(books);
+    Ok(count)
+}
+
+/// Indeks ulang buku satu kategori setelah nama kategorinya berubah (rename/merge).
+/// Dipanggil setelah commit; kalau gagal cukup dicatat, job latar akan membangun ulang indeks.
+async fn reindex_category(state: &AppState, library_id: i32, category_id: i32) {
+    let books = sqlx::query_as::<_, Book>(
+        "SELECT id, public_id, title, author, category, year, total_copies,
+                available_copies, version, updated_at, location
+         FROM books WHERE category_id = ? AND library_id = ?",
+    )
+    .bind(category_id)
+    .bind(library_id)
+    .fetch_all(&state.pool)
     .await;
+    match books {
+        Ok(books) => books.iter().for_each(|book| state.similar.upsert(library_id, book)),
+        Err(e) => eprintln!("DB error on similarity reindex (category_id={category_id}): {e}"),
+    }
+}
 
-    match result {
-        Ok(res) => {
-            let new_id = res.last_insert_id() as i32;
-            let fetched = sqlx::query_as::<_, Book>(
-                "SELECT id, title, author, category, year, total_copies, available_copies
-                 FROM books WHERE id = ?",
-            )
-            .bind(new_id)
-            .fetch_one(&state.pool)
-            .await
-            .expect("newly inserted book not found");
+/// 400 untuk nilai query/payload yang tidak dikenal; `expected` = daftar nilai yang valid.
+fn unknown_value(field: &str, value: &str, expected: impl std::fmt::Display) -> ApiError {
+    ApiError::bad_request(
+        Message::new("validation.unknown_value")
+            .param("field", field)
+            .param("value", value)
+            .param("expected", expected),
+    )
+}
+
+/// Ambil versi dari header `If-Match` (`"3"`, `W/"3"`, atau `3`). `*` = versi apa saja.
+fn if_match_version(headers: &HeaderMap) -> Result<Option<i32>, ApiError> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+    let raw = value
+        .to_str()
+        .map_err(|_| ApiError::bad_request(Message::new("validation.invalid_if_match")))?
+        .trim();
+    if raw == "*" {
+        return Ok(None);
+    }
+    raw.trim_start_matches("W/")
+        .trim_matches('"')
+        .parse()
+        .map(Some)
+        .map_err(|_| ApiError::bad_request(Message::new("validation.invalid_if_match")))
+}
+
+/// Id dan nama kanonik kategori buku; kategori baru dibuat otomatis kecuali
+/// AUTO_CREATE_CATEGORIES=false, yang membuat nama tak dikenal ditolak 400.
+/// `tx` harus transaksi yang sama dengan INSERT/UPDATE bukunya, supaya kategori yang baru
+/// dibuat ikut di-rollback kalau menyimpan buku gagal.
+async fn book_category(
+    tx: &mut Transaction<'_, MySql>,
+    state: &AppState,
+    library_id: i32,
+    name: &str,
+) -> Result<(i32, String), ApiError> {
+    category::resolve(tx, library_id, name, state.config.auto_create_categories)
+        .await?
+        .ok_or_else(|| {
+            ApiError::bad_request(Message::new("validation.unknown_category").param("name", name))
+        })
+}
+
+/// PUT/PATCH /books/:id – ubah buku dengan optimistic concurrency.
+/// Kalau versi dari If-Match (412) atau `expected_version` (409) sudah basi,
+/// error-nya membawa data buku terbaru di `details.current` supaya client bisa merge ulang.
+async fn update_book(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<UpdateBook>,
+) -> Result<Json<Book>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let id: BookId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+    let if_match = if_match_version(&headers)?;
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+
+    let current = repo::lock_book(&mut tx, tenant.library_id, id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.book", &raw_id))?;
+
+    let stale = |expected: i32| {
+        Message::new("book.stale_version")
+            .param("id", &raw_id)
+            .param("expected", expected)
+            .param("current", current.version)
+    };
+    if let Some(expected) = if_match {
+        if expected != current.version {
+            return Err(ApiError::precondition_failed(stale(expected))
+                .with_details(serde_json::json!({ "current": current })));
+        }
+    } else if let Some(expected) = payload.expected_version {
+        if expected != current.version {
+            return Err(ApiError::conflict(stale(expected))
+                .with_details(serde_json::json!({ "current": current })));
+        }
+    }
+
+    // Perubahan total eksemplar ikut menggeser stok tersedia; yang sedang dipinjam tetap.
+    let total_copies = payload.total_copies.unwrap_or(current.total_copies);
+    let available_copies = current.available_copies + (total_copies - current.total_copies);
+    if available_copies < 0 {
+        return Err(ApiError::conflict(
+            Message::new("book.below_on_loan")
+                .param("total", total_copies)
+                .param("on_loan", current.total_copies - current.available_copies),
+        ));
+    }
+
+    let category = match &payload.category {
+        Some(name) => Some(book_category(&mut tx, &state, tenant.library_id, name).await?),
+        None => None,
+    };
+    let location = match payload.location {
+        Some(location) if location.is_empty() => None,
+        Some(location) => Some(location),
+        None => current.location.clone(),
+    };
+    let title = payload.title.as_ref().unwrap_or(&current.title);
+    let author = payload.author.as_ref().unwrap_or(&current.author);
+
+    sqlx::query(
+        "UPDATE books
+         SET title = ?, author = ?, category = ?, category_id = COALESCE(?, category_id),
+             year = ?, total_copies = ?, available_copies = ?, location = ?,
+             title_sort_key = ?, author_sort_key = ?, version = version + 1, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(title)
+    .bind(author)
+    .bind(category.as_ref().map_or(&current.category, |(_, name)| name))
+    .bind(category.as_ref().map(|(id, _)| *id))
+    .bind(payload.year.unwrap_or(current.year))
+    .bind(total_copies)
+    .bind(available_copies)
+    .bind(&location)
+    .bind(collation::sort_key(title, &state.config.collation_articles))
+    .bind(collation::sort_key(author, &state.config.collation_articles))
+    .bind(now)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    if available_copies != current.available_copies {
+        Movement {
+            library_id: tenant.library_id,
+            book_id: id,
+            delta: available_copies - current.available_copies,
+            reason: StockReason::Adjustment,
+            reference_id: None,
+        }
+        .write(&mut *tx, now)
+        .await?;
+    }
+
+    let updated = repo::find_book(&mut *tx, tenant.library_id, id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.book", &raw_id))?;
+
+    let details = serde_json::json!({ "before": current, "after": updated });
+    AuditEntry::new(&tenant, ACTION_UPDATE, Entity::Book, id.0, details)
+        .write(&mut *tx, now)
+        .await?;
+
+    tx.commit().await?;
+    state.similar.upsert(tenant.library_id, &updated);
+    Ok(Json(updated))
+}
+
+/// DELETE /books/:id – hapus baris dari DB. `:id` boleh public id atau integer lama.
+async fn delete_book(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Json<bool> {
+    let resolved = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await;
+    let id: BookId = match resolved {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("delete_book: {}", e.message);
+            return Json(false);
+        }
+    };
+
+    match repo::delete_book(&state.pool, tenant.library_id, id).await {
+        Ok(true) => {
+            AuditEntry::new(&tenant, ACTION_DELETE, Entity::Book, id.0, ())
+                .write_logged(&state.pool, state.clock.now_naive())
+                .await;
+            state.similar.remove(tenant.library_id, id.0);
+            Json(true)
+        }
+        Ok(false) => Json(false),
+        Err(e) => {
+            eprintln!("DB error on delete_book: {e}");
+            Json(false)
+        }
+    }
+}
+
+/// WHERE untuk recategorize massal: semua buku milik `library_id` dengan kategori `from_category`.
+/// Dipakai bersama oleh preview dan operasi massalnya supaya keduanya selalu menyasar baris yang sama.
+fn push_recategorize_filter(qb: &mut QueryBuilder<'_, MySql>, library_id: i32, from_category: &str) {
+    qb.push(" WHERE library_id = ")
+        .push_bind(library_id)
+        .push(" AND category = ")
+        .push_bind(from_category.to_string());
+}
+
+/// Query string untuk GET /books/recategorize/preview.
+#[derive(Deserialize)]
+struct RecategorizeParams {
+    from_category: String,
+}
+
+/// GET /books/recategorize/preview?from_category= – dry-run: buku yang akan diubah kategorinya.
+async fn preview_recategorize(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<RecategorizeParams>,
+) -> Result<Json<RecategorizePreview>, ApiError> {
+    let from_category =
+        normalize::required("from_category", &params.from_category).map_err(ApiError::bad_request)?;
+    let from_category = from_category.as_str();
+
+    let mut qb = QueryBuilder::new(
+        "SELECT id, public_id, title, author, category, year, total_copies, available_copies,
+                version, updated_at, location
+         FROM books",
+    );
+    push_recategorize_filter(&mut qb, tenant.library_id, from_category);
+    qb.push(" ORDER BY id");
+    let books = qb.build_query_as::<Book>().fetch_all(&state.pool).await?;
+
+    Ok(Json(RecategorizePreview {
+        from_category: from_category.to_string(),
+        count: books.len(),
+        books,
+    }))
+}
+
+/// Query string untuk GET /books/reorder-suggestions.
+#[derive(Deserialize)]
+struct ReorderParams {
+    /// Jendela permintaan dalam hari (default 90).
+    days: Option<u32>,
+    /// Minimal jumlah pinjaman dalam jendela supaya buku dianggap laris (default 3).
+    min_loans: Option<i64>,
+    /// Rasio eksemplar tersedia yang ingin dijaga, 0..1 (default 0.25).
+    min_available_ratio: Option<f64>,
+}
+
+/// GET /books/reorder-suggestions – saran jumlah eksemplar tambahan untuk buku yang
+/// sering dipinjam tapi stoknya menipis. Urut dari saran terbanyak.
+async fn reorder_suggestions(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<ReorderParams>,
+) -> Result<Json<Vec<ReorderSuggestion>>, ApiError> {
+    let days = params.days.unwrap_or(90);
+    if days == 0 || days > 3650 {
+        return Err(ApiError::out_of_range("days", 3650));
+    }
+    let min_loans = params.min_loans.unwrap_or(3).max(1);
+    let ratio = params.min_available_ratio.unwrap_or(0.25);
+    if !(0.0..1.0).contains(&ratio) {
+        return Err(ApiError::bad_request(
+            Message::new("validation.ratio").param("field", "min_available_ratio"),
+        ));
+    }
+    let since = state.clock.now_naive() - chrono::Duration::days(i64::from(days));
+
+    let demand: Vec<(i32, i64)> = sqlx::query_as(
+        "SELECT book_id, COUNT(*) FROM loans
+         WHERE library_id = ? AND borrowed_at >= ?
+         GROUP BY book_id",
+    )
+    .bind(tenant.library_id)
+    .bind(since)
+    .fetch_all(&state.pool)
+    .await?;
+    let demand: HashMap<i32, i64> = demand.into_iter().collect();
+    if demand.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let books = sqlx::query_as::<_, Book>(
+        "SELECT id, public_id, title, author, category, year, total_copies, available_copies,
+                version, updated_at, location
+         FROM books WHERE library_id = ?",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut suggestions: Vec<ReorderSuggestion> = books
+        .into_iter()
+        .filter_map(|book| {
+            let recent = demand.get(&book.id.0).copied().unwrap_or(0);
+            reorder_suggestion(book, recent, days, min_loans, ratio)
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.suggested_additional_copies
+            .cmp(&a.suggested_additional_copies)
+            .then(a.book.id.0.cmp(&b.book.id.0))
+    });
+
+    Ok(Json(suggestions))
+}
+
+/// GET /books/by-location/:location – semua buku di satu rak, urut judul.
+async fn books_by_location(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(location): Path<String>,
+) -> Result<Json<Vec<Book>>, ApiError> {
+    let books = sqlx::query_as::<_, Book>(
+        "SELECT id, public_id, title, author, category, year, total_copies, available_copies,
+                version, updated_at, location
+         FROM books WHERE library_id = ? AND location = ?
+         ORDER BY title_sort_key, title, id",
+    )
+    .bind(tenant.library_id)
+    .bind(normalize::collapse_whitespace(&location))
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(books))
+}
+
+/// GET /locations – kode rak yang dipakai beserta jumlah judul dan eksemplarnya.
+async fn list_locations(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<Vec<LocationCount>>, ApiError> {
+    let locations = sqlx::query_as::<_, LocationCount>(
+        "SELECT location, COUNT(*) AS books,
+                CAST(SUM(total_copies) AS SIGNED) AS total_copies,
+                CAST(SUM(available_copies) AS SIGNED) AS available_copies
+         FROM books WHERE library_id = ? AND location IS NOT NULL
+         GROUP BY location
+         ORDER BY location",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(locations))
+}
+
+/// POST /books/availability – stok tersedia banyak buku sekaligus (satu query),
+/// urut sesuai `ids` di request; id yang tidak ada ditandai `found: false`.
+async fn books_availability(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<AvailabilityQuery>,
+) -> Result<Json<Vec<BookAvailability>>, ApiError> {
+    let max = state.config.max_page_size as usize;
+    if payload.ids.len() > max {
+        return Err(ApiError::out_of_range("ids", max));
+    }
+
+    let books = repo::get_books_by_ids(&state.pool, tenant.library_id, &payload.ids).await?;
+    let availability = payload
+        .ids
+        .iter()
+        .map(|id| {
+            let available_copies = books.get(id).map(|book| book.available_copies);
+            BookAvailability { id: *id, found: available_copies.is_some(), available_copies }
+        })
+        .collect();
+    Ok(Json(availability))
+}
+
+//
+// ---------------------- CATEGORIES ----------------------
+//
+
+async fn load_category<'e, E>(executor: E, library_id: i32, id: i32) -> Result<Category, ApiError>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let sql = format!("{} WHERE c.id = ? AND c.library_id = ?", category::SELECT);
+    sqlx::query_as::<_, Category>(&sql)
+        .bind(id)
+        .bind(library_id)
+        .fetch_optional(executor)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.category", id))
+}
+
+/// Induk harus kategori tingkat atas di perpustakaan yang sama (hierarki maksimal dua tingkat).
+async fn check_category_parent(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    parent_id: i32,
+) -> Result<(), ApiError> {
+    let parent = load_category(&mut *conn, library_id, parent_id).await?;
+    if parent.parent_id.is_some() {
+        return Err(ApiError::bad_request(
+            Message::new("category.too_deep").param("id", parent_id),
+        ));
+    }
+    Ok(())
+}
+
+async fn category_name_taken(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    name: &str,
+    except: Option<i32>,
+) -> Result<bool, sqlx::Error> {
+    let existing: Option<i32> =
+        sqlx::query_scalar("SELECT id FROM categories WHERE library_id = ? AND name = ?")
+            .bind(library_id)
+            .bind(name)
+            .fetch_optional(&mut *conn)
+            .await?;
+    Ok(existing.is_some_and(|id| Some(id) != except))
+}
+
+/// GET /categories – semua kategori, urut nama.
+async fn list_categories(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<Vec<Category>>, ApiError> {
+    let categories = sqlx::query_as::<_, Category>(&format!(
+        "{} WHERE c.library_id = ? ORDER BY c.name, c.id",
+        category::SELECT
+    ))
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(categories))
+}
+
+/// GET /categories/:id
+async fn get_category(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+) -> Result<Json<Category>, ApiError> {
+    Ok(Json(load_category(&state.pool, tenant.library_id, id).await?))
+}
+
+/// POST /categories – buat kategori; nama yang sudah ada (tanpa membedakan huruf besar/kecil)
+/// → 409.
+async fn create_category(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<NewCategory>,
+) -> Result<Json<Category>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+    if let Some(parent_id) = payload.parent_id {
+        check_category_parent(&mut tx, tenant.library_id, parent_id).await?;
+    }
+    if category_name_taken(&mut tx, tenant.library_id, &payload.name, None).await? {
+        return Err(ApiError::conflict(
+            Message::new("category.exists").param("name", &payload.name),
+        ));
+    }
+
+    let res = sqlx::query(
+        "INSERT INTO categories (library_id, name, parent_id, created_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(tenant.library_id)
+    .bind(&payload.name)
+    .bind(payload.parent_id)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+    let category = load_category(&mut *tx, tenant.library_id, res.last_insert_id() as i32).await?;
+
+    AuditEntry::bulk(&tenant, ACTION_CATEGORY_CREATE, None, &category)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+    Ok(Json(category))
+}
+
+/// PUT /categories/:id – ganti nama dan/atau induk. Nama baru ikut ditulis ke semua buku
+/// di kategori ini (versi buku naik); nama yang dipakai kategori lain → 409, pakai merge.
+async fn update_category(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    JsonBody(payload): JsonBody<UpdateCategory>,
+) -> Result<Json<Category>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+    sqlx::query("SELECT id FROM categories WHERE id = ? FOR UPDATE")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    let current = load_category(&mut *tx, tenant.library_id, id).await?;
+
+    if let Some(Some(parent_id)) = payload.parent_id {
+        if parent_id == id {
+            return Err(ApiError::bad_request(Message::new("category.own_parent")));
+        }
+        check_category_parent(&mut tx, tenant.library_id, parent_id).await?;
+        let children: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM categories WHERE parent_id = ?")
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if children > 0 {
+            return Err(ApiError::conflict(Message::new("category.has_children").param("id", id)));
+        }
+    }
+
+    let mut renamed_books = 0;
+    if let Some(name) = payload.name.as_ref().filter(|name| **name != current.name) {
+        if category_name_taken(&mut tx, tenant.library_id, name, Some(id)).await? {
+            return Err(ApiError::conflict(
+                Message::new("category.rename_taken").param("name", name),
+            ));
+        }
+        sqlx::query("UPDATE categories SET name = ? WHERE id = ?")
+            .bind(name)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        renamed_books = sqlx::query(
+            "UPDATE books SET category = ?, version = version + 1, updated_at = ?
+             WHERE category_id = ? AND library_id = ?",
+        )
+        .bind(name)
+        .bind(now)
+        .bind(id)
+        .bind(tenant.library_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+    if let Some(parent_id) = payload.parent_id {
+        sqlx::query("UPDATE categories SET parent_id = ? WHERE id = ?")
+            .bind(parent_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let updated = load_category(&mut *tx, tenant.library_id, id).await?;
+    let details = serde_json::json!({
+        "before": current,
+        "after": updated,
+        "renamed_books": renamed_books,
+    });
+    AuditEntry::bulk(&tenant, ACTION_CATEGORY_UPDATE, Some(Entity::Book), details)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+    if renamed_books > 0 {
+        reindex_category(&state, tenant.library_id, id).await;
+    }
+    Ok(Json(updated))
+}
+
+/// DELETE /categories/:id – hanya kategori tanpa buku dan tanpa sub-kategori.
+async fn delete_category(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+) -> Result<Json<bool>, ApiError> {
+    let now = state.clock.now_naive();
+    let mut tx = state.pool.begin().await?;
+    let current = load_category(&mut *tx, tenant.library_id, id).await?;
+    let children: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM categories WHERE parent_id = ?")
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if current.books > 0 || children > 0 {
+        return Err(ApiError::conflict(
+            Message::new("category.not_empty")
+                .param("id", id)
+                .param("books", current.books)
+                .param("children", children),
+        ));
+    }
+
+    sqlx::query("DELETE FROM categories WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    AuditEntry::bulk(&tenant, ACTION_CATEGORY_DELETE, None, &current)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+    Ok(Json(true))
+}
+
+/// POST /categories/:id/merge – lebur kategori `:id` ke `into_id`: bukunya pindah
+/// (nama ikut diganti), sub-kategorinya pindah ke `into_id`, lalu `:id` dihapus.
+async fn merge_category(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    JsonBody(payload): JsonBody<MergeCategory>,
+) -> Result<Json<Category>, ApiError> {
+    let into_id = payload.into_id;
+    if into_id == id {
+        return Err(ApiError::bad_request(Message::new("category.merge_self")));
+    }
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+    sqlx::query("SELECT id FROM categories WHERE id IN (?, ?) ORDER BY id FOR UPDATE")
+        .bind(id)
+        .bind(into_id)
+        .execute(&mut *tx)
+        .await?;
+    let source = load_category(&mut *tx, tenant.library_id, id).await?;
+    let target = load_category(&mut *tx, tenant.library_id, into_id).await?;
+
+    let children: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM categories WHERE parent_id = ?")
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if children > 0 && target.parent_id.is_some() {
+        return Err(ApiError::conflict(
+            Message::new("category.merge_into_subcategory")
+                .param("id", id)
+                .param("into_id", into_id),
+        ));
+    }
+    if target.parent_id == Some(id) {
+        return Err(ApiError::conflict(
+            Message::new("category.merge_into_child")
+                .param("id", id)
+                .param("into_id", into_id),
+        ));
+    }
+
+    let moved_books = sqlx::query(
+        "UPDATE books SET category_id = ?, category = ?, version = version + 1, updated_at = ?
+         WHERE category_id = ? AND library_id = ?",
+    )
+    .bind(into_id)
+    .bind(&target.name)
+    .bind(now)
+    .bind(id)
+    .bind(tenant.library_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    sqlx::query("UPDATE categories SET parent_id = ? WHERE parent_id = ?")
+        .bind(into_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM categories WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    let details = serde_json::json!({
+        "source": source,
+        "into_id": into_id,
+        "moved_books": moved_books,
+        "moved_subcategories": children,
+    });
+    AuditEntry::bulk(&tenant, ACTION_CATEGORY_MERGE, Some(Entity::Book), details)
+        .write(&mut *tx, now)
+        .await?;
+
+    let merged = load_category(&mut *tx, tenant.library_id, into_id).await?;
+    tx.commit().await?;
+    if moved_books > 0 {
+        reindex_category(&state, tenant.library_id, into_id).await;
+    }
+    Ok(Json(merged))
+}
+
+//
+// ---------------------- SEARCH (PARALLEL) ----------------------
+//
+
+/// Query string untuk /search?mode=title&q=rust
+#[derive(Deserialize)]
+struct SearchParams {
+    mode: Option<String>,
+    q: String,
+    /// Kalau true, mode yang tidak dikenal ditolak dengan 400
+    /// alih-alih diam-diam jatuh ke Title.
+    #[serde(default)]
+    strict_mode: bool,
+    /// Field untuk mode regex: title (default), author, atau category.
+    field: Option<String>,
+    /// Paging cursor: jumlah item per halaman (default DEFAULT_PAGE_SIZE).
+    limit: Option<u32>,
+    /// `next_cursor` dari halaman sebelumnya.
+    cursor: Option<String>,
+    /// relevance, title, author, atau year; default SEARCH_DEFAULT_SORT.
+    sort: Option<String>,
+    /// Sparse fieldset, mis. `id,title,available_copies`.
+    fields: Option<String>,
+    /// `work`: satu hasil per karya, dengan `copies_in_group`. Default mati.
+    dedupe: Option<String>,
+}
+
+/// GET /search – ambil semua buku dari DB, lalu FP + parallel search.
+async fn search_handler(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<SearchParams>,
+) -> Result<Response, ApiError> {
+    // 0) Tentukan mode search (default Title).
+    let mode = match params.mode.as_deref() {
+        None => SearchMode::Title,
+        Some(raw) => match SearchMode::from_str(raw) {
+            Some(mode) => mode,
+            None if params.strict_mode => {
+                return Err(unknown_value("mode", raw, "title, author, category, regex"));
+            }
+            None => SearchMode::Title,
+        },
+    };
+
+    let sort = match params.sort.as_deref() {
+        None => state.config.search_default_sort,
+        Some(raw) => SearchSort::from_str(raw)
+            .ok_or_else(|| unknown_value("sort", raw, "relevance, title, author, year"))?,
+    };
+
+    // Paging hanya aktif kalau diminta, supaya client lama tetap menerima array polos.
+    let paged = params.limit.is_some() || params.cursor.is_some();
+    let limit = params.limit.unwrap_or(state.config.default_page_size);
+    if limit == 0 || limit > state.config.max_page_size {
+        return Err(ApiError::out_of_range("limit", state.config.max_page_size));
+    }
+    let cursor = match params.cursor.as_deref() {
+        None => None,
+        Some(raw) => match SearchCursor::decode(raw, sort) {
+            Some(cursor) => Some(cursor),
+            None => return Err(ApiError::invalid_cursor()),
+        },
+    };
+    // Mode regex mencocokkan pola ke field pilihan; mode lain field-nya ditentukan mode itu sendiri.
+    let field = match (mode, params.field.as_deref()) {
+        (SearchMode::Regex, Some(raw)) => match SearchMode::from_str(raw) {
+            Some(field) if field != SearchMode::Regex => field,
+            _ => return Err(unknown_value("field", raw, "title, author, category")),
+        },
+        (SearchMode::Regex, None) => SearchMode::Title,
+        (mode, _) => mode,
+    };
+
+    // Query divalidasi (dan regex dikompilasi) sekali sebelum menyentuh DB;
+    // error jadi 400 lewat `From<SearchError>`.
+    let matcher = Arc::new(Matcher::new(&params.q, mode)?);
+    let dedupe = match params.dedupe.as_deref() {
+        None => None,
+        Some(raw) => {
+            Some(Dedupe::from_str(raw).ok_or_else(|| unknown_value("dedupe", raw, "work"))?)
+        }
+    };
+    let fields = match dedupe {
+        None => FieldSet::parse_opt(params.fields.as_deref(), book::FIELDS, &[])?,
+        Some(_) => {
+            let known = [book::FIELDS, &["copies_in_group"]].concat();
+            FieldSet::parse_opt(params.fields.as_deref(), &known, &[])?
+        }
+    };
+
+    // 1) Ambil snapshot dari DB (harus konsisten dengan struct Book).
+    let snapshot_query = load_search_snapshot(&state, tenant.library_id);
+    let books_snapshot = match state.metrics.time("search.snapshot", snapshot_query).await {
+        Ok(books) => books,
+        Err(e) => {
+            eprintln!("DB error on search_handler (load books): {e}");
+            Vec::new()
+        }
+    };
+
+    // 2) Bagi data jadi chunk dan proses paralel.
+    // Snapshot dan query dibagi lewat Arc; tiap task hanya mengembalikan indeks yang cocok.
+    let num_cores = num_cpus::get().max(1);
+    let len = books_snapshot.len();
+    if len == 0 {
+        return Ok(if paged {
+            Json(CursorPage::<Book> { items: Vec::new(), next_cursor: None }).into_response()
+        } else {
+            Json(Vec::<Book>::new()).into_response()
+        });
+    }
+    // Jumlah task dibatasi supaya tiap chunk minimal SEARCH_MIN_CHUNK_SIZE buku;
+    // katalog kecil di mesin ber-core banyak tidak dipecah jadi task satu-dua buku.
+    let workers = num_cores.min(len.div_ceil(state.config.search_min_chunk_size)).max(1);
+    let chunk_size = len.div_ceil(workers);
+
+    let snapshot = Arc::new(SearchSnapshot::new(books_snapshot, field, &matcher));
+    let mut tasks = Vec::new();
+
+    for start in (0..len).step_by(chunk_size) {
+        let end = (start + chunk_size).min(len);
+        let snapshot = Arc::clone(&snapshot);
+        let matcher = Arc::clone(&matcher);
+
+        // pure function dari modul search, indeks sudah posisi global di snapshot
+        let handle = tokio::spawn(async move { scored_matches(&snapshot, start..end, &matcher) });
+
+        tasks.push(handle);
+    }
+
+    let mut matched: Vec<(usize, u32)> = Vec::new();
+
+    for task in tasks {
+        match task.await {
+            Ok(mut partial) => matched.append(&mut partial),
+            Err(e) => eprintln!("Task search gagal: {e}"),
+        }
+    }
+
+    // 3) Semua task sudah selesai, jadi snapshot bisa diambil lagi tanpa clone
+    //    dan buku yang cocok dipindahkan (bukan di-clone) ke hasil.
+    let books = Arc::try_unwrap(snapshot)
+        .unwrap_or_else(|shared| (*shared).clone())
+        .into_books();
+
+    // 4) Gabungkan hasil semua chunk sesuai urutan yang diminta (id naik sebagai pemutus seri).
+    //    Dedupe dilakukan sebelum cursor supaya wakil tiap karya sama di semua halaman.
+    //    Mode paging melewati semua yang sudah dilihat (<= cursor) dan mengambil satu
+    //    ekstra untuk tahu apakah masih ada halaman berikutnya.
+    let mut ranked: Vec<(SortKey, usize)> = matched
+        .into_iter()
+        .map(|(i, score)| (sort.key(&books[i], score, &state.config.collation_articles), i))
+        .collect();
+    ranked.sort_by(|a, b| a.0.order(&b.0).then(books[a.1].id.0.cmp(&books[b.1].id.0)));
+    let group_sizes = dedupe.map(|dedupe| search::collapse(&mut ranked, &books, dedupe));
+    if let Some(cursor) = &cursor {
+        ranked.retain(|(key, i)| cursor.precedes(key, books[*i].id.0));
+    }
+
+    let mut next_cursor = None;
+    if paged {
+        let limit = limit as usize;
+        if ranked.len() > limit {
+            ranked.truncate(limit);
+            next_cursor = ranked
+                .last()
+                .map(|(key, i)| SearchCursor { key: key.clone(), id: books[*i].id.0 }.encode());
+        }
+    }
+
+    let mut slots: Vec<Option<Book>> = books.into_iter().map(Some).collect();
+    let items: Vec<SearchHit> = ranked
+        .into_iter()
+        .filter_map(|(_, i)| {
+            let book = slots[i].take()?;
+            let copies_in_group = group_sizes.as_ref().and_then(|sizes| sizes.get(&i).copied());
+            Some(SearchHit { book, copies_in_group })
+        })
+        .collect();
+
+    Ok(match (paged, fields) {
+        (true, Some(fields)) => {
+            Json(fields.select_page(&CursorPage { items, next_cursor })).into_response()
+        }
+        (true, None) => Json(CursorPage { items, next_cursor }).into_response(),
+        (false, Some(fields)) => Json(fields.select(&items)).into_response(),
+        (false, None) => Json(items).into_response(),
+    })
+}
+
+/// Query string untuk /search/global?q=budi
+#[derive(Deserialize)]
+struct GlobalSearchParams {
+    q: String,
+    /// Jumlah maksimum hasil per jenis (default GLOBAL_SEARCH_LIMIT).
+    limit: Option<u32>,
+}
+
+/// Hasil per jenis di /search/global kalau `limit` tidak diisi.
+const GLOBAL_SEARCH_LIMIT: u32 = 10;
+
+/// GET /search/global – satu kotak pencarian untuk meja layanan: buku (judul/pengarang) dan
+/// anggota (nama/email), dikelompokkan per jenis dan diurutkan relevansi.
+async fn global_search(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<GlobalSearchParams>,
+) -> Result<Json<GlobalSearch>, ApiError> {
+    if params.q.trim().is_empty() {
+        return Err(ApiError::missing("q"));
+    }
+    let limit = params.limit.unwrap_or(GLOBAL_SEARCH_LIMIT);
+    if limit == 0 || limit > state.config.max_page_size {
+        return Err(ApiError::out_of_range("limit", state.config.max_page_size));
+    }
+    let limit = limit as usize;
+    let matcher = Matcher::new(&params.q, SearchMode::Title)?;
+
+    let members_query = sqlx::query_as::<_, Member>(
+        "SELECT id, public_id, name, email, joined_at FROM members WHERE library_id = ?",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool);
+    let (books, members) = tokio::try_join!(
+        state.metrics.time("search.global.books", load_search_snapshot(&state, tenant.library_id)),
+        state.metrics.time("search.global.members", members_query),
+    )
+    .map_err(|e| {
+        eprintln!("DB error on global_search: {e}");
+        ApiError::from(e)
+    })?;
+
+    Ok(Json(GlobalSearch {
+        books: top_matches(books, |b| [b.title.as_str(), b.author.as_str()], &matcher, limit),
+        members: top_matches(members, |m| [m.name.as_str(), m.email.as_str()], &matcher, limit),
+    }))
+}
+
+/// Snapshot katalog untuk /search. Buku tanpa eksemplar (katalog saja) disembunyikan kalau
+/// HIDE_ZERO_COPY_BOOKS aktif. Dengan SEARCH_CONSISTENT_SNAPSHOT, query dijalankan di
+/// transaksi REPEATABLE READ read-only yang dibuka dengan consistent snapshot, jadi isinya
+/// persis keadaan DB di satu titik waktu walaupun ada buku yang ditambah saat dimuat.
+async fn load_search_snapshot(state: &AppState, library_id: i32) -> Result<Vec<Book>, sqlx::Error> {
+    const SQL: &str = "SELECT id, public_id, title, author, category, year, total_copies,
+                              available_copies, version, updated_at, location
+                       FROM books WHERE library_id = ? AND (total_copies > 0 OR NOT ?)";
+    let hide_zero = state.config.hide_zero_copy_books;
+
+    if !state.config.search_consistent_snapshot {
+        return sqlx::query_as::<_, Book>(SQL)
+            .bind(library_id)
+            .bind(hide_zero)
+            .fetch_all(&state.pool)
+            .await;
+    }
+
+    // SET TRANSACTION hanya berlaku untuk transaksi berikutnya di koneksi yang sama, jadi
+    // diset dulu lalu `begin()`; guard-nya me-rollback saat di-drop kalau query gagal,
+    // sehingga koneksi tidak pernah kembali ke pool dengan transaksi terbuka.
+    let mut conn = state.pool.acquire().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *conn)
+        .await?;
+    let mut tx = conn.begin().await?;
+    let books = sqlx::query_as::<_, Book>(SQL)
+        .bind(library_id)
+        .bind(hide_zero)
+        .fetch_all(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(books)
+}
+
+//
+// ---------------------- MEMBERS ----------------------
+//
+
+/// GET /members – ambil semua anggota.
+async fn list_members(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
+    style: RowStyle,
+    Query(params): Query<ListParams>,
+) -> Response {
+    const SQL: &str = "SELECT id, public_id, name, email, joined_at FROM members WHERE library_id = ?";
+
+    if params.all {
+        if let Err(e) = pagination::allow_full_listing(&tenant, "/members") {
+            return e.into_response();
+        }
+    }
+
+    if let Some(format) = StreamFormat::negotiate(&headers, params.stream || params.all) {
+        let pool = state.pool.clone();
+        let library_id = tenant.library_id;
+        let response =
+            stream::spawn_rows(format, style, stream::request_id(&headers), move |sink| async move {
+                let rows = sqlx::query_as::<_, Member>(SQL).bind(library_id).fetch(&pool);
+                sink.drain(rows).await;
+            });
+        return if params.all { pagination::mark_complete(response) } else { response };
+    }
+
+    let limit = pagination::cap_limit(state.config.list_result_cap);
+    let capped = match limit {
+        Some(_) => format!("{SQL} LIMIT ?"),
+        None => SQL.to_string(),
+    };
+    let query = sqlx::query_as::<_, Member>(&capped).bind(tenant.library_id);
+    let query = match limit {
+        Some(limit) => query.bind(limit),
+        None => query,
+    };
+    let result = state.metrics.time("members.list", query.fetch_all(&state.pool)).await;
+    let count = sqlx::query_scalar("SELECT COUNT(*) FROM members WHERE library_id = ?")
+        .bind(tenant.library_id)
+        .fetch_one(&state.pool);
+
+    let result = match result {
+        Ok(mut members) => pagination::soft_cap(&mut members, state.config.list_result_cap, count)
+            .await
+            .map(|cap| (members, cap)),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok((members, cap)) => {
+            let response = Json(members).into_response();
+            match cap {
+                Some(cap) => cap.apply(response),
+                None => response,
+            }
+        }
+        Err(e) => {
+            eprintln!("DB error on list_members: {e}");
+            Json(Vec::<Member>::new()).into_response()
+        }
+    }
+}
+
+/// Query string untuk GET /members/inactive.
+#[derive(Deserialize)]
+struct InactiveParams {
+    /// Anggota yang pinjaman terakhirnya sebelum tanggal ini juga ikut. Kosong = belum pernah meminjam.
+    #[serde(default, deserialize_with = "timestamps::opt_date")]
+    since: Option<NaiveDate>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+/// GET /members/inactive?since=YYYY-MM-DD – anggota tanpa pinjaman (sama sekali, atau sejak
+/// `since`) untuk keperluan outreach. Selalu dipaging, urut id.
+async fn list_inactive_members(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<InactiveParams>,
+) -> Result<Json<Vec<InactiveMember>>, ApiError> {
+    let page = PageParams {
+        page: params.page,
+        per_page: params.per_page,
+    }
+    .resolve(&state.config)?;
+
+    let mut qb = QueryBuilder::<MySql>::new(
+        "SELECT m.id, m.public_id, m.name, m.email, m.joined_at,
+                MAX(l.borrowed_at) AS last_borrowed_at
+         FROM members m
+         LEFT JOIN loans l ON l.member_id = m.id AND l.library_id = m.library_id
+         WHERE m.library_id = ",
+    );
+    qb.push_bind(tenant.library_id)
+        .push(" GROUP BY m.id, m.public_id, m.name, m.email, m.joined_at")
+        .push(" HAVING last_borrowed_at IS NULL");
+    if let Some(since) = params.since {
+        qb.push(" OR last_borrowed_at < ").push_bind(since.and_time(NaiveTime::MIN));
+    }
+    qb.push(" ORDER BY m.id LIMIT ")
+        .push_bind(page.limit)
+        .push(" OFFSET ")
+        .push_bind(page.offset);
+
+    let members = qb
+        .build_query_as::<InactiveMember>()
+        .fetch_all(&state.pool)
+        .await?;
+    Ok(Json(members))
+}
+
+/// POST /members – buat anggota baru.
+/// Kalau MAX_MEMBERS di-set dan jumlah anggota sudah mencapai batas, tolak dengan 409.
+/// Hitungan dan INSERT ada di satu transaksi yang mengunci baris perpustakaan, supaya dua
+/// request bersamaan tidak sama-sama lolos batas.
+async fn create_member(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<NewMember>,
+) -> Result<Json<Member>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+
+    let mut tx = state.pool.begin().await?;
+    if let Some(cap) = state.config.max_members {
+        sqlx::query("SELECT id FROM libraries WHERE id = ? FOR UPDATE")
+            .bind(tenant.library_id)
+            .execute(&mut *tx)
+            .await?;
+        let row = sqlx::query("SELECT COUNT(*) AS total FROM members WHERE library_id = ?")
+            .bind(tenant.library_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let total: i64 = row.get("total");
+        if total >= cap {
+            eprintln!(
+                "Member cap reached for library_id={} ({total}/{cap}); raise MAX_MEMBERS to allow more",
+                tenant.library_id
+            );
+            return Err(ApiError::conflict(Message::new("member.limit_reached").param("cap", cap)));
+        }
+    }
+
+    let mut attempts = 1;
+    let result = loop {
+        let res = sqlx::query(
+            "INSERT INTO members (library_id, public_id, name, email) VALUES (?, ?, ?, ?)",
+        )
+        .bind(tenant.library_id)
+        .bind(public_id::generate(Entity::Member))
+        .bind(&payload.name)
+        .bind(&payload.email)
+        .execute(&mut *tx)
+        .await;
+
+        match res {
+            Err(e) if public_id::is_collision(&e) && attempts < public_id::MAX_INSERT_ATTEMPTS => {
+                attempts += 1;
+            }
+            other => break other,
+        }
+    };
+    let res = result?;
+    tx.commit().await?;
+    let new_id = MemberId(res.last_insert_id() as i32);
+
+    // Ambil kembali baris yang baru dibuat untuk mendapatkan joined_at
+    let member = repo::find_member(&state.pool, tenant.library_id, new_id)
+        .await?
+        .ok_or_else(|| ApiError::internal("newly inserted member not found"))?;
+
+    AuditEntry::new(&tenant, ACTION_CREATE, Entity::Member, new_id.0, &member)
+        .write_logged(&state.pool, state.clock.now_naive())
+        .await;
+    Ok(Json(member))
+}
+
+/// Query string untuk DELETE /members/:id.
+#[derive(Deserialize)]
+struct DeleteMemberParams {
+    /// Pindahkan pinjaman aktif ke anggota ini sebelum menghapus.
+    reassign_to: Option<String>,
+    /// Tanpa `reassign_to`: tutup pinjaman aktif (stok dikembalikan) lalu hapus.
+    #[serde(default)]
+    force: bool,
+}
+
+/// DELETE /members/:id – hapus anggota. `:id` boleh public id atau integer lama.
+/// Anggota yang masih punya pinjaman aktif ditolak (409) kecuali pakai
+/// `?reassign_to=<member>` (tetap menghormati MAX_ACTIVE_LOANS) atau `?force=true`.
+async fn delete_member(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+    Query(params): Query<DeleteMemberParams>,
+) -> Result<Json<bool>, ApiError> {
+    let id: MemberId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+    let reassign_to = match params.reassign_to.as_deref() {
+        Some(raw) => Some(public_id::resolve::<MemberId>(&state.pool, tenant.library_id, raw).await?),
+        None => None,
+    };
+    if reassign_to == Some(id) {
+        return Err(ApiError::bad_request(Message::new("member.reassign_self")));
+    }
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+
+    // Kunci pinjaman aktif anggota ini supaya tidak ada yang dikembalikan/dibuat di tengah jalan.
+    let active: Vec<(LoanId, BookId)> = sqlx::query_as(
+        "SELECT id, book_id FROM loans
+         WHERE member_id = ? AND library_id = ? AND returned_at IS NULL
+         FOR UPDATE",
+    )
+    .bind(id)
+    .bind(tenant.library_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if !active.is_empty() {
+        match reassign_to {
+            Some(target) => {
+                let target_active: Option<i64> = sqlx::query_scalar(
+                    "SELECT (SELECT COUNT(*) FROM loans l
+                             WHERE l.member_id = m.id AND l.returned_at IS NULL)
+                     FROM members m WHERE m.id = ? AND m.library_id = ? FOR UPDATE",
+                )
+                .bind(target)
+                .bind(tenant.library_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+                let Some(target_active) = target_active else {
+                    return Err(ApiError::missing_resource("not_found.member", target));
+                };
+                if let Some(cap) = state.config.max_active_loans {
+                    let after = target_active + active.len() as i64;
+                    if after > cap {
+                        return Err(ApiError::conflict(
+                            Message::new("member.reassign_over_limit")
+                                .param("id", target)
+                                .param("after", after)
+                                .param("cap", cap),
+                        ));
+                    }
+                }
+
+                sqlx::query(
+                    "UPDATE loans SET member_id = ?
+                     WHERE member_id = ? AND library_id = ? AND returned_at IS NULL",
+                )
+                .bind(target)
+                .bind(id)
+                .bind(tenant.library_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None if params.force => {
+                sqlx::query(
+                    "UPDATE loans SET returned_at = ?
+                     WHERE member_id = ? AND library_id = ? AND returned_at IS NULL",
+                )
+                .bind(now)
+                .bind(id)
+                .bind(tenant.library_id)
+                .execute(&mut *tx)
+                .await?;
+                for (loan_id, book_id) in &active {
+                    sqlx::query(
+                        "UPDATE books
+                         SET available_copies = available_copies + 1, version = version + 1,
+                             updated_at = ?
+                         WHERE id = ? AND library_id = ?",
+                    )
+                    .bind(now)
+                    .bind(book_id)
+                    .bind(tenant.library_id)
+                    .execute(&mut *tx)
+                    .await?;
+                    Movement {
+                        library_id: tenant.library_id,
+                        book_id: *book_id,
+                        delta: 1,
+                        reason: StockReason::Return,
+                        reference_id: Some(loan_id.0),
+                    }
+                    .write(&mut *tx, now)
+                    .await?;
+                }
+            }
+            None => {
+                return Err(ApiError::conflict(
+                    Message::new("member.has_active_loans")
+                        .param("id", &raw_id)
+                        .param("count", active.len()),
+                ));
+            }
+        }
+    }
+
+    if !repo::delete_member(&mut *tx, tenant.library_id, id).await? {
+        return Ok(Json(false));
+    }
+
+    let loan_ids: Vec<LoanId> = active.iter().map(|(loan_id, _)| *loan_id).collect();
+    let details = match reassign_to {
+        Some(target) => serde_json::json!({ "reassigned_to": target, "loans": loan_ids }),
+        None => serde_json::json!({ "closed_loans": loan_ids }),
+    };
+    AuditEntry::new(&tenant, ACTION_DELETE, Entity::Member, id.0, details)
+        .write(&mut *tx, now)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Json(true))
+}
+
+/// POST /members/:id/merge – gabungkan anggota ganda `source_id` ke `:id`.
+async fn merge_member(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+    JsonBody(payload): JsonBody<MergeMember>,
+) -> Result<Json<MemberMerge>, ApiError> {
+    let target: MemberId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+    let source = payload.source_id;
+    if source == target {
+        return Err(ApiError::bad_request(Message::new("member.merge_self")));
+    }
+
+    let mut tx = state.pool.begin().await?;
+    let merge = absorb_member(&mut tx, &tenant, target, source, state.clock.now_naive()).await?;
+    tx.commit().await?;
+    Ok(Json(merge))
+}
+
+/// Pinjaman (aktif maupun riwayat), denda, usulan pengadaan, dan keikutsertaan program
+/// anggota `source` dipindah ke `target`, lalu baris `source` diarsipkan ke `merged_members`
+/// (tidak dibuang). Dijalankan di dalam transaksi pemanggil; kedua anggota dikunci di sini.
+async fn absorb_member(
+    conn: &mut MySqlConnection,
+    tenant: &Tenant,
+    target: MemberId,
+    source: MemberId,
+    now: NaiveDateTime,
+) -> Result<MemberMerge, ApiError> {
+    let locked: Vec<(MemberId, String)> = sqlx::query_as(
+        "SELECT id, public_id FROM members
+         WHERE id IN (?, ?) AND library_id = ? ORDER BY id FOR UPDATE",
+    )
+    .bind(target)
+    .bind(source)
+    .bind(tenant.library_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    if !locked.iter().any(|(id, _)| *id == target) {
+        return Err(ApiError::missing_resource("not_found.member", target));
+    }
+    let Some((_, source_public_id)) = locked.iter().find(|(id, _)| *id == source) else {
+        return Err(ApiError::missing_resource("not_found.member", source));
+    };
+
+    // Dua pinjaman aktif untuk buku yang sama tetap dua pinjaman; MAX_ACTIVE_LOANS
+    // tidak dicek karena bukunya memang sudah dipegang orang yang sama.
+    let loans = sqlx::query("UPDATE loans SET member_id = ? WHERE member_id = ? AND library_id = ?")
+        .bind(target)
+        .bind(source)
+        .bind(tenant.library_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    let fines = sqlx::query("UPDATE fines SET member_id = ? WHERE member_id = ? AND library_id = ?")
+        .bind(target)
+        .bind(source)
+        .bind(tenant.library_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    let purchase_requests = sqlx::query(
+        "UPDATE purchase_requests SET requester_member_id = ?
+         WHERE requester_member_id = ? AND library_id = ?",
+    )
+    .bind(target)
+    .bind(source)
+    .bind(tenant.library_id)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    // Tabel dengan PK (…, member_id): baris yang bentrok dengan milik tujuan dibuang.
+    sqlx::query("UPDATE IGNORE purchase_request_interests SET member_id = ? WHERE member_id = ?")
+        .bind(target)
+        .bind(source)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM purchase_request_interests WHERE member_id = ?")
+        .bind(source)
+        .execute(&mut *conn)
+        .await?;
+    let program_enrollments = sqlx::query(
+        "UPDATE IGNORE program_enrollments SET member_id = ? WHERE member_id = ?",
+    )
+    .bind(target)
+    .bind(source)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+    sqlx::query("DELETE FROM program_enrollments WHERE member_id = ?")
+        .bind(source)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO merged_members
+            (id, library_id, public_id, name, email, joined_at, merged_into, merged_at)
+         SELECT id, library_id, public_id, name, email, joined_at, ?, ?
+         FROM members WHERE id = ? AND library_id = ?",
+    )
+    .bind(target)
+    .bind(now)
+    .bind(source)
+    .bind(tenant.library_id)
+    .execute(&mut *conn)
+    .await?;
+    repo::delete_member(&mut *conn, tenant.library_id, source).await?;
+
+    let details = serde_json::json!({
+        "source_id": source,
+        "source_public_id": source_public_id,
+        "loans": loans,
+        "fines": fines,
+        "purchase_requests": purchase_requests,
+        "program_enrollments": program_enrollments,
+    });
+    AuditEntry::new(tenant, ACTION_MERGE, Entity::Member, target.0, details)
+        .write(&mut *conn, now)
+        .await?;
+
+    let member = repo::find_member(&mut *conn, tenant.library_id, target)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.member", target))?;
+    Ok(MemberMerge {
+        member,
+        source_id: source,
+        loans,
+        fines,
+        purchase_requests,
+        program_enrollments,
+    })
+}
+
+/// GET /admin/members/duplicates – pasangan anggota yang kemungkinan orang yang sama
+/// (email sama/mirip atau nama hampir identik), skor tertinggi dulu.
+async fn list_duplicate_members(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<Vec<DuplicateCandidate>>, ApiError> {
+    let members = sqlx::query_as::<_, Member>(
+        "SELECT id, public_id, name, email, joined_at FROM members
+         WHERE library_id = ? ORDER BY id",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(member::duplicate_candidates(&members)))
+}
+
+/// GET /admin/duplicate-members – kelompok anggota dengan email yang sama (setelah
+/// normalisasi), untuk dibersihkan sebelum email dijadikan unik.
+async fn list_duplicate_member_emails(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<Vec<DuplicateEmailGroup>>, ApiError> {
+    let members = sqlx::query_as::<_, Member>(
+        "SELECT id, public_id, name, email, joined_at FROM members
+         WHERE library_id = ? ORDER BY id",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(member::group_by_email(members)))
+}
+
+/// POST /admin/merge-members – gabungkan semua `duplicate_ids` ke `primary_id` dalam satu
+/// transaksi (sama seperti `POST /members/:id/merge` untuk tiap duplikat).
+async fn merge_members(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<MergeMembers>,
+) -> Result<Json<Vec<MemberMerge>>, ApiError> {
+    if payload.duplicate_ids.is_empty() {
+        return Err(ApiError::required("duplicate_ids"));
+    }
+    if payload.duplicate_ids.contains(&payload.primary_id) {
+        return Err(ApiError::bad_request(Message::new("member.primary_in_duplicates")));
+    }
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+    let mut merges = Vec::with_capacity(payload.duplicate_ids.len());
+    for source in payload.duplicate_ids {
+        merges.push(absorb_member(&mut tx, &tenant, payload.primary_id, source, now).await?);
+    }
+    tx.commit().await?;
+    Ok(Json(merges))
+}
+
+/// Berapa lama perubahan email menunggu konfirmasi sebelum kedaluwarsa.
+const PENDING_EMAIL_TTL_HOURS: i64 = 48;
+
+/// Muat anggota beserta perubahan email yang masih berlaku.
+async fn load_member_detail<'e, E>(
+    executor: E,
+    library_id: i32,
+    id: MemberId,
+    now: NaiveDateTime,
+) -> Result<Option<MemberDetail>, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let row = sqlx::query(
+        "SELECT id, public_id, name, email, joined_at, pending_email, pending_email_expires_at
+         FROM members WHERE id = ? AND library_id = ?",
+    )
+    .bind(id)
+    .bind(library_id)
+    .fetch_optional(executor)
+    .await?;
+
+    row.map(|row| {
+        let member = Member::from_row(&row)?;
+        let pending: Option<String> = row.try_get("pending_email")?;
+        let expires_at: Option<NaiveDateTime> = row.try_get("pending_email_expires_at")?;
+        let pending_email = pending
+            .zip(expires_at)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(email, expires_at)| PendingEmail { email, expires_at });
+        Ok(MemberDetail { member, pending_email, email_confirmation: None })
+    })
+    .transpose()
+}
+
+/// GET /members/:id – detail anggota, termasuk perubahan email yang menunggu konfirmasi.
+async fn get_member(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<MemberDetail>, ApiError> {
+    let id: MemberId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+    load_member_detail(&state.pool, tenant.library_id, id, state.clock.now_naive())
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::missing_resource("not_found.member", &raw_id))
+}
+
+/// Query string untuk PUT /members/:id.
+#[derive(Deserialize)]
+struct UpdateMemberParams {
+    /// Koreksi di meja layanan: email baru langsung dipakai tanpa konfirmasi.
+    #[serde(default)]
+    force: bool,
+}
+
+/// PUT /members/:id – ubah nama/email anggota. Email baru disimpan sebagai
+/// `pending_email` sampai anggota membuka link konfirmasi, kecuali `?force=true`.
+async fn update_member(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+    Query(params): Query<UpdateMemberParams>,
+    JsonBody(payload): JsonBody<UpdateMember>,
+) -> Result<Json<MemberDetail>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let id: MemberId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+    let now = state.clock.now();
+
+    let mut tx = state.pool.begin().await?;
+    let current = repo::lock_member(&mut tx, tenant.library_id, id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.member", &raw_id))?
+        .email;
+
+    if let Some(name) = &payload.name {
+        sqlx::query("UPDATE members SET name = ? WHERE id = ?")
+            .bind(name)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let mut confirmation = None;
+    match &payload.email {
+        // Email sama dengan yang sekarang (atau koreksi paksa): batalkan perubahan yang menunggu.
+        Some(email) if *email == current || params.force => {
+            sqlx::query(
+                "UPDATE members SET email = ?, pending_email = NULL, pending_email_expires_at = NULL
+                 WHERE id = ?",
+            )
+            .bind(email)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        Some(email) => {
+            let secret = member_link_secret(&state)?;
+            let expires_at = now + chrono::Duration::hours(PENDING_EMAIL_TTL_HOURS);
+            sqlx::query(
+                "UPDATE members SET pending_email = ?, pending_email_expires_at = ? WHERE id = ?",
+            )
+            .bind(email)
+            .bind(expires_at.naive_utc())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+            let token = EmailChangeClaims::new(tenant.library_id, id, email, expires_at.timestamp())
+                .sign(secret);
+            confirmation = Some(MemberAccessLink {
+                url: format!("{}?token={token}", state.config.email_confirm_url),
+                token,
+                expires_at: expires_at.naive_utc(),
+            });
+        }
+        None => {}
+    }
+
+    let details = serde_json::json!({
+        "name": payload.name,
+        "email": payload.email,
+        "previous_email": current,
+        "force": params.force,
+        "pending": confirmation.is_some(),
+    });
+    AuditEntry::new(&tenant, ACTION_UPDATE, Entity::Member, id.0, details)
+        .write(&mut *tx, now.naive_utc())
+        .await?;
+
+    let mut detail = load_member_detail(&mut *tx, tenant.library_id, id, now.naive_utc())
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.member", &raw_id))?;
+    tx.commit().await?;
+
+    detail.email_confirmation = confirmation;
+    Ok(Json(detail))
+}
+
+/// GET /members/confirm-email?token= – anggota mengonfirmasi email baru dari link yang dikirim.
+/// Token hanya berlaku untuk alamat `pending_email` saat token dibuat.
+async fn confirm_member_email(
+    State(state): State<AppState>,
+    Query(params): Query<MeParams>,
+) -> Result<Json<Member>, ApiError> {
+    let secret = member_link_secret(&state)?;
+    let now = state.clock.now();
+    let claims = EmailChangeClaims::verify(&params.token, secret, now)?;
+
+    let mut tx = state.pool.begin().await?;
+    let row = sqlx::query(
+        "SELECT email, pending_email, pending_email_expires_at FROM members
+         WHERE id = ? AND library_id = ? FOR UPDATE",
+    )
+    .bind(claims.member_id)
+    .bind(claims.library_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(TokenError::Invalid)?;
+
+    let previous: String = row.get("email");
+    let pending: Option<String> = row.get("pending_email");
+    let expires_at: Option<NaiveDateTime> = row.get("pending_email_expires_at");
+    // Perubahan sudah dibatalkan, dikonfirmasi, atau diganti alamat lain.
+    let Some(email) = pending.filter(|email| claims.matches(email)) else {
+        return Err(TokenError::Revoked.into());
+    };
+    if expires_at.is_none_or(|expires_at| expires_at <= now.naive_utc()) {
+        return Err(TokenError::Expired.into());
+    }
+
+    sqlx::query(
+        "UPDATE members SET email = ?, pending_email = NULL, pending_email_expires_at = NULL
+         WHERE id = ?",
+    )
+    .bind(&email)
+    .bind(claims.member_id)
+    .execute(&mut *tx)
+    .await?;
+
+    AuditEntry {
+        library_id: claims.library_id,
+        actor: format!("member:{}", claims.member_id),
+        action: ACTION_EMAIL_CONFIRM,
+        entity: Some(Entity::Member),
+        entity_id: Some(claims.member_id.0),
+        details: serde_json::json!({ "email": email, "previous_email": previous }),
+    }
+    .write(&mut *tx, now.naive_utc())
+    .await?;
+
+    let member = repo::find_member(&mut *tx, claims.library_id, claims.member_id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.member", claims.member_id))?;
+    tx.commit().await?;
+
+    Ok(Json(member))
+}
+
+/// GET /members/:id/summary – ringkasan peminjaman & denda satu anggota.
+async fn member_summary(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<MemberSummary>, ApiError> {
+    let id: MemberId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+
+    if repo::find_member(&state.pool, tenant.library_id, id).await?.is_none() {
+        return Err(ApiError::missing_resource("not_found.member", &raw_id));
+    }
+
+    let now = state.clock.now_naive();
+    let loans = sqlx::query(
+        "SELECT COUNT(*) AS total_borrowed,
+                CAST(COALESCE(SUM(returned_at IS NULL), 0) AS SIGNED) AS currently_out,
+                CAST(COALESCE(SUM(returned_at IS NULL AND due_at < ?), 0) AS SIGNED) AS overdue
+         FROM loans WHERE member_id = ? AND library_id = ?",
+    )
+    .bind(now)
+    .bind(id)
+    .bind(tenant.library_id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    let fines = sqlx::query(
+        "SELECT CAST(COALESCE(SUM(amount), 0) AS SIGNED) AS total_fines
+         FROM fines WHERE member_id = ? AND library_id = ? AND paid_at IS NULL",
+    )
+    .bind(id)
+    .bind(tenant.library_id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    // Buku semua pinjaman aktif diambil sekaligus, bukan satu query per pinjaman.
+    let active = sqlx::query_as::<_, Loan>(
+        "SELECT id, public_id, book_id, member_id, borrowed_at, due_at, returned_at, lost_at
+         FROM loans WHERE member_id = ? AND library_id = ? AND returned_at IS NULL
+         ORDER BY due_at, id",
+    )
+    .bind(id)
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+    let include = LoanInclude { book: true, member: false };
+    let current_loans = repo::expand_loans(&state.pool, tenant.library_id, active, include).await?;
+
+    let total_fines: i64 = fines.get("total_fines");
+    Ok(Json(MemberSummary {
+        total_borrowed: loans.get("total_borrowed"),
+        currently_out: loans.get("currently_out"),
+        overdue: loans.get("overdue"),
+        total_fines,
+        total_fines_formatted: currency::format(&state.config.currency, total_fines),
+        current_loans,
+    }))
+}
+
+/// GET /members/:id/loans.ics?token= – feed iCalendar berisi satu event per pinjaman aktif
+/// pada tanggal batas kembalinya, untuk di-subscribe dari aplikasi kalender.
+/// Aplikasi kalender tidak bisa mengirim header X-Api-Key, jadi otorisasinya memakai token
+/// link self-service anggota (lihat `create_access_link`), bukan API key.
+async fn member_loans_ical(
+    State(state): State<AppState>,
+    Path(raw_id): Path<String>,
+    Query(params): Query<MeParams>,
+) -> Result<Response, ApiError> {
+    let claims = member_from_token(&state, &params.token).await?;
+    let library_id = claims.library_id;
+    let id: MemberId = public_id::resolve(&state.pool, library_id, &raw_id).await?;
+    // Token hanya berlaku untuk feed anggotanya sendiri.
+    if id != claims.member_id {
+        return Err(ApiError::missing_resource("not_found.member", &raw_id));
+    }
+
+    let Some(Member { name: member_name, .. }) =
+        repo::find_member(&state.pool, library_id, id).await?
+    else {
+        return Err(ApiError::missing_resource("not_found.member", &raw_id));
+    };
+
+    let rows = sqlx::query(
+        "SELECT l.public_id, l.borrowed_at, l.due_at, b.title, b.author
+         FROM loans l
+         JOIN books b ON b.id = l.book_id
+         WHERE l.member_id = ? AND l.library_id = ? AND l.returned_at IS NULL
+         ORDER BY l.due_at, l.id",
+    )
+    .bind(id)
+    .bind(library_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut calendar = ical::Calendar::new(
+        format!("Pinjaman {member_name}"),
+        state.clock.now_naive(),
+    );
+    for row in rows {
+        let loan_id: String = row.get("public_id");
+        let title: String = row.get("title");
+        let author: String = row.get("author");
+        let borrowed_at: NaiveDateTime = row.get("borrowed_at");
+        let due_at: NaiveDateTime = row.get("due_at");
+        calendar.push(ical::Event {
+            uid: format!("{loan_id}@library-{library_id}"),
+            date: due_at.date(),
+            summary: format!("Batas kembali: {title}"),
+            description: format!(
+                "{title} oleh {author}, dipinjam {}",
+                borrowed_at.format("%Y-%m-%d")
+            ),
+        });
+    }
+
+    Ok(([(header::CONTENT_TYPE, ical::CONTENT_TYPE)], calendar.render()).into_response())
+}
+
+/// POST /members/:id/access-link – buat link self-service bertanda tangan untuk dikirim ke anggota.
+async fn create_access_link(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<MemberAccessLink>, ApiError> {
+    let secret = member_link_secret(&state)?;
+    let id: MemberId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+
+    let generation: i32 = sqlx::query_scalar(
+        "SELECT token_generation FROM members WHERE id = ? AND library_id = ?",
+    )
+    .bind(id)
+    .bind(tenant.library_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::missing_resource("not_found.member", &raw_id))?;
+
+    let now = state.clock.now();
+    let expires_at = now + chrono::Duration::hours(state.config.member_link_ttl_hours);
+    let token = MemberClaims {
+        library_id: tenant.library_id,
+        member_id: id,
+        generation,
+        expires_at: expires_at.timestamp(),
+    }
+    .sign(secret);
+
+    let details = serde_json::json!({ "generation": generation, "expires_at": expires_at });
+    AuditEntry::new(&tenant, ACTION_ACCESS_LINK, Entity::Member, id.0, details)
+        .write_logged(&state.pool, now.naive_utc())
+        .await;
+
+    Ok(Json(MemberAccessLink {
+        url: format!("{}?token={token}", state.config.member_link_url),
+        token,
+        expires_at: expires_at.naive_utc(),
+    }))
+}
+
+/// DELETE /members/:id/access-link – cabut semua link self-service anggota yang sudah dikirim.
+async fn revoke_access_links(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<bool>, ApiError> {
+    let id: MemberId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+
+    let res = sqlx::query(
+        "UPDATE members SET token_generation = token_generation + 1
+         WHERE id = ? AND library_id = ?",
+    )
+    .bind(id)
+    .bind(tenant.library_id)
+    .execute(&state.pool)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::missing_resource("not_found.member", &raw_id));
+    }
+
+    AuditEntry::new(&tenant, ACTION_REVOKE_ACCESS_LINKS, Entity::Member, id.0, ())
+        .write_logged(&state.pool, state.clock.now_naive())
+        .await;
+
+    Ok(Json(true))
+}
+
+fn member_link_secret(state: &AppState) -> Result<&[u8], ApiError> {
+    state
+        .config
+        .member_link_secret
+        .as_deref()
+        .map(str::as_bytes)
+        .ok_or_else(|| {
+            ApiError::forbidden(Message::new("member.self_service_disabled"))
+        })
+}
+
+//
+// ---------------------- SELF-SERVICE (/me) ----------------------
+//
+
+/// Query string untuk /me/*.
+#[derive(Deserialize)]
+struct MeParams {
+    token: String,
+}
+
+/// Validasi token link self-service: tanda tangan, masa berlaku, dan generasi di DB.
+async fn member_from_token(state: &AppState, token: &str) -> Result<MemberClaims, ApiError> {
+    let secret = member_link_secret(state)?;
+    let claims = MemberClaims::verify(token, secret, state.clock.now())?;
+
+    let generation: Option<i32> = sqlx::query_scalar(
+        "SELECT token_generation FROM members WHERE id = ? AND library_id = ?",
+    )
+    .bind(claims.member_id)
+    .bind(claims.library_id)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    match generation {
+        Some(current) if current == claims.generation => Ok(claims),
+        Some(_) => Err(TokenError::Revoked.into()),
+        // Anggotanya sudah dihapus.
+        None => Err(TokenError::Invalid.into()),
+    }
+}
+
+/// GET /me/loans?token= – semua pinjaman milik anggota pemegang link.
+async fn my_loans(
+    State(state): State<AppState>,
+    Query(params): Query<MeParams>,
+) -> Result<Json<Vec<Loan>>, ApiError> {
+    let claims = member_from_token(&state, &params.token).await?;
+
+    let loans = sqlx::query_as::<_, Loan>(
+        "SELECT id, public_id, book_id, member_id, borrowed_at, due_at, returned_at, lost_at
+         FROM loans WHERE member_id = ? AND library_id = ?
+         ORDER BY borrowed_at DESC, id DESC",
+    )
+    .bind(claims.member_id)
+    .bind(claims.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(loans))
+}
+
+/// GET /me/fines?token= – semua denda milik anggota pemegang link.
+async fn my_fines(
+    State(state): State<AppState>,
+    Query(params): Query<MeParams>,
+) -> Result<Json<Vec<Fine>>, ApiError> {
+    let claims = member_from_token(&state, &params.token).await?;
+
+    let fines = sqlx::query_as::<_, Fine>(
+        "SELECT id, loan_id, member_id, amount, reason, created_at, paid_at
+         FROM fines WHERE member_id = ? AND library_id = ?
+         ORDER BY created_at DESC, id DESC",
+    )
+    .bind(claims.member_id)
+    .bind(claims.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let currency = &state.config.currency;
+    Ok(Json(fines.into_iter().map(|f| f.with_currency(currency)).collect()))
+}
+
+//
+// ---------------------- LOANS ----------------------
+//
+
+/// Query string untuk GET /loans.
+/// (Sengaja tanpa `#[serde(flatten)]`: serde_urlencoded tidak bisa parse angka lewat flatten.)
+#[derive(Deserialize)]
+struct LoanListParams {
+    #[serde(default)]
+    stream: bool,
+    #[serde(default, deserialize_with = "timestamps::opt_date")]
+    borrowed_from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "timestamps::opt_date")]
+    borrowed_to: Option<NaiveDate>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+    /// `book`, `member`, atau `book,member` untuk meng-embed relasi.
+    include: Option<String>,
+    /// Sparse fieldset; relasi yang di-include boleh dipilih dengan `book.title`.
+    fields: Option<String>,
+    /// Daftar lengkap tanpa LIST_RESULT_CAP (streaming, wajib API key).
+    #[serde(default)]
+    all: bool,
+    /// Paging keyset: `next_cursor` dari halaman sebelumnya.
+    cursor: Option<String>,
+    limit: Option<u32>,
+}
+
+/// Cara paging GET /loans.
+#[derive(Debug, Clone, Copy)]
+enum LoanPaging {
+    All,
+    /// Tanpa paging tapi dibatasi LIST_RESULT_CAP: LIMIT dari `pagination::cap_limit`.
+    Capped(i64),
+    /// `?page=&per_page=`, urut borrowed_at, id menaik.
+    Offset(Page),
+    /// `?cursor=&limit=`, urut borrowed_at, id menurun, mulai setelah `after`.
+    Keyset { after: Option<LoanCursor>, limit: i64 },
+}
+
+/// Susun SELECT loans milik `library_id` dengan filter dan paging opsional.
+fn loans_query(library_id: i32, filter: &LoanFilter, paging: LoanPaging) -> QueryBuilder<'static, MySql> {
+    let mut qb = loans_filtered(
+        "SELECT id, public_id, book_id, member_id, borrowed_at, due_at, returned_at, lost_at
+         FROM loans WHERE library_id = ",
+        library_id,
+        filter,
+    );
+    match paging {
+        LoanPaging::All => {}
+        LoanPaging::Capped(limit) => {
+            qb.push(" LIMIT ").push_bind(limit);
+        }
+        LoanPaging::Offset(page) => {
+            qb.push(" ORDER BY borrowed_at, id LIMIT ")
+                .push_bind(page.limit)
+                .push(" OFFSET ")
+                .push_bind(page.offset);
+        }
+        LoanPaging::Keyset { after, limit } => {
+            // Range query di atas idx_loans_library_borrowed (library_id, borrowed_at, id).
+            if let Some(after) = after {
+                qb.push(" AND (borrowed_at < ")
+                    .push_bind(after.borrowed_at)
+                    .push(" OR (borrowed_at = ")
+                    .push_bind(after.borrowed_at)
+                    .push(" AND id < ")
+                    .push_bind(after.id)
+                    .push("))");
+            }
+            qb.push(" ORDER BY borrowed_at DESC, id DESC LIMIT ").push_bind(limit);
+        }
+    }
+    qb
+}
+
+/// COUNT(*) pinjaman dengan filter yang sama seperti `loans_query`, untuk X-Total-Count.
+fn loans_count_query(library_id: i32, filter: &LoanFilter) -> QueryBuilder<'static, MySql> {
+    loans_filtered("SELECT COUNT(*) FROM loans WHERE library_id = ", library_id, filter)
+}
+
+/// `select` (diakhiri `library_id = `) plus filter tanggal pinjam.
+fn loans_filtered(
+    select: &'static str,
+    library_id: i32,
+    filter: &LoanFilter,
+) -> QueryBuilder<'static, MySql> {
+    let mut qb = QueryBuilder::new(select);
+    qb.push_bind(library_id);
+
+    if let Some(from) = filter.borrowed_from {
+        qb.push(" AND borrowed_at >= ").push_bind(from.and_hms_opt(0, 0, 0));
+    }
+    if let Some(to) = filter.borrowed_to {
+        // inklusif: semua jam di tanggal `to`
+        let end = to.succ_opt().and_then(|d| d.and_hms_opt(0, 0, 0));
+        qb.push(" AND borrowed_at < ").push_bind(end);
+    }
+    qb
+}
+
+/// GET /loans – ambil semua peminjaman dari tabel `loans`.
+/// `?borrowed_from=&borrowed_to=` memfilter jendela tanggal pinjam (untuk laporan sirkulasi
+/// bulanan) dan otomatis memakai paging; tanpa parameter hasilnya sama seperti dulu.
+/// `?include=book,member` meng-embed buku/anggota ke tiap pinjaman.
+async fn list_loans(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
+    style: RowStyle,
+    Query(params): Query<LoanListParams>,
+) -> Response {
+    let filter = LoanFilter {
+        borrowed_from: params.borrowed_from,
+        borrowed_to: params.borrowed_to,
+    };
+    let page_params = PageParams {
+        page: params.page,
+        per_page: params.per_page,
+    };
+    if let (Some(from), Some(to)) = (filter.borrowed_from, filter.borrowed_to) {
+        if from > to {
+            return ApiError::bad_request(
+                Message::new("validation.date_order")
+                    .param("start_field", "borrowed_from")
+                    .param("start", from)
+                    .param("end_field", "borrowed_to")
+                    .param("end", to),
+            )
+            .into_response();
+        }
+    }
+
+    let keyset = params.cursor.is_some() || params.limit.is_some();
+    if keyset && page_params.is_requested() {
+        return ApiError::bad_request(
+            Message::new("validation.conflicting_params")
+                .param("a", "cursor/limit")
+                .param("b", "page/per_page"),
+        )
+        .into_response();
+    }
+    if params.all {
+        if keyset || page_params.is_requested() {
+            return ApiError::bad_request(
+                Message::new("validation.conflicting_params")
+                    .param("a", "all")
+                    .param("b", "cursor/limit/page/per_page"),
+            )
+            .into_response();
+        }
+        if let Err(e) = pagination::allow_full_listing(&tenant, "/loans") {
+            return e.into_response();
+        }
+    }
+
+    let paging = if keyset {
+        let limit = params.limit.unwrap_or(state.config.default_page_size);
+        if limit == 0 || limit > state.config.max_page_size {
+            return ApiError::out_of_range("limit", state.config.max_page_size)
+            .into_response();
+        }
+        let after = match params.cursor.as_deref().map(LoanCursor::decode) {
+            None => None,
+            Some(Some(cursor)) => Some(cursor),
+            Some(None) => return ApiError::invalid_cursor().into_response(),
+        };
+        // Ambil satu ekstra untuk tahu apakah masih ada halaman berikutnya.
+        LoanPaging::Keyset {
+            after,
+            limit: i64::from(limit) + 1,
+        }
+    } else if !params.all && (page_params.is_requested() || !filter.is_empty()) {
+        match page_params.resolve(&state.config) {
+            Ok(page) => LoanPaging::Offset(page),
+            Err(e) => return e.into_response(),
+        }
+    } else {
+        LoanPaging::All
+    };
+
+    let include = match params.include.as_deref().map(LoanInclude::parse) {
+        None => LoanInclude::default(),
+        Some(Ok(include)) => include,
+        Some(Err(msg)) => return ApiError::bad_request(msg).into_response(),
+    };
+    let nested = include.nested_fields();
+    let fields = match FieldSet::parse_opt(params.fields.as_deref(), loan::FIELDS, &nested) {
+        Ok(fields) => fields,
+        Err(e) => return e.into_response(),
+    };
+
+    if let Some(format) = StreamFormat::negotiate(&headers, params.stream || params.all) {
+        if include.any() || fields.is_some() {
+            let message =
+                Message::new("validation.not_streamable").param("param", "include/fields");
+            return ApiError::bad_request(message).into_response();
+        }
+        if keyset {
+            let message = Message::new("validation.not_streamable").param("param", "cursor");
+            return ApiError::bad_request(message).into_response();
+        }
+        let pool = state.pool.clone();
+        let library_id = tenant.library_id;
+        let response =
+            stream::spawn_rows(format, style, stream::request_id(&headers), move |sink| async move {
+                let mut qb = loans_query(library_id, &filter, paging);
+                let rows = qb.build_query_as::<Loan>().fetch(&pool);
+                sink.drain(rows).await;
+            });
+        return if params.all { pagination::mark_complete(response) } else { response };
+    }
+
+    // Tanpa paging, LIST_RESULT_CAP berlaku: ambil satu ekstra untuk tahu ada kelebihan.
+    let paging = match (paging, pagination::cap_limit(state.config.list_result_cap)) {
+        (LoanPaging::All, Some(limit)) => LoanPaging::Capped(limit),
+        (paging, _) => paging,
+    };
+    let mut qb = loans_query(tenant.library_id, &filter, paging);
+    let result = state
+        .metrics
+        .time("loans.list", qb.build_query_as::<Loan>().fetch_all(&state.pool))
+        .await;
+
+    let mut loans = match result {
+        Ok(loans) => loans,
+        Err(e) => {
+            if keyset {
+                return ApiError::from(e).into_response();
+            }
+            eprintln!("DB error on list_loans: {e}");
+            return Json(Vec::<Loan>::new()).into_response();
+        }
+    };
+
+    let next_cursor = match paging {
+        LoanPaging::Keyset { limit, .. } if loans.len() as i64 >= limit => {
+            loans.truncate(limit as usize - 1);
+            loans.last().map(|loan| LoanCursor::of(loan).encode())
+        }
+        _ => None,
+    };
+    // LIST_RESULT_CAP hanya untuk daftar tanpa paging; dipotong sebelum expand.
+    let cap = match paging {
+        LoanPaging::Capped(_) => {
+            let mut count_qb = loans_count_query(tenant.library_id, &filter);
+            let count = count_qb.build_query_scalar().fetch_one(&state.pool);
+            match pagination::soft_cap(&mut loans, state.config.list_result_cap, count).await {
+                Ok(cap) => cap,
+                Err(e) => {
+                    eprintln!("DB error on list_loans (count): {e}");
+                    return Json(Vec::<Loan>::new()).into_response();
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let response = if !include.any() {
+        loan_listing(CursorPage { items: loans, next_cursor }, keyset, fields.as_ref())
+    } else {
+        let expanded = state
+            .metrics
+            .time(
+                "loans.expand",
+                repo::expand_loans(&state.pool, tenant.library_id, loans, include),
+            )
+            .await;
+        match expanded {
+            Ok(details) => {
+                loan_listing(CursorPage { items: details, next_cursor }, keyset, fields.as_ref())
+            }
+            Err(e) => return ApiError::from(e).into_response(),
+        }
+    };
+    match cap {
+        Some(cap) => cap.apply(response),
+        None => response,
+    }
+}
+
+/// Bentuk response GET /loans: `CursorPage` untuk paging keyset, array polos selain itu.
+fn loan_listing<T: Serialize>(
+    page: CursorPage<T>,
+    keyset: bool,
+    fields: Option<&FieldSet>,
+) -> Response {
+    match (keyset, fields) {
+        (true, Some(fields)) => Json(fields.select_page(&page)).into_response(),
+        (true, None) => Json(page).into_response(),
+        (false, Some(fields)) => Json(fields.select(&page.items)).into_response(),
+        (false, None) => Json(page.items).into_response(),
+    }
+}
+
+/// Query string GET /loans/:id.
+#[derive(Deserialize)]
+struct LoanDetailParams {
+    /// Sparse fieldset, mis. `id,due_at,book.title,member.name`.
+    fields: Option<String>,
+}
+
+/// GET /loans/:id – detail satu peminjaman beserta buku dan anggotanya.
+async fn get_loan(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+    Query(params): Query<LoanDetailParams>,
+) -> Result<Response, ApiError> {
+    let include = LoanInclude {
+        book: true,
+        member: true,
+    };
+    let fields =
+        FieldSet::parse_opt(params.fields.as_deref(), loan::FIELDS, &include.nested_fields())?;
+    let id: LoanId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+
+    let loan = repo::find_loan(&state.pool, tenant.library_id, id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.loan", &raw_id))?;
+
+    let mut details = repo::expand_loans(&state.pool, tenant.library_id, vec![loan], include).await?;
+    let detail = details.pop().ok_or_else(|| ApiError::internal("loan detail missing"))?;
+
+    Ok(match fields {
+        Some(fields) => Json(fields.select(&detail)).into_response(),
+        None => Json(detail).into_response(),
+    })
+}
+
+/// Query string untuk GET /loans/by-status.
+#[derive(Deserialize)]
+struct LoansByStatusParams {
+    #[serde(default)]
+    counts_only: bool,
+}
+
+/// GET /loans/by-status – pinjaman dikelompokkan jadi active / overdue / returned.
+/// Batas overdue memakai jam aplikasi (`Clock`), sama seperti perhitungan denda.
+async fn loans_by_status(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<LoansByStatusParams>,
+) -> Result<Response, ApiError> {
+    let now = state.clock.now_naive();
+
+    if params.counts_only {
+        let row = sqlx::query(
+            "SELECT CAST(COALESCE(SUM(returned_at IS NULL AND due_at >= ?), 0) AS SIGNED) AS active,
+                    CAST(COALESCE(SUM(returned_at IS NULL AND due_at < ?), 0) AS SIGNED) AS overdue,
+                    CAST(COALESCE(SUM(returned_at IS NOT NULL), 0) AS SIGNED) AS returned
+             FROM loans WHERE library_id = ?",
+        )
+        .bind(now)
+        .bind(now)
+        .bind(tenant.library_id)
+        .fetch_one(&state.pool)
+        .await?;
+        let count = |column: &str| row.get::<i64, _>(column) as usize;
+        return Ok(Json(LoanStatusCounts {
+            active: count("active"),
+            overdue: count("overdue"),
+            returned: count("returned"),
+        })
+        .into_response());
+    }
+
+    let loans = sqlx::query_as::<_, Loan>(
+        "SELECT id, public_id, book_id, member_id, borrowed_at, due_at, returned_at, lost_at
+         FROM loans WHERE library_id = ?
+         ORDER BY due_at, id",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(LoansByStatus::group(loans, now)).into_response())
+}
+
+/// POST /loans – buat peminjaman baru.
+/// Body JSON: { "book_id": 1, "member_id": 1, "due_date": "2025-12-01" }
+/// Aturan dan langkah peminjaman ada di `loan::create_loan`; handler hanya memberi transaksi
+/// dan menulis audit.
+async fn create_loan(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<NewLoan>, // book_id, member_id, due_date (YYYY-MM-DD)
+) -> Result<Json<Loan>, ApiError> {
+    let mut tx = state.pool.begin().await?;
+    let created =
+        loan::create_loan(&mut *tx, &*state.clock, &state.config, tenant.library_id, &payload)
+            .await?;
+    tx.commit().await?;
+
+    AuditEntry::new(&tenant, ACTION_CREATE, Entity::Loan, created.id.0, &created)
+        .write_logged(&state.pool, state.clock.now_naive())
+        .await;
+
+    Ok(Json(created))
+}
+
+/// POST /loans/:id/return – tandai peminjaman sudah dikembalikan.
+/// `:id` boleh public id atau integer lama. Respons sama dengan /return-and-pay (denda belum
+/// lunas); pinjaman yang sudah kembali ditolak 409, jadi pengembalian kedua yang datang
+/// bersamaan tidak menambah stok lagi.
+async fn return_loan(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<LoanReturn>, ApiError> {
+    close_loan(&state, &tenant, &raw_id, false).await.map(Json)
+}
+
+/// POST /loans/:id/return-and-pay – kembalikan buku dan, kalau `pay_fine` true, langsung
+/// lunasi denda keterlambatannya di transaksi yang sama (alur meja layanan).
+async fn return_and_pay_loan(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+    JsonBody(payload): JsonBody<ReturnAndPay>,
+) -> Result<Json<LoanReturn>, ApiError> {
+    close_loan(&state, &tenant, &raw_id, payload.pay_fine).await.map(Json)
+}
+
+/// Isi bersama /return dan /return-and-pay: tutup pinjamannya lewat `loan::return_loan`,
+/// audit di transaksi yang sama, dan kembalikan pinjaman beserta denda keterlambatan yang
+/// sudah diformat CURRENCY.
+async fn close_loan(
+    state: &AppState,
+    tenant: &Tenant,
+    raw_id: &str,
+    pay_fine: bool,
+) -> Result<LoanReturn, ApiError> {
+    let id: LoanId = public_id::resolve(&state.pool, tenant.library_id, raw_id).await?;
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+
+    let returned =
+        loan::return_loan(&mut *tx, &*state.clock, &state.config, tenant.library_id, id, pay_fine)
+            .await?;
+    let loan = returned.loan;
+    let amount = returned.fine;
+
+    let mut details =
+        serde_json::json!({ "book_id": loan.book_id, "member_id": loan.member_id, "fine": amount });
+    if pay_fine && amount > 0 {
+        details["fine_paid"] = serde_json::Value::Bool(true);
+    }
+    AuditEntry::new(tenant, ACTION_RETURN, Entity::Loan, id.0, details)
+        .write(&mut *tx, now)
+        .await?;
+
+    let fine = sqlx::query_as::<_, Fine>(
+        "SELECT id, loan_id, member_id, amount, reason, created_at, paid_at
+         FROM fines WHERE loan_id = ? AND reason = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(id)
+    .bind(REASON_LATE)
+    .fetch_optional(&mut *tx)
+    .await?
+    .filter(|_| amount > 0)
+    .map(|fine| fine.with_currency(&state.config.currency));
+
+    tx.commit().await?;
+    Ok(LoanReturn { loan, fine })
+}
+
+/// POST /loans/:id/mark-lost – tutup pinjaman aktif karena bukunya hilang.
+/// Stok tersedia tidak bertambah, total eksemplar berkurang satu,
+/// dan biaya buku hilang dicatat di buku besar denda.
+async fn mark_loan_lost(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(raw_id): Path<String>,
+) -> Result<Json<Loan>, ApiError> {
+    let id: LoanId = public_id::resolve(&state.pool, tenant.library_id, &raw_id).await?;
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+
+    // 1. Kunci baris pinjaman dan pastikan masih aktif
+    let Some(Loan { book_id, member_id, returned_at, .. }) =
+        repo::lock_loan(&mut tx, tenant.library_id, id).await?
+    else {
+        return Err(ApiError::missing_resource("not_found.loan", &raw_id));
+    };
+    if returned_at.is_some() {
+        return Err(ApiError::conflict(Message::new("loan.not_active").param("id", raw_id)));
+    }
+
+    close_loan_as_lost(
+        &mut tx,
+        LostLoan { library_id: tenant.library_id, id, book_id, member_id },
+        tenant.actor(),
+        now,
+        state.config.lost_book_fee,
+    )
+    .await?;
+
+    let loan = repo::find_loan(&mut *tx, tenant.library_id, id)
+        .await?
+        .ok_or_else(|| ApiError::missing_resource("not_found.loan", &raw_id))?;
+
+    tx.commit().await?;
+    Ok(Json(loan))
+}
+
+/// Pinjaman aktif yang akan ditutup sebagai hilang.
+#[derive(Debug, Clone, Copy)]
+struct LostLoan {
+    library_id: i32,
+    id: LoanId,
+    book_id: BookId,
+    member_id: MemberId,
+}
+
+/// Tutup pinjaman (yang sudah dikunci dan dipastikan aktif) sebagai hilang: total eksemplar
+/// berkurang, biaya buku hilang `fee` dicatat, lalu audit atas nama `actor`.
+/// Dipakai oleh /mark-lost dan job AUTO_LOST_DAYS.
+async fn close_loan_as_lost(
+    conn: &mut MySqlConnection,
+    loan: LostLoan,
+    actor: String,
+    now: NaiveDateTime,
+    fee: i64,
+) -> Result<(), sqlx::Error> {
+    // 2. Tutup pinjaman sebagai hilang
+    sqlx::query("UPDATE loans SET returned_at = ?, lost_at = ? WHERE id = ?")
+        .bind(now)
+        .bind(now)
+        .bind(loan.id)
+        .execute(&mut *conn)
+        .await?;
+
+    // 3. Eksemplar hilang: total berkurang, stok tersedia tetap
+    sqlx::query(
+        "UPDATE books
+         SET total_copies = GREATEST(total_copies - 1, 0), version = version + 1, updated_at = ?
+         WHERE id = ? AND library_id = ?",
+    )
+    .bind(now)
+    .bind(loan.book_id)
+    .bind(loan.library_id)
+    .execute(&mut *conn)
+    .await?;
+    Movement {
+        library_id: loan.library_id,
+        book_id: loan.book_id,
+        delta: 0,
+        reason: StockReason::Lost,
+        reference_id: Some(loan.id.0),
+    }
+    .write(&mut *conn, now)
+    .await?;
+
+    // 4. Catat biaya buku hilang
+    sqlx::query(
+        "INSERT INTO fines (library_id, loan_id, member_id, amount, reason)
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(loan.library_id)
+    .bind(loan.id)
+    .bind(loan.member_id)
+    .bind(fee)
+    .bind(REASON_LOST)
+    .execute(&mut *conn)
+    .await?;
+
+    AuditEntry {
+        library_id: loan.library_id,
+        actor,
+        action: ACTION_MARK_LOST,
+        entity: Some(Entity::Loan),
+        entity_id: Some(loan.id.0),
+        details: serde_json::json!({ "book_id": loan.book_id, "fee": fee }),
+    }
+    .write(&mut *conn, now)
+    .await
+}
+
+/// Job AUTO_LOST_DAYS: pinjaman yang sudah lewat jatuh tempo lebih dari N hari dianggap
+/// hilang dan ditutup lewat `close_loan_as_lost`. Tiap pinjaman punya transaksi sendiri,
+/// jadi satu kegagalan tidak membatalkan yang lain. Hasilnya jumlah pinjaman yang ditutup.
+async fn auto_mark_lost(state: &AppState) -> Result<u64, sqlx::Error> {
+    let Some(days) = state.config.auto_lost_days else {
+        return Ok(0);
+    };
+    let now = state.clock.now_naive();
+    let cutoff = now - chrono::Duration::days(days);
+
+    let candidates: Vec<(LoanId, i32)> = sqlx::query_as(
+        "SELECT id, library_id FROM loans WHERE returned_at IS NULL AND due_at < ? ORDER BY id",
+    )
+    .bind(cutoff)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut closed = 0;
+    for (id, library_id) in candidates {
+        let mut tx = state.pool.begin().await?;
+        // Kunci ulang: pinjaman bisa saja dikembalikan sejak daftar di atas dibaca.
+        let row = sqlx::query(
+            "SELECT book_id, member_id, due_at FROM loans
+             WHERE id = ? AND returned_at IS NULL AND due_at < ? FOR UPDATE",
+        )
+        .bind(id)
+        .bind(cutoff)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(row) = row else {
+            continue;
+        };
+        let loan = LostLoan {
+            library_id,
+            id,
+            book_id: row.get("book_id"),
+            member_id: row.get("member_id"),
+        };
+        let due_at: NaiveDateTime = row.get("due_at");
+
+        let fee = state.config.lost_book_fee;
+        match close_loan_as_lost(&mut tx, loan, "system:auto_lost".to_string(), now, fee).await {
+            Ok(()) => {
+                tx.commit().await?;
+                closed += 1;
+                println!(
+                    "Auto-lost: loan {} (library_id={library_id}, book_id={}, member_id={}) \
+                     due {due_at} marked lost after AUTO_LOST_DAYS={days}",
+                    loan.id, loan.book_id, loan.member_id
+                );
+            }
+            Err(e) => {
+                eprintln!("DB error on auto-lost loan {id}: {e}");
+                tx.rollback().await.ok();
+            }
+        }
+    }
+    Ok(closed)
+}
+
+//
+// ---------------------- DONATIONS ----------------------
+//
+
+/// Ambil satu batch donasi milik tenant beserta item-itemnya.
+async fn load_donation(
+    pool: &MySqlPool,
+    library_id: i32,
+    id: i32,
+) -> Result<Option<DonationDetail>, sqlx::Error> {
+    let Some(donation) = sqlx::query_as::<_, Donation>(
+        "SELECT id, donor_name, donated_on, created_at
+         FROM donations WHERE id = ? AND library_id = ?",
+    )
+    .bind(id)
+    .bind(library_id)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let items = sqlx::query_as::<_, DonationItem>(
+        "SELECT id, title, author, copies, status, reject_reason, book_id, decided_at
+         FROM donation_items WHERE donation_id = ? ORDER BY id",
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(DonationDetail { donation, items }))
+}
+
+/// POST /donations – catat satu batch donasi; semua itemnya mulai berstatus pending.
+async fn create_donation(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<NewDonation>,
+) -> Result<Json<DonationDetail>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+    let res = sqlx::query(
+        "INSERT INTO donations (library_id, donor_name, donated_on, created_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(tenant.library_id)
+    .bind(&payload.donor_name)
+    .bind(payload.donated_on)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+    let donation_id = res.last_insert_id() as i32;
+
+    let mut qb = QueryBuilder::<MySql>::new(
+        "INSERT INTO donation_items (donation_id, title, author, copies, status) ",
+    );
+    qb.push_values(&payload.items, |mut row, item| {
+        row.push_bind(donation_id)
+            .push_bind(&item.title)
+            .push_bind(&item.author)
+            .push_bind(item.copies)
+            .push_bind(donation::STATUS_PENDING);
+    });
+    qb.build().execute(&mut *tx).await?;
+
+    let details = serde_json::json!({
+        "donation_id": donation_id,
+        "donor_name": payload.donor_name,
+        "items": payload.items.len(),
+    });
+    AuditEntry::bulk(&tenant, ACTION_DONATION_RECEIVE, None, details)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+
+    let detail = load_donation(&state.pool, tenant.library_id, donation_id)
+        .await?
+        .ok_or_else(|| ApiError::internal("donation missing after insert"))?;
+    Ok(Json(detail))
+}
+
+/// GET /donations/:id – batch donasi beserta status tiap item.
+async fn get_donation(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+) -> Result<Json<DonationDetail>, ApiError> {
+    load_donation(&state.pool, tenant.library_id, id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::missing_resource("not_found.donation", id))
+}
+
+/// Kunci item donasi yang masih pending (FOR UPDATE) di dalam transaksi.
+async fn lock_pending_item(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    donation_id: i32,
+    item_id: i32,
+) -> Result<DonationItem, ApiError> {
+    let item = sqlx::query_as::<_, DonationItem>(
+        "SELECT i.id, i.title, i.author, i.copies, i.status, i.reject_reason, i.book_id, i.decided_at
+         FROM donation_items i
+         JOIN donations d ON d.id = i.donation_id
+         WHERE i.id = ? AND i.donation_id = ? AND d.library_id = ?
+         FOR UPDATE",
+    )
+    .bind(item_id)
+    .bind(donation_id)
+    .bind(library_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| {
+        ApiError::not_found(
+            Message::new("not_found.donation_item")
+                .param("id", item_id)
+                .param("donation_id", donation_id),
+        )
+    })?;
+
+    if item.status != donation::STATUS_PENDING {
+        return Err(ApiError::conflict(
+            Message::new("donation.item_decided")
+                .param("id", item_id)
+                .param("status", &item.status),
+        ));
+    }
+    Ok(item)
+}
+
+/// POST /donations/:id/items/:item_id/accept – item jadi buku di katalog. Kalau judul+pengarang
+/// (dibandingkan lewat `normalize::search_key`) sudah ada, eksemplarnya ditambahkan ke buku itu.
+/// Semua langkah dalam satu transaksi, dan item menyimpan id buku sebagai provenance.
+async fn accept_donation_item(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path((donation_id, item_id)): Path<(i32, i32)>,
+    JsonBody(payload): JsonBody<AcceptDonationItem>,
+) -> Result<Json<AcceptedDonationItem>, ApiError> {
+    let now = state.clock.now_naive();
+    let mut tx = state.pool.begin().await?;
+    let item = lock_pending_item(&mut tx, tenant.library_id, donation_id, item_id).await?;
+
+    let author = payload
+        .author
+        .as_deref()
+        .or(item.author.as_deref())
+        .map(normalize::collapse_whitespace)
+        .filter(|a| !a.is_empty())
+        .ok_or_else(|| ApiError::missing("author"))?;
+
+    // Deteksi duplikat: judul dan pengarang sama setelah normalisasi.
+    let title_key = normalize::search_key(&item.title);
+    let author_key = normalize::search_key(&author);
+    let candidates = sqlx::query_as::<_, Book>(
+        "SELECT id, public_id, title, author, category, year, total_copies, available_copies,
+                version, updated_at, location
+         FROM books WHERE library_id = ? AND LOWER(title) = ? FOR UPDATE",
+    )
+    .bind(tenant.library_id)
+    .bind(&title_key)
+    .fetch_all(&mut *tx)
+    .await?;
+    let existing = candidates.into_iter().find(|b| {
+        normalize::search_key(&b.title) == title_key && normalize::search_key(&b.author) == author_key
+    });
+
+    let (book_id, created) = match &existing {
+        Some(book) => {
+            sqlx::query(
+                "UPDATE books
+                 SET total_copies = total_copies + ?, available_copies = available_copies + ?,
+                     version = version + 1, updated_at = ?
+                 WHERE id = ?",
+            )
+            .bind(item.copies)
+            .bind(item.copies)
+            .bind(now)
+            .bind(book.id)
+            .execute(&mut *tx)
+            .await?;
+            (book.id, false)
+        }
+        None => {
+            let new_book = NewBook {
+                title: item.title.clone(),
+                author,
+                category: payload.category.clone().unwrap_or_default(),
+                year: payload.year,
+                total_copies: item.copies,
+                location: None,
+            }
+            .normalized()
+            .map_err(ApiError::bad_request)?;
+
+            let (category_id, category) =
+                book_category(&mut tx, &state, tenant.library_id, &new_book.category).await?;
+            let res = sqlx::query(
+                "INSERT INTO books (library_id, public_id, title, author, category, year, total_copies, available_copies,
+                                    category_id, title_sort_key, author_sort_key)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(tenant.library_id)
+            .bind(public_id::generate(Entity::Book))
+            .bind(&new_book.title)
+            .bind(&new_book.author)
+            .bind(&category)
+            .bind(new_book.year)
+            .bind(new_book.total_copies)
+            .bind(new_book.total_copies)
+            .bind(category_id)
+            .bind(collation::sort_key(&new_book.title, &state.config.collation_articles))
+            .bind(collation::sort_key(&new_book.author, &state.config.collation_articles))
+            .execute(&mut *tx)
+            .await?;
+            (BookId(res.last_insert_id() as i32), true)
+        }
+    };
+
+    sqlx::query(
+        "UPDATE donation_items SET status = ?, book_id = ?, decided_at = ? WHERE id = ?",
+    )
+    .bind(donation::STATUS_ACCEPTED)
+    .bind(book_id)
+    .bind(now)
+    .bind(item_id)
+    .execute(&mut *tx)
+    .await?;
+    Movement {
+        library_id: tenant.library_id,
+        book_id,
+        delta: item.copies,
+        reason: StockReason::Restock,
+        reference_id: Some(item_id),
+    }
+    .write(&mut *tx, now)
+    .await?;
+
+    let book = repo::find_book(&mut *tx, tenant.library_id, book_id)
+        .await?
+        .ok_or_else(|| ApiError::internal("accepted book not found"))?;
+
+    let (action, details) = if created {
+        (ACTION_CREATE, serde_json::json!({ "after": book, "donation_id": donation_id }))
+    } else {
+        (
+            ACTION_UPDATE,
+            serde_json::json!({ "before": existing, "after": book, "donation_id": donation_id }),
+        )
+    };
+    AuditEntry::new(&tenant, action, Entity::Book, book_id.0, details)
+        .write(&mut *tx, now)
+        .await?;
+    AuditEntry::new(&tenant, ACTION_DONATION_ACCEPT, Entity::Book, book_id.0, &item)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+
+    let item = DonationItem {
+        status: donation::STATUS_ACCEPTED.to_string(),
+        book_id: Some(book_id),
+        decided_at: Some(now),
+        ..item
+    };
+    Ok(Json(AcceptedDonationItem { item, book, created }))
+}
+
+/// POST /donations/:id/items/:item_id/reject – tolak item dengan alasan.
+async fn reject_donation_item(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path((donation_id, item_id)): Path<(i32, i32)>,
+    JsonBody(payload): JsonBody<RejectDonationItem>,
+) -> Result<Json<DonationItem>, ApiError> {
+    let reason = normalize::required("reason", &payload.reason).map_err(ApiError::bad_request)?;
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+    let item = lock_pending_item(&mut tx, tenant.library_id, donation_id, item_id).await?;
+
+    sqlx::query(
+        "UPDATE donation_items SET status = ?, reject_reason = ?, decided_at = ? WHERE id = ?",
+    )
+    .bind(donation::STATUS_REJECTED)
+    .bind(&reason)
+    .bind(now)
+    .bind(item_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let item = DonationItem {
+        status: donation::STATUS_REJECTED.to_string(),
+        reject_reason: Some(reason),
+        decided_at: Some(now),
+        ..item
+    };
+    let details = serde_json::json!({ "donation_id": donation_id, "item": item });
+    AuditEntry::bulk(&tenant, ACTION_DONATION_REJECT, None, details)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Json(item))
+}
+
+//
+// ---------------------- RESERVE LISTS ----------------------
+//
+
+/// Nonaktifkan daftar reserve yang semesternya sudah berakhir. Dijalankan berkala dari `main`.
+async fn deactivate_expired_reserve_lists(state: &AppState) -> Result<u64, sqlx::Error> {
+    let today = state.clock.now().date_naive();
+    let res = sqlx::query("UPDATE reserve_lists SET active = FALSE WHERE active AND ends_on < ?")
+        .bind(today)
+        .execute(&state.pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+async fn load_reserve_list(
+    pool: &MySqlPool,
+    library_id: i32,
+    id: i32,
+) -> Result<ReserveListDetail, ApiError> {
+    let list = sqlx::query_as::<_, ReserveList>(
+        "SELECT id, course_code, name, semester, owner, loan_days, renewal_limit, ends_on, active,
+                created_at
+         FROM reserve_lists WHERE id = ? AND library_id = ?",
+    )
+    .bind(id)
+    .bind(library_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::missing_resource("not_found.reserve_list", id))?;
+
+    let books = sqlx::query_as::<_, Book>(
+        "SELECT b.id, b.public_id, b.title, b.author, b.category, b.year, b.total_copies,
+                b.available_copies, b.version, b.updated_at, b.location
+         FROM reserve_list_books r
+         JOIN books b ON b.id = r.book_id
+         WHERE r.list_id = ? AND b.library_id = ?
+         ORDER BY b.title_sort_key, b.title, b.id",
+    )
+    .bind(id)
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ReserveListDetail { list, books })
+}
+
+/// GET /reserve-lists – semua daftar reserve, yang aktif dulu.
+async fn list_reserve_lists(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<Vec<ReserveList>>, ApiError> {
+    let lists = sqlx::query_as::<_, ReserveList>(
+        "SELECT id, course_code, name, semester, owner, loan_days, renewal_limit, ends_on, active,
+                created_at
+         FROM reserve_lists WHERE library_id = ?
+         ORDER BY active DESC, ends_on DESC, id",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(lists))
+}
+
+/// POST /reserve-lists – buat daftar reserve untuk satu mata kuliah.
+async fn create_reserve_list(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<NewReserveList>,
+) -> Result<Json<ReserveListDetail>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let now = state.clock.now_naive();
+    if payload.ends_on < now.date() {
+        return Err(ApiError::bad_request(
+            Message::new("validation.date_in_past")
+                .param("field", "ends_on")
+                .param("date", payload.ends_on),
+        ));
+    }
+
+    let res = sqlx::query(
+        "INSERT INTO reserve_lists
+            (library_id, course_code, name, semester, owner, loan_days, renewal_limit, ends_on, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(tenant.library_id)
+    .bind(&payload.course_code)
+    .bind(&payload.name)
+    .bind(&payload.semester)
+    .bind(&payload.owner)
+    .bind(payload.loan_days)
+    .bind(payload.renewal_limit)
+    .bind(payload.ends_on)
+    .bind(now)
+    .execute(&state.pool)
+    .await?;
+    let id = res.last_insert_id() as i32;
+
+    let detail = load_reserve_list(&state.pool, tenant.library_id, id).await?;
+    AuditEntry::bulk(&tenant, ACTION_RESERVE_LIST_CREATE, None, &detail.list)
+        .write_logged(&state.pool, now)
+        .await;
+    Ok(Json(detail))
+}
+
+/// GET /reserve-lists/:id – daftar beserta bukunya (termasuk stok tersedia).
+async fn get_reserve_list(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+) -> Result<Json<ReserveListDetail>, ApiError> {
+    Ok(Json(load_reserve_list(&state.pool, tenant.library_id, id).await?))
+}
+
+/// POST /reserve-lists/:id/books – tambah buku ke daftar (idempoten).
+async fn add_reserve_book(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    JsonBody(payload): JsonBody<AddReserveBook>,
+) -> Result<Json<ReserveListDetail>, ApiError> {
+    // Validasi kepemilikan daftar dan buku sebelum menulis.
+    load_reserve_list(&state.pool, tenant.library_id, id).await?;
+    let book: Option<i32> =
+        sqlx::query_scalar("SELECT id FROM books WHERE id = ? AND library_id = ?")
+            .bind(payload.book_id)
+            .bind(tenant.library_id)
+            .fetch_optional(&state.pool)
+            .await?;
+    if book.is_none() {
+        return Err(ApiError::missing_resource("not_found.book", payload.book_id));
+    }
+
+    let now = state.clock.now_naive();
+    sqlx::query(
+        "INSERT IGNORE INTO reserve_list_books (list_id, book_id, added_at) VALUES (?, ?, ?)",
+    )
+    .bind(id)
+    .bind(payload.book_id)
+    .bind(now)
+    .execute(&state.pool)
+    .await?;
+
+    let details = serde_json::json!({ "list_id": id, "book_id": payload.book_id });
+    AuditEntry::bulk(&tenant, ACTION_RESERVE_LIST_ADD_BOOK, Some(Entity::Book), details)
+        .write_logged(&state.pool, now)
+        .await;
+    Ok(Json(load_reserve_list(&state.pool, tenant.library_id, id).await?))
+}
+
+/// DELETE /reserve-lists/:id/books/:book_id – keluarkan buku dari daftar.
+async fn remove_reserve_book(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path((id, raw_book_id)): Path<(i32, String)>,
+) -> Result<Json<ReserveListDetail>, ApiError> {
+    load_reserve_list(&state.pool, tenant.library_id, id).await?;
+    let book_id: BookId = public_id::resolve(&state.pool, tenant.library_id, &raw_book_id).await?;
+
+    let res = sqlx::query("DELETE FROM reserve_list_books WHERE list_id = ? AND book_id = ?")
+        .bind(id)
+        .bind(book_id)
+        .execute(&state.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::not_found(
+            Message::new("not_found.reserve_entry")
+                .param("book", &raw_book_id)
+                .param("id", id),
+        ));
+    }
+
+    let details = serde_json::json!({ "list_id": id, "book_id": book_id });
+    AuditEntry::bulk(&tenant, ACTION_RESERVE_LIST_REMOVE_BOOK, Some(Entity::Book), details)
+        .write_logged(&state.pool, state.clock.now_naive())
+        .await;
+    Ok(Json(load_reserve_list(&state.pool, tenant.library_id, id).await?))
+}
+
+//
+// ---------------------- READING PROGRAMS ----------------------
+//
+
+async fn load_program(pool: &MySqlPool, library_id: i32, id: i32) -> Result<Program, ApiError> {
+    sqlx::query_as::<_, Program>(
+        "SELECT id, name, starts_on, ends_on, target_count, created_at
+         FROM programs WHERE id = ? AND library_id = ?",
+    )
+    .bind(id)
+    .bind(library_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::missing_resource("not_found.program", id))
+}
+
+/// GET /programs – semua program membaca, terbaru dulu.
+async fn list_programs(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<Vec<Program>>, ApiError> {
+    let programs = sqlx::query_as::<_, Program>(
+        "SELECT id, name, starts_on, ends_on, target_count, created_at
+         FROM programs WHERE library_id = ? ORDER BY starts_on DESC, id DESC",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(programs))
+}
+
+/// POST /programs – buat program membaca baru.
+async fn create_program(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<NewProgram>,
+) -> Result<Json<Program>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let now = state.clock.now_naive();
+
+    let res = sqlx::query(
+        "INSERT INTO programs (library_id, name, starts_on, ends_on, target_count, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(tenant.library_id)
+    .bind(&payload.name)
+    .bind(payload.starts_on)
+    .bind(payload.ends_on)
+    .bind(payload.target_count)
+    .bind(now)
+    .execute(&state.pool)
+    .await?;
+
+    let program = load_program(&state.pool, tenant.library_id, res.last_insert_id() as i32).await?;
+    AuditEntry::bulk(&tenant, ACTION_PROGRAM_CREATE, None, &program)
+        .write_logged(&state.pool, now)
+        .await;
+    Ok(Json(program))
+}
+
+/// POST /programs/:id/enrollments – daftarkan anggota ke program (idempoten).
+async fn enroll_program_member(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    JsonBody(payload): JsonBody<NewEnrollment>,
+) -> Result<Json<ProgramProgress>, ApiError> {
+    let program = load_program(&state.pool, tenant.library_id, id).await?;
+    let member: Option<i32> =
+        sqlx::query_scalar("SELECT id FROM members WHERE id = ? AND library_id = ?")
+            .bind(payload.member_id)
+            .bind(tenant.library_id)
+            .fetch_optional(&state.pool)
+            .await?;
+    if member.is_none() {
+        return Err(ApiError::missing_resource("not_found.member", payload.member_id));
+    }
+
+    let now = state.clock.now_naive();
+    sqlx::query(
+        "INSERT IGNORE INTO program_enrollments (program_id, member_id, enrolled_at)
+         VALUES (?, ?, ?)",
+    )
+    .bind(id)
+    .bind(payload.member_id)
+    .bind(now)
+    .execute(&state.pool)
+    .await?;
+
+    let details = serde_json::json!({ "program_id": id, "member_id": payload.member_id });
+    AuditEntry::bulk(&tenant, ACTION_PROGRAM_ENROLL, Some(Entity::Member), details)
+        .write_logged(&state.pool, now)
+        .await;
+
+    Ok(Json(program_progress(&state.pool, tenant.library_id, &program, payload.member_id).await?))
+}
+
+/// Progres satu anggota: buku berbeda yang dikembalikan (bukan hilang) di dalam jendela program.
+async fn program_progress(
+    pool: &MySqlPool,
+    library_id: i32,
+    program: &Program,
+    member_id: MemberId,
+) -> Result<ProgramProgress, sqlx::Error> {
+    let (start, end) = program.window();
+    let books: Vec<BookId> = sqlx::query_scalar(
+        "SELECT DISTINCT book_id FROM loans
+         WHERE library_id = ? AND member_id = ? AND lost_at IS NULL
+           AND returned_at >= ? AND returned_at < ?
+         ORDER BY book_id",
+    )
+    .bind(library_id)
+    .bind(member_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+
+    let completed = books.len() as i64;
+    Ok(ProgramProgress {
+        program_id: program.id,
+        member_id,
+        completed,
+        target_count: program.target_count,
+        reached_target: completed >= i64::from(program.target_count),
+        books,
+    })
+}
+
+/// GET /programs/:id/leaderboard – peserta program urut jumlah buku selesai terbanyak.
+async fn program_leaderboard(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<LeaderboardEntry>>, ApiError> {
+    let program = load_program(&state.pool, tenant.library_id, id).await?;
+    let (start, end) = program.window();
+
+    let mut entries = sqlx::query_as::<_, LeaderboardEntry>(
+        "SELECT m.id AS member_id, m.name, COUNT(DISTINCT l.book_id) AS completed
+         FROM program_enrollments e
+         JOIN members m ON m.id = e.member_id AND m.library_id = ?
+         LEFT JOIN loans l ON l.member_id = m.id AND l.library_id = m.library_id
+              AND l.lost_at IS NULL AND l.returned_at >= ? AND l.returned_at < ?
+         WHERE e.program_id = ?
+         GROUP BY m.id, m.name
+         ORDER BY completed DESC, m.id",
+    )
+    .bind(tenant.library_id)
+    .bind(start)
+    .bind(end)
+    .bind(id)
+    .fetch_all(&state.pool)
+    .await?;
+    for entry in &mut entries {
+        entry.reached_target = entry.completed >= i64::from(program.target_count);
+    }
+
+    Ok(Json(entries))
+}
+
+/// GET /programs/:id/members/:member_id/progress – progres satu peserta.
+async fn program_member_progress(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path((id, raw_member_id)): Path<(i32, String)>,
+) -> Result<Json<ProgramProgress>, ApiError> {
+    let program = load_program(&state.pool, tenant.library_id, id).await?;
+    let member_id: MemberId =
+        public_id::resolve(&state.pool, tenant.library_id, &raw_member_id).await?;
+
+    let enrolled: Option<i32> = sqlx::query_scalar(
+        "SELECT member_id FROM program_enrollments WHERE program_id = ? AND member_id = ?",
+    )
+    .bind(id)
+    .bind(member_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    if enrolled.is_none() {
+        return Err(ApiError::not_found(
+            Message::new("not_found.enrollment")
+                .param("member", &raw_member_id)
+                .param("id", id),
+        ));
+    }
+
+    Ok(Json(program_progress(&state.pool, tenant.library_id, &program, member_id).await?))
+}
+
+//
+// ---------------------- PURCHASE REQUESTS ----------------------
+//
+
+/// SELECT usulan pengadaan beserta jumlah peminatnya; tambahkan WHERE sendiri.
+const PURCHASE_REQUEST_SELECT: &str = "SELECT r.id, r.title, r.author, r.requester_member_id, r.note,
+        r.status,
+        (SELECT COUNT(*) FROM purchase_request_interests i WHERE i.request_id = r.id) AS interested,
+        r.expected_copies, r.reject_reason, r.book_id, r.created_at, r.decided_at, r.received_at
+ FROM purchase_requests r";
+
+async fn load_purchase_request<'e, E>(
+    executor: E,
+    library_id: i32,
+    id: i32,
+) -> Result<PurchaseRequest, ApiError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, PurchaseRequest>(&format!(
+        "{PURCHASE_REQUEST_SELECT} WHERE r.id = ? AND r.library_id = ?"
+    ))
+    .bind(id)
+    .bind(library_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| ApiError::missing_resource("not_found.purchase_request", id))
+}
+
+/// Kunci baris usulan (FOR UPDATE) dan pastikan statusnya salah satu dari `allowed`.
+async fn lock_purchase_request(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    id: i32,
+    allowed: &[&str],
+) -> Result<PurchaseRequest, ApiError> {
+    let status: Option<String> = sqlx::query_scalar(
+        "SELECT status FROM purchase_requests WHERE id = ? AND library_id = ? FOR UPDATE",
+    )
+    .bind(id)
+    .bind(library_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    match status {
+        None => Err(ApiError::missing_resource("not_found.purchase_request", id)),
+        Some(status) if !allowed.contains(&status.as_str()) => Err(ApiError::conflict(
+            Message::new("purchase.wrong_status")
+                .param("id", id)
+                .param("status", status)
+                .param("expected", allowed.join(", ")),
+        )),
+        Some(_) => load_purchase_request(&mut *conn, library_id, id).await,
+    }
+}
+
+/// POST /purchase-requests – usulkan judul baru. Kalau judul yang sama (setelah normalisasi)
+/// masih terbuka, anggota ini dicatat sebagai peminat tambahan ("+1") di usulan itu.
+async fn create_purchase_request(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<NewPurchaseRequest>,
+) -> Result<Json<SubmittedPurchaseRequest>, ApiError> {
+    let payload = payload.normalized().map_err(ApiError::bad_request)?;
+    let title_key = normalize::search_key(&payload.title);
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+
+    let member_exists: Option<i32> =
+        sqlx::query_scalar("SELECT id FROM members WHERE id = ? AND library_id = ?")
+            .bind(payload.member_id)
+            .bind(tenant.library_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    if member_exists.is_none() {
+        return Err(ApiError::missing_resource("not_found.member", payload.member_id));
+    }
+
+    let existing: Option<i32> = sqlx::query_scalar(
+        "SELECT id FROM purchase_requests
+         WHERE library_id = ? AND title_key = ? AND status IN (?, ?)
+         ORDER BY id LIMIT 1 FOR UPDATE",
+    )
+    .bind(tenant.library_id)
+    .bind(&title_key)
+    .bind(purchase_request::OPEN_STATUSES[0])
+    .bind(purchase_request::OPEN_STATUSES[1])
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let (id, merged) = match existing {
+        Some(id) => (id, true),
+        None => {
+            let res = sqlx::query(
+                "INSERT INTO purchase_requests
+                    (library_id, title, title_key, author, requester_member_id, note, status, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(tenant.library_id)
+            .bind(&payload.title)
+            .bind(&title_key)
+            .bind(&payload.author)
+            .bind(payload.member_id)
+            .bind(&payload.note)
+            .bind(purchase_request::STATUS_PENDING)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+            (res.last_insert_id() as i32, false)
+        }
+    };
+
+    // Anggota yang sama mengusulkan dua kali tetap dihitung satu peminat.
+    sqlx::query(
+        "INSERT IGNORE INTO purchase_request_interests (request_id, member_id, note, created_at)
+         VALUES (?, ?, ?, ?)",
+    )
+    .bind(id)
+    .bind(payload.member_id)
+    .bind(&payload.note)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+
+    let request = load_purchase_request(&mut *tx, tenant.library_id, id).await?;
+    let details = serde_json::json!({
+        "request": request,
+        "member_id": payload.member_id,
+        "merged": merged,
+    });
+    AuditEntry::bulk(&tenant, ACTION_PURCHASE_REQUEST_SUBMIT, None, details)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Json(SubmittedPurchaseRequest { request, merged }))
+}
+
+/// Query string untuk GET /purchase-requests.
+#[derive(Deserialize)]
+struct PurchaseRequestParams {
+    status: Option<String>,
+    /// Hanya usulan dengan minimal sekian peminat.
+    min_interested: Option<i64>,
+}
+
+/// GET /purchase-requests?status=pending – daftar usulan, paling banyak peminat dulu.
+async fn list_purchase_requests(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<PurchaseRequestParams>,
+) -> Result<Json<Vec<PurchaseRequest>>, ApiError> {
+    let mut qb = QueryBuilder::<MySql>::new(format!("SELECT * FROM ({PURCHASE_REQUEST_SELECT}"));
+    qb.push(" WHERE r.library_id = ").push_bind(tenant.library_id);
+    if let Some(status) = params.status.as_deref() {
+        let known = [
+            purchase_request::STATUS_PENDING,
+            purchase_request::STATUS_APPROVED,
+            purchase_request::STATUS_REJECTED,
+            purchase_request::STATUS_RECEIVED,
+        ];
+        if !known.contains(&status) {
+            return Err(unknown_value("status", status, known.join(", ")));
+        }
+        qb.push(" AND r.status = ").push_bind(status.to_string());
+    }
+    qb.push(") AS requests");
+    if let Some(min) = params.min_interested {
+        qb.push(" WHERE interested >= ").push_bind(min);
+    }
+    qb.push(" ORDER BY interested DESC, id");
+
+    let requests = qb
+        .build_query_as::<PurchaseRequest>()
+        .fetch_all(&state.pool)
+        .await?;
+    Ok(Json(requests))
+}
+
+/// POST /purchase-requests/:id/approve – setujui usulan dengan jumlah eksemplar yang akan dibeli.
+async fn approve_purchase_request(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    JsonBody(payload): JsonBody<ApprovePurchaseRequest>,
+) -> Result<Json<PurchaseRequest>, ApiError> {
+    if payload.expected_copies < 1 {
+        return Err(ApiError::bad_request(
+            Message::new("validation.min").param("field", "expected_copies").param("min", 1),
+        ));
+    }
+    let now = state.clock.now_naive();
+    let mut tx = state.pool.begin().await?;
+    let pending = [purchase_request::STATUS_PENDING];
+    lock_purchase_request(&mut tx, tenant.library_id, id, &pending).await?;
+
+    sqlx::query(
+        "UPDATE purchase_requests SET status = ?, expected_copies = ?, decided_at = ? WHERE id = ?",
+    )
+    .bind(purchase_request::STATUS_APPROVED)
+    .bind(payload.expected_copies)
+    .bind(now)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    let request = load_purchase_request(&mut *tx, tenant.library_id, id).await?;
+    AuditEntry::bulk(&tenant, ACTION_PURCHASE_REQUEST_APPROVE, None, &request)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+    Ok(Json(request))
+}
+
+/// POST /purchase-requests/:id/reject – tolak usulan dengan alasan.
+async fn reject_purchase_request(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    JsonBody(payload): JsonBody<RejectPurchaseRequest>,
+) -> Result<Json<PurchaseRequest>, ApiError> {
+    let reason = normalize::required("reason", &payload.reason).map_err(ApiError::bad_request)?;
+    let now = state.clock.now_naive();
+    let mut tx = state.pool.begin().await?;
+    lock_purchase_request(&mut tx, tenant.library_id, id, &purchase_request::OPEN_STATUSES).await?;
+
+    sqlx::query(
+        "UPDATE purchase_requests SET status = ?, reject_reason = ?, decided_at = ? WHERE id = ?",
+    )
+    .bind(purchase_request::STATUS_REJECTED)
+    .bind(&reason)
+    .bind(now)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    let request = load_purchase_request(&mut *tx, tenant.library_id, id).await?;
+    AuditEntry::bulk(&tenant, ACTION_PURCHASE_REQUEST_REJECT, None, &request)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+    Ok(Json(request))
+}
+
+/// POST /purchase-requests/:id/received – buku yang disetujui sudah datang: buat `Book`
+/// dan tautkan ke usulan, dalam satu transaksi.
+async fn receive_purchase_request(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    JsonBody(payload): JsonBody<ReceivePurchaseRequest>,
+) -> Result<Json<ReceivedPurchaseRequest>, ApiError> {
+    let now = state.clock.now_naive();
+    let mut tx = state.pool.begin().await?;
+    let request =
+        lock_purchase_request(&mut tx, tenant.library_id, id, &[purchase_request::STATUS_APPROVED])
+            .await?;
+
+    let copies = payload.copies.or(request.expected_copies).unwrap_or(1);
+    if copies < 1 {
+        return Err(ApiError::bad_request(
+            Message::new("validation.min").param("field", "copies").param("min", 1),
+        ));
+    }
+    let new_book = NewBook {
+        title: request.title.clone(),
+        author: payload
+            .author
+            .or_else(|| request.author.clone())
+            .ok_or_else(|| ApiError::missing("author"))?,
+        category: payload.category,
+        year: payload.year,
+        total_copies: copies,
+        location: None,
+    }
+    .normalized()
+    .map_err(ApiError::bad_request)?;
+
+    let (category_id, category) =
+        book_category(&mut tx, &state, tenant.library_id, &new_book.category).await?;
+    let res = sqlx::query(
+        "INSERT INTO books (library_id, public_id, title, author, category, year, total_copies, available_copies,
+                            category_id, title_sort_key, author_sort_key)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(tenant.library_id)
+    .bind(public_id::generate(Entity::Book))
+    .bind(&new_book.title)
+    .bind(&new_book.author)
+    .bind(&category)
+    .bind(new_book.year)
+    .bind(new_book.total_copies)
+    .bind(new_book.total_copies)
+    .bind(category_id)
+    .bind(collation::sort_key(&new_book.title, &state.config.collation_articles))
+    .bind(collation::sort_key(&new_book.author, &state.config.collation_articles))
+    .execute(&mut *tx)
+    .await?;
+    let book_id = BookId(res.last_insert_id() as i32);
+    Movement {
+        library_id: tenant.library_id,
+        book_id,
+        delta: copies,
+        reason: StockReason::Restock,
+        reference_id: Some(id),
+    }
+    .write(&mut *tx, now)
+    .await?;
+
+    sqlx::query(
+        "UPDATE purchase_requests SET status = ?, book_id = ?, received_at = ? WHERE id = ?",
+    )
+    .bind(purchase_request::STATUS_RECEIVED)
+    .bind(book_id)
+    .bind(now)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    let book = repo::find_book(&mut *tx, tenant.library_id, book_id)
+        .await?
+        .ok_or_else(|| ApiError::internal("newly inserted book not found"))?;
+    let request = load_purchase_request(&mut *tx, tenant.library_id, id).await?;
+
+    AuditEntry::new(&tenant, ACTION_CREATE, Entity::Book, book_id.0, &book)
+        .write(&mut *tx, now)
+        .await?;
+    AuditEntry::bulk(&tenant, ACTION_PURCHASE_REQUEST_RECEIVE, Some(Entity::Book), &request)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Json(ReceivedPurchaseRequest { request, book }))
+}
+
+//
+// ---------------------- STATS ----------------------
+//
+
+/// Query string GET /stats/busiest-days.
+#[derive(Deserialize)]
+struct BusiestDaysParams {
+    /// True = dikelompokkan per hari dan jam.
+    #[serde(default)]
+    by_hour: bool,
+}
+
+/// GET /stats/busiest-days – jumlah pinjaman per hari dalam seminggu (dari `borrowed_at`),
+/// paling ramai dulu, untuk perencanaan jadwal petugas. `?by_hour=true` memecah per jam.
+/// `borrowed_at` disimpan UTC, jadi digeser dulu ke LIBRARY_UTC_OFFSET sebelum diambil hari
+/// dan jamnya (pinjaman Senin 06:00 WIB = Minggu 23:00 UTC).
+async fn busiest_days(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<BusiestDaysParams>,
+) -> Result<Json<Vec<BusyDay>>, ApiError> {
+    let sql = if params.by_hour {
+        "SELECT CAST(DAYOFWEEK(local_at) AS SIGNED) AS day_of_week,
+                CAST(HOUR(local_at) AS SIGNED) AS hour,
+                COUNT(*) AS loans
+         FROM (SELECT borrowed_at + INTERVAL ? SECOND AS local_at
+               FROM loans WHERE library_id = ?) l
+         GROUP BY day_of_week, hour
+         ORDER BY loans DESC, day_of_week, hour"
+    } else {
+        "SELECT CAST(DAYOFWEEK(local_at) AS SIGNED) AS day_of_week,
+                CAST(NULL AS SIGNED) AS hour,
+                COUNT(*) AS loans
+         FROM (SELECT borrowed_at + INTERVAL ? SECOND AS local_at
+               FROM loans WHERE library_id = ?) l
+         GROUP BY day_of_week
+         ORDER BY loans DESC, day_of_week"
+    };
+    let offset_seconds = state.config.library_utc_offset.0.local_minus_utc();
+    let query = sqlx::query_as::<_, BusyDay>(sql)
+        .bind(offset_seconds)
+        .bind(tenant.library_id)
+        .fetch_all(&state.pool);
+    let days = state.metrics.time("stats.busiest_days", query).await?;
+
+    Ok(Json(
+        days.into_iter()
+            .map(|day| BusyDay { day_name: BusyDay::day_name(day.day_of_week), ..day })
+            .collect(),
+    ))
+}
+
+//
+// ---------------------- ADMIN ----------------------
+//
+
+/// GET /admin/orphaned-loans – peminjaman yang buku/anggotanya sudah tidak ada.
+/// Baris yang gagal di-decode menggagalkan request, bukan dilewati diam-diam.
+async fn list_orphaned_loans(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<Vec<OrphanedLoan>>, ApiError> {
+    let result = sqlx::query(
+        "SELECT l.id, l.public_id, l.book_id, l.member_id, l.borrowed_at, l.due_at, l.returned_at, l.lost_at,
+                b.id AS existing_book_id, m.id AS existing_member_id
+         FROM loans l
+         LEFT JOIN books b ON b.id = l.book_id AND b.library_id = l.library_id
+         LEFT JOIN members m ON m.id = l.member_id AND m.library_id = l.library_id
+         WHERE l.library_id = ? AND (b.id IS NULL OR m.id IS NULL)",
+    )
+    .bind(tenant.library_id)
+    .fetch_all(&state.pool)
+    .await;
+
+    let orphans = result.and_then(|rows| {
+        rows.iter()
+            .map(|row| {
+                let book_ref: Option<i32> = row.try_get("existing_book_id")?;
+                let member_ref: Option<i32> = row.try_get("existing_member_id")?;
+                Ok(OrphanedLoan {
+                    loan: Loan::from_row(row)?,
+                    book_missing: book_ref.is_none(),
+                    member_missing: member_ref.is_none(),
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()
+    });
+
+    match orphans {
+        Ok(orphans) => Ok(Json(orphans)),
+        Err(e) => {
+            eprintln!("DB error on list_orphaned_loans: {e}");
+            Err(e.into())
+        }
+    }
+}
+
+/// POST /admin/extend-all-loans – geser due_at semua pinjaman aktif sejauh `days` hari
+/// dalam satu statement (penutupan/libur), supaya tidak ada yang kena denda.
+async fn extend_all_loans(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    JsonBody(payload): JsonBody<ExtendLoans>,
+) -> Result<Json<u64>, ApiError> {
+    if !(1..=loan::MAX_EXTEND_DAYS).contains(&payload.days) {
+        return Err(ApiError::out_of_range("days", loan::MAX_EXTEND_DAYS));
+    }
+    let now = state.clock.now_naive();
+
+    let mut tx = state.pool.begin().await?;
+    let extended = sqlx::query(
+        "UPDATE loans SET due_at = due_at + INTERVAL ? DAY
+         WHERE library_id = ? AND returned_at IS NULL",
+    )
+    .bind(payload.days)
+    .bind(tenant.library_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    let details = serde_json::json!({ "days": payload.days, "extended": extended });
+    AuditEntry::bulk(&tenant, ACTION_LOANS_EXTEND, Some(Entity::Loan), details)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+
+    println!(
+        "Extended {extended} active loans by {} days (library_id={})",
+        payload.days, tenant.library_id
+    );
+    Ok(Json(extended))
+}
+
+/// DELETE /admin/orphaned-loans – hapus semua peminjaman yatim.
+/// Kalau bukunya masih ada dan pinjaman belum dikembalikan, stoknya dikembalikan dulu.
+async fn purge_orphaned_loans(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<u64>, ApiError> {
+    let now = state.clock.now_naive();
+    let mut tx = state.pool.begin().await?;
+
+    // 1. Kembalikan stok untuk pinjaman aktif yang anggotanya hilang tapi bukunya masih ada,
+    //    satu baris buku besar per pinjaman.
+    sqlx::query(
+        "INSERT INTO stock_movements (library_id, book_id, delta, reason, reference_id, created_at)
+         SELECT l.library_id, l.book_id, 1, ?, l.id, ?
+         FROM loans l
+         JOIN books b ON b.id = l.book_id AND b.library_id = l.library_id
+         LEFT JOIN members m ON m.id = l.member_id AND m.library_id = l.library_id
+         WHERE l.library_id = ? AND m.id IS NULL AND l.returned_at IS NULL",
+    )
+    .bind(StockReason::Return.name())
+    .bind(now)
+    .bind(tenant.library_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "UPDATE books b
+         JOIN (SELECT l.book_id, COUNT(*) AS cnt
+               FROM loans l
+               LEFT JOIN members m ON m.id = l.member_id AND m.library_id = l.library_id
+               WHERE l.library_id = ? AND m.id IS NULL AND l.returned_at IS NULL
+               GROUP BY l.book_id) o ON o.book_id = b.id
+         SET b.available_copies = b.available_copies + o.cnt,
+             b.version = b.version + 1,
+             b.updated_at = ?
+         WHERE b.library_id = ?",
+    )
+    .bind(tenant.library_id)
+    .bind(now)
+    .bind(tenant.library_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // 2. Hapus baris yatim
+    let deleted = sqlx::query(
+        "DELETE l FROM loans l
+         LEFT JOIN books b ON b.id = l.book_id AND b.library_id = l.library_id
+         LEFT JOIN members m ON m.id = l.member_id AND m.library_id = l.library_id
+         WHERE l.library_id = ? AND (b.id IS NULL OR m.id IS NULL)",
+    )
+    .bind(tenant.library_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    // 3. Audit di transaksi yang sama, jadi hanya tercatat kalau purge-nya benar-benar commit.
+    let details = serde_json::json!({ "deleted": deleted });
+    AuditEntry::bulk(&tenant, ACTION_PURGE_ORPHANS, Some(Entity::Loan), details)
+        .write(&mut *tx, now)
+        .await?;
+    tx.commit().await?;
+    Ok(Json(deleted))
+}
+
+/// Query string untuk GET /admin/integrity.
+#[derive(Deserialize)]
+struct IntegrityParams {
+    /// Bentuk lama `GET ?repair=true`; masih dilayani seperti `POST`, tapi usang.
+    #[serde(default)]
+    repair: bool,
+}
+
+/// Cek 1: angka stok tiap buku dibanding jumlah pinjaman aktifnya.
+async fn load_stock_snapshots(
+    conn: &mut sqlx::MySqlConnection,
+    library_id: i32,
+) -> Result<Vec<StockSnapshot>, sqlx::Error> {
+    sqlx::query_as::<_, StockSnapshot>(
+        "SELECT b.id AS book_id, b.total_copies, b.available_copies,
+                COUNT(l.id) AS active_loans,
+                CAST(COALESCE((SELECT SUM(s.delta) FROM stock_movements s
+                               WHERE s.book_id = b.id), 0) AS SIGNED) AS ledger_balance
+         FROM books b
+         LEFT JOIN loans l
+           ON l.book_id = b.id AND l.library_id = b.library_id AND l.returned_at IS NULL
+         WHERE b.library_id = ?
+         GROUP BY b.id, b.total_copies, b.available_copies
+         ORDER BY b.id",
+    )
+    .bind(library_id)
+    .fetch_all(conn)
+    .await
+}
+
+/// Cek 2: pinjaman yang buku atau anggotanya sudah tidak ada.
+async fn load_orphan_refs(
+    conn: &mut sqlx::MySqlConnection,
+    library_id: i32,
+) -> Result<Vec<LoanRefs>, sqlx::Error> {
+    sqlx::query_as::<_, LoanRefs>(
+        "SELECT l.id AS loan_id, b.id AS existing_book_id, m.id AS existing_member_id
+         FROM loans l
+         LEFT JOIN books b ON b.id = l.book_id AND b.library_id = l.library_id
+         LEFT JOIN members m ON m.id = l.member_id AND m.library_id = l.library_id
+         WHERE l.library_id = ? AND (b.id IS NULL OR m.id IS NULL)
+         ORDER BY l.id",
+    )
+    .bind(library_id)
+    .fetch_all(conn)
+    .await
+}
+
+/// Cek 3: anggota yang memegang lebih banyak eksemplar satu buku daripada total_copies-nya.
+async fn load_excess_holdings(
+    conn: &mut sqlx::MySqlConnection,
+    library_id: i32,
+) -> Result<Vec<MemberHolding>, sqlx::Error> {
+    sqlx::query_as::<_, MemberHolding>(
+        "SELECT l.member_id, l.book_id, COUNT(*) AS active_loans, b.total_copies
+         FROM loans l
+         JOIN books b ON b.id = l.book_id AND b.library_id = l.library_id
+         WHERE l.library_id = ? AND l.returned_at IS NULL
+         GROUP BY l.member_id, l.book_id, b.total_copies
+         HAVING COUNT(*) > b.total_copies
+         ORDER BY l.member_id, l.book_id",
+    )
+    .bind(library_id)
+    .fetch_all(conn)
+    .await
+}
+
+/// Jalankan semua cek integritas untuk satu perpustakaan di dalam `conn`.
+/// Mengembalikan laporan (tanpa `repaired`) beserta snapshot stok yang diperiksa.
+async fn integrity_report(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+) -> Result<(IntegrityReport, Vec<StockSnapshot>), sqlx::Error> {
+    let stocks = load_stock_snapshots(&mut *conn, library_id).await?;
+    let orphans = load_orphan_refs(&mut *conn, library_id).await?;
+    let holdings = load_excess_holdings(&mut *conn, library_id).await?;
+
+    let books: Vec<BookViolations> = stocks
+        .iter()
+        .map(|stock| BookViolations {
+            book_id: stock.book_id,
+            violations: check_book(stock),
+        })
+        .filter(|b| !b.violations.is_empty())
+        .collect();
+    let loans: Vec<LoanViolations> = orphans
+        .iter()
+        .filter_map(|refs| {
+            check_loan(refs).map(|violation| LoanViolations {
+                loan_id: refs.loan_id,
+                violations: vec![violation],
+            })
+        })
+        .collect();
+    let members = member_violations(&holdings);
+
+    let report = IntegrityReport {
+        healthy: books.is_empty() && loans.is_empty() && members.is_empty(),
+        books_checked: stocks.len(),
+        books,
+        loans,
+        members,
+        repaired: None,
+    };
+    Ok((report, stocks))
+}
+
+/// GET /admin/integrity – jalankan semua cek integritas untuk perpustakaan pemilik key
+/// (butuh scope `admin`). Hanya membaca; perbaikan lewat `POST /admin/integrity`.
+/// `?repair=true` (kontrak lama) tetap memperbaiki seperti POST, dengan header `Deprecation`
+/// dan `Link` yang menunjuk ke POST.
+async fn check_integrity(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<IntegrityParams>,
+) -> Result<Response, ApiError> {
+    if params.repair {
+        let report = repair_integrity(State(state), tenant).await?;
+        let headers = [
+            (header::HeaderName::from_static("deprecation"), "true"),
+            (header::LINK, "</admin/integrity>; rel=\"successor-version\"; method=\"POST\""),
+        ];
+        return Ok((headers, report).into_response());
+    }
+
+    let mut conn = state.pool.acquire().await?;
+    let (report, _) = integrity_report(&mut conn, tenant.library_id).await?;
+    Ok(Json(report).into_response())
+}
+
+/// POST /admin/integrity – cek seperti GET, lalu hitung ulang available_copies dari tabel
+/// loans untuk buku yang melanggar dan tambah pergerakan `repair` supaya buku besar stok
+/// kembali sama dengan counter-nya. Laporan berisi pelanggaran sebelum perbaikan.
+async fn repair_integrity(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<IntegrityReport>, ApiError> {
+    let library_id = tenant.library_id;
+    let mut tx = state.pool.begin().await?;
+    let (mut report, stocks) = integrity_report(&mut tx, library_id).await?;
+
+    let now = state.clock.now_naive();
+    let mut count = 0;
+    for change in stocks.iter().filter_map(plan_recount) {
+        if change.after != change.before {
+            count += sqlx::query(
+                "UPDATE books SET available_copies = ?, version = version + 1, updated_at = ?
+                 WHERE id = ? AND library_id = ?",
+            )
+            .bind(change.after)
+            .bind(now)
+            .bind(change.book_id)
+            .bind(library_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        if let Some(delta) = change.ledger_correction {
+            Movement {
+                library_id,
+                book_id: change.book_id,
+                delta,
+                reason: StockReason::Repair,
+                reference_id: None,
+            }
+            .write(&mut *tx, now)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+
+    println!(
+        "Integrity repair: recomputed available_copies for {count} books (library_id={library_id})"
+    );
+    report.repaired = Some(count);
+    Ok(Json(report))
+}
+
+/// Query string untuk POST /admin/books/recount.
+#[derive(Deserialize)]
+struct RecountParams {
+    /// Batasi recount ke satu buku (public id atau id integer).
+    book_id: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// POST /admin/books/recount – set available_copies = total_copies - pinjaman aktif
+/// (dijepit ke [0, total]), satu transaksi per buku beserta entri audit-nya.
+/// `?dry_run=true` hanya melaporkan.
+async fn recount_books(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<RecountParams>,
+) -> Result<Json<RecountReport>, ApiError> {
+    let book_ids: Vec<BookId> = match params.book_id.as_deref() {
+        Some(raw) => vec![public_id::resolve::<BookId>(&state.pool, tenant.library_id, raw).await?],
+        None => sqlx::query_scalar("SELECT id FROM books WHERE library_id = ? ORDER BY id")
+            .bind(tenant.library_id)
+            .fetch_all(&state.pool)
+            .await?,
+    };
+
+    let now = state.clock.now_naive();
+    let mut changed = Vec::new();
+    let mut books_checked = 0;
+
+    for book_id in book_ids {
+        let mut tx = state.pool.begin().await?;
+
+        // Kunci baris buku supaya pinjam/kembali tidak menyelip di antara hitung dan update.
+        let Some(row) = sqlx::query(
+            "SELECT total_copies, available_copies FROM books
+             WHERE id = ? AND library_id = ? FOR UPDATE",
+        )
+        .bind(book_id)
+        .bind(tenant.library_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            if params.book_id.is_some() {
+                return Err(ApiError::missing_resource("not_found.book", book_id));
+            }
+            // Dihapus sejak daftar id diambil.
+            continue;
+        };
+
+        let active_loans: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM loans
+             WHERE book_id = ? AND library_id = ? AND returned_at IS NULL",
+        )
+        .bind(book_id)
+        .bind(tenant.library_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let ledger_balance: i64 = sqlx::query_scalar(
+            "SELECT CAST(COALESCE(SUM(delta), 0) AS SIGNED) FROM stock_movements WHERE book_id = ?",
+        )
+        .bind(book_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let stock = StockSnapshot {
+            book_id,
+            total_copies: row.get("total_copies"),
+            available_copies: row.get("available_copies"),
+            active_loans,
+            ledger_balance,
+        };
+        books_checked += 1;
+
+        let Some(change) = plan_recount(&stock) else {
+            continue;
+        };
+
+        // Dry-run menjalankan UPDATE yang sama lalu rollback (tx di-drop tanpa commit),
+        // jadi validasi dan hasilnya identik dengan run sungguhan.
+        if change.after != change.before {
+            sqlx::query(
+                "UPDATE books SET available_copies = ?, version = version + 1, updated_at = ?
+                 WHERE id = ? AND library_id = ?",
+            )
+            .bind(change.after)
+            .bind(now)
+            .bind(book_id)
+            .bind(tenant.library_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        if let Some(delta) = change.ledger_correction {
+            Movement {
+                library_id: tenant.library_id,
+                book_id,
+                delta,
+                reason: StockReason::Repair,
+                reference_id: None,
+            }
+            .write(&mut *tx, now)
+            .await?;
+        }
+        // Audit ikut transaksi perbaikannya: stok berubah tanpa audit (atau sebaliknya) tidak
+        // mungkin terjadi.
+        AuditEntry::new(&tenant, ACTION_BOOKS_RECOUNT, Entity::Book, book_id.0, &change)
+            .write(&mut *tx, now)
+            .await?;
+        if params.dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+
+        changed.push(change);
+    }
+
+    let report = RecountReport {
+        dry_run: params.dry_run,
+        books_checked,
+        changed,
+    };
+
+    if !params.dry_run {
+        println!(
+            "Recount: {} of {} books changed (library_id={})",
+            report.changed.len(),
+            report.books_checked,
+            tenant.library_id
+        );
+    }
+
+    Ok(Json(report))
+}
+
+/// Query string untuk GET /admin/audit.
+#[derive(Deserialize)]
+struct AuditParams {
+    /// `book`, `member`, atau `loan`.
+    entity: Option<String>,
+    /// Public id atau id integer; butuh `entity`.
+    id: Option<String>,
+    limit: Option<u32>,
+}
+
+/// GET /admin/audit?entity=book&id=5 – riwayat perubahan, terbaru dulu.
+async fn list_audit(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<AuditParams>,
+) -> Result<Json<Vec<AuditRecord>>, ApiError> {
+    let entity = match params.entity.as_deref() {
+        None => None,
+        Some(raw) => Some(
+            Entity::from_name(raw)
+                .ok_or_else(|| unknown_value("entity", raw, "book, member, loan"))?,
+        ),
+    };
+    let entity_id = match (entity, params.id.as_deref()) {
+        (_, None) => None,
+        (None, Some(_)) => {
+            return Err(ApiError::bad_request(
+                Message::new("validation.requires").param("field", "id").param("other", "entity"),
+            ));
+        }
+        (Some(Entity::Book), Some(raw)) => {
+            Some(public_id::resolve::<BookId>(&state.pool, tenant.library_id, raw).await?.0)
+        }
+        (Some(Entity::Member), Some(raw)) => {
+            Some(public_id::resolve::<MemberId>(&state.pool, tenant.library_id, raw).await?.0)
+        }
+        (Some(Entity::Loan), Some(raw)) => {
+            Some(public_id::resolve::<LoanId>(&state.pool, tenant.library_id, raw).await?.0)
+        }
+    };
+    let limit = params.limit.unwrap_or(state.config.default_page_size);
+    if limit == 0 || limit > state.config.max_page_size {
+        return Err(ApiError::out_of_range("limit", state.config.max_page_size));
+    }
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "SELECT id, action, entity, entity_id, actor, details, created_at
+         FROM audit_log WHERE library_id = ",
+    );
+    qb.push_bind(tenant.library_id);
+    if let Some(entity) = entity {
+        qb.push(" AND entity = ").push_bind(entity.name());
+    }
+    if let Some(entity_id) = entity_id {
+        qb.push(" AND entity_id = ").push_bind(entity_id);
+    }
+    qb.push(" ORDER BY created_at DESC, id DESC LIMIT ").push_bind(i64::from(limit));
+
+    let records = qb.build_query_as::<AuditRecord>().fetch_all(&state.pool).await?;
+    Ok(Json(records))
+}
+
+/// GET /admin/config – konfigurasi efektif yang dimuat saat startup (tanpa rahasia).
+async fn get_config(State(state): State<AppState>, _op: Operator) -> Json<AppConfig> {
+    Json(state.config.as_ref().clone())
+}
+
+/// GET /admin/maintenance – status mode pemeliharaan.
+async fn get_maintenance(State(state): State<AppState>, _op: Operator) -> Json<MaintenanceMode> {
+    Json(MaintenanceMode {
+        enabled: state.maintenance.load(Ordering::SeqCst),
+    })
+}
+
+/// PUT /admin/maintenance – nyalakan/matikan mode pemeliharaan. Berlaku untuk semua
+/// perpustakaan, jadi perubahannya dicatat di audit setiap perpustakaan.
+async fn set_maintenance(
+    State(state): State<AppState>,
+    _op: Operator,
+    JsonBody(payload): JsonBody<MaintenanceMode>,
+) -> Json<MaintenanceMode> {
+    let was_enabled = state.maintenance.swap(payload.enabled, Ordering::SeqCst);
+    if was_enabled == payload.enabled {
+        return Json(payload);
+    }
+    let now = state.clock.now_naive();
+    match sqlx::query_scalar::<_, i32>("SELECT id FROM libraries").fetch_all(&state.pool).await {
+        Ok(ids) => {
+            for library_id in ids {
+                AuditEntry::operator(library_id, ACTION_MAINTENANCE, &payload)
+                    .write_logged(&state.pool, now)
+                    .await;
+            }
+        }
+        Err(e) => eprintln!("DB error on audit_log ({ACTION_MAINTENANCE}): {e}"),
+    }
+    Json(payload)
+}
+
+/// Query string untuk GET /admin/backup.
+#[derive(Deserialize)]
+struct BackupParams {
+    /// Perpustakaan yang di-backup (default DEFAULT_LIBRARY_ID).
+    library_id: Option<i32>,
+    /// `json` (default) atau `ndjson.zst`.
+    format: Option<String>,
+}
+
+/// Format file backup/restore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackupFormat {
+    /// Satu dokumen JSON `Backup`.
+    Json,
+    /// `BackupRecord` per baris, dikompresi zstd dan dialirkan sebagai stream.
+    NdJsonZst,
+}
+
+impl BackupFormat {
+    fn parse(raw: Option<&str>) -> Result<Self, ApiError> {
+        match raw {
+            None | Some("json") => Ok(Self::Json),
+            Some(backup::FORMAT_NDJSON_ZST) => Ok(Self::NdJsonZst),
+            Some(other) => Err(unknown_value(
+                "format",
+                other,
+                format!("json, {}", backup::FORMAT_NDJSON_ZST),
+            )),
+        }
+    }
+}
+
+const BACKUP_BOOKS_SQL: &str =
+    "SELECT id, public_id, title, author, category, year, total_copies, available_copies,
+            version, updated_at, location
+     FROM books WHERE library_id = ? ORDER BY id";
+const BACKUP_MEMBERS_SQL: &str =
+    "SELECT id, public_id, name, email, joined_at FROM members WHERE library_id = ? ORDER BY id";
+const BACKUP_LOANS_SQL: &str =
+    "SELECT id, public_id, book_id, member_id, borrowed_at, due_at, returned_at, lost_at
+     FROM loans WHERE library_id = ? ORDER BY id";
+const BACKUP_FINES_SQL: &str = "SELECT id, loan_id, member_id, amount, reason, created_at, paid_at
+     FROM fines WHERE library_id = ? ORDER BY id";
+
+/// GET /admin/backup (alias GET /admin/snapshot) – satu file JSON berisi books, members,
+/// loans, dan fines satu perpustakaan. Dipulihkan lewat POST /admin/restore.
+/// `?format=ndjson.zst` mengalirkan backup yang sama sebagai NDJSON terkompresi zstd.
+async fn backup_library(
+    State(state): State<AppState>,
+    _op: Operator,
+    Query(params): Query<BackupParams>,
+) -> Result<Response, ApiError> {
+    let Some(library_id) = params.library_id.or(state.config.default_library_id) else {
+        return Err(ApiError::missing("library_id"));
+    };
+    let format = BackupFormat::parse(params.format.as_deref())?;
+    let now = state.clock.now();
+
+    if format == BackupFormat::NdJsonZst {
+        let filename = format!(
+            "attachment; filename=\"backup-{library_id}-{}.{}\"",
+            now.format("%Y%m%d-%H%M%S"),
+            backup::FORMAT_NDJSON_ZST
+        );
+        let body = stream_backup_zst(state.pool.clone(), library_id, now.naive_utc());
+        return Ok((
+            [
+                (header::CONTENT_TYPE, backup::ZST_CONTENT_TYPE.to_string()),
+                (header::CONTENT_DISPOSITION, filename),
+            ],
+            body,
+        )
+            .into_response());
+    }
+
+    // Satu transaksi supaya keempat tabel konsisten satu sama lain.
+    let mut tx = state.pool.begin().await?;
+
+    let books = sqlx::query_as::<_, Book>(BACKUP_BOOKS_SQL)
+        .bind(library_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    let members = sqlx::query_as::<_, Member>(BACKUP_MEMBERS_SQL)
+        .bind(library_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    let loans = sqlx::query_as::<_, Loan>(BACKUP_LOANS_SQL)
+        .bind(library_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    let fines = sqlx::query_as::<_, Fine>(BACKUP_FINES_SQL)
+        .bind(library_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let backup = Backup {
+        version: BACKUP_VERSION,
+        library_id,
+        created_at: now.naive_utc(),
+        books,
+        members,
+        loans,
+        fines,
+    };
+    let filename = format!(
+        "attachment; filename=\"backup-{library_id}-{}.json\"",
+        now.format("%Y%m%d-%H%M%S")
+    );
+
+    Ok(([(header::CONTENT_DISPOSITION, filename)], Json(backup)).into_response())
+}
+
+/// Body backup NDJSON+zstd. Baris dibaca per stream dari DB di dalam satu transaksi dan
+/// dikompresi bertahap, jadi memori tetap datar berapa pun besar tabelnya. Kalau gagal di
+/// tengah jalan, body diputus dengan error (file terpotong, bukan frame zstd yang valid).
+fn stream_backup_zst(pool: MySqlPool, library_id: i32, created_at: NaiveDateTime) -> Body {
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, std::io::Error>>(8);
+    tokio::spawn(async move {
+        if let Err(e) = write_backup_zst(&pool, library_id, created_at, &tx).await {
+            eprintln!("Backup stream error (library_id={library_id}): {e}");
+            tx.send(Err(std::io::Error::other("backup stream terminated"))).await.ok();
+        }
+    });
+    Body::from_stream(futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }))
+}
+
+type ChunkSender = tokio::sync::mpsc::Sender<Result<Bytes, std::io::Error>>;
+
+async fn write_backup_zst(
+    pool: &MySqlPool,
+    library_id: i32,
+    created_at: NaiveDateTime,
+    out: &ChunkSender,
+) -> std::io::Result<()> {
+    let mut writer = ZstLineWriter::new()?;
+    let header = BackupRecord::Header {
+        version: BACKUP_VERSION,
+        library_id,
+        created_at,
+    };
+    send_chunk(out, writer.push(&header)?).await?;
+
+    let mut tx = pool.begin().await.map_err(std::io::Error::other)?;
+    let conn = &mut *tx;
+    stream_table(conn, BACKUP_BOOKS_SQL, library_id, &mut writer, out, BackupRecord::Book).await?;
+    stream_table(conn, BACKUP_MEMBERS_SQL, library_id, &mut writer, out, BackupRecord::Member)
+        .await?;
+    stream_table(conn, BACKUP_LOANS_SQL, library_id, &mut writer, out, BackupRecord::Loan).await?;
+    stream_table(conn, BACKUP_FINES_SQL, library_id, &mut writer, out, BackupRecord::Fine).await?;
+    tx.commit().await.map_err(std::io::Error::other)?;
 
-            Json(fetched)
-        }
-        Err(e) => {
-            eprintln!("DB error on create_book: {e}");
-            // fallback minimal
-            Json(Book {
-                id: -1,
-                title: payload.title,
-                author: payload.author,
-                category: payload.category,
-                year: payload.year,
-                total_copies: payload.total_copies,
-                available_copies: payload.total_copies,
-            })
-        }
-    }
+    send_chunk(out, Some(writer.finish()?)).await
 }
 
-/// DELETE /books/:id – hapus baris dari DB.
-async fn delete_book(
-    State(state): State<AppState>,
-    Path(id): Path<i32>,
-) -> Json<bool> {
-    let result = sqlx::query("DELETE FROM books WHERE id = ?")
-        .bind(id)
-        .execute(&state.pool)
-        .await;
+/// Alirkan semua baris `sql` sebagai record backup.
+async fn stream_table<T>(
+    conn: &mut MySqlConnection,
+    sql: &'static str,
+    library_id: i32,
+    writer: &mut ZstLineWriter,
+    out: &ChunkSender,
+    record: fn(T) -> BackupRecord,
+) -> std::io::Result<()>
+where
+    T: for<'r> FromRow<'r, sqlx::mysql::MySqlRow> + Send + Unpin,
+{
+    let mut rows = sqlx::query_as::<_, T>(sql).bind(library_id).fetch(conn);
+    while let Some(row) = rows.try_next().await.map_err(std::io::Error::other)? {
+        send_chunk(out, writer.push(&record(row))?).await?;
+    }
+    Ok(())
+}
 
-    match result {
-        Ok(res) => Json(res.rows_affected() > 0),
-        Err(e) => {
-            eprintln!("DB error on delete_book: {e}");
-            Json(false)
-        }
+/// Kirim chunk terkompresi ke body; error kalau client sudah memutus koneksi.
+async fn send_chunk(out: &ChunkSender, chunk: Option<Vec<u8>>) -> std::io::Result<()> {
+    match chunk {
+        Some(chunk) if !chunk.is_empty() => out
+            .send(Ok(Bytes::from(chunk)))
+            .await
+            .map_err(|_| std::io::Error::other("client disconnected")),
+        _ => Ok(()),
     }
 }
 
-//
-// ---------------------- SEARCH (PARALLEL) ----------------------
-//
+/// Batas ukuran body POST /admin/restore (default axum hanya 2 MB).
+const RESTORE_BODY_LIMIT: usize = 256 * 1024 * 1024;
 
-/// Query string untuk /search?mode=title&q=rust
+/// Query string untuk POST /admin/restore.
 #[derive(Deserialize)]
-struct SearchParams {
-    mode: Option<String>,
-    q: String,
+struct RestoreParams {
+    /// `json` (default) atau `ndjson.zst`; `Content-Type: application/zstd` juga berarti zst.
+    format: Option<String>,
 }
 
-/// GET /search – ambil semua buku dari DB, lalu FP + parallel search.
-async fn search_handler(
+/// POST /admin/restore – hapus lalu isi ulang data satu perpustakaan dari hasil backup.
+/// Hanya jalan saat mode pemeliharaan aktif, supaya tidak ada tulisan lain di tengah restore,
+/// dan hanya ke database asal backup (id dipertahankan, lihat `ensure_ids_unused`).
+/// Backup `ndjson.zst` didekompresi sebagai stream dan di-INSERT per RESTORE_CHUNK_SIZE baris.
+async fn restore_library(
     State(state): State<AppState>,
-    Query(params): Query<SearchParams>,
-) -> Json<Vec<Book>> {
-    // 1) Ambil snapshot dari DB (harus konsisten dengan struct Book).
-    let books_snapshot = match sqlx::query_as::<_, Book>(
-        "SELECT id, title, author, category, year, total_copies, available_copies FROM books",
-    )
-    .fetch_all(&state.pool)
-    .await
-    {
-        Ok(books) => books,
-        Err(e) => {
-            eprintln!("DB error on search_handler (load books): {e}");
-            Vec::new()
-        }
+    _op: Operator,
+    Query(params): Query<RestoreParams>,
+    request: Request,
+) -> Result<Json<RestoreReport>, ApiError> {
+    if !state.maintenance.load(Ordering::SeqCst) {
+        return Err(ApiError::conflict(Message::new("maintenance.required")));
+    }
+    let zst_body = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(backup::ZST_CONTENT_TYPE));
+    let format = match BackupFormat::parse(params.format.as_deref())? {
+        BackupFormat::Json if zst_body => BackupFormat::NdJsonZst,
+        format => format,
     };
 
-    // 2) Tentukan mode search (default Title).
-    let mode = params
-        .mode
-        .as_deref()
-        .and_then(SearchMode::from_str)
-        .unwrap_or(SearchMode::Title);
-
-    let query = params.q;
+    let report = match format {
+        BackupFormat::NdJsonZst => restore_zst(&state, request.into_body()).await?,
+        BackupFormat::Json => {
+            let JsonBody(backup) = JsonBody::<Backup>::from_request(request, &state).await?;
+            restore_json(&state, backup).await?
+        }
+    };
+    Ok(Json(report))
+}
 
-    // 3) Bagi data jadi chunk dan proses paralel.
-    let num_cores = num_cpus::get().max(1);
-    let len = books_snapshot.len();
-    if len == 0 {
-        return Json(Vec::new());
+/// Cek versi dan perpustakaan, lalu buka transaksi restore yang sudah mengosongkan data lama.
+async fn begin_restore(
+    state: &AppState,
+    version: u32,
+    library_id: i32,
+) -> Result<sqlx::Transaction<'static, MySql>, ApiError> {
+    if version != BACKUP_VERSION {
+        return Err(ApiError::bad_request(
+            Message::new("backup.unsupported_version")
+                .param("version", version)
+                .param("expected", BACKUP_VERSION),
+        ));
+    }
+    let exists: Option<i32> = sqlx::query_scalar("SELECT id FROM libraries WHERE id = ?")
+        .bind(library_id)
+        .fetch_optional(&state.pool)
+        .await?;
+    if exists.is_none() {
+        return Err(ApiError::missing_resource("not_found.library", library_id));
     }
-    let chunk_size = (len + num_cores - 1) / num_cores;
 
-    let mut tasks = Vec::new();
+    // Semua DELETE + INSERT dalam satu transaksi; INSERT dipecah per RESTORE_CHUNK_SIZE baris.
+    let mut tx = state.pool.begin().await?;
 
-    for chunk in books_snapshot.chunks(chunk_size) {
-        let chunk_vec: Vec<Book> = chunk.to_vec();
-        let query_clone = query.clone();
-        let mode_copy = mode;
+    for table in ["fines", "loans", "members", "stock_movements", "books"] {
+        sqlx::query(&format!("DELETE FROM {table} WHERE library_id = ?"))
+            .bind(library_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    Ok(tx)
+}
 
-        let handle = tokio::spawn(async move {
-            // pure function dari modul search
-            search_books_fn(&chunk_vec, mode_copy, &query_clone)
-        });
+/// Restore dari satu dokumen JSON `Backup`.
+async fn restore_json(state: &AppState, backup: Backup) -> Result<RestoreReport, ApiError> {
+    let library_id = backup.library_id;
+    let mut tx = begin_restore(state, backup.version, library_id).await?;
 
-        tasks.push(handle);
+    for chunk in backup.books.chunks(RESTORE_CHUNK_SIZE) {
+        restore_books(&mut tx, library_id, chunk).await?;
+    }
+    for chunk in backup.members.chunks(RESTORE_CHUNK_SIZE) {
+        restore_members(&mut tx, library_id, chunk).await?;
     }
+    for chunk in backup.loans.chunks(RESTORE_CHUNK_SIZE) {
+        restore_loans(&mut tx, library_id, chunk).await?;
+    }
+    for chunk in backup.fines.chunks(RESTORE_CHUNK_SIZE) {
+        restore_fines(&mut tx, library_id, chunk).await?;
+    }
+    // Backup hanya membawa nama kategori; tabel categories dan category_id disusun ulang.
+    category::sync_from_books(&mut tx, library_id).await?;
+    collation::sync_sort_keys(&mut tx, Some(library_id), &state.config.collation_articles).await?;
+    let now = state.clock.now_naive();
+    stock::open_balances(&mut *tx, library_id, now).await?;
+
+    let report = RestoreReport {
+        library_id,
+        books: backup.books.len(),
+        members: backup.members.len(),
+        loans: backup.loans.len(),
+        fines: backup.fines.len(),
+    };
+    AuditEntry::operator(library_id, ACTION_RESTORE, &report).write(&mut *tx, now).await?;
+    tx.commit().await?;
+    Ok(report)
+}
 
-    let mut results: Vec<Book> = Vec::new();
+/// Restore dari backup NDJSON+zstd. Yang ditahan di memori hanya satu chunk body, baris yang
+/// belum lengkap, dan paling banyak RESTORE_CHUNK_SIZE record yang menunggu di-INSERT.
+async fn restore_zst(state: &AppState, body: Body) -> Result<RestoreReport, ApiError> {
+    let mut reader = ZstLineReader::new().map_err(|e| ApiError::internal(e.to_string()))?;
+    let mut restore: Option<ZstRestore> = None;
+    let mut line_no = 0usize;
 
-    for task in tasks {
-        match task.await {
-            Ok(mut partial) => results.append(&mut partial),
-            Err(e) => eprintln!("Task search gagal: {e}"),
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| invalid_backup(format!("failed to read body: {e}")))?;
+        reader.push(&chunk);
+        while let Some(lines) = reader
+            .next_lines()
+            .map_err(|e| invalid_backup(format!("invalid zstd stream: {e}")))?
+        {
+            restore_lines(state, &mut restore, &mut line_no, lines).await?;
         }
     }
+    let lines = reader
+        .finish()
+        .map_err(|e| invalid_backup(format!("invalid zstd stream: {e}")))?;
+    restore_lines(state, &mut restore, &mut line_no, lines).await?;
 
-    Json(results)
+    let Some(mut restore) = restore else {
+        return Err(invalid_backup("backup is empty".into()));
+    };
+    restore.flush().await?;
+    let library_id = restore.report.library_id;
+    category::sync_from_books(&mut restore.tx, library_id).await?;
+    collation::sync_sort_keys(&mut restore.tx, Some(library_id), &state.config.collation_articles)
+        .await?;
+    let now = state.clock.now_naive();
+    stock::open_balances(&mut *restore.tx, library_id, now).await?;
+    AuditEntry::operator(library_id, ACTION_RESTORE, &restore.report)
+        .write(&mut *restore.tx, now)
+        .await?;
+    restore.tx.commit().await?;
+    Ok(restore.report)
 }
 
-//
-// ---------------------- MEMBERS ----------------------
-//
-
-/// GET /members – ambil semua anggota.
-async fn list_members(State(state): State<AppState>) -> Json<Vec<Member>> {
-    let result = sqlx::query_as::<_, Member>(
-        "SELECT id, name, email, joined_at FROM members",
+fn invalid_backup(reason: String) -> ApiError {
+    ApiError::bad_request(
+        Message::new("validation.invalid_value")
+            .param("field", "body")
+            .param("reason", reason),
     )
-    .fetch_all(&state.pool)
-    .await;
+}
 
-    match result {
-        Ok(members) => Json(members),
-        Err(e) => {
-            eprintln!("DB error on list_members: {e}");
-            Json(Vec::new())
+/// Terapkan baris-baris backup; transaksi restore dibuka saat header ditemukan.
+async fn restore_lines(
+    state: &AppState,
+    restore: &mut Option<ZstRestore>,
+    line_no: &mut usize,
+    lines: Vec<Vec<u8>>,
+) -> Result<(), ApiError> {
+    for line in lines {
+        *line_no += 1;
+        let record: BackupRecord = serde_json::from_slice(&line)
+            .map_err(|e| invalid_backup(format!("line {line_no}: {e}")))?;
+        match (restore.as_mut(), record) {
+            (None, BackupRecord::Header { version, library_id, .. }) => {
+                let tx = begin_restore(state, version, library_id).await?;
+                *restore = Some(ZstRestore::new(library_id, tx));
+            }
+            (None, _) => return Err(invalid_backup("first line must be the backup header".into())),
+            (Some(_), BackupRecord::Header { .. }) => {
+                return Err(invalid_backup(format!("line {line_no}: duplicate header")));
+            }
+            (Some(restore), record) => {
+                if record.rank() < restore.rank {
+                    return Err(invalid_backup(format!(
+                        "line {line_no}: records must be ordered books, members, loans, fines"
+                    )));
+                }
+                restore.push(record).await?;
+            }
         }
     }
+    Ok(())
 }
 
-/// POST /members – buat anggota baru.
-async fn create_member(
-    State(state): State<AppState>,
-    Json(payload): Json<NewMember>,
-) -> Json<Member> {
-    let result = sqlx::query(
-        "INSERT INTO members (name, email) VALUES (?, ?)",
-    )
-    .bind(&payload.name)
-    .bind(&payload.email)
-    .execute(&state.pool)
-    .await;
-
-    match result {
-        Ok(res) => {
-            let new_id = res.last_insert_id() as i32;
+/// Record yang menunggu di-INSERT saat restore streaming. Karena record datang berurutan,
+/// hanya satu tabel yang punya antrean pada satu waktu.
+struct ZstRestore {
+    tx: sqlx::Transaction<'static, MySql>,
+    rank: u8,
+    books: Vec<Book>,
+    members: Vec<Member>,
+    loans: Vec<Loan>,
+    fines: Vec<Fine>,
+    report: RestoreReport,
+}
 
-            // Ambil kembali baris yang baru dibuat untuk mendapatkan joined_at
-            let fetched = sqlx::query_as::<_, Member>(
-                "SELECT id, name, email, joined_at FROM members WHERE id = ?",
-            )
-            .bind(new_id)
-            .fetch_one(&state.pool)
-            .await;
+impl ZstRestore {
+    fn new(library_id: i32, tx: sqlx::Transaction<'static, MySql>) -> Self {
+        Self {
+            tx,
+            rank: 0,
+            books: Vec::new(),
+            members: Vec::new(),
+            loans: Vec::new(),
+            fines: Vec::new(),
+            report: RestoreReport {
+                library_id,
+                books: 0,
+                members: 0,
+                loans: 0,
+                fines: 0,
+            },
+        }
+    }
 
-            match fetched {
-                Ok(member) => Json(member),
-                Err(e) => {
-                    eprintln!("DB error on fetch new member: {e}");
-                    // fallback kalau gagal fetch – minimal kirim sesuatu
-                    Json(Member {
-                        id: new_id,
-                        name: payload.name,
-                        email: payload.email,
-                        joined_at: chrono::NaiveDateTime::MIN,
-                    })
-                }
-            }
+    async fn push(&mut self, record: BackupRecord) -> Result<(), ApiError> {
+        if record.rank() > self.rank {
+            // Tabel sebelumnya selesai; INSERT sisanya dulu supaya foreign key terpenuhi.
+            self.flush().await?;
+            self.rank = record.rank();
         }
-        Err(e) => {
-            eprintln!("DB error on create_member: {e}");
-            Json(Member {
-                id: -1,
-                name: "ERROR".to_string(),
-                email: "".to_string(),
-                joined_at: chrono::NaiveDateTime::MIN,
-            })
+        let pending = match record {
+            BackupRecord::Header { .. } => 0,
+            BackupRecord::Book(b) => {
+                self.books.push(b);
+                self.books.len()
+            }
+            BackupRecord::Member(m) => {
+                self.members.push(m);
+                self.members.len()
+            }
+            BackupRecord::Loan(l) => {
+                self.loans.push(l);
+                self.loans.len()
+            }
+            BackupRecord::Fine(f) => {
+                self.fines.push(f);
+                self.fines.len()
+            }
+        };
+        if pending >= RESTORE_CHUNK_SIZE {
+            self.flush().await?;
         }
+        Ok(())
     }
-}
-
-/// DELETE /members/:id – hapus anggota.
-async fn delete_member(
-    State(state): State<AppState>,
-    Path(id): Path<i32>,
-) -> Json<bool> {
-    let result = sqlx::query("DELETE FROM members WHERE id = ?")
-        .bind(id)
-        .execute(&state.pool)
-        .await;
 
-    match result {
-        Ok(res) => Json(res.rows_affected() > 0),
-        Err(e) => {
-            eprintln!("DB error on delete_member: {e}");
-            Json(false)
+    async fn flush(&mut self) -> Result<(), ApiError> {
+        let library_id = self.report.library_id;
+        if !self.books.is_empty() {
+            restore_books(&mut self.tx, library_id, &self.books).await?;
+            self.report.books += std::mem::take(&mut self.books).len();
         }
+        if !self.members.is_empty() {
+            restore_members(&mut self.tx, library_id, &self.members).await?;
+            self.report.members += std::mem::take(&mut self.members).len();
+        }
+        if !self.loans.is_empty() {
+            restore_loans(&mut self.tx, library_id, &self.loans).await?;
+            self.report.loans += std::mem::take(&mut self.loans).len();
+        }
+        if !self.fines.is_empty() {
+            restore_fines(&mut self.tx, library_id, &self.fines).await?;
+            self.report.fines += std::mem::take(&mut self.fines).len();
+        }
+        Ok(())
     }
 }
 
-//
-// ---------------------- LOANS ----------------------
-//
+/// Restore hanya untuk database asal backup: id baris dipakai apa adanya, dan tabel lain
+/// (donation_items, reserve_list_books, program_enrollments, ...) tetap menunjuk ke id itu.
+/// Data lama perpustakaan ini sudah dihapus di `begin_restore`, jadi id yang masih terpakai
+/// pasti milik perpustakaan lain: backup berasal dari database lain dan ditolak.
+async fn ensure_ids_unused(
+    conn: &mut MySqlConnection,
+    table: &'static str,
+    ids: impl Iterator<Item = i32>,
+) -> Result<(), ApiError> {
+    let mut qb: QueryBuilder<MySql> =
+        QueryBuilder::new(format!("SELECT id FROM {table} WHERE id IN ("));
+    let mut list = qb.separated(", ");
+    for id in ids {
+        list.push_bind(id);
+    }
+    list.push_unseparated(") LIMIT 1");
+    let taken: Option<i32> = qb.build_query_scalar().fetch_optional(conn).await?;
+    match taken {
+        Some(id) => Err(ApiError::conflict(
+            Message::new("backup.foreign_database").param("table", table).param("id", id),
+        )),
+        None => Ok(()),
+    }
+}
 
-/// GET /loans – ambil semua peminjaman dari tabel `loans`.
-async fn list_loans(State(state): State<AppState>) -> Json<Vec<Loan>> {
-    let result = sqlx::query_as::<_, Loan>(
-        "SELECT id, book_id, member_id, borrowed_at, due_at, returned_at FROM loans",
-    )
-    .fetch_all(&state.pool)
-    .await;
+async fn restore_books(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    chunk: &[Book],
+) -> Result<(), ApiError> {
+    ensure_ids_unused(&mut *conn, "books", chunk.iter().map(|b| b.id.0)).await?;
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "INSERT INTO books (id, library_id, public_id, title, author, category, year,
+                            total_copies, available_copies, version, updated_at, location) ",
+    );
+    qb.push_values(chunk, |mut row, b| {
+        row.push_bind(b.id)
+            .push_bind(library_id)
+            .push_bind(&b.public_id)
+            .push_bind(&b.title)
+            .push_bind(&b.author)
+            .push_bind(&b.category)
+            .push_bind(b.year)
+            .push_bind(b.total_copies)
+            .push_bind(b.available_copies)
+            .push_bind(b.version)
+            .push_bind(b.updated_at)
+            .push_bind(&b.location);
+    });
+    qb.build().execute(conn).await?;
+    Ok(())
+}
 
-    match result {
-        Ok(loans) => Json(loans),
-        Err(e) => {
-            eprintln!("DB error on list_loans: {e}");
-            Json(Vec::new())
-        }
-    }
+async fn restore_members(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    chunk: &[Member],
+) -> Result<(), ApiError> {
+    ensure_ids_unused(&mut *conn, "members", chunk.iter().map(|m| m.id.0)).await?;
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "INSERT INTO members (id, library_id, public_id, name, email, joined_at) ",
+    );
+    qb.push_values(chunk, |mut row, m| {
+        row.push_bind(m.id)
+            .push_bind(library_id)
+            .push_bind(&m.public_id)
+            .push_bind(&m.name)
+            .push_bind(&m.email)
+            .push_bind(m.joined_at);
+    });
+    qb.build().execute(conn).await?;
+    Ok(())
 }
 
-/// POST /loans – buat peminjaman baru.
-/// Body JSON: { "book_id": 1, "member_id": 1, "due_date": "2025-12-01" }
-async fn create_loan(
-    State(state): State<AppState>,
-    Json(payload): Json<NewLoan>, // book_id, member_id, due_date (YYYY-MM-DD)
-) -> Json<Loan> {
-    // 0) Parse dan validasi due_date
-    let due_date = match NaiveDate::parse_from_str(&payload.due_date, "%Y-%m-%d") {
-        Ok(date) => {
-            let today = Utc::now().date_naive();
-            if date < today {
-                eprintln!(
-                    "Validation error: due_date {} lebih kecil dari hari ini {}",
-                    date, today
-                );
-                return Json(Loan {
-                    id: -1,
-                    book_id: payload.book_id,
-                    member_id: payload.member_id,
-                    borrowed_at: NaiveDateTime::MIN,
-                    due_at: NaiveDateTime::MIN,
-                    returned_at: None,
-                });
-            }
-            date
-        }
-        Err(e) => {
-            eprintln!(
-                "Validation error: gagal parse due_date '{}' : {e}",
-                payload.due_date
-            );
-            return Json(Loan {
-                id: -1,
-                book_id: payload.book_id,
-                member_id: payload.member_id,
-                borrowed_at: NaiveDateTime::MIN,
-                due_at: NaiveDateTime::MIN,
-                returned_at: None,
-            });
-        }
-    };
+async fn restore_loans(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    chunk: &[Loan],
+) -> Result<(), ApiError> {
+    ensure_ids_unused(&mut *conn, "loans", chunk.iter().map(|l| l.id.0)).await?;
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "INSERT INTO loans (id, library_id, public_id, book_id, member_id, borrowed_at, due_at,
+                            returned_at, lost_at) ",
+    );
+    qb.push_values(chunk, |mut row, l| {
+        row.push_bind(l.id)
+            .push_bind(library_id)
+            .push_bind(&l.public_id)
+            .push_bind(l.book_id)
+            .push_bind(l.member_id)
+            .push_bind(l.borrowed_at)
+            .push_bind(l.due_at)
+            .push_bind(l.returned_at)
+            .push_bind(l.lost_at);
+    });
+    qb.build().execute(conn).await?;
+    Ok(())
+}
 
-    let due_at = due_date
-        .and_hms_opt(0, 0, 0)
-        .unwrap_or(NaiveDateTime::MIN);
+async fn restore_fines(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    chunk: &[Fine],
+) -> Result<(), ApiError> {
+    ensure_ids_unused(&mut *conn, "fines", chunk.iter().map(|f| f.id)).await?;
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "INSERT INTO fines (id, library_id, loan_id, member_id, amount, reason, created_at,
+                            paid_at) ",
+    );
+    qb.push_values(chunk, |mut row, f| {
+        row.push_bind(f.id)
+            .push_bind(library_id)
+            .push_bind(f.loan_id)
+            .push_bind(f.member_id)
+            .push_bind(f.amount)
+            .push_bind(&f.reason)
+            .push_bind(f.created_at)
+            .push_bind(f.paid_at);
+    });
+    qb.build().execute(conn).await?;
+    Ok(())
+}
 
-    // Mulai transaksi
-    let mut tx = state.pool.begin().await.expect("failed to begin tx");
+/// GET /admin/stats – jumlah eksekusi serta durasi rata-rata/maksimum per query sejak start.
+async fn get_stats(State(state): State<AppState>, _op: Operator) -> Json<Stats> {
+    Json(Stats {
+        queries: state.metrics.summary(),
+    })
+}
 
-    // 1) Cek stok tersedia
-    let row = sqlx::query!(
-        "SELECT available_copies FROM books WHERE id = ?",
-        payload.book_id
+/// GET /admin/tenants – daftar perpustakaan (khusus operator).
+async fn list_tenants(
+    State(state): State<AppState>,
+    _op: Operator,
+) -> Result<Json<Vec<Library>>, ApiError> {
+    let libraries = sqlx::query_as::<_, Library>(
+        "SELECT id, name, created_at FROM libraries ORDER BY id",
     )
-    .fetch_one(&mut *tx)
-    .await;
+    .fetch_all(&state.pool)
+    .await?;
 
-    let available = match row {
-        Ok(r) => r.available_copies,
-        Err(e) => {
-            eprintln!("DB error on select available_copies: {e}");
-            tx.rollback().await.ok();
-            return Json(Loan {
-                id: -1,
-                book_id: payload.book_id,
-                member_id: payload.member_id,
-                borrowed_at: NaiveDateTime::MIN,
-                due_at,
-                returned_at: None,
-            });
-        }
-    };
+    Ok(Json(libraries))
+}
 
-    if available <= 0 {
-        // stok habis → tolak peminjaman
-        tx.rollback().await.ok();
-        eprintln!("Stok buku habis untuk book_id={}", payload.book_id);
-        return Json(Loan {
-            id: -1,
-            book_id: payload.book_id,
-            member_id: payload.member_id,
-            borrowed_at: NaiveDateTime::MIN,
-            due_at,
-            returned_at: None,
-        });
+/// POST /admin/tenants – daftarkan perpustakaan baru beserta API key pertamanya.
+async fn create_tenant(
+    State(state): State<AppState>,
+    _op: Operator,
+    JsonBody(payload): JsonBody<NewLibrary>,
+) -> Result<Json<ProvisionedLibrary>, ApiError> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::required("name"));
     }
 
-    // 2) Insert ke loans
-    let insert_res = sqlx::query(
-        "INSERT INTO loans (book_id, member_id, due_at) VALUES (?, ?, ?)",
-    )
-    .bind(payload.book_id)
-    .bind(payload.member_id)
-    .bind(due_at)
-    .execute(&mut *tx)
-    .await
-    .expect("failed to insert loan");
-
-    let new_id = insert_res.last_insert_id() as i32;
+    let mut tx = state.pool.begin().await?;
 
-    // 3) Kurangi stok tersedia
-    sqlx::query("UPDATE books SET available_copies = available_copies - 1 WHERE id = ?")
-        .bind(payload.book_id)
+    let res = sqlx::query("INSERT INTO libraries (name) VALUES (?)")
+        .bind(name)
         .execute(&mut *tx)
-        .await
-        .expect("failed to update available_copies");
+        .await?;
+    let library_id = res.last_insert_id() as i32;
+
+    let (_, api_key) = issue_api_key(&mut tx, Some(library_id), "initial", Scopes::all()).await?;
 
-    // 4) Ambil loan yang baru dibuat
-    let fetched = sqlx::query_as::<_, Loan>(
-        "SELECT id, book_id, member_id, borrowed_at, due_at, returned_at
-         FROM loans WHERE id = ?",
+    let library = sqlx::query_as::<_, Library>(
+        "SELECT id, name, created_at FROM libraries WHERE id = ?",
     )
-    .bind(new_id)
+    .bind(library_id)
     .fetch_one(&mut *tx)
-    .await
-    .expect("newly inserted loan not found");
+    .await?;
 
-    tx.commit().await.ok();
+    tx.commit().await?;
+    state.keys.invalidate();
+    println!("Provisioned library id={library_id} ({name})");
 
-    Json(fetched)
+    Ok(Json(ProvisionedLibrary { library, api_key }))
 }
 
-/// POST /loans/:id/return – tandai peminjaman sudah dikembalikan.
-async fn return_loan(
+const API_KEY_COLUMNS: &str =
+    "id, prefix, label, library_id, scopes, created_at, last_used_at, revoked_at";
+
+/// Query string untuk GET /admin/keys.
+#[derive(Deserialize)]
+struct KeyListParams {
+    library_id: Option<i32>,
+}
+
+/// GET /admin/keys – daftar API key (tanpa hash), opsional per perpustakaan.
+async fn list_keys(
     State(state): State<AppState>,
-    Path(id): Path<i32>,
-) -> Json<bool> {
-    let now = Utc::now().naive_utc();
+    _op: Operator,
+    Query(params): Query<KeyListParams>,
+) -> Result<Json<Vec<ApiKeyInfo>>, ApiError> {
+    let mut qb: QueryBuilder<MySql> =
+        QueryBuilder::new(format!("SELECT {API_KEY_COLUMNS} FROM api_keys"));
+    if let Some(library_id) = params.library_id {
+        qb.push(" WHERE library_id = ").push_bind(library_id);
+    }
+    qb.push(" ORDER BY id");
+    let keys = qb.build_query_as::<ApiKeyInfo>().fetch_all(&state.pool).await?;
 
-    let mut tx = match state.pool.begin().await {
-        Ok(t) => t,
-        Err(e) => {
-            eprintln!("Failed to begin transaction on return_loan: {e}");
-            return Json(false);
+    Ok(Json(keys))
+}
+
+/// POST /admin/keys – buat API key baru; plaintext-nya hanya dikirim di respons ini.
+async fn create_key(
+    State(state): State<AppState>,
+    _op: Operator,
+    JsonBody(payload): JsonBody<NewApiKey>,
+) -> Result<Json<IssuedApiKey>, ApiError> {
+    let label = payload.label.trim();
+    if label.is_empty() {
+        return Err(ApiError::required("label"));
+    }
+    let library_id = match (payload.operator, payload.library_id) {
+        (true, None) => None,
+        (true, Some(_)) => {
+            return Err(ApiError::bad_request(Message::new("api_key.operator_library")));
         }
+        (false, Some(library_id)) => Some(library_id),
+        (false, None) => return Err(ApiError::missing("library_id")),
     };
-
-    // 1. Ambil book_id
-    let row = sqlx::query("SELECT book_id FROM loans WHERE id = ?")
-        .bind(id)
-        .fetch_one(&mut *tx)
-        .await;
-
-    let book_id: i32 = match row {
-        Ok(r) => r.get("book_id"),
-        Err(e) => {
-            eprintln!("DB error on select loan book_id: {e}");
-            tx.rollback().await.ok();
-            return Json(false);
+    let scopes = match (&payload.scopes, library_id) {
+        (Some(_), None) => {
+            return Err(ApiError::bad_request(Message::new("api_key.operator_scopes")));
         }
+        (None, None) => Scopes::default(),
+        (None, Some(_)) => Scopes::all(),
+        (Some(names), Some(_)) => Scopes::parse(names).map_err(ApiError::bad_request)?,
     };
 
-    // 2. Set returned_at
-    if let Err(e) = sqlx::query("UPDATE loans SET returned_at = ? WHERE id = ?")
-        .bind(now)
-        .bind(id)
-        .execute(&mut *tx)
-        .await
-    {
-        eprintln!("DB error on update returned_at: {e}");
-        tx.rollback().await.ok();
-        return Json(false);
+    if let Some(library_id) = library_id {
+        let exists: Option<i32> = sqlx::query_scalar("SELECT id FROM libraries WHERE id = ?")
+            .bind(library_id)
+            .fetch_optional(&state.pool)
+            .await?;
+        if exists.is_none() {
+            return Err(ApiError::missing_resource("not_found.library", library_id));
+        }
     }
 
-    // 3. Tambah stok tersedia
-    if let Err(e) = sqlx::query(
-        "UPDATE books SET available_copies = available_copies + 1 WHERE id = ?",
-    )
-    .bind(book_id)
-    .execute(&mut *tx)
-    .await
-    {
-        eprintln!("DB error on update available_copies (return): {e}");
-        tx.rollback().await.ok();
-        return Json(false);
+    let mut conn = state.pool.acquire().await?;
+    let (id, api_key) = issue_api_key(&mut conn, library_id, label, scopes).await?;
+    let info = sqlx::query_as::<_, ApiKeyInfo>(&format!(
+        "SELECT {API_KEY_COLUMNS} FROM api_keys WHERE id = ?"
+    ))
+    .bind(id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    state.keys.invalidate();
+    println!("Issued API key id={id} ({label})");
+
+    Ok(Json(IssuedApiKey { info, api_key }))
+}
+
+/// DELETE /admin/keys/:id – cabut API key; berlaku langsung karena cache dikosongkan.
+async fn revoke_key(
+    State(state): State<AppState>,
+    _op: Operator,
+    Path(id): Path<i32>,
+) -> Result<Json<ApiKeyInfo>, ApiError> {
+    let res = sqlx::query("UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+        .bind(state.clock.now_naive())
+        .bind(id)
+        .execute(&state.pool)
+        .await?;
+    state.keys.invalidate();
+
+    let info = sqlx::query_as::<_, ApiKeyInfo>(&format!(
+        "SELECT {API_KEY_COLUMNS} FROM api_keys WHERE id = ?"
+    ))
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::missing_resource("not_found.api_key", id))?;
+
+    if res.rows_affected() > 0 {
+        println!("Revoked API key id={id}");
     }
 
-    tx.commit().await.ok();
-    Json(true)
+    Ok(Json(info))
 }
 
 //
 // ---------------------- MAIN ----------------------
 //
 
+/// Handler satu baris `ROUTES`, dibungkus fungsi supaya tabelnya bisa `const`.
+type RouteHandler = fn() -> MethodRouter<AppState>;
+
+/// Semua route API: method, pola path axum, scope key perpustakaan, dan handler-nya.
+/// `app` membangun router dari tabel ini dan `scope::required_scope` membaca scope-nya dari
+/// sini juga. Scope None = handler tidak memakai `Tenant` (publik, operator, atau token
+/// anggota); handler yang memakai `Tenant` wajib punya scope, kalau tidak request-nya ditolak.
+const ROUTES: &[(Method, &str, Option<Scope>, RouteHandler)] = &[
+    (Method::GET, "/health", None, || get(health_check)),
+    (Method::GET, "/health/ready", None, || get(readiness)),
+    (Method::GET, "/health/schema", None, || get(schema_status)),
+    (Method::GET, "/version", None, || get(version_info)),
+    (Method::GET, "/books", Some(Scope::BooksRead), || get(list_books)),
+    (Method::POST, "/books", Some(Scope::BooksWrite), || post(create_book)),
+    (Method::GET, "/books/:id", Some(Scope::BooksRead), || get(get_book)),
+    (Method::DELETE, "/books/:id", Some(Scope::BooksWrite), || delete(delete_book)),
+    (Method::PUT, "/books/:id", Some(Scope::BooksWrite), || put(update_book)),
+    (Method::PATCH, "/books/:id", Some(Scope::BooksWrite), || patch(update_book)),
+    (Method::GET, "/books/:id/next-available", Some(Scope::BooksRead), || get(book_next_available)),
+    (Method::GET, "/books/:id/similar", Some(Scope::BooksRead), || get(similar_books)),
+    (
+        Method::GET,
+        "/books/:id/stock-movements",
+        Some(Scope::BooksRead),
+        || get(book_stock_movements),
+    ),
+    (
+        Method::GET,
+        "/books/recategorize/preview",
+        Some(Scope::BooksRead),
+        || get(preview_recategorize),
+    ),
+    (
+        Method::GET,
+        "/books/reorder-suggestions",
+        Some(Scope::BooksRead),
+        || get(reorder_suggestions),
+    ),
+    (Method::POST, "/books/availability", Some(Scope::BooksRead), || post(books_availability)),
+    (
+        Method::GET,
+        "/books/by-location/:location",
+        Some(Scope::BooksRead),
+        || get(books_by_location),
+    ),
+    (Method::GET, "/locations", Some(Scope::BooksRead), || get(list_locations)),
+    (Method::GET, "/categories", Some(Scope::BooksRead), || get(list_categories)),
+    (Method::POST, "/categories", Some(Scope::BooksWrite), || post(create_category)),
+    (Method::GET, "/categories/:id", Some(Scope::BooksRead), || get(get_category)),
+    (Method::PUT, "/categories/:id", Some(Scope::BooksWrite), || put(update_category)),
+    (Method::DELETE, "/categories/:id", Some(Scope::BooksWrite), || delete(delete_category)),
+    (Method::POST, "/categories/:id/merge", Some(Scope::BooksWrite), || post(merge_category)),
+    (Method::GET, "/members", Some(Scope::MembersRead), || get(list_members)),
+    (Method::POST, "/members", Some(Scope::MembersWrite), || post(create_member)),
+    (Method::GET, "/members/inactive", Some(Scope::MembersRead), || get(list_inactive_members)),
+    (Method::GET, "/members/confirm-email", None, || get(confirm_member_email)),
+    (Method::GET, "/members/:id", Some(Scope::MembersRead), || get(get_member)),
+    (Method::PUT, "/members/:id", Some(Scope::MembersWrite), || put(update_member)),
+    (Method::DELETE, "/members/:id", Some(Scope::MembersWrite), || delete(delete_member)),
+    (Method::POST, "/members/:id/merge", Some(Scope::MembersWrite), || post(merge_member)),
+    (Method::GET, "/members/:id/summary", Some(Scope::MembersRead), || get(member_summary)),
+    (Method::GET, "/members/:id/loans.ics", None, || get(member_loans_ical)),
+    (
+        Method::POST,
+        "/members/:id/access-link",
+        Some(Scope::MembersWrite),
+        || post(create_access_link),
+    ),
+    (
+        Method::DELETE,
+        "/members/:id/access-link",
+        Some(Scope::MembersWrite),
+        || delete(revoke_access_links),
+    ),
+    (Method::GET, "/me/loans", None, || get(my_loans)),
+    (Method::GET, "/me/fines", None, || get(my_fines)),
+    (Method::GET, "/loans", Some(Scope::LoansRead), || get(list_loans)),
+    (Method::POST, "/loans", Some(Scope::LoansCreate), || post(create_loan)),
+    (Method::GET, "/loans/by-status", Some(Scope::LoansRead), || get(loans_by_status)),
+    (Method::GET, "/loans/:id", Some(Scope::LoansRead), || get(get_loan)),
+    (Method::POST, "/loans/:id/return", Some(Scope::LoansWrite), || post(return_loan)),
+    (
+        Method::POST,
+        "/loans/:id/return-and-pay",
+        Some(Scope::LoansWrite),
+        || post(return_and_pay_loan),
+    ),
+    (Method::POST, "/loans/:id/mark-lost", Some(Scope::LoansWrite), || post(mark_loan_lost)),
+    (Method::POST, "/donations", Some(Scope::BooksWrite), || post(create_donation)),
+    (Method::GET, "/donations/:id", Some(Scope::BooksRead), || get(get_donation)),
+    (
+        Method::POST,
+        "/donations/:id/items/:item_id/accept",
+        Some(Scope::BooksWrite),
+        || post(accept_donation_item),
+    ),
+    (
+        Method::POST,
+        "/donations/:id/items/:item_id/reject",
+        Some(Scope::BooksWrite),
+        || post(reject_donation_item),
+    ),
+    (Method::GET, "/reserve-lists", Some(Scope::BooksRead), || get(list_reserve_lists)),
+    (Method::POST, "/reserve-lists", Some(Scope::BooksWrite), || post(create_reserve_list)),
+    (Method::GET, "/reserve-lists/:id", Some(Scope::BooksRead), || get(get_reserve_list)),
+    (Method::POST, "/reserve-lists/:id/books", Some(Scope::BooksWrite), || post(add_reserve_book)),
+    (
+        Method::DELETE,
+        "/reserve-lists/:id/books/:book_id",
+        Some(Scope::BooksWrite),
+        || delete(remove_reserve_book),
+    ),
+    (Method::GET, "/programs", Some(Scope::MembersRead), || get(list_programs)),
+    (Method::POST, "/programs", Some(Scope::MembersWrite), || post(create_program)),
+    (
+        Method::POST,
+        "/programs/:id/enrollments",
+        Some(Scope::MembersWrite),
+        || post(enroll_program_member),
+    ),
+    (
+        Method::GET,
+        "/programs/:id/leaderboard",
+        Some(Scope::MembersRead),
+        || get(program_leaderboard),
+    ),
+    (
+        Method::GET,
+        "/programs/:id/members/:member_id/progress",
+        Some(Scope::MembersRead),
+        || get(program_member_progress),
+    ),
+    (Method::GET, "/purchase-requests", Some(Scope::BooksRead), || get(list_purchase_requests)),
+    (Method::POST, "/purchase-requests", Some(Scope::BooksWrite), || post(create_purchase_request)),
+    (
+        Method::POST,
+        "/purchase-requests/:id/approve",
+        Some(Scope::BooksWrite),
+        || post(approve_purchase_request),
+    ),
+    (
+        Method::POST,
+        "/purchase-requests/:id/reject",
+        Some(Scope::BooksWrite),
+        || post(reject_purchase_request),
+    ),
+    (
+        Method::POST,
+        "/purchase-requests/:id/received",
+        Some(Scope::BooksWrite),
+        || post(receive_purchase_request),
+    ),
+    (Method::GET, "/search", Some(Scope::BooksRead), || get(search_handler)),
+    // Hasilnya memuat nama dan email anggota, jadi butuh scope anggota.
+    (Method::GET, "/search/global", Some(Scope::MembersRead), || get(global_search)),
+    (Method::GET, "/stats/busiest-days", Some(Scope::LoansRead), || get(busiest_days)),
+    (Method::GET, "/admin/orphaned-loans", Some(Scope::Admin), || get(list_orphaned_loans)),
+    (Method::DELETE, "/admin/orphaned-loans", Some(Scope::Admin), || delete(purge_orphaned_loans)),
+    (Method::GET, "/admin/config", None, || get(get_config)),
+    (Method::GET, "/admin/audit", Some(Scope::Admin), || get(list_audit)),
+    (Method::GET, "/admin/stats", None, || get(get_stats)),
+    (Method::GET, "/admin/integrity", Some(Scope::Admin), || get(check_integrity)),
+    (Method::POST, "/admin/integrity", Some(Scope::Admin), || post(repair_integrity)),
+    (Method::POST, "/admin/books/recount", Some(Scope::Admin), || post(recount_books)),
+    (Method::POST, "/admin/extend-all-loans", Some(Scope::Admin), || post(extend_all_loans)),
+    (Method::GET, "/admin/members/duplicates", Some(Scope::Admin), || get(list_duplicate_members)),
+    (
+        Method::GET,
+        "/admin/duplicate-members",
+        Some(Scope::Admin),
+        || get(list_duplicate_member_emails),
+    ),
+    (Method::POST, "/admin/merge-members", Some(Scope::Admin), || post(merge_members)),
+    (Method::GET, "/admin/tenants", None, || get(list_tenants)),
+    (Method::POST, "/admin/tenants", None, || post(create_tenant)),
+    (Method::GET, "/admin/keys", None, || get(list_keys)),
+    (Method::POST, "/admin/keys", None, || post(create_key)),
+    (Method::DELETE, "/admin/keys/:id", None, || delete(revoke_key)),
+    (Method::GET, "/admin/maintenance", None, || get(get_maintenance)),
+    (Method::PUT, "/admin/maintenance", None, || put(set_maintenance)),
+    (Method::GET, "/admin/backup", None, || get(backup_library)),
+    (Method::GET, "/admin/snapshot", None, || get(backup_library)),
+    (
+        Method::POST,
+        "/admin/restore",
+        None,
+        || post(restore_library).layer(DefaultBodyLimit::max(RESTORE_BODY_LIMIT)),
+    ),
+];
+
+/// Router lengkap (semua `ROUTES` dan middleware-nya), tanpa CORS.
+fn app(state: AppState) -> Router {
+    ROUTES
+        .iter()
+        .fold(Router::new(), |router, (_, path, _, handler)| router.route(path, handler()))
+        .layer(middleware::from_fn_with_state(state.clone(), maintenance::reject_writes))
+        .layer(middleware::from_fn(i18n::localize))
+        .layer(middleware::from_fn_with_state(state.clone(), timestamps::convert_response))
+        .layer(middleware::from_fn_with_state(state.clone(), json_case::convert_response))
+        .with_state(state)
+}
+
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
@@ -500,20 +5701,55 @@ async fn main() {
 
     let pool = create_pool().await;
     println!("Connected to database");
+    run_migrations(&pool).await;
 
-    let state = AppState { pool };
-
-    let app = Router::new()
-        .route("/health", get(health_check))
-        .route("/books", get(list_books).post(create_book))
-        .route("/books/:id", delete(delete_book))
-        .route("/members", get(list_members).post(create_member))
-        .route("/members/:id", delete(delete_member))
-        .route("/loans", get(list_loans).post(create_loan))
-        .route("/loans/:id/return", post(return_loan))
-        .route("/search", get(search_handler))
-        .with_state(state)
-        .layer(cors);
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+
+    let config = AppConfig::from_env();
+    let metrics = QueryMetrics::new(Duration::from_millis(config.slow_query_ms));
+    let state = AppState {
+        pool,
+        config: Arc::new(config),
+        clock,
+        metrics: Arc::new(metrics),
+        keys: Arc::new(KeyCache::default()),
+        maintenance: Arc::new(AtomicBool::new(false)),
+        similar: Arc::new(SimilarityIndex::default()),
+    };
+
+    // Job latar belakang tiap jam (tick pertama langsung saat start): indeks buku serupa
+    // dibangun ulang, daftar reserve yang semesternya berakhir dinonaktifkan, dan (kalau
+    // AUTO_LOST_DAYS diisi) pinjaman yang sangat terlambat ditutup sebagai hilang.
+    // Sekali saat start, kunci urut buku disamakan dengan COLLATION_IGNORED_ARTICLES yang
+    // berlaku sekarang; tidak menahan server bind.
+    let job_state = state.clone();
+    tokio::spawn(async move {
+        match sync_all_sort_keys(&job_state).await {
+            Ok(0) => {}
+            Ok(n) => println!("Updated sort keys of {n} book(s)"),
+            Err(e) => eprintln!("DB error on sync_sort_keys: {e}"),
+        }
+        let mut ticker = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            ticker.tick().await;
+            match rebuild_similarity_index(&job_state).await {
+                Ok(n) => println!("Similarity index rebuilt ({n} book(s))"),
+                Err(e) => eprintln!("DB error on rebuild_similarity_index: {e}"),
+            }
+            match deactivate_expired_reserve_lists(&job_state).await {
+                Ok(0) => {}
+                Ok(n) => println!("Deactivated {n} expired reserve list(s)"),
+                Err(e) => eprintln!("DB error on deactivate_expired_reserve_lists: {e}"),
+            }
+            match auto_mark_lost(&job_state).await {
+                Ok(0) => {}
+                Ok(n) => println!("Auto-lost: closed {n} overdue loan(s)"),
+                Err(e) => eprintln!("DB error on auto_mark_lost: {e}"),
+            }
+        }
+    });
+
+    let app = app(state).layer(cors);
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 8000));
     println!("Backend running at http://{addr}");
@@ -525,4 +5761,137 @@ async fn main() {
     axum::serve(listener, app)
         .await
         .expect("server error");
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+    use chrono::Timelike;
+    use sqlx::mysql::MySqlPoolOptions;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::scope::{required_scope, Scope};
+
+    /// State untuk router tanpa DB: pool-nya lazy ke port yang tidak dipakai, jadi handler
+    /// yang sempat menyentuh DB langsung gagal. Request tanpa key jatuh ke perpustakaan 1
+    /// dengan ANONYMOUS_SCOPES kosong.
+    fn offline_state() -> AppState {
+        let pool = MySqlPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(200))
+            .connect_lazy("mysql://root@127.0.0.1:9/none")
+            .unwrap();
+        let mut config = AppConfig::from_env();
+        config.default_library_id = Some(1);
+        config.anonymous_scopes = Scopes::default();
+        AppState {
+            pool,
+            config: Arc::new(config),
+            clock: Arc::new(SystemClock),
+            metrics: Arc::new(QueryMetrics::new(Duration::from_secs(1))),
+            keys: Arc::new(KeyCache::default()),
+            maintenance: Arc::new(AtomicBool::new(false)),
+            similar: Arc::new(SimilarityIndex::default()),
+        }
+    }
+
+    /// Path konkret untuk pola axum: setiap `:param` diganti `1`.
+    fn concrete(path: &str) -> String {
+        path.split('/')
+            .map(|segment| if segment.starts_with(':') { "1" } else { segment })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn routes_are_unique() {
+        for (i, (method, path, _, _)) in ROUTES.iter().enumerate() {
+            let dup = ROUTES[i + 1..].iter().any(|(m, p, _, _)| m == method && p == path);
+            assert!(!dup, "{method} {path} is listed twice");
+        }
+    }
+
+    #[test]
+    fn admin_routes_require_admin_scope() {
+        let admin: Vec<_> =
+            ROUTES.iter().filter(|(_, path, _, _)| path.starts_with("/admin/")).collect();
+        assert!(!admin.is_empty());
+        for (method, path, scope, _) in admin {
+            assert_eq!(required_scope(method, path), Some(Scope::Admin), "{method} {path}");
+            assert!(matches!(scope, None | Some(Scope::Admin)), "{method} {path}");
+        }
+    }
+
+    #[test]
+    fn required_scope_reads_the_route_table() {
+        assert_eq!(required_scope(&Method::PATCH, "/books/:id"), Some(Scope::BooksWrite));
+        assert_eq!(required_scope(&Method::POST, "/loans"), Some(Scope::LoansCreate));
+        assert_eq!(required_scope(&Method::GET, "/me/loans"), None);
+        assert_eq!(required_scope(&Method::GET, "/nope"), None);
+    }
+
+    /// Lewat router sungguhan: route ber-scope harus menolak request tanpa scope itu (jadi
+    /// handler-nya memang memakai `Tenant`), dan route tanpa scope tidak boleh memakai
+    /// `Tenant` (kalau memakai, request-nya ditolak 403 karena tidak ada scope-nya).
+    #[tokio::test]
+    async fn route_table_matches_tenant_handlers() {
+        let app = app(offline_state());
+        for (method, path, scope, _) in ROUTES {
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(concrete(path))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+            match scope {
+                Some(scope) => {
+                    assert_eq!(status, StatusCode::FORBIDDEN, "{method} {path}: {body}");
+                    let missing = &body["error"]["details"]["missing_scope"];
+                    assert_eq!(missing, scope.name(), "{method} {path}: {body}");
+                }
+                None => assert_ne!(status, StatusCode::FORBIDDEN, "{method} {path}: {body}"),
+            }
+        }
+    }
+
+    async fn insert_member(pool: &sqlx::MySqlPool, name: &str) -> MemberId {
+        let id = sqlx::query(
+            "INSERT INTO members (library_id, public_id, name, email) VALUES (1, ?, ?, ?)",
+        )
+        .bind(format!("mb_{name}"))
+        .bind(name)
+        .bind(format!("{name}@example.test"))
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id();
+        MemberId(id as i32)
+    }
+
+    // Butuh MySQL: `DATABASE_URL=mysql://... cargo test -- --ignored`.
+    #[sqlx::test(migrator = "crate::config::MIGRATOR")]
+    #[ignore = "butuh DATABASE_URL (MySQL)"]
+    async fn merged_member_is_archived_not_deleted(pool: sqlx::MySqlPool) {
+        let target = insert_member(&pool, "ikal").await;
+        let source = insert_member(&pool, "ikal2").await;
+        let now = chrono::Utc::now().naive_utc().with_nanosecond(0).unwrap();
+        let tenant = Tenant { library_id: 1, key_id: None };
+
+        let mut tx = pool.begin().await.unwrap();
+        let merge = absorb_member(&mut tx, &tenant, target, source, now).await.unwrap();
+        tx.commit().await.unwrap();
+
+        assert_eq!(merge.member.id, target);
+        assert!(repo::find_member(&pool, 1, source).await.unwrap().is_none());
+        let (email, merged_into, merged_at): (String, MemberId, NaiveDateTime) = sqlx::query_as(
+            "SELECT email, merged_into, merged_at FROM merged_members WHERE id = ?",
+        )
+        .bind(source)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!((email.as_str(), merged_into, merged_at), ("ikal2@example.test", target, now));
+    }
+}
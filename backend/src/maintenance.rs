@@ -0,0 +1,42 @@
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+
+use crate::error::ApiError;
+use crate::i18n::Message;
+use crate::AppState;
+
+/// Status mode pemeliharaan (`GET/PUT /admin/maintenance`).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+}
+
+/// Endpoint POST yang hanya membaca data (body dipakai sebagai parameter), tetap dilayani.
+const READ_ONLY_POSTS: &[&str] = &["/books/availability"];
+
+/// Selama mode pemeliharaan, semua request yang mengubah data ditolak dengan 503,
+/// kecuali endpoint /admin/* (dipakai untuk restore dan mematikan mode ini lagi).
+pub async fn reject_writes(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let read_only = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        || (req.method() == Method::POST && READ_ONLY_POSTS.contains(&req.uri().path()));
+    if state.maintenance.load(Ordering::SeqCst)
+        && !read_only
+        && !req.uri().path().starts_with("/admin/")
+    {
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "maintenance",
+            Message::new("maintenance.active"),
+        )
+        .into_response();
+    }
+
+    next.run(req).await
+}
@@ -1,11 +1,21 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use chrono::NaiveDateTime;
+use std::collections::BTreeMap;
+
+use crate::i18n::Message;
+use crate::ids::MemberId;
+use crate::loan::LoanDetail;
+use crate::normalize;
+
+/// Nama field `Member` yang boleh dipilih lewat `?fields=`.
+pub const FIELDS: &[&str] = &["id", "public_id", "name", "email", "joined_at"];
 
 /// Satu anggota perpustakaan (sesuai tabel `members`).
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Member {
-    pub id: i32,
+    pub id: MemberId,
+    pub public_id: String,
     pub name: String,
     pub email: String,
     pub joined_at: NaiveDateTime,
@@ -13,7 +23,222 @@ pub struct Member {
 
 /// Payload untuk membuat anggota baru.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct NewMember {
     pub name: String,
     pub email: String,
 }
+
+impl NewMember {
+    /// Nama di-trim dan diringkas whitespace-nya, email juga di-lowercase.
+    pub fn normalized(self) -> Result<Self, Message> {
+        let email = normalize::email(&self.email);
+        if email.is_empty() {
+            return Err(Message::new("validation.required").param("field", "email"));
+        }
+        Ok(Self {
+            name: normalize::required("name", &self.name)?,
+            email,
+        })
+    }
+}
+
+/// Payload `PUT /members/:id`. Field yang tidak dikirim tidak diubah.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateMember {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl UpdateMember {
+    pub fn normalized(self) -> Result<Self, Message> {
+        let name = self.name.map(|n| normalize::required("name", &n)).transpose()?;
+        let email = match self.email {
+            Some(email) => {
+                let email = normalize::email(&email);
+                if email.is_empty() {
+                    return Err(Message::new("validation.required").param("field", "email"));
+                }
+                Some(email)
+            }
+            None => None,
+        };
+        Ok(Self { name, email })
+    }
+}
+
+/// Perubahan email yang menunggu konfirmasi anggota.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingEmail {
+    pub email: String,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Detail anggota untuk `GET/PUT /members/:id`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberDetail {
+    #[serde(flatten)]
+    pub member: Member,
+    /// None kalau tidak ada perubahan email yang menunggu (atau sudah kedaluwarsa).
+    pub pending_email: Option<PendingEmail>,
+    /// Hanya di response PUT yang mengubah email tanpa `force`: link konfirmasi
+    /// untuk dikirim ke alamat baru.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_confirmation: Option<MemberAccessLink>,
+}
+
+/// Anggota yang belum pernah meminjam (atau tidak meminjam sejak tanggal tertentu),
+/// hasil `GET /members/inactive`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct InactiveMember {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub member: Member,
+    /// None = belum pernah meminjam.
+    pub last_borrowed_at: Option<NaiveDateTime>,
+}
+
+/// Ringkasan peminjaman satu anggota untuk widget profil.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberSummary {
+    pub total_borrowed: i64,
+    pub currently_out: i64,
+    pub overdue: i64,
+    /// Total denda yang belum dibayar (rupiah).
+    pub total_fines: i64,
+    /// `total_fines` yang sudah diformat sesuai CURRENCY.
+    pub total_fines_formatted: String,
+    /// Pinjaman yang belum kembali beserta bukunya, jatuh tempo paling awal dulu.
+    pub current_loans: Vec<LoanDetail>,
+}
+
+/// Link self-service yang dikirim ke anggota lewat email.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberAccessLink {
+    pub url: String,
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Nama dengan jarak edit sebesar ini (setelah normalisasi) dianggap mirip.
+const MAX_NAME_DISTANCE: usize = 2;
+
+/// Pasangan anggota yang kemungkinan orang yang sama, hasil `GET /admin/members/duplicates`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCandidate {
+    pub a: Member,
+    pub b: Member,
+    /// 0..=1, makin tinggi makin mirip.
+    pub score: f64,
+    /// Sinyal yang cocok: `email`, `similar_email`, `name`, `similar_name`.
+    pub reasons: Vec<&'static str>,
+}
+
+/// Bagian lokal email tanpa titik dan tanpa `+tag`, supaya
+/// `budi.s+lib@x.com` dan `budis@x.com` dianggap alamat yang sama.
+fn email_parts(email: &str) -> (String, &str) {
+    let (local, domain) = email.rsplit_once('@').unwrap_or((email, ""));
+    let local = local.split('+').next().unwrap_or(local).replace('.', "");
+    (local, domain)
+}
+
+/// Bandingkan semua pasangan anggota; pasangan tanpa sinyal kemiripan dilewati.
+/// Urut dari skor tertinggi.
+pub fn duplicate_candidates(members: &[Member]) -> Vec<DuplicateCandidate> {
+    let keys: Vec<(String, (String, &str))> = members
+        .iter()
+        .map(|m| (normalize::search_key(&m.name), email_parts(&m.email)))
+        .collect();
+
+    let mut candidates = Vec::new();
+    for i in 0..members.len() {
+        for j in (i + 1)..members.len() {
+            let (name_a, (local_a, domain_a)) = &keys[i];
+            let (name_b, (local_b, domain_b)) = &keys[j];
+            let mut reasons = Vec::new();
+            let mut score: f64 = 0.0;
+
+            if domain_a == domain_b && !local_a.is_empty() {
+                if local_a == local_b {
+                    reasons.push("email");
+                    score = score.max(1.0);
+                } else if normalize::edit_distance(local_a, local_b) == 1 {
+                    reasons.push("similar_email");
+                    score = score.max(0.8);
+                }
+            }
+
+            if name_a.len().abs_diff(name_b.len()) <= MAX_NAME_DISTANCE {
+                let distance = normalize::edit_distance(name_a, name_b);
+                let longest = name_a.chars().count().max(name_b.chars().count()).max(1);
+                if distance == 0 {
+                    reasons.push("name");
+                    score = score.max(0.9);
+                } else if distance <= MAX_NAME_DISTANCE && longest > 2 * distance {
+                    reasons.push("similar_name");
+                    score = score.max(1.0 - distance as f64 / longest as f64);
+                }
+            }
+
+            if !reasons.is_empty() {
+                candidates.push(DuplicateCandidate {
+                    a: members[i].clone(),
+                    b: members[j].clone(),
+                    score: (score * 100.0).round() / 100.0,
+                    reasons,
+                });
+            }
+        }
+    }
+
+    candidates.sort_by(|x, y| y.score.total_cmp(&x.score).then(x.a.id.0.cmp(&y.a.id.0)));
+    candidates
+}
+
+/// Payload `POST /members/:id/merge`: anggota `source_id` digabung ke `:id`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MergeMember {
+    pub source_id: MemberId,
+}
+
+/// Hasil penggabungan: anggota tujuan dan jumlah data yang dipindahkan.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberMerge {
+    pub member: Member,
+    pub source_id: MemberId,
+    pub loans: u64,
+    pub fines: u64,
+    pub purchase_requests: u64,
+    pub program_enrollments: u64,
+}
+
+/// Anggota dengan email yang sama setelah normalisasi, hasil `GET /admin/duplicate-members`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateEmailGroup {
+    pub email: String,
+    /// Urut id; yang pertama biasanya dipakai sebagai anggota utama.
+    pub members: Vec<Member>,
+}
+
+/// Kelompokkan anggota per `normalize::email`; hanya kelompok berisi lebih dari satu anggota.
+pub fn group_by_email(members: Vec<Member>) -> Vec<DuplicateEmailGroup> {
+    let mut groups: BTreeMap<String, Vec<Member>> = BTreeMap::new();
+    for member in members {
+        groups.entry(normalize::email(&member.email)).or_default().push(member);
+    }
+    groups
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(email, members)| DuplicateEmailGroup { email, members })
+        .collect()
+}
+
+/// Payload `POST /admin/merge-members`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MergeMembers {
+    pub primary_id: MemberId,
+    pub duplicate_ids: Vec<MemberId>,
+}
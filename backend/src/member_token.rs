@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::ids::MemberId;
+use crate::pagination::{decode_cursor, encode_cursor};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Isi token link self-service anggota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemberClaims {
+    pub library_id: i32,
+    pub member_id: MemberId,
+    /// Harus sama dengan `members.token_generation`; beda = link sudah dicabut.
+    pub generation: i32,
+    /// Unix timestamp (detik).
+    pub expires_at: i64,
+}
+
+/// Kenapa token ditolak; masing-masing punya kode error sendiri untuk frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    Invalid,
+    Expired,
+    /// Tanda tangan sah tapi generasinya sudah dinaikkan (link dicabut).
+    Revoked,
+}
+
+impl MemberClaims {
+    fn payload(&self) -> String {
+        format!(
+            "{}.{}.{}.{}",
+            self.library_id, self.member_id, self.generation, self.expires_at
+        )
+    }
+
+    pub fn sign(&self, secret: &[u8]) -> String {
+        sign_payload(&self.payload(), secret)
+    }
+
+    /// Cek tanda tangan dan masa berlaku. Generasi dicek pemanggil ke DB.
+    pub fn verify(token: &str, secret: &[u8], now: DateTime<Utc>) -> Result<Self, TokenError> {
+        let payload = verify_payload(token, secret)?;
+        let mut parts = payload.split('.').map(str::parse::<i64>);
+        let mut next = || parts.next().and_then(Result::ok).ok_or(TokenError::Invalid);
+        let claims = Self {
+            library_id: i32::try_from(next()?).map_err(|_| TokenError::Invalid)?,
+            member_id: MemberId(i32::try_from(next()?).map_err(|_| TokenError::Invalid)?),
+            generation: i32::try_from(next()?).map_err(|_| TokenError::Invalid)?,
+            expires_at: next()?,
+        };
+
+        if now.timestamp() >= claims.expires_at {
+            return Err(TokenError::Expired);
+        }
+        Ok(claims)
+    }
+}
+
+/// Isi token konfirmasi perubahan email. `email_hash` mengikat token ke alamat
+/// `pending_email` saat token dibuat, jadi token lama tidak bisa mengonfirmasi alamat lain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailChangeClaims {
+    pub library_id: i32,
+    pub member_id: MemberId,
+    pub email_hash: String,
+    /// Unix timestamp (detik).
+    pub expires_at: i64,
+}
+
+impl EmailChangeClaims {
+    pub fn new(library_id: i32, member_id: MemberId, email: &str, expires_at: i64) -> Self {
+        Self { library_id, member_id, email_hash: email_hash(email), expires_at }
+    }
+
+    /// Prefix `email` supaya token ini tidak bisa dipakai sebagai link self-service.
+    fn payload(&self) -> String {
+        format!(
+            "email.{}.{}.{}.{}",
+            self.library_id, self.member_id, self.email_hash, self.expires_at
+        )
+    }
+
+    pub fn sign(&self, secret: &[u8]) -> String {
+        sign_payload(&self.payload(), secret)
+    }
+
+    /// Cek tanda tangan dan masa berlaku. Kecocokan `email_hash` dicek pemanggil ke DB.
+    pub fn verify(token: &str, secret: &[u8], now: DateTime<Utc>) -> Result<Self, TokenError> {
+        let payload = verify_payload(token, secret)?;
+        let rest = payload.strip_prefix("email.").ok_or(TokenError::Invalid)?;
+        let parts: Vec<&str> = rest.split('.').collect();
+        let [library_id, member_id, email_hash, expires_at] = parts[..] else {
+            return Err(TokenError::Invalid);
+        };
+        let claims = Self {
+            library_id: library_id.parse().map_err(|_| TokenError::Invalid)?,
+            member_id: MemberId(member_id.parse().map_err(|_| TokenError::Invalid)?),
+            email_hash: email_hash.to_string(),
+            expires_at: expires_at.parse().map_err(|_| TokenError::Invalid)?,
+        };
+
+        if now.timestamp() >= claims.expires_at {
+            return Err(TokenError::Expired);
+        }
+        Ok(claims)
+    }
+
+    pub fn matches(&self, email: &str) -> bool {
+        self.email_hash == email_hash(email)
+    }
+}
+
+/// 16 hex pertama SHA-256 alamat email; cukup untuk membedakan alamat, tidak membocorkannya.
+fn email_hash(email: &str) -> String {
+    hex::encode(&Sha256::digest(email.as_bytes())[..8])
+}
+
+/// Token = base64url(payload) "." hex(HMAC-SHA256(payload)).
+fn sign_payload(payload: &str, secret: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    let signature = hex::encode(mac.finalize().into_bytes());
+    format!("{}.{signature}", encode_cursor(payload))
+}
+
+/// Kembalikan payload kalau tanda tangannya sah.
+fn verify_payload(token: &str, secret: &[u8]) -> Result<String, TokenError> {
+    let (payload, signature) = token.split_once('.').ok_or(TokenError::Invalid)?;
+    let payload = decode_cursor(payload).ok_or(TokenError::Invalid)?;
+    let signature = hex::decode(signature).map_err(|_| TokenError::Invalid)?;
+
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature).map_err(|_| TokenError::Invalid)?;
+    Ok(payload)
+}
@@ -0,0 +1,91 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Statistik per nama query sejak server start.
+#[derive(Debug, Clone, Copy, Default)]
+struct QueryStat {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+/// Ringkasan satu query untuk `GET /admin/stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuerySummary {
+    pub count: u64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Registry durasi query. Query yang lebih lambat dari `slow_threshold`
+/// dicatat ke log (hanya nama query; nilai parameter tidak pernah ikut dicetak).
+#[derive(Debug)]
+pub struct QueryMetrics {
+    slow_threshold: Duration,
+    stats: Mutex<HashMap<&'static str, QueryStat>>,
+}
+
+impl QueryMetrics {
+    pub fn new(slow_threshold: Duration) -> Self {
+        Self {
+            slow_threshold,
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Jalankan `fut` (biasanya satu `fetch_*`) dan catat durasinya di bawah `name`.
+    pub async fn time<F: Future>(&self, name: &'static str, fut: F) -> F::Output {
+        let started = Instant::now();
+        let output = fut.await;
+        self.record(name, started.elapsed());
+        output
+    }
+
+    pub fn record(&self, name: &'static str, elapsed: Duration) {
+        if elapsed > self.slow_threshold {
+            eprintln!(
+                "WARN slow query '{name}': {:.1} ms (threshold {} ms, params redacted)",
+                as_ms(elapsed),
+                self.slow_threshold.as_millis()
+            );
+        }
+
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let stat = stats.entry(name).or_default();
+        stat.count += 1;
+        stat.total += elapsed;
+        stat.max = stat.max.max(elapsed);
+    }
+
+    /// Salinan statistik semua query, urut nama.
+    pub fn summary(&self) -> BTreeMap<&'static str, QuerySummary> {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats
+            .iter()
+            .map(|(name, stat)| {
+                let avg = stat.total / u32::try_from(stat.count.max(1)).unwrap_or(u32::MAX);
+                (
+                    *name,
+                    QuerySummary {
+                        count: stat.count,
+                        avg_ms: as_ms(avg),
+                        max_ms: as_ms(stat.max),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+fn as_ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Respons `GET /admin/stats`.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub queries: BTreeMap<&'static str, QuerySummary>,
+}
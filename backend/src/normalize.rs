@@ -0,0 +1,44 @@
+// Aturan normalisasi teks dipakai bersama oleh payload create/update dan search,
+// supaya "  rust  in action " dan "rust in action" selalu dianggap sama.
+
+use crate::i18n::Message;
+
+/// Trim dan ringkas setiap deretan whitespace di tengah jadi satu spasi. Huruf besar/kecil dibiarkan.
+pub fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Email: whitespace dibuang di pinggir, semua huruf kecil.
+pub fn email(s: &str) -> String {
+    collapse_whitespace(s).to_lowercase()
+}
+
+/// Kunci perbandingan untuk search/deteksi duplikat: whitespace diringkas, huruf kecil.
+pub fn search_key(s: &str) -> String {
+    collapse_whitespace(s).to_lowercase()
+}
+
+/// Field teks wajib: dinormalisasi lalu ditolak kalau jadi kosong.
+pub fn required(field: &str, value: &str) -> Result<String, Message> {
+    let normalized = collapse_whitespace(value);
+    if normalized.is_empty() {
+        return Err(Message::new("validation.required").param("field", field));
+    }
+    Ok(normalized)
+}
+
+/// Jarak Levenshtein (per karakter) antara dua string.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = Vec::with_capacity(b.len() + 1);
+        row.push(i + 1);
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != *cb);
+            row.push(substitute.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,179 @@
+use axum::http::HeaderValue;
+use axum::response::Response;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+use crate::auth::Tenant;
+use crate::config::AppConfig;
+use crate::error::ApiError;
+
+/// Header daftar tanpa paging: apakah hasilnya dipotong LIST_RESULT_CAP, dan jumlah totalnya.
+pub const RESULT_TRUNCATED_HEADER: &str = "x-result-truncated";
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Hasil `soft_cap` untuk header respons.
+#[derive(Debug, Clone, Copy)]
+pub struct CapInfo {
+    pub total: usize,
+    pub truncated: bool,
+}
+
+impl CapInfo {
+    /// Tambahkan `X-Result-Truncated` dan `X-Total-Count` ke respons.
+    pub fn apply(self, mut response: Response) -> Response {
+        let headers = response.headers_mut();
+        headers.insert(
+            RESULT_TRUNCATED_HEADER,
+            HeaderValue::from_static(if self.truncated { "true" } else { "false" }),
+        );
+        headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(self.total));
+        response
+    }
+}
+
+/// LIMIT untuk query daftar tanpa paging: satu di atas LIST_RESULT_CAP, supaya kelebihannya
+/// ketahuan tanpa memuat seluruh tabel. None kalau batasnya tidak diisi.
+pub fn cap_limit(cap: Option<usize>) -> Option<i64> {
+    cap.map(|cap| cap as i64 + 1)
+}
+
+/// Batas lunak daftar tanpa paging (LIST_RESULT_CAP): `items` hasil query ber-LIMIT
+/// `cap_limit`, kelebihannya dibuang dan client diberi tahu lewat header, bukan error.
+/// `count` (COUNT(*) dengan filter yang sama) hanya dijalankan kalau hasilnya terpotong.
+/// None kalau batasnya tidak diisi (daftar dikirim utuh).
+pub async fn soft_cap<T>(
+    items: &mut Vec<T>,
+    cap: Option<usize>,
+    count: impl Future<Output = Result<i64, sqlx::Error>>,
+) -> Result<Option<CapInfo>, sqlx::Error> {
+    let Some(cap) = cap else {
+        return Ok(None);
+    };
+    if items.len() <= cap {
+        return Ok(Some(CapInfo {
+            total: items.len(),
+            truncated: false,
+        }));
+    }
+    items.truncate(cap);
+    let total = count.await?;
+    Ok(Some(CapInfo {
+        total: usize::try_from(total).unwrap_or(0),
+        truncated: true,
+    }))
+}
+
+/// `?all=true` melewati LIST_RESULT_CAP dan hanya untuk request ber-API key; pemakaiannya
+/// dicatat di log.
+pub fn allow_full_listing(tenant: &Tenant, path: &str) -> Result<(), ApiError> {
+    if tenant.key_id.is_none() {
+        return Err(ApiError::forbidden(format!("{path}?all=true requires an API key")));
+    }
+    println!(
+        "Full listing {path}?all=true by {} (library_id={})",
+        tenant.actor(),
+        tenant.library_id
+    );
+    Ok(())
+}
+
+/// Tandai respons `?all=true` sebagai daftar lengkap.
+pub fn mark_complete(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(RESULT_TRUNCATED_HEADER, HeaderValue::from_static("false"));
+    response
+}
+
+/// Parameter paging offset: `?page=2&per_page=50` (page mulai dari 1).
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct PageParams {
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+/// Hasil validasi paging, siap dipakai sebagai LIMIT/OFFSET.
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl PageParams {
+    /// True kalau client mengirim salah satu parameter paging.
+    pub fn is_requested(&self) -> bool {
+        self.page.is_some() || self.per_page.is_some()
+    }
+
+    /// Validasi terhadap batas di config (DEFAULT_PAGE_SIZE / MAX_PAGE_SIZE).
+    pub fn resolve(&self, config: &AppConfig) -> Result<Page, ApiError> {
+        let page = self.page.unwrap_or(1);
+        if page == 0 {
+            return Err(ApiError::bad_request("page starts at 1"));
+        }
+        let per_page = self.per_page.unwrap_or(config.default_page_size);
+        if per_page == 0 || per_page > config.max_page_size {
+            return Err(ApiError::out_of_range("per_page", config.max_page_size));
+        }
+
+        Ok(Page {
+            limit: i64::from(per_page),
+            offset: i64::from(page - 1) * i64::from(per_page),
+        })
+    }
+}
+
+/// Respons paging cursor: satu halaman item plus cursor halaman berikutnya (null di akhir).
+#[derive(Debug, Serialize)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+/// Bungkus posisi cursor (teks polos) jadi string opaque untuk client.
+pub fn encode_cursor(raw: &str) -> String {
+    URL_SAFE_NO_PAD.encode(raw)
+}
+
+/// Kebalikan `encode_cursor`; `None` kalau bukan base64url/UTF-8 yang valid.
+pub fn decode_cursor(cursor: &str) -> Option<String> {
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::ready;
+
+    #[test]
+    fn cap_limit_fetches_one_extra_row() {
+        assert_eq!(cap_limit(Some(100)), Some(101));
+        assert_eq!(cap_limit(None), None);
+    }
+
+    #[tokio::test]
+    async fn soft_cap_counts_only_when_truncated() {
+        let mut items: Vec<i32> = (0..3).collect();
+        let info = soft_cap(&mut items, Some(3), async { panic!("COUNT(*) not needed") })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((info.total, info.truncated), (3, false));
+        assert_eq!(items.len(), 3);
+
+        let mut items: Vec<i32> = (0..4).collect();
+        let info = soft_cap(&mut items, Some(3), ready(Ok(250))).await.unwrap().unwrap();
+        assert_eq!((info.total, info.truncated), (250, true));
+        assert_eq!(items, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn soft_cap_without_cap_keeps_everything() {
+        let mut items: Vec<i32> = (0..4).collect();
+        assert!(soft_cap(&mut items, None, ready(Ok(4))).await.unwrap().is_none());
+        assert_eq!(items.len(), 4);
+    }
+}
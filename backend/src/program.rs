@@ -0,0 +1,93 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::i18n::Message;
+use crate::ids::{BookId, MemberId};
+use crate::normalize;
+use crate::timestamps;
+
+/// Satu baris di tabel `programs`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Program {
+    pub id: i32,
+    pub name: String,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub target_count: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl Program {
+    /// Jendela program sebagai rentang `[mulai, selesai)` DATETIME; hari terakhir ikut dihitung.
+    pub fn window(&self) -> (NaiveDateTime, NaiveDateTime) {
+        let start = self.starts_on.and_time(NaiveTime::MIN);
+        let end = self
+            .ends_on
+            .succ_opt()
+            .unwrap_or(self.ends_on)
+            .and_time(NaiveTime::MIN);
+        (start, end)
+    }
+}
+
+/// Payload `POST /programs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewProgram {
+    pub name: String,
+    #[serde(deserialize_with = "timestamps::date")]
+    pub starts_on: NaiveDate,
+    #[serde(deserialize_with = "timestamps::date")]
+    pub ends_on: NaiveDate,
+    pub target_count: i32,
+}
+
+impl NewProgram {
+    pub fn normalized(self) -> Result<Self, Message> {
+        if self.ends_on < self.starts_on {
+            return Err(Message::new("validation.invalid_value")
+                .param("field", "ends_on")
+                .param("reason", "must not be before starts_on"));
+        }
+        if self.target_count < 1 {
+            return Err(Message::new("validation.invalid_value")
+                .param("field", "target_count")
+                .param("reason", "must be at least 1"));
+        }
+        Ok(Self {
+            name: normalize::required("name", &self.name)?,
+            ..self
+        })
+    }
+}
+
+/// Payload `POST /programs/:id/enrollments`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewEnrollment {
+    pub member_id: MemberId,
+}
+
+/// Satu baris leaderboard `GET /programs/:id/leaderboard`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct LeaderboardEntry {
+    pub member_id: MemberId,
+    pub name: String,
+    /// Jumlah buku berbeda yang dikembalikan di dalam jendela program.
+    pub completed: i64,
+    #[sqlx(skip)]
+    pub reached_target: bool,
+}
+
+/// Respons `GET /programs/:id/members/:member_id/progress`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramProgress {
+    pub program_id: i32,
+    pub member_id: MemberId,
+    pub completed: i64,
+    pub target_count: i32,
+    pub reached_target: bool,
+    /// Buku yang dihitung, masing-masing sekali.
+    pub books: Vec<BookId>,
+}
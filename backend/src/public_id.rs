@@ -0,0 +1,108 @@
+use rand::Rng;
+use sqlx::{MySqlPool, Row};
+
+use crate::error::ApiError;
+
+/// Berapa kali insert diulang kalau public id yang dibuat kebetulan bentrok.
+pub const MAX_INSERT_ATTEMPTS: usize = 3;
+
+/// Alfabet Crockford base32 huruf kecil (tanpa i, l, o, u supaya tidak ambigu).
+const ALPHABET: &[u8] = b"0123456789abcdefghjkmnpqrstvwxyz";
+const RANDOM_LEN: usize = 16;
+
+/// Primary key bertipe yang bisa di-resolve dari parameter path.
+pub trait HasPublicId: Sized {
+    const ENTITY: Entity;
+
+    fn from_raw(id: i32) -> Self;
+}
+
+/// Entitas yang punya public id.
+#[derive(Debug, Clone, Copy)]
+pub enum Entity {
+    Book,
+    Member,
+    Loan,
+}
+
+impl Entity {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Book => "bk",
+            Self::Member => "mb",
+            Self::Loan => "ln",
+        }
+    }
+
+    /// Nama entitas di API, mis. `?entity=book`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Book => "book",
+            Self::Member => "member",
+            Self::Loan => "loan",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Book, Self::Member, Self::Loan]
+            .into_iter()
+            .find(|entity| entity.name() == name)
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            Self::Book => "books",
+            Self::Member => "members",
+            Self::Loan => "loans",
+        }
+    }
+}
+
+/// Buat public id baru, contoh: `bk_8f3kq2m9x0c7v1ta`.
+pub fn generate(entity: Entity) -> String {
+    let mut rng = rand::thread_rng();
+    let random: String = (0..RANDOM_LEN)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+        .collect();
+    format!("{}_{random}", entity.prefix())
+}
+
+/// True kalau error insert disebabkan public id yang sudah dipakai.
+pub fn is_collision(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .map(|db| db.is_unique_violation() && db.message().contains("public_id"))
+        .unwrap_or(false)
+}
+
+/// Ubah parameter path (public id, atau integer lama selama masa deprecation)
+/// menjadi primary key bertipe milik perpustakaan `library_id`.
+pub async fn resolve<T: HasPublicId>(
+    pool: &MySqlPool,
+    library_id: i32,
+    raw: &str,
+) -> Result<T, ApiError> {
+    let entity = T::ENTITY;
+    if let Ok(id) = raw.parse::<i32>() {
+        return Ok(T::from_raw(id));
+    }
+
+    let expected = format!("{}_", entity.prefix());
+    if !raw.starts_with(&expected) {
+        return Err(ApiError::bad_request(format!(
+            "invalid id '{raw}', expected '{expected}...' or an integer"
+        )));
+    }
+
+    let sql = format!(
+        "SELECT id FROM {} WHERE public_id = ? AND library_id = ?",
+        entity.table()
+    );
+    let row = sqlx::query(&sql)
+        .bind(raw)
+        .bind(library_id)
+        .fetch_optional(pool)
+        .await?;
+
+    row.map(|r| T::from_raw(r.get("id")))
+        .ok_or_else(|| ApiError::missing_resource("not_found.id", raw))
+}
@@ -0,0 +1,103 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::book::Book;
+use crate::i18n::Message;
+use crate::ids::{BookId, MemberId};
+use crate::normalize;
+
+/// Status usulan yang disimpan di kolom `purchase_requests.status`.
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_APPROVED: &str = "approved";
+pub const STATUS_REJECTED: &str = "rejected";
+pub const STATUS_RECEIVED: &str = "received";
+
+/// Status yang masih "terbuka": usulan baru dengan judul sama digabung ke sini.
+pub const OPEN_STATUSES: [&str; 2] = [STATUS_PENDING, STATUS_APPROVED];
+
+/// Satu usulan pengadaan beserta jumlah peminatnya.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PurchaseRequest {
+    pub id: i32,
+    pub title: String,
+    pub author: Option<String>,
+    pub requester_member_id: MemberId,
+    pub note: Option<String>,
+    pub status: String,
+    /// Jumlah anggota yang menginginkan judul ini (pengusul + "+1").
+    pub interested: i64,
+    pub expected_copies: Option<i32>,
+    pub reject_reason: Option<String>,
+    pub book_id: Option<BookId>,
+    pub created_at: NaiveDateTime,
+    pub decided_at: Option<NaiveDateTime>,
+    pub received_at: Option<NaiveDateTime>,
+}
+
+/// Payload `POST /purchase-requests`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewPurchaseRequest {
+    pub title: String,
+    pub author: Option<String>,
+    pub member_id: MemberId,
+    pub note: Option<String>,
+}
+
+impl NewPurchaseRequest {
+    pub fn normalized(self) -> Result<Self, Message> {
+        let optional = |v: Option<String>| {
+            v.map(|v| normalize::collapse_whitespace(&v))
+                .filter(|v| !v.is_empty())
+        };
+        Ok(Self {
+            title: normalize::required("title", &self.title)?,
+            author: optional(self.author),
+            member_id: self.member_id,
+            note: optional(self.note),
+        })
+    }
+}
+
+/// Hasil `POST /purchase-requests`: `merged` true kalau digabung ke usulan yang sudah ada.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmittedPurchaseRequest {
+    #[serde(flatten)]
+    pub request: PurchaseRequest,
+    pub merged: bool,
+}
+
+/// Payload `POST /purchase-requests/:id/approve`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApprovePurchaseRequest {
+    pub expected_copies: i32,
+}
+
+/// Payload `POST /purchase-requests/:id/reject`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RejectPurchaseRequest {
+    pub reason: String,
+}
+
+/// Payload `POST /purchase-requests/:id/received`: data katalog untuk buku yang dibuat.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReceivePurchaseRequest {
+    /// Wajib kalau usulan tidak mencatat pengarang.
+    pub author: Option<String>,
+    pub category: String,
+    #[serde(default)]
+    pub year: Option<i32>,
+    /// Default `expected_copies` saat approve.
+    pub copies: Option<i32>,
+}
+
+/// Hasil `POST /purchase-requests/:id/received`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedPurchaseRequest {
+    pub request: PurchaseRequest,
+    pub book: Book,
+}
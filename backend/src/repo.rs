@@ -0,0 +1,967 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::{Executor, MySql, MySqlConnection, MySqlPool, QueryBuilder};
+
+use crate::book::Book;
+use crate::fine::REASON_LATE;
+use crate::ids::{BookId, LoanId, MemberId};
+use crate::loan::{Loan, LoanDetail, LoanInclude};
+use crate::member::Member;
+use crate::reserve_list::ReservePolicy;
+use crate::stock::Movement;
+
+// Akses baris buku, anggota, dan pinjaman per id. Setiap query di sini memfilter
+// `library_id`, jadi id milik perpustakaan lain diperlakukan sama dengan id yang tidak ada:
+// handler cukup memanggil fungsi ini dan tidak menulis filter tenant sendiri.
+
+const BOOK_SELECT: &str =
+    "SELECT id, public_id, title, author, category, year, total_copies, available_copies,
+            version, updated_at, location
+     FROM books";
+const MEMBER_SELECT: &str = "SELECT id, public_id, name, email, joined_at FROM members";
+const LOAN_SELECT: &str =
+    "SELECT id, public_id, book_id, member_id, borrowed_at, due_at, returned_at, lost_at
+     FROM loans";
+
+pub async fn find_book<'e, E>(
+    executor: E,
+    library_id: i32,
+    id: BookId,
+) -> Result<Option<Book>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as(&format!("{BOOK_SELECT} WHERE id = ? AND library_id = ?"))
+        .bind(id)
+        .bind(library_id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Seperti `find_book`, tapi barisnya dikunci (`FOR UPDATE`) sampai transaksi selesai.
+pub async fn lock_book(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    id: BookId,
+) -> Result<Option<Book>, sqlx::Error> {
+    sqlx::query_as(&format!("{BOOK_SELECT} WHERE id = ? AND library_id = ? FOR UPDATE"))
+        .bind(id)
+        .bind(library_id)
+        .fetch_optional(conn)
+        .await
+}
+
+/// True kalau ada baris yang terhapus.
+pub async fn delete_book<'e, E>(
+    executor: E,
+    library_id: i32,
+    id: BookId,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("DELETE FROM books WHERE id = ? AND library_id = ?")
+        .bind(id)
+        .bind(library_id)
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn find_member<'e, E>(
+    executor: E,
+    library_id: i32,
+    id: MemberId,
+) -> Result<Option<Member>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as(&format!("{MEMBER_SELECT} WHERE id = ? AND library_id = ?"))
+        .bind(id)
+        .bind(library_id)
+        .fetch_optional(executor)
+        .await
+}
+
+pub async fn lock_member(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    id: MemberId,
+) -> Result<Option<Member>, sqlx::Error> {
+    sqlx::query_as(&format!("{MEMBER_SELECT} WHERE id = ? AND library_id = ? FOR UPDATE"))
+        .bind(id)
+        .bind(library_id)
+        .fetch_optional(conn)
+        .await
+}
+
+/// True kalau ada baris yang terhapus.
+pub async fn delete_member<'e, E>(
+    executor: E,
+    library_id: i32,
+    id: MemberId,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("DELETE FROM members WHERE id = ? AND library_id = ?")
+        .bind(id)
+        .bind(library_id)
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn find_loan<'e, E>(
+    executor: E,
+    library_id: i32,
+    id: LoanId,
+) -> Result<Option<Loan>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as(&format!("{LOAN_SELECT} WHERE id = ? AND library_id = ?"))
+        .bind(id)
+        .bind(library_id)
+        .fetch_optional(executor)
+        .await
+}
+
+pub async fn lock_loan(
+    conn: &mut MySqlConnection,
+    library_id: i32,
+    id: LoanId,
+) -> Result<Option<Loan>, sqlx::Error> {
+    sqlx::query_as(&format!("{LOAN_SELECT} WHERE id = ? AND library_id = ? FOR UPDATE"))
+        .bind(id)
+        .bind(library_id)
+        .fetch_optional(conn)
+        .await
+}
+
+/// Kebijakan reserve yang paling ketat untuk `book_id` per hari `today`, kalau ada.
+/// Daftar yang `ends_on`-nya sudah lewat diabaikan walau job penonaktifan belum jalan.
+pub async fn reserve_policy<'e, E>(
+    executor: E,
+    library_id: i32,
+    book_id: BookId,
+    today: NaiveDate,
+) -> Result<Option<ReservePolicy>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, ReservePolicy>(
+        "SELECT MIN(l.loan_days) AS loan_days, MIN(l.renewal_limit) AS renewal_limit
+         FROM reserve_lists l
+         JOIN reserve_list_books b ON b.list_id = l.id
+         WHERE l.library_id = ? AND b.book_id = ? AND l.active AND l.ends_on >= ?
+         HAVING COUNT(*) > 0",
+    )
+    .bind(library_id)
+    .bind(book_id)
+    .bind(today)
+    .fetch_optional(executor)
+    .await
+}
+
+// Lookup batch untuk respons yang meng-embed buku/anggota per pinjaman:
+// satu query `WHERE id IN (...)` per jenis, bukan satu query per baris.
+
+/// Ambil banyak buku sekaligus, dikunci per id. Id yang tidak ada tidak muncul di map.
+pub async fn get_books_by_ids(
+    pool: &MySqlPool,
+    library_id: i32,
+    ids: &[BookId],
+) -> Result<HashMap<BookId, Book>, sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(BOOK_SELECT);
+    qb.push(" WHERE library_id = ");
+    qb.push_bind(library_id).push(" AND id IN (");
+    let mut list = qb.separated(", ");
+    for id in dedup(ids) {
+        list.push_bind(id);
+    }
+    list.push_unseparated(")");
+
+    let books = qb.build_query_as::<Book>().fetch_all(pool).await?;
+    Ok(books.into_iter().map(|b| (b.id, b)).collect())
+}
+
+/// Ambil banyak anggota sekaligus, dikunci per id.
+pub async fn get_members_by_ids(
+    pool: &MySqlPool,
+    library_id: i32,
+    ids: &[MemberId],
+) -> Result<HashMap<MemberId, Member>, sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(MEMBER_SELECT);
+    qb.push(" WHERE library_id = ");
+    qb.push_bind(library_id).push(" AND id IN (");
+    let mut list = qb.separated(", ");
+    for id in dedup(ids) {
+        list.push_bind(id);
+    }
+    list.push_unseparated(")");
+
+    let members = qb.build_query_as::<Member>().fetch_all(pool).await?;
+    Ok(members.into_iter().map(|m| (m.id, m)).collect())
+}
+
+/// Sumber lookup batch untuk `expand_loans`: satu panggilan = satu query.
+/// Di produksi ini pool-nya; test memakai implementasi yang menghitung panggilan.
+#[async_trait]
+pub trait BatchLookup: Sync {
+    async fn books(
+        &self,
+        library_id: i32,
+        ids: &[BookId],
+    ) -> Result<HashMap<BookId, Book>, sqlx::Error>;
+
+    async fn members(
+        &self,
+        library_id: i32,
+        ids: &[MemberId],
+    ) -> Result<HashMap<MemberId, Member>, sqlx::Error>;
+}
+
+#[async_trait]
+impl BatchLookup for MySqlPool {
+    async fn books(
+        &self,
+        library_id: i32,
+        ids: &[BookId],
+    ) -> Result<HashMap<BookId, Book>, sqlx::Error> {
+        get_books_by_ids(self, library_id, ids).await
+    }
+
+    async fn members(
+        &self,
+        library_id: i32,
+        ids: &[MemberId],
+    ) -> Result<HashMap<MemberId, Member>, sqlx::Error> {
+        get_members_by_ids(self, library_id, ids).await
+    }
+}
+
+/// Lengkapi daftar pinjaman dengan relasi yang diminta: paling banyak dua query tambahan,
+/// berapa pun jumlah pinjamannya.
+pub async fn expand_loans(
+    lookup: &impl BatchLookup,
+    library_id: i32,
+    loans: Vec<Loan>,
+    include: LoanInclude,
+) -> Result<Vec<LoanDetail>, sqlx::Error> {
+    let books = if include.book && !loans.is_empty() {
+        let ids: Vec<BookId> = loans.iter().map(|l| l.book_id).collect();
+        lookup.books(library_id, &ids).await?
+    } else {
+        HashMap::new()
+    };
+    let members = if include.member && !loans.is_empty() {
+        let ids: Vec<MemberId> = loans.iter().map(|l| l.member_id).collect();
+        lookup.members(library_id, &ids).await?
+    } else {
+        HashMap::new()
+    };
+
+    Ok(loans
+        .into_iter()
+        .map(|loan| LoanDetail {
+            // Satu buku bisa muncul di banyak pinjaman, jadi clone (bukan remove).
+            book: books.get(&loan.book_id).cloned(),
+            member: members.get(&loan.member_id).cloned(),
+            loan,
+        })
+        .collect())
+}
+
+/// Baris yang dibaca dan ditulis `loan::create_loan` / `loan::return_loan`, semuanya di
+/// dalam satu transaksi milik pemanggil. Di produksi ini koneksi transaksinya; test memakai
+/// `memory::MemoryRepo`.
+#[async_trait]
+pub trait LoanRepo: Send {
+    async fn find_member(
+        &mut self,
+        library_id: i32,
+        id: MemberId,
+    ) -> Result<Option<Member>, sqlx::Error>;
+
+    /// Jumlah pinjaman aktif anggota `member_id`.
+    async fn active_loans(
+        &mut self,
+        library_id: i32,
+        member_id: MemberId,
+    ) -> Result<i64, sqlx::Error>;
+
+    /// Periode pinjam maksimal dari daftar reserve aktif yang memuat buku ini.
+    async fn reserve_loan_days(
+        &mut self,
+        library_id: i32,
+        book_id: BookId,
+        today: NaiveDate,
+    ) -> Result<Option<i32>, sqlx::Error>;
+
+    /// Baca buku dan kunci barisnya sampai transaksi selesai.
+    async fn lock_book(
+        &mut self,
+        library_id: i32,
+        id: BookId,
+    ) -> Result<Option<Book>, sqlx::Error>;
+
+    /// Insert satu pinjaman aktif; public id yang bentrok dikembalikan sebagai error aslinya
+    /// (lihat `public_id::is_collision`).
+    async fn insert_loan(
+        &mut self,
+        library_id: i32,
+        public_id: &str,
+        book_id: BookId,
+        member_id: MemberId,
+        due_at: NaiveDateTime,
+    ) -> Result<LoanId, sqlx::Error>;
+
+    async fn find_loan(
+        &mut self,
+        library_id: i32,
+        id: LoanId,
+    ) -> Result<Option<Loan>, sqlx::Error>;
+
+    /// Baca pinjaman dan kunci barisnya sampai transaksi selesai.
+    async fn lock_loan(
+        &mut self,
+        library_id: i32,
+        id: LoanId,
+    ) -> Result<Option<Loan>, sqlx::Error>;
+
+    async fn mark_returned(
+        &mut self,
+        library_id: i32,
+        id: LoanId,
+        now: NaiveDateTime,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Ubah available_copies sebesar `movement.delta` dan catat pergerakannya di buku besar.
+    async fn move_stock(
+        &mut self,
+        movement: Movement,
+        now: NaiveDateTime,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Catat denda keterlambatan `amount` untuk `loan`; `paid_at` diisi kalau langsung lunas.
+    async fn insert_late_fine(
+        &mut self,
+        library_id: i32,
+        loan: &Loan,
+        amount: i64,
+        paid_at: Option<NaiveDateTime>,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl LoanRepo for MySqlConnection {
+    async fn find_member(
+        &mut self,
+        library_id: i32,
+        id: MemberId,
+    ) -> Result<Option<Member>, sqlx::Error> {
+        find_member(self, library_id, id).await
+    }
+
+    async fn active_loans(
+        &mut self,
+        library_id: i32,
+        member_id: MemberId,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM loans
+             WHERE member_id = ? AND library_id = ? AND returned_at IS NULL",
+        )
+        .bind(member_id)
+        .bind(library_id)
+        .fetch_one(self)
+        .await
+    }
+
+    async fn reserve_loan_days(
+        &mut self,
+        library_id: i32,
+        book_id: BookId,
+        today: NaiveDate,
+    ) -> Result<Option<i32>, sqlx::Error> {
+        let policy = reserve_policy(self, library_id, book_id, today).await?;
+        Ok(policy.map(|policy| policy.loan_days))
+    }
+
+    async fn lock_book(
+        &mut self,
+        library_id: i32,
+        id: BookId,
+    ) -> Result<Option<Book>, sqlx::Error> {
+        lock_book(self, library_id, id).await
+    }
+
+    async fn insert_loan(
+        &mut self,
+        library_id: i32,
+        public_id: &str,
+        book_id: BookId,
+        member_id: MemberId,
+        due_at: NaiveDateTime,
+    ) -> Result<LoanId, sqlx::Error> {
+        let res = sqlx::query(
+            "INSERT INTO loans (library_id, public_id, book_id, member_id, due_at)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(library_id)
+        .bind(public_id)
+        .bind(book_id)
+        .bind(member_id)
+        .bind(due_at)
+        .execute(self)
+        .await?;
+        Ok(LoanId(res.last_insert_id() as i32))
+    }
+
+    async fn find_loan(
+        &mut self,
+        library_id: i32,
+        id: LoanId,
+    ) -> Result<Option<Loan>, sqlx::Error> {
+        find_loan(self, library_id, id).await
+    }
+
+    async fn lock_loan(
+        &mut self,
+        library_id: i32,
+        id: LoanId,
+    ) -> Result<Option<Loan>, sqlx::Error> {
+        lock_loan(self, library_id, id).await
+    }
+
+    async fn mark_returned(
+        &mut self,
+        library_id: i32,
+        id: LoanId,
+        now: NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE loans SET returned_at = ? WHERE id = ? AND library_id = ?")
+            .bind(now)
+            .bind(id)
+            .bind(library_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    async fn move_stock(
+        &mut self,
+        movement: Movement,
+        now: NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE books
+             SET available_copies = available_copies + ?, version = version + 1, updated_at = ?
+             WHERE id = ? AND library_id = ?",
+        )
+        .bind(movement.delta)
+        .bind(now)
+        .bind(movement.book_id)
+        .bind(movement.library_id)
+        .execute(&mut *self)
+        .await?;
+        movement.write(self, now).await
+    }
+
+    async fn insert_late_fine(
+        &mut self,
+        library_id: i32,
+        loan: &Loan,
+        amount: i64,
+        paid_at: Option<NaiveDateTime>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO fines (library_id, loan_id, member_id, amount, reason, paid_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(library_id)
+        .bind(loan.id)
+        .bind(loan.member_id)
+        .bind(amount)
+        .bind(REASON_LATE)
+        .bind(paid_at)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Buang id duplikat tanpa mengubah urutan kemunculan pertama.
+fn dedup<T: Copy + Eq + std::hash::Hash>(ids: &[T]) -> Vec<T> {
+    let mut seen = std::collections::HashSet::new();
+    ids.iter().copied().filter(|id| seen.insert(*id)).collect()
+}
+
+/// `LoanRepo` di memori untuk menguji aturan pinjaman tanpa DB. Hanya satu perpustakaan
+/// (`LIBRARY_ID`); id milik perpustakaan lain diperlakukan sama dengan id yang tidak ada.
+#[cfg(test)]
+pub mod memory {
+    use super::*;
+    use crate::stock::StockReason;
+    use sqlx::error::{DatabaseError, ErrorKind};
+    use std::{error::Error, fmt};
+
+    pub const LIBRARY_ID: i32 = 1;
+
+    #[derive(Default)]
+    pub struct MemoryRepo {
+        pub members: Vec<Member>,
+        pub books: Vec<Book>,
+        pub loans: Vec<Loan>,
+        pub movements: Vec<Movement>,
+        /// (pinjaman, jumlah, paid_at) per denda keterlambatan.
+        pub fines: Vec<(LoanId, i64, Option<NaiveDateTime>)>,
+        pub reserve_loan_days: HashMap<BookId, i32>,
+        /// Jumlah insert pinjaman berikutnya yang gagal karena public id bentrok.
+        pub collisions: usize,
+    }
+
+    impl MemoryRepo {
+        pub fn add_member(&mut self, joined_at: NaiveDateTime) -> MemberId {
+            let id = MemberId(self.members.len() as i32 + 1);
+            self.members.push(Member {
+                id,
+                public_id: format!("mb_{}", id.0),
+                name: format!("Anggota {}", id.0),
+                email: format!("a{}@example.test", id.0),
+                joined_at,
+            });
+            id
+        }
+
+        /// Buku baru dengan `copies` eksemplar, saldo awalnya dicatat di buku besar.
+        pub fn add_book(&mut self, copies: i32, now: NaiveDateTime) -> BookId {
+            let id = BookId(self.books.len() as i32 + 1);
+            self.books.push(Book {
+                id,
+                public_id: format!("bk_{}", id.0),
+                title: format!("Buku {}", id.0),
+                author: "Penulis".into(),
+                category: "Novel".into(),
+                year: None,
+                total_copies: copies,
+                available_copies: copies,
+                version: 1,
+                updated_at: now,
+                location: None,
+            });
+            self.movements.push(Movement {
+                library_id: LIBRARY_ID,
+                book_id: id,
+                delta: copies,
+                reason: StockReason::Restock,
+                reference_id: None,
+            });
+            id
+        }
+
+        /// Tambah `copies` eksemplar ke buku yang sudah ada (alur donasi/pembelian).
+        pub fn restock(&mut self, id: BookId, copies: i32) {
+            let book = self.book_mut(id).expect("book exists");
+            book.total_copies += copies;
+            book.available_copies += copies;
+            self.movements.push(Movement {
+                library_id: LIBRARY_ID,
+                book_id: id,
+                delta: copies,
+                reason: StockReason::Restock,
+                reference_id: None,
+            });
+        }
+
+        pub fn book(&self, id: BookId) -> &Book {
+            self.books.iter().find(|b| b.id == id).expect("book exists")
+        }
+
+        fn book_mut(&mut self, id: BookId) -> Option<&mut Book> {
+            self.books.iter_mut().find(|b| b.id == id)
+        }
+
+        /// SUM(delta) buku besar stok untuk `id`.
+        pub fn ledger_balance(&self, id: BookId) -> i64 {
+            self.movements.iter().filter(|m| m.book_id == id).map(|m| i64::from(m.delta)).sum()
+        }
+
+        pub fn active_loans_for(&self, f: impl Fn(&Loan) -> bool) -> i64 {
+            self.loans.iter().filter(|l| l.returned_at.is_none() && f(l)).count() as i64
+        }
+    }
+
+    /// Unique violation seperti yang dikirim MySQL saat public id bentrok.
+    #[derive(Debug)]
+    struct DuplicatePublicId;
+
+    impl fmt::Display for DuplicatePublicId {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.message())
+        }
+    }
+
+    impl Error for DuplicatePublicId {}
+
+    impl DatabaseError for DuplicatePublicId {
+        fn message(&self) -> &str {
+            "Duplicate entry 'ln_x' for key 'loans.uq_loans_public_id'"
+        }
+
+        fn as_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::UniqueViolation
+        }
+    }
+
+    #[async_trait]
+    impl LoanRepo for MemoryRepo {
+        async fn find_member(
+            &mut self,
+            library_id: i32,
+            id: MemberId,
+        ) -> Result<Option<Member>, sqlx::Error> {
+            let found = self.members.iter().find(|m| m.id == id);
+            Ok(found.filter(|_| library_id == LIBRARY_ID).cloned())
+        }
+
+        async fn active_loans(
+            &mut self,
+            _library_id: i32,
+            member_id: MemberId,
+        ) -> Result<i64, sqlx::Error> {
+            Ok(self.active_loans_for(|l| l.member_id == member_id))
+        }
+
+        async fn reserve_loan_days(
+            &mut self,
+            _library_id: i32,
+            book_id: BookId,
+            _today: NaiveDate,
+        ) -> Result<Option<i32>, sqlx::Error> {
+            Ok(self.reserve_loan_days.get(&book_id).copied())
+        }
+
+        async fn lock_book(
+            &mut self,
+            library_id: i32,
+            id: BookId,
+        ) -> Result<Option<Book>, sqlx::Error> {
+            let found = self.books.iter().find(|b| b.id == id);
+            Ok(found.filter(|_| library_id == LIBRARY_ID).cloned())
+        }
+
+        async fn insert_loan(
+            &mut self,
+            _library_id: i32,
+            public_id: &str,
+            book_id: BookId,
+            member_id: MemberId,
+            due_at: NaiveDateTime,
+        ) -> Result<LoanId, sqlx::Error> {
+            if self.collisions > 0 {
+                self.collisions -= 1;
+                return Err(sqlx::Error::Database(Box::new(DuplicatePublicId)));
+            }
+            let id = LoanId(self.loans.len() as i32 + 1);
+            self.loans.push(Loan {
+                id,
+                public_id: public_id.to_string(),
+                book_id,
+                member_id,
+                // Di DB diisi DEFAULT CURRENT_TIMESTAMP.
+                borrowed_at: chrono::Utc::now().naive_utc(),
+                due_at,
+                returned_at: None,
+                lost_at: None,
+            });
+            Ok(id)
+        }
+
+        async fn find_loan(
+            &mut self,
+            library_id: i32,
+            id: LoanId,
+        ) -> Result<Option<Loan>, sqlx::Error> {
+            let found = self.loans.iter().find(|l| l.id == id);
+            Ok(found.filter(|_| library_id == LIBRARY_ID).cloned())
+        }
+
+        async fn lock_loan(
+            &mut self,
+            library_id: i32,
+            id: LoanId,
+        ) -> Result<Option<Loan>, sqlx::Error> {
+            self.find_loan(library_id, id).await
+        }
+
+        async fn mark_returned(
+            &mut self,
+            _library_id: i32,
+            id: LoanId,
+            now: NaiveDateTime,
+        ) -> Result<(), sqlx::Error> {
+            if let Some(loan) = self.loans.iter_mut().find(|l| l.id == id) {
+                loan.returned_at = Some(now);
+            }
+            Ok(())
+        }
+
+        async fn move_stock(
+            &mut self,
+            movement: Movement,
+            now: NaiveDateTime,
+        ) -> Result<(), sqlx::Error> {
+            if let Some(book) = self.book_mut(movement.book_id) {
+                book.available_copies += movement.delta;
+                book.version += 1;
+                book.updated_at = now;
+            }
+            self.movements.push(movement);
+            Ok(())
+        }
+
+        async fn insert_late_fine(
+            &mut self,
+            _library_id: i32,
+            loan: &Loan,
+            amount: i64,
+            paid_at: Option<NaiveDateTime>,
+        ) -> Result<(), sqlx::Error> {
+            self.fines.push((loan.id, amount, paid_at));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::sync::Mutex;
+
+    /// `BatchLookup` palsu: mencatat setiap panggilan (= satu query) beserta id-nya.
+    #[derive(Default)]
+    struct CountingLookup {
+        calls: Mutex<Vec<(&'static str, usize)>>,
+    }
+
+    #[async_trait]
+    impl BatchLookup for CountingLookup {
+        async fn books(
+            &self,
+            _library_id: i32,
+            ids: &[BookId],
+        ) -> Result<HashMap<BookId, Book>, sqlx::Error> {
+            self.calls.lock().unwrap().push(("books", dedup(ids).len()));
+            Ok(dedup(ids).into_iter().map(|id| (id, book(id))).collect())
+        }
+
+        async fn members(
+            &self,
+            _library_id: i32,
+            ids: &[MemberId],
+        ) -> Result<HashMap<MemberId, Member>, sqlx::Error> {
+            self.calls.lock().unwrap().push(("members", dedup(ids).len()));
+            Ok(dedup(ids).into_iter().map(|id| (id, member(id))).collect())
+        }
+    }
+
+    fn at(day: u32) -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap().and_hms_opt(9, 0, 0).unwrap()
+    }
+
+    fn book(id: BookId) -> Book {
+        Book {
+            id,
+            public_id: format!("bk_{}", id.0),
+            title: format!("Buku {}", id.0),
+            author: "Penulis".into(),
+            category: "Novel".into(),
+            year: Some(2020),
+            total_copies: 3,
+            available_copies: 1,
+            version: 1,
+            updated_at: at(1),
+            location: None,
+        }
+    }
+
+    fn member(id: MemberId) -> Member {
+        Member {
+            id,
+            public_id: format!("mb_{}", id.0),
+            name: format!("Anggota {}", id.0),
+            email: format!("a{}@example.test", id.0),
+            joined_at: at(1),
+        }
+    }
+
+    /// `n` pinjaman yang berbagi 5 buku dan 3 anggota.
+    fn loans(n: i32) -> Vec<Loan> {
+        (1..=n)
+            .map(|i| Loan {
+                id: LoanId(i),
+                public_id: format!("ln_{i}"),
+                book_id: BookId(i % 5 + 1),
+                member_id: MemberId(i % 3 + 1),
+                borrowed_at: at(1),
+                due_at: at(15),
+                returned_at: None,
+                lost_at: None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn expand_loans_uses_one_lookup_per_relation_regardless_of_size() {
+        for n in [1, 10, 250] {
+            let lookup = CountingLookup::default();
+            let include = LoanInclude { book: true, member: true };
+            let details = expand_loans(&lookup, 1, loans(n), include).await.unwrap();
+
+            assert_eq!(details.len(), n as usize);
+            let calls = lookup.calls.into_inner().unwrap();
+            let expected = n.min(5) as usize;
+            assert_eq!(calls, vec![("books", expected), ("members", n.min(3) as usize)]);
+            for detail in &details {
+                assert_eq!(detail.book.as_ref().map(|b| b.id), Some(detail.loan.book_id));
+                assert_eq!(detail.member.as_ref().map(|m| m.id), Some(detail.loan.member_id));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn expand_loans_skips_relations_not_requested() {
+        let lookup = CountingLookup::default();
+        let include = LoanInclude { book: true, member: false };
+        let details = expand_loans(&lookup, 1, loans(4), include).await.unwrap();
+        assert!(details.iter().all(|d| d.book.is_some() && d.member.is_none()));
+        assert_eq!(lookup.calls.lock().unwrap().len(), 1);
+
+        let none = expand_loans(&lookup, 1, Vec::new(), LoanInclude { book: true, member: true });
+        assert!(none.await.unwrap().is_empty());
+        assert_eq!(lookup.calls.lock().unwrap().len(), 1);
+    }
+
+    // Sisanya butuh MySQL: `DATABASE_URL=mysql://... cargo test -- --ignored`. `sqlx::test`
+    // membuat database sementara per test dan menjalankan semua migrasi.
+
+    /// Satu perpustakaan dengan satu buku, satu anggota, dan satu pinjaman aktif.
+    struct Seeded {
+        library_id: i32,
+        book: BookId,
+        member: MemberId,
+        loan: LoanId,
+    }
+
+    async fn seed(pool: &MySqlPool, name: &str) -> Seeded {
+        let library_id = sqlx::query("INSERT INTO libraries (name) VALUES (?)")
+            .bind(name)
+            .execute(pool)
+            .await
+            .unwrap()
+            .last_insert_id() as i32;
+        let book = sqlx::query(
+            "INSERT INTO books (library_id, public_id, title, author, category, year,
+                                total_copies, available_copies)
+             VALUES (?, ?, 'Laskar Pelangi', 'Andrea Hirata', 'Novel', 2005, 2, 1)",
+        )
+        .bind(library_id)
+        .bind(format!("bk_{name}"))
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as i32;
+        let member = sqlx::query(
+            "INSERT INTO members (library_id, public_id, name, email) VALUES (?, ?, 'Ikal', ?)",
+        )
+        .bind(library_id)
+        .bind(format!("mb_{name}"))
+        .bind(format!("ikal@{name}.test"))
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as i32;
+        let loan = sqlx::query(
+            "INSERT INTO loans (library_id, public_id, book_id, member_id, due_at)
+             VALUES (?, ?, ?, ?, NOW() + INTERVAL 7 DAY)",
+        )
+        .bind(library_id)
+        .bind(format!("ln_{name}"))
+        .bind(book)
+        .bind(member)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as i32;
+        Seeded {
+            library_id,
+            book: BookId(book),
+            member: MemberId(member),
+            loan: LoanId(loan),
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::config::MIGRATOR")]
+    #[ignore = "butuh DATABASE_URL (MySQL)"]
+    async fn tenant_reads_only_its_own_rows(pool: MySqlPool) {
+        let a = seed(&pool, "a").await;
+        let b = seed(&pool, "b").await;
+
+        assert!(find_book(&pool, a.library_id, a.book).await.unwrap().is_some());
+        assert!(find_book(&pool, a.library_id, b.book).await.unwrap().is_none());
+        assert!(find_member(&pool, a.library_id, b.member).await.unwrap().is_none());
+        assert!(find_loan(&pool, a.library_id, b.loan).await.unwrap().is_none());
+
+        let books = get_books_by_ids(&pool, a.library_id, &[a.book, b.book]).await.unwrap();
+        assert_eq!(books.keys().copied().collect::<Vec<_>>(), vec![a.book]);
+        let members = get_members_by_ids(&pool, b.library_id, &[a.member]).await.unwrap();
+        assert!(members.is_empty());
+    }
+
+    #[sqlx::test(migrator = "crate::config::MIGRATOR")]
+    #[ignore = "butuh DATABASE_URL (MySQL)"]
+    async fn tenant_cannot_lock_or_delete_other_rows(pool: MySqlPool) {
+        let a = seed(&pool, "a").await;
+        let b = seed(&pool, "b").await;
+
+        let mut tx = pool.begin().await.unwrap();
+        assert!(lock_book(&mut tx, a.library_id, b.book).await.unwrap().is_none());
+        assert!(lock_member(&mut tx, a.library_id, b.member).await.unwrap().is_none());
+        assert!(lock_loan(&mut tx, a.library_id, b.loan).await.unwrap().is_none());
+        tx.rollback().await.unwrap();
+
+        assert!(!delete_member(&pool, a.library_id, b.member).await.unwrap());
+        assert!(find_member(&pool, b.library_id, b.member).await.unwrap().is_some());
+
+        // Buku B masih dipinjam, jadi hapus lewat pinjamannya dulu.
+        sqlx::query("DELETE FROM loans WHERE id = ?").bind(b.loan).execute(&pool).await.unwrap();
+        assert!(!delete_book(&pool, a.library_id, b.book).await.unwrap());
+        assert!(find_book(&pool, b.library_id, b.book).await.unwrap().is_some());
+        assert!(delete_book(&pool, b.library_id, b.book).await.unwrap());
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::book::Book;
+use crate::i18n::Message;
+use crate::ids::BookId;
+use crate::normalize;
+use crate::timestamps;
+
+/// Satu baris di tabel `reserve_lists`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ReserveList {
+    pub id: i32,
+    pub course_code: String,
+    pub name: String,
+    pub semester: String,
+    pub owner: String,
+    /// Periode pinjam maksimum (hari) untuk buku di daftar ini.
+    pub loan_days: i32,
+    pub renewal_limit: i32,
+    pub ends_on: NaiveDate,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+/// Respons `GET /reserve-lists/:id`: daftar beserta buku dan ketersediaannya.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReserveListDetail {
+    #[serde(flatten)]
+    pub list: ReserveList,
+    pub books: Vec<Book>,
+}
+
+/// Payload `POST /reserve-lists`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewReserveList {
+    pub course_code: String,
+    pub name: String,
+    pub semester: String,
+    pub owner: String,
+    pub loan_days: i32,
+    #[serde(default)]
+    pub renewal_limit: i32,
+    /// Tanggal akhir semester "YYYY-MM-DD" (atau RFC 3339).
+    #[serde(deserialize_with = "timestamps::date")]
+    pub ends_on: NaiveDate,
+}
+
+impl NewReserveList {
+    pub fn normalized(self) -> Result<Self, Message> {
+        if self.loan_days < 1 {
+            return Err(Message::new("validation.invalid_value")
+                .param("field", "loan_days")
+                .param("reason", "must be at least 1"));
+        }
+        if self.renewal_limit < 0 {
+            return Err(Message::new("validation.invalid_value")
+                .param("field", "renewal_limit")
+                .param("reason", "must not be negative"));
+        }
+        Ok(Self {
+            course_code: normalize::required("course_code", &self.course_code)?.to_uppercase(),
+            name: normalize::required("name", &self.name)?,
+            semester: normalize::required("semester", &self.semester)?,
+            owner: normalize::required("owner", &self.owner)?,
+            ..self
+        })
+    }
+}
+
+/// Payload `POST /reserve-lists/:id/books`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AddReserveBook {
+    pub book_id: BookId,
+}
+
+/// Kebijakan pinjam yang berlaku untuk satu buku karena ada di daftar reserve aktif.
+/// Kalau buku ada di beberapa daftar, yang paling ketat menang.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRow)]
+pub struct ReservePolicy {
+    pub loan_days: i32,
+    pub renewal_limit: i32,
+}
@@ -0,0 +1,133 @@
+use axum::http::Method;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// Izin yang bisa diberikan ke API key perpustakaan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    BooksRead,
+    BooksWrite,
+    MembersRead,
+    MembersWrite,
+    LoansRead,
+    LoansCreate,
+    LoansWrite,
+    /// Endpoint pemeliharaan per perpustakaan (/admin/* yang memakai key perpustakaan).
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 8] = [
+        Scope::BooksRead,
+        Scope::BooksWrite,
+        Scope::MembersRead,
+        Scope::MembersWrite,
+        Scope::LoansRead,
+        Scope::LoansCreate,
+        Scope::LoansWrite,
+        Scope::Admin,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::BooksRead => "books:read",
+            Self::BooksWrite => "books:write",
+            Self::MembersRead => "members:read",
+            Self::MembersWrite => "members:write",
+            Self::LoansRead => "loans:read",
+            Self::LoansCreate => "loans:create",
+            Self::LoansWrite => "loans:write",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.name() == name)
+    }
+
+    fn bit(self) -> u16 {
+        1 << (self as u16)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Himpunan scope milik satu key. Disimpan di kolom `api_keys.scopes`
+/// sebagai nama dipisah koma, dikirim ke client sebagai array nama.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Scopes(u16);
+
+impl Scopes {
+    pub fn all() -> Self {
+        Self((1 << Scope::ALL.len()) - 1)
+    }
+
+    pub fn contains(self, scope: Scope) -> bool {
+        self.0 & scope.bit() != 0
+    }
+
+    /// Parse daftar nama dari payload; error berisi nama yang tidak dikenal.
+    pub fn parse<S: AsRef<str>>(names: &[S]) -> Result<Self, String> {
+        let mut scopes = Self::default();
+        for name in names {
+            let name = name.as_ref().trim();
+            let scope = Scope::from_name(name).ok_or_else(|| {
+                let known: Vec<&str> = Scope::ALL.iter().map(|s| s.name()).collect();
+                format!("unknown scope '{name}', expected one of: {}", known.join(", "))
+            })?;
+            scopes.0 |= scope.bit();
+        }
+        Ok(scopes)
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Scope> {
+        Scope::ALL.into_iter().filter(move |scope| self.contains(*scope))
+    }
+
+    /// Scope request tanpa API key (jalur DEFAULT_LIBRARY_ID) dari ANONYMOUS_SCOPES.
+    /// Nama tak dikenal diabaikan, dan `admin` selalu dibuang: /admin/* wajib API key.
+    pub fn anonymous(names: &str) -> Self {
+        Self(Self::from(names.to_string()).0 & !Scope::Admin.bit())
+    }
+
+    /// Bentuk kolom DB: `books:read,loans:create`.
+    pub fn to_column(self) -> String {
+        self.iter().map(Scope::name).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Default ANONYMOUS_SCOPES: request tanpa key hanya boleh membaca.
+pub const ANONYMOUS_DEFAULT: &str = "books:read,members:read,loans:read";
+
+/// Dari kolom DB; nama yang tidak dikenal (mis. scope yang sudah dihapus) diabaikan.
+impl From<String> for Scopes {
+    fn from(column: String) -> Self {
+        let bits = column
+            .split(',')
+            .filter_map(|name| Scope::from_name(name.trim()))
+            .fold(0, |bits, scope| bits | scope.bit());
+        Self(bits)
+    }
+}
+
+impl Serialize for Scopes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(Scope::name))
+    }
+}
+
+/// Scope yang dibutuhkan route ini menurut tabel `ROUTES` (method + pola path axum);
+/// `/admin/*` selalu butuh `admin`. None = route tanpa scope, request `Tenant`-nya ditolak.
+pub fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    if path.starts_with("/admin/") {
+        return Some(Scope::Admin);
+    }
+    crate::ROUTES
+        .iter()
+        .find(|(m, p, _, _)| m == method && *p == path)
+        .and_then(|(_, _, scope, _)| *scope)
+}
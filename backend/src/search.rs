@@ -1,41 +1,509 @@
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
 use crate::book::Book;
+use crate::collation;
+use crate::member::Member;
+use crate::normalize;
+use crate::pagination::{decode_cursor, encode_cursor};
 
 /// Mode pencarian yang didukung.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SearchMode {
     Title,
     Author,
     Category,
+    /// Pola regex (case-insensitive) terhadap field pilihan, default judul.
+    Regex,
 }
 
+/// Alias lokal untuk nama mode, mis. frontend yang mengirim `?mode=judul`.
+const MODE_ALIASES: &[(&str, SearchMode)] = &[
+    ("judul", SearchMode::Title),
+    ("pengarang", SearchMode::Author),
+    ("penulis", SearchMode::Author),
+    ("kategori", SearchMode::Category),
+];
+
 impl SearchMode {
-    /// Konversi dari string query (?mode=title/author/category) ke enum.
+    /// Konversi dari string query (?mode=title/author/category/regex) ke enum.
+    /// Nama mode berbahasa Indonesia (lihat `MODE_ALIASES`) juga diterima.
     pub fn from_str(s: &str) -> Option<Self> {
-        match s {
+        let s = s.trim().to_lowercase();
+        match s.as_str() {
             "title" => Some(Self::Title),
             "author" => Some(Self::Author),
             "category" => Some(Self::Category),
-            _ => None,
+            "regex" => Some(Self::Regex),
+            other => MODE_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == other)
+                .map(|(_, mode)| *mode),
+        }
+    }
+
+    /// Field buku yang dicocokkan oleh mode ini (Regex memakai judul).
+    fn field(self, book: &Book) -> &str {
+        match self {
+            Self::Title | Self::Regex => &book.title,
+            Self::Author => &book.author,
+            Self::Category => &book.category,
+        }
+    }
+}
+
+/// Panjang maksimum query pencarian (karakter).
+pub const MAX_QUERY_LEN: usize = 200;
+
+/// Batas ukuran regex hasil kompilasi dan kedalaman nesting, supaya pola
+/// raksasa tidak menghabiskan memori/CPU saat kompilasi.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+const REGEX_NEST_LIMIT: u32 = 32;
+
+/// Error dari modul search; dipetakan ke 400 oleh handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchError {
+    QueryTooLong { len: usize, max: usize },
+    InvalidPattern(String),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::QueryTooLong { len, max } => {
+                write!(f, "query is {len} characters long, at most {max} allowed")
+            }
+            Self::InvalidPattern(reason) => write!(f, "invalid regex: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+/// Query yang sudah divalidasi dan dinormalisasi, disiapkan sekali di handler
+/// lalu dipakai bersama oleh semua chunk paralel.
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// Substring case-insensitive (query sudah lewat `normalize::search_key`).
+    Substring(String),
+    /// Regex yang sudah dikompilasi sekali.
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn new(query: &str, mode: SearchMode) -> Result<Self, SearchError> {
+        let len = query.chars().count();
+        if len > MAX_QUERY_LEN {
+            return Err(SearchError::QueryTooLong { len, max: MAX_QUERY_LEN });
+        }
+        match mode {
+            SearchMode::Regex => RegexBuilder::new(query)
+                .case_insensitive(true)
+                .size_limit(REGEX_SIZE_LIMIT)
+                .dfa_size_limit(REGEX_SIZE_LIMIT)
+                .nest_limit(REGEX_NEST_LIMIT)
+                .build()
+                .map(Self::Regex)
+                .map_err(|e| SearchError::InvalidPattern(e.to_string())),
+            _ => Ok(Self::Substring(normalize::search_key(query))),
+        }
+    }
+
+    /// Skor relevansi satu field, `None` = tidak cocok.
+    fn score(&self, field: &str) -> Option<u32> {
+        match self {
+            Self::Substring(q) => relevance(&normalize::search_key(field), q),
+            Self::Regex(re) => regex_relevance(re, field),
+        }
+    }
+}
+
+/// Skor regex terhadap teks asli field: 3 = seluruh field, 2 = di awal, 1 = di tengah.
+fn regex_relevance(re: &Regex, field: &str) -> Option<u32> {
+    let m = re.find(field)?;
+    if m.start() == 0 && m.end() == field.len() {
+        Some(3)
+    } else if m.start() == 0 {
+        Some(2)
+    } else {
+        Some(1)
+    }
+}
+
+/// Skor relevansi satu field (sudah lowercase) terhadap query (sudah lowercase):
+/// 3 = sama persis, 2 = awalan, 1 = memuat, `None` = tidak cocok.
+fn relevance(field: &str, q: &str) -> Option<u32> {
+    if field == q {
+        Some(3)
+    } else if field.starts_with(q) {
+        Some(2)
+    } else if field.contains(q) {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// Snapshot katalog untuk satu /search. Field yang dicocokkan sudah lewat
+/// `normalize::search_key` sekali di sini, jadi chunk paralel tidak mengalokasi per baris.
+#[derive(Debug, Clone)]
+pub struct SearchSnapshot {
+    books: Vec<Book>,
+    field: SearchMode,
+    /// Kunci per buku (indeks sama dengan `books`); kosong untuk regex, yang memakai teks asli.
+    keys: Vec<String>,
+}
+
+impl SearchSnapshot {
+    pub fn new(books: Vec<Book>, field: SearchMode, matcher: &Matcher) -> Self {
+        let keys = match matcher {
+            Matcher::Substring(_) => {
+                books.iter().map(|book| normalize::search_key(field.field(book))).collect()
+            }
+            Matcher::Regex(_) => Vec::new(),
+        };
+        Self { books, field, keys }
+    }
+
+    pub fn into_books(self) -> Vec<Book> {
+        self.books
+    }
+
+    fn score(&self, i: usize, matcher: &Matcher) -> Option<u32> {
+        match matcher {
+            Matcher::Substring(q) => relevance(&self.keys[i], q),
+            Matcher::Regex(re) => regex_relevance(re, self.field.field(&self.books[i])),
         }
     }
 }
 
 /// Pure function: tidak mengubah input, tidak mengakses IO.
-/// Hanya mem-filter slice books berdasarkan mode & query.
-pub fn search_books(books: &[Book], mode: SearchMode, query: &str) -> Vec<Book> {
-    let q = query.to_lowercase();
-
-    books
-        .iter()
-        .filter(|book| {
-            let field = match mode {
-                SearchMode::Title => &book.title,
-                SearchMode::Author => &book.author,
-                SearchMode::Category => &book.category,
-            };
-
-            field.to_lowercase().contains(&q)
+/// Mengembalikan `(indeks, skor)` buku di `range` yang cocok dengan query (indeks ke snapshot,
+/// urut naik), supaya pemanggil tidak perlu meng-clone `Book` sampai saat serialisasi.
+pub fn scored_matches(
+    snapshot: &SearchSnapshot,
+    range: Range<usize>,
+    matcher: &Matcher,
+) -> Vec<(usize, u32)> {
+    range.filter_map(|i| snapshot.score(i, matcher).map(|score| (i, score))).collect()
+}
+
+/// Pure function untuk /search/global: item yang salah satu field-nya cocok, diurutkan skor
+/// terbaik turun (urutan asal sebagai pemutus seri), paling banyak `limit` item.
+pub fn top_matches<T, const N: usize>(
+    items: Vec<T>,
+    fields: impl Fn(&T) -> [&str; N],
+    matcher: &Matcher,
+    limit: usize,
+) -> Vec<T> {
+    let mut scored: Vec<(u32, usize, T)> = items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, item)| {
+            let best = fields(&item).into_iter().filter_map(|f| matcher.score(f)).max()?;
+            Some((best, i, item))
         })
-        .cloned()
-        .collect()
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().take(limit).map(|(_, _, item)| item).collect()
+}
+
+/// Pengelompokan hasil /search (`?dedupe=`). Katalog ini belum punya relasi edisi, jadi satu
+/// karya dikenali dari judul + pengarang yang sama setelah `normalize::search_key`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dedupe {
+    Work,
+}
+
+impl Dedupe {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "work" => Some(Self::Work),
+            _ => None,
+        }
+    }
+
+    fn key(self, book: &Book) -> (String, String) {
+        match self {
+            Self::Work => (normalize::search_key(&book.title), normalize::search_key(&book.author)),
+        }
+    }
+}
+
+/// Pure function: sisakan satu wakil (yang paling atas di `ranked`) per kelompok dan kembalikan
+/// jumlah hasil di tiap kelompok, diindeks dengan posisi buku wakilnya.
+pub fn collapse<K>(
+    ranked: &mut Vec<(K, usize)>,
+    books: &[Book],
+    dedupe: Dedupe,
+) -> HashMap<usize, usize> {
+    let mut representative: HashMap<(String, String), usize> = HashMap::new();
+    let mut sizes: HashMap<usize, usize> = HashMap::new();
+    ranked.retain(|(_, i)| {
+        let rep = *representative.entry(dedupe.key(&books[*i])).or_insert(*i);
+        *sizes.entry(rep).or_insert(0) += 1;
+        rep == *i
+    });
+    sizes
+}
+
+/// Satu hasil /search. `copies_in_group` hanya ada dengan `?dedupe=`: jumlah hasil yang
+/// digabung ke buku ini (termasuk dirinya).
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub book: Book,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copies_in_group: Option<usize>,
+}
+
+/// Hasil /search/global, dikelompokkan per jenis.
+#[derive(Debug, Clone, Serialize)]
+pub struct GlobalSearch {
+    pub books: Vec<Book>,
+    pub members: Vec<Member>,
+}
+
+/// Urutan hasil /search (`?sort=`, default dari SEARCH_DEFAULT_SORT).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSort {
+    /// Skor relevansi turun.
+    #[default]
+    Relevance,
+    /// Judul A-Z menurut `collation::sort_key` (aksen dan kata sandang di awal diabaikan).
+    Title,
+    /// Pengarang A-Z, kolasi sama dengan judul.
+    Author,
+    /// Tahun terbit, terbaru dulu; tahun yang tidak diketahui paling akhir.
+    Year,
+}
+
+impl SearchSort {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "relevance" => Some(Self::Relevance),
+            "title" => Some(Self::Title),
+            "author" => Some(Self::Author),
+            "year" => Some(Self::Year),
+            _ => None,
+        }
+    }
+
+    /// Kunci urut satu hasil untuk sort ini; `articles` = COLLATION_IGNORED_ARTICLES.
+    pub fn key(self, book: &Book, score: u32, articles: &[String]) -> SortKey {
+        match self {
+            Self::Relevance => SortKey::Score(score),
+            Self::Title => SortKey::Title(collation::sort_key(&book.title, articles)),
+            Self::Author => SortKey::Author(collation::sort_key(&book.author, articles)),
+            Self::Year => SortKey::Year(book.year),
+        }
+    }
+}
+
+/// Kunci urut hasil search; id buku selalu jadi pemutus seri (naik).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortKey {
+    Score(u32),
+    Title(String),
+    Author(String),
+    Year(Option<i32>),
+}
+
+impl SortKey {
+    /// `Less` kalau `self` tampil lebih dulu dari `other`.
+    pub fn order(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Score(a), Self::Score(b)) => b.cmp(a),
+            (Self::Title(a), Self::Title(b)) | (Self::Author(a), Self::Author(b)) => a.cmp(b),
+            // None < Some, jadi urutan turun menaruh tahun kosong di akhir.
+            (Self::Year(a), Self::Year(b)) => b.cmp(a),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// Posisi terakhir yang sudah dilihat client: kunci urut dan id item terakhir di halaman.
+/// Dikirim ke client sebagai string opaque (base64url dari `"score:id"`,
+/// `"year:<tahun>:id"` dengan tahun kosong kalau tidak diketahui, `"title:id:<judul>"`, atau
+/// `"author:id:<pengarang>"`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchCursor {
+    pub key: SortKey,
+    pub id: i32,
+}
+
+impl SearchCursor {
+    pub fn encode(&self) -> String {
+        let text = match &self.key {
+            SortKey::Score(score) => format!("{score}:{}", self.id),
+            SortKey::Year(year) => {
+                let year = year.map(|y| y.to_string()).unwrap_or_default();
+                format!("year:{year}:{}", self.id)
+            }
+            SortKey::Title(title) => format!("title:{}:{title}", self.id),
+            SortKey::Author(author) => format!("author:{}:{author}", self.id),
+        };
+        encode_cursor(&text)
+    }
+
+    /// `None` kalau cursor rusak atau dibuat untuk sort lain.
+    pub fn decode(raw: &str, sort: SearchSort) -> Option<Self> {
+        let text = decode_cursor(raw)?;
+        let (key, id) = match sort {
+            SearchSort::Relevance => {
+                let (score, id) = text.split_once(':')?;
+                (SortKey::Score(score.parse().ok()?), id)
+            }
+            SearchSort::Year => {
+                let (year, id) = text.strip_prefix("year:")?.split_once(':')?;
+                let year = match year {
+                    "" => None,
+                    year => Some(year.parse().ok()?),
+                };
+                (SortKey::Year(year), id)
+            }
+            SearchSort::Title => {
+                let (id, title) = text.strip_prefix("title:")?.split_once(':')?;
+                (SortKey::Title(title.to_string()), id)
+            }
+            SearchSort::Author => {
+                let (id, author) = text.strip_prefix("author:")?.split_once(':')?;
+                (SortKey::Author(author.to_string()), id)
+            }
+        };
+        Some(Self { key, id: id.parse().ok()? })
+    }
+
+    /// True kalau item `(key, id)` berada setelah cursor.
+    pub fn precedes(&self, key: &SortKey, id: i32) -> bool {
+        self.key.order(key).then(self.id.cmp(&id)) == Ordering::Less
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regex(pattern: &str) -> Result<Matcher, SearchError> {
+        Matcher::new(pattern, SearchMode::Regex)
+    }
+
+    #[test]
+    fn regex_mode_compiles_valid_patterns_case_insensitively() {
+        let m = regex(r"^the\s+\w+$").unwrap();
+        assert!(matches!(m, Matcher::Regex(_)));
+        assert_eq!(m.score("The Hobbit"), Some(3));
+        assert_eq!(m.score("the hobbit returns"), None);
+
+        let m = regex("hob+it").unwrap();
+        assert_eq!(m.score("Hobbit"), Some(3));
+        assert_eq!(m.score("HOBBIT, The"), Some(2));
+        assert_eq!(m.score("The Hobbit"), Some(1));
+        assert_eq!(m.score("Dune"), None);
+    }
+
+    #[test]
+    fn regex_mode_rejects_invalid_patterns() {
+        for pattern in ["(", "[a-", "a{2,1}", r"\p{NotAClass}", "*"] {
+            assert!(
+                matches!(regex(pattern), Err(SearchError::InvalidPattern(_))),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn regex_mode_enforces_size_limit() {
+        // Pendek sebagai teks, tapi mengembang jadi program regex raksasa.
+        let pattern = r"(\w{50}){50}";
+        assert!(pattern.chars().count() <= MAX_QUERY_LEN);
+        assert!(matches!(regex(pattern), Err(SearchError::InvalidPattern(_))));
+    }
+
+    #[test]
+    fn regex_mode_enforces_nest_limit() {
+        let depth = REGEX_NEST_LIMIT as usize + 8;
+        let nested = format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(nested.chars().count() <= MAX_QUERY_LEN);
+        assert!(matches!(regex(&nested), Err(SearchError::InvalidPattern(_))));
+
+        let shallow = format!("{}a{}", "(".repeat(4), ")".repeat(4));
+        assert!(regex(&shallow).is_ok());
+    }
+
+    #[test]
+    fn regex_mode_checks_length_before_compiling() {
+        let pattern = "(".repeat(MAX_QUERY_LEN + 1);
+        assert_eq!(
+            regex(&pattern).unwrap_err(),
+            SearchError::QueryTooLong { len: MAX_QUERY_LEN + 1, max: MAX_QUERY_LEN }
+        );
+    }
+
+    fn book(id: i32, title: &str, author: &str) -> Book {
+        Book {
+            id: crate::ids::BookId(id),
+            public_id: format!("B{id}"),
+            title: title.into(),
+            author: author.into(),
+            category: "Novel".into(),
+            year: None,
+            total_copies: 1,
+            available_copies: 1,
+            version: 1,
+            updated_at: chrono::NaiveDateTime::MIN,
+            location: None,
+        }
+    }
+
+    fn books() -> Vec<Book> {
+        vec![
+            book(1, "Bumi Manusia", "Pramoedya"),
+            book(2, "  BUMI   manusia ", "Pramoedya"),
+            book(3, "Anak Semua Bangsa", "Pramoedya"),
+            book(4, "Di Bumi", "Tere Liye"),
+        ]
+    }
+
+    #[test]
+    fn snapshot_keys_match_scoring_per_field() {
+        for (mode, query) in [(SearchMode::Title, " bumi  manusia"), (SearchMode::Author, "PRAM")] {
+            let matcher = Matcher::new(query, mode).unwrap();
+            let books = books();
+            let expected: Vec<(usize, u32)> = books
+                .iter()
+                .enumerate()
+                .filter_map(|(i, b)| matcher.score(mode.field(b)).map(|score| (i, score)))
+                .collect();
+            let snapshot = SearchSnapshot::new(books, mode, &matcher);
+            assert_eq!(scored_matches(&snapshot, 0..snapshot.books.len(), &matcher), expected);
+        }
+    }
+
+    #[test]
+    fn snapshot_scores_ranges_with_global_indices() {
+        let matcher = Matcher::new("bumi", SearchMode::Title).unwrap();
+        let snapshot = SearchSnapshot::new(books(), SearchMode::Title, &matcher);
+        assert_eq!(scored_matches(&snapshot, 0..2, &matcher), vec![(0, 2), (1, 2)]);
+        assert_eq!(scored_matches(&snapshot, 2..4, &matcher), vec![(3, 1)]);
+    }
+
+    #[test]
+    fn regex_snapshot_matches_original_text() {
+        let matcher = regex("^Bumi").unwrap();
+        let snapshot = SearchSnapshot::new(books(), SearchMode::Title, &matcher);
+        assert!(snapshot.keys.is_empty());
+        assert_eq!(scored_matches(&snapshot, 0..snapshot.books.len(), &matcher), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn other_modes_do_not_compile_regex() {
+        let m = Matcher::new("  Rust  (In Action ", SearchMode::Title).unwrap();
+        assert!(matches!(m, Matcher::Substring(ref q) if q == "rust (in action"));
+    }
 }
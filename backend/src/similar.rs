@@ -0,0 +1,252 @@
+// "Buku serupa" berdasarkan teks: judul + kategori tiap buku diubah jadi vektor TF-IDF
+// (kata utuh dan trigram karakter per kata), disimpan di memori per perpustakaan, lalu
+// tetangga terdekat dicari dengan cosine similarity. Berguna untuk buku yang belum pernah
+// dipinjam sehingga belum punya data co-occurrence. Murni Rust, tanpa dependensi ML.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::book::Book;
+use crate::normalize;
+
+/// Frekuensi term satu dokumen.
+type Terms = HashMap<String, u32>;
+
+/// Kata yang lebih pendek dari ini tidak dijadikan term kata (trigramnya tetap dipakai).
+const MIN_WORD_LEN: usize = 2;
+
+/// Term dari judul dan kategori: `w:<kata>` untuk kata utuh, `c:<trigram>` untuk trigram
+/// karakter kata yang diberi batas `^`/`$`, dan `k:<kategori>` untuk kategori utuh.
+pub fn terms(title: &str, category: &str) -> Terms {
+    let mut terms = Terms::new();
+    let mut add = |term: String| *terms.entry(term).or_insert(0) += 1;
+
+    let text = normalize::search_key(&format!("{title} {category}"));
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        if word.chars().count() >= MIN_WORD_LEN {
+            add(format!("w:{word}"));
+        }
+        let chars: Vec<char> = format!("^{word}$").chars().collect();
+        for gram in chars.windows(3) {
+            add(format!("c:{}", gram.iter().collect::<String>()));
+        }
+    }
+    let category = normalize::search_key(category);
+    if !category.is_empty() {
+        add(format!("k:{category}"));
+    }
+    terms
+}
+
+/// Indeks satu perpustakaan: term tiap buku dan document frequency tiap term.
+/// Bobot TF-IDF dihitung saat query, jadi tambah/ubah/hapus satu buku cukup memperbarui
+/// `df` untuk term buku itu saja.
+#[derive(Debug, Default)]
+struct Catalog {
+    docs: HashMap<i32, Terms>,
+    df: HashMap<String, u32>,
+}
+
+impl Catalog {
+    fn insert(&mut self, book_id: i32, terms: Terms) {
+        self.remove(book_id);
+        for term in terms.keys() {
+            *self.df.entry(term.clone()).or_insert(0) += 1;
+        }
+        self.docs.insert(book_id, terms);
+    }
+
+    fn remove(&mut self, book_id: i32) {
+        let Some(old) = self.docs.remove(&book_id) else {
+            return;
+        };
+        for term in old.keys() {
+            if let Some(n) = self.df.get_mut(term) {
+                *n -= 1;
+                if *n == 0 {
+                    self.df.remove(term);
+                }
+            }
+        }
+    }
+
+    /// Vektor TF-IDF ter-normalisasi (panjang 1) satu dokumen.
+    fn weights<'t>(&self, terms: &'t Terms) -> HashMap<&'t str, f64> {
+        let n = self.docs.len() as f64;
+        let mut weights: HashMap<&str, f64> = terms
+            .iter()
+            .map(|(term, tf)| {
+                let df = self.df.get(term).copied().unwrap_or(1) as f64;
+                (term.as_str(), *tf as f64 * (1.0 + n / df).ln())
+            })
+            .collect();
+        let norm = weights.values().map(|w| w * w).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            weights.values_mut().for_each(|w| *w /= norm);
+        }
+        weights
+    }
+
+    fn similar(&self, book_id: i32, limit: usize) -> Option<Vec<(i32, f64)>> {
+        let target = self.weights(self.docs.get(&book_id)?);
+        let mut scored: Vec<(i32, f64)> = self
+            .docs
+            .iter()
+            .filter(|(id, _)| **id != book_id)
+            .filter_map(|(id, terms)| {
+                let score = cosine(&target, &self.weights(terms));
+                (score > 0.0).then_some((*id, score))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scored.truncate(limit);
+        Some(scored)
+    }
+}
+
+/// Cosine dua vektor yang sudah ter-normalisasi = dot product.
+fn cosine(a: &HashMap<&str, f64>, b: &HashMap<&str, f64>) -> f64 {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    small
+        .iter()
+        .filter_map(|(term, w)| large.get(term).map(|v| w * v))
+        .sum()
+}
+
+/// Indeks kemiripan semua perpustakaan, dibagi lewat `AppState`.
+#[derive(Debug, Default)]
+pub struct SimilarityIndex {
+    libraries: RwLock<HashMap<i32, Catalog>>,
+}
+
+impl SimilarityIndex {
+    /// Ganti seluruh isi indeks dengan `books` (pasangan library_id, buku). Dipakai job latar.
+    pub fn rebuild(&self, books: Vec<(i32, Book)>) {
+        let mut libraries: HashMap<i32, Catalog> = HashMap::new();
+        for (library_id, book) in books {
+            libraries
+                .entry(library_id)
+                .or_default()
+                .insert(book.id.0, terms(&book.title, &book.category));
+        }
+        *self.libraries.write().unwrap_or_else(|e| e.into_inner()) = libraries;
+    }
+
+    /// Tambah atau perbarui satu buku setelah create/update.
+    pub fn upsert(&self, library_id: i32, book: &Book) {
+        self.libraries
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(library_id)
+            .or_default()
+            .insert(book.id.0, terms(&book.title, &book.category));
+    }
+
+    pub fn remove(&self, library_id: i32, book_id: i32) {
+        if let Some(catalog) =
+            self.libraries.write().unwrap_or_else(|e| e.into_inner()).get_mut(&library_id)
+        {
+            catalog.remove(book_id);
+        }
+    }
+
+    /// `(book_id, skor)` paling mirip, skor turun. None kalau buku belum ada di indeks.
+    pub fn similar(&self, library_id: i32, book_id: i32, limit: usize) -> Option<Vec<(i32, f64)>> {
+        self.libraries
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&library_id)?
+            .similar(book_id, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Katalog kecil: dua buku Rust yang hampir sama, satu buku Rust lain, satu buku masak.
+    fn fixture() -> Catalog {
+        let mut catalog = Catalog::default();
+        for (id, title, category) in [
+            (1, "Rust Programming", "Technology"),
+            (2, "Programming in Rust", "Technology"),
+            (3, "Bread Baking Basics", "Cooking"),
+            (4, "Advanced Rust", "Technology"),
+        ] {
+            catalog.insert(id, terms(title, category));
+        }
+        catalog
+    }
+
+    #[test]
+    fn terms_include_words_trigrams_and_category() {
+        let t = terms("The Go Way", "Tech");
+        assert_eq!(t.get("w:go"), Some(&1));
+        assert_eq!(t.get("w:the"), Some(&1));
+        assert_eq!(t.get("c:^go"), Some(&1));
+        assert_eq!(t.get("c:go$"), Some(&1));
+        assert_eq!(t.get("k:tech"), Some(&1));
+        // Kategori juga ikut sebagai kata.
+        assert_eq!(t.get("w:tech"), Some(&1));
+    }
+
+    #[test]
+    fn terms_are_case_insensitive_and_count_repeats() {
+        let t = terms("Data DATA data", "");
+        assert_eq!(t.get("w:data"), Some(&3));
+        assert!(!t.keys().any(|k| k.starts_with("k:")));
+    }
+
+    #[test]
+    fn terms_skip_short_words_but_keep_their_trigrams() {
+        let t = terms("A Tale", "");
+        assert!(!t.contains_key("w:a"));
+        assert_eq!(t.get("c:^a$"), Some(&1));
+    }
+
+    #[test]
+    fn cosine_of_normalized_vectors() {
+        let catalog = fixture();
+        let rust = terms("Rust Programming", "Technology");
+        let a = catalog.weights(&rust);
+        assert!((cosine(&a, &a) - 1.0).abs() < 1e-9);
+
+        let x: HashMap<&str, f64> = [("w:x", 1.0)].into();
+        let y: HashMap<&str, f64> = [("w:y", 1.0)].into();
+        assert_eq!(cosine(&x, &y), 0.0);
+        assert_eq!(cosine(&x, &HashMap::new()), 0.0);
+    }
+
+    #[test]
+    fn similar_ranks_closest_books_first() {
+        let ranked = fixture().similar(1, 10).unwrap();
+        let ids: Vec<i32> = ranked.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids[..2], [2, 4]);
+        assert!(!ids.contains(&1));
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+        if let Some(bread) = ranked.iter().find(|(id, _)| *id == 3) {
+            assert!(bread.1 < ranked[1].1);
+        }
+    }
+
+    #[test]
+    fn similar_respects_limit_and_unknown_book() {
+        let catalog = fixture();
+        assert_eq!(catalog.similar(1, 1).unwrap().len(), 1);
+        assert_eq!(catalog.similar(99, 5), None);
+    }
+
+    #[test]
+    fn remove_and_reinsert_keep_document_frequency_consistent() {
+        let mut catalog = fixture();
+        let df_before = catalog.df.clone();
+        catalog.remove(2);
+        assert!(catalog.similar(1, 10).unwrap().iter().all(|(id, _)| *id != 2));
+        catalog.insert(2, terms("Programming in Rust", "Technology"));
+        assert_eq!(catalog.df, df_before);
+        // Insert ulang dengan kategori baru (rename kategori) mengganti term lama.
+        catalog.insert(2, terms("Programming in Rust", "Cooking"));
+        assert_eq!(catalog.df.get("k:cooking"), Some(&2));
+        assert_eq!(catalog.df.get("k:technology"), Some(&2));
+    }
+}
@@ -0,0 +1,116 @@
+// Buku besar stok (`stock_movements`): setiap perubahan `available_copies` punya satu baris
+// append-only dengan alasan dan referensinya, ditulis di transaksi yang sama dengan UPDATE
+// counter-nya. Jumlah `delta` per buku harus sama dengan `available_copies`
+// (dicek /admin/integrity).
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{Executor, FromRow, MySql};
+
+use crate::ids::BookId;
+
+/// Alasan satu pergerakan stok, disimpan di kolom `stock_movements.reason`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StockReason {
+    /// Eksemplar keluar karena dipinjam.
+    Loan,
+    /// Eksemplar kembali (termasuk pinjaman yang ditutup paksa).
+    Return,
+    /// Eksemplar baru: buku baru, donasi, atau pembelian.
+    Restock,
+    /// Pinjaman ditutup sebagai hilang; delta 0 karena eksemplarnya sudah keluar saat dipinjam,
+    /// dicatat supaya jejaknya lengkap.
+    Lost,
+    /// Koreksi oleh recount / perbaikan integritas.
+    Repair,
+    /// Perubahan manual total eksemplar, saldo awal, atau restore.
+    Adjustment,
+}
+
+impl StockReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Loan => "loan",
+            Self::Return => "return",
+            Self::Restock => "restock",
+            Self::Lost => "lost",
+            Self::Repair => "repair",
+            Self::Adjustment => "adjustment",
+        }
+    }
+}
+
+/// Satu pergerakan sebelum ditulis.
+#[derive(Debug, Clone, Copy)]
+pub struct Movement {
+    pub library_id: i32,
+    pub book_id: BookId,
+    pub delta: i32,
+    pub reason: StockReason,
+    pub reference_id: Option<i32>,
+}
+
+impl Movement {
+    /// Tulis pergerakan; wajib di transaksi yang sama dengan UPDATE `available_copies`.
+    pub async fn write<'e, E>(&self, executor: E, now: NaiveDateTime) -> Result<(), sqlx::Error>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        sqlx::query(
+            "INSERT INTO stock_movements (library_id, book_id, delta, reason, reference_id, created_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(self.library_id)
+        .bind(self.book_id)
+        .bind(self.delta)
+        .bind(self.reason.name())
+        .bind(self.reference_id)
+        .bind(now)
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Satu baris `stock_movements` untuk `GET /books/:id/stock-movements`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct StockMovement {
+    pub id: i32,
+    pub delta: i32,
+    pub reason: String,
+    pub reference_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Respons `GET /books/:id/stock-movements`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StockLedger {
+    pub book_id: BookId,
+    pub available_copies: i32,
+    /// Jumlah semua `delta`; sama dengan `available_copies` kalau buku besar konsisten.
+    pub balance: i64,
+    pub movements: Vec<StockMovement>,
+}
+
+/// Saldo awal `adjustment` untuk semua buku satu perpustakaan, dipakai setelah restore
+/// (buku besar lama ikut dihapus bersama bukunya).
+pub async fn open_balances<'e, E>(
+    executor: E,
+    library_id: i32,
+    now: NaiveDateTime,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        "INSERT INTO stock_movements (library_id, book_id, delta, reason, reference_id, created_at)
+         SELECT library_id, id, available_copies, ?, NULL, ?
+         FROM books WHERE library_id = ? AND available_copies <> 0",
+    )
+    .bind(StockReason::Adjustment.name())
+    .bind(now)
+    .bind(library_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,305 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use chrono::FixedOffset;
+use futures_util::{Stream, StreamExt};
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::Value;
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use tokio::sync::mpsc;
+
+use crate::json_case::{rename_keys, snake_to_camel, JsonCase};
+use crate::timestamps::convert_timestamps;
+
+/// Header untuk melacak satu request di log.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Berapa chunk yang boleh antre sebelum producer menunggu client (backpressure).
+const CHANNEL_CAPACITY: usize = 32;
+
+/// Bentuk output streaming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// Satu array JSON `[...]` yang ditulis bertahap.
+    JsonArray,
+    /// Satu objek JSON per baris (`application/x-ndjson`).
+    NdJson,
+}
+
+impl StreamFormat {
+    /// Pilih format dari header Accept dan flag `?stream=true`.
+    /// `None` artinya pakai respons JSON biasa (tidak streaming).
+    pub fn negotiate(headers: &HeaderMap, stream_flag: bool) -> Option<Self> {
+        let wants_ndjson = headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.contains("application/x-ndjson"))
+            .unwrap_or(false);
+
+        if wants_ndjson {
+            Some(Self::NdJson)
+        } else if stream_flag {
+            Some(Self::JsonArray)
+        } else {
+            None
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::JsonArray => "application/json",
+            Self::NdJson => "application/x-ndjson",
+        }
+    }
+}
+
+/// Konversi output per baris untuk body streaming. Diisi middleware (`timestamps`,
+/// `json_case`) di extension request; body streaming tidak bisa di-buffer lalu diubah
+/// seperti response JSON biasa, jadi konversinya dijalankan di sini saat tiap baris
+/// diserialisasi.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RowStyle {
+    pub case: JsonCase,
+    /// None = waktu tetap naive (TIMESTAMP_FORMAT=naive).
+    pub timestamp_offset: Option<FixedOffset>,
+}
+
+impl RowStyle {
+    fn is_plain(self) -> bool {
+        self == Self::default()
+    }
+
+    /// Waktu dulu, baru nama key (waktu dikenali dari akhiran `_at`).
+    pub fn apply(self, value: Value) -> Value {
+        let value = match self.timestamp_offset {
+            Some(offset) => convert_timestamps(value, offset),
+            None => value,
+        };
+        match self.case {
+            JsonCase::Snake => value,
+            JsonCase::Camel => rename_keys(value, snake_to_camel),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RowStyle {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<RowStyle>().copied().unwrap_or_default())
+    }
+}
+
+/// Penanda di extension response: body-nya streaming, jadi middleware yang mengubah JSON
+/// tidak boleh mem-buffer-nya (konversinya sudah dilakukan per baris lewat `RowStyle`).
+#[derive(Debug, Clone, Copy)]
+pub struct Streamed;
+
+/// Ambil request id dari header, atau buat yang baru.
+pub fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            let mut bytes = [0u8; 8];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            hex::encode(bytes)
+        })
+}
+
+/// Ujung penulis stream: baris diserialisasi satu per satu ke body respons.
+pub struct RowSink {
+    tx: mpsc::Sender<Result<Bytes, io::Error>>,
+    format: StreamFormat,
+    style: RowStyle,
+    request_id: String,
+    first: bool,
+}
+
+impl RowSink {
+    async fn send(&self, chunk: Vec<u8>) -> bool {
+        self.tx.send(Ok(Bytes::from(chunk))).await.is_ok()
+    }
+
+    fn write_row<T: Serialize>(&self, chunk: &mut Vec<u8>, row: &T) -> serde_json::Result<()> {
+        if self.style.is_plain() {
+            return serde_json::to_writer(chunk, row);
+        }
+        let value = self.style.apply(serde_json::to_value(row)?);
+        serde_json::to_writer(chunk, &value)
+    }
+
+    /// Tulis satu baris. Ok(false) kalau client sudah memutus koneksi; error serialisasi
+    /// dikembalikan tanpa mengirim apa pun, supaya body tidak berisi baris setengah jadi.
+    async fn push<T: Serialize>(&mut self, row: &T) -> serde_json::Result<bool> {
+        let mut chunk = Vec::new();
+        match self.format {
+            StreamFormat::JsonArray => {
+                chunk.push(if self.first { b'[' } else { b',' });
+                self.write_row(&mut chunk, row)?;
+            }
+            StreamFormat::NdJson => {
+                self.write_row(&mut chunk, row)?;
+                chunk.push(b'\n');
+            }
+        }
+        self.first = false;
+        Ok(self.send(chunk).await)
+    }
+
+    /// Putus stream dengan error: body berhenti tanpa penutup yang valid
+    /// (array tanpa `]`, NDJSON dengan baris error terakhir) lalu koneksi di-abort.
+    async fn fail(self, e: impl fmt::Display) {
+        eprintln!("Stream error (request_id={}): {e}", self.request_id);
+        if self.format == StreamFormat::NdJson {
+            let line = serde_json::json!({
+                "error": {
+                    "code": "stream_error",
+                    "message": "stream terminated",
+                    "request_id": self.request_id,
+                }
+            });
+            self.send(format!("{line}\n").into_bytes()).await;
+        }
+        self.tx
+            .send(Err(io::Error::other("stream terminated")))
+            .await
+            .ok();
+    }
+
+    /// Alirkan semua baris dari stream sqlx, lalu tutup body.
+    pub async fn drain<T, S>(mut self, rows: S)
+    where
+        T: Serialize,
+        S: Stream<Item = Result<T, sqlx::Error>>,
+    {
+        let mut rows = std::pin::pin!(rows);
+        while let Some(row) = rows.next().await {
+            match row {
+                Ok(row) => match self.push(&row).await {
+                    Ok(true) => {}
+                    Ok(false) => return,
+                    Err(e) => return self.fail(e).await,
+                },
+                Err(e) => return self.fail(e).await,
+            }
+        }
+
+        if self.format == StreamFormat::JsonArray {
+            let end: &[u8] = if self.first { b"[]" } else { b"]" };
+            self.send(end.to_vec()).await;
+        }
+    }
+}
+
+/// Jalankan `producer` di task terpisah dan kirim hasilnya sebagai body streaming.
+/// Memori tetap datar berapa pun jumlah barisnya karena channel-nya terbatas.
+pub fn spawn_rows<F, Fut>(
+    format: StreamFormat,
+    style: RowStyle,
+    request_id: String,
+    producer: F,
+) -> Response
+where
+    F: FnOnce(RowSink) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let sink = RowSink {
+        tx,
+        format,
+        style,
+        request_id: request_id.clone(),
+        first: true,
+    };
+    tokio::spawn(producer(sink));
+
+    let body = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+
+    let mut response = Body::from_stream(body).into_response();
+    response.extensions_mut().insert(Streamed);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn sink(format: StreamFormat) -> (RowSink, mpsc::Receiver<Result<Bytes, io::Error>>) {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let sink = RowSink {
+            tx,
+            format,
+            style: RowStyle::default(),
+            request_id: "req-1".to_string(),
+            first: true,
+        };
+        (sink, rx)
+    }
+
+    /// Map dengan key tuple tidak bisa jadi JSON, jadi serialisasinya gagal.
+    type Row = HashMap<(i32, i32), i32>;
+
+    fn rows() -> Vec<Result<Row, sqlx::Error>> {
+        vec![Ok(HashMap::new()), Ok(HashMap::from([((1, 2), 3)])), Ok(HashMap::new())]
+    }
+
+    async fn collect(
+        mut rx: mpsc::Receiver<Result<Bytes, io::Error>>,
+    ) -> (String, Option<io::Error>) {
+        let mut body = String::new();
+        while let Some(chunk) = rx.recv().await {
+            match chunk {
+                Ok(bytes) => body.push_str(std::str::from_utf8(&bytes).unwrap()),
+                Err(e) => return (body, Some(e)),
+            }
+        }
+        (body, None)
+    }
+
+    #[tokio::test]
+    async fn serialization_error_terminates_ndjson_stream() {
+        let (sink, rx) = sink(StreamFormat::NdJson);
+        sink.drain(futures_util::stream::iter(rows())).await;
+
+        let (body, err) = collect(rx).await;
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines.len(), 2, "{body}");
+        assert_eq!(lines[0], "{}");
+        assert!(lines[1].contains("stream_error") && lines[1].contains("req-1"));
+        assert!(err.is_some());
+    }
+
+    #[tokio::test]
+    async fn serialization_error_leaves_json_array_unclosed() {
+        let (sink, rx) = sink(StreamFormat::JsonArray);
+        sink.drain(futures_util::stream::iter(rows())).await;
+
+        let (body, err) = collect(rx).await;
+        assert_eq!(body, "[{}");
+        assert!(err.is_some());
+    }
+}
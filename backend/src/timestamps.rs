@@ -0,0 +1,257 @@
+// Serialisasi waktu: semua kolom DATETIME disimpan UTC tanpa zona (`NaiveDateTime`). Secara
+// default response tetap memakai bentuk lama `2025-12-01T00:00:00` supaya client lama tidak
+// berubah; lewat header `X-Timestamp-Format` (atau env TIMESTAMP_FORMAT) field `*_at` dikirim
+// sebagai RFC 3339 dengan offset eksplisit, dalam UTC (`Z`) atau zona perpustakaan.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header::CONTENT_LENGTH, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::error::ApiError;
+use crate::json_body::is_json;
+use crate::stream::{RowStyle, Streamed};
+use crate::AppState;
+
+/// Header request untuk memilih format waktu di response (`naive`, `utc`, atau `local`).
+pub const TIMESTAMP_FORMAT_HEADER: &str = "x-timestamp-format";
+
+/// Format field waktu di body response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampFormat {
+    /// Bentuk lama tanpa zona (implisit UTC).
+    #[default]
+    Naive,
+    /// RFC 3339 dalam UTC, mis. `2025-12-01T00:00:00Z`.
+    Utc,
+    /// RFC 3339 dengan offset perpustakaan (LIBRARY_UTC_OFFSET), mis. `...T07:00:00+07:00`.
+    Local,
+}
+
+impl TimestampFormat {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "naive" => Some(Self::Naive),
+            "utc" => Some(Self::Utc),
+            "local" => Some(Self::Local),
+            _ => None,
+        }
+    }
+}
+
+/// Offset zona waktu perpustakaan, ditulis `+07:00` / `-05:30` (atau `Z`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtcOffset(pub FixedOffset);
+
+impl Default for UtcOffset {
+    fn default() -> Self {
+        Self(FixedOffset::east_opt(0).expect("zero offset"))
+    }
+}
+
+impl UtcOffset {
+    pub fn from_str(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("z") || s.eq_ignore_ascii_case("utc") {
+            return Some(Self::default());
+        }
+        let (sign, rest) = match s.as_bytes().first()? {
+            b'+' => (1, &s[1..]),
+            b'-' => (-1, &s[1..]),
+            _ => return None,
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let hours: i32 = hours.parse().ok()?;
+        let minutes: i32 = minutes.parse().ok()?;
+        if hours > 14 || minutes > 59 {
+            return None;
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(Self)
+    }
+}
+
+impl Serialize for UtcOffset {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+/// Tanggal dari input client: `YYYY-MM-DD`, atau RFC 3339 yang diambil tanggalnya di
+/// offset-nya sendiri (`2025-12-01T23:00:00+07:00` = 1 Desember).
+pub fn parse_date(raw: &str) -> Option<NaiveDate> {
+    let raw = raw.trim();
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()
+        .or_else(|| DateTime::parse_from_rfc3339(raw).ok().map(|t| t.date_naive()))
+}
+
+/// `deserialize_with` untuk field tanggal di payload/query string (lihat `parse_date`).
+pub fn date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    parse_date(&raw).ok_or_else(|| {
+        de::Error::custom(format!("'{raw}' is not a YYYY-MM-DD or RFC 3339 date"))
+    })
+}
+
+/// Seperti `date` untuk field opsional; pakai bersama `#[serde(default)]`.
+pub fn opt_date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveDate>, D::Error> {
+    date(deserializer).map(Some)
+}
+
+/// Waktu UTC dari DB → RFC 3339 di offset `offset` (UTC ditulis `Z`).
+pub fn to_rfc3339(value: NaiveDateTime, offset: FixedOffset) -> String {
+    let utc = offset.local_minus_utc() == 0;
+    offset
+        .from_utc_datetime(&value)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, utc)
+}
+
+/// Ubah nilai string field `*_at` (rekursif) yang berupa waktu naive ke RFC 3339.
+/// Field lain dan nilai yang bukan waktu tidak disentuh.
+pub fn convert_timestamps(value: Value, offset: FixedOffset) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(s) if key.ends_with("_at") => Value::String(
+                            NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f")
+                                .map(|t| to_rfc3339(t, offset))
+                                .unwrap_or(s),
+                        ),
+                        other => convert_timestamps(other, offset),
+                    };
+                    (key, value)
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(
+            items.into_iter().map(|v| convert_timestamps(v, offset)).collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Response JSON diberi offset waktu kalau header `X-Timestamp-Format` (atau default
+/// TIMESTAMP_FORMAT) meminta `utc`/`local`. Harus jalan sebelum `json_case` mengganti
+/// nama key, karena yang dikenali adalah akhiran `_at`. Body streaming tidak di-buffer;
+/// konversinya dipasang per baris lewat `RowStyle`.
+pub async fn convert_response(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let format = req
+        .headers()
+        .get(TIMESTAMP_FORMAT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(TimestampFormat::from_str)
+        .unwrap_or(state.config.timestamp_format);
+    let offset = match format {
+        TimestampFormat::Naive => None,
+        TimestampFormat::Utc => Some(UtcOffset::default().0),
+        TimestampFormat::Local => Some(state.config.library_utc_offset.0),
+    };
+    let extensions = req.extensions_mut();
+    let style = extensions.get::<RowStyle>().copied().unwrap_or_default();
+    extensions.insert(RowStyle {
+        timestamp_offset: offset,
+        ..style
+    });
+
+    let response = next.run(req).await;
+    let Some(offset) = offset else {
+        return response;
+    };
+    if !is_json(response.headers()) || response.extensions().get::<Streamed>().is_some() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("timestamps: failed to buffer response: {e}");
+            return ApiError::internal("failed to read response body").into_response();
+        }
+    };
+    let Ok(value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let converted = match serde_json::to_vec(&convert_timestamps(value, offset)) {
+        Ok(converted) => converted,
+        Err(_) => return Response::from_parts(parts, Body::from(bytes)),
+    };
+    parts.headers.insert(CONTENT_LENGTH, HeaderValue::from(converted.len()));
+    Response::from_parts(parts, Body::from(converted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_case::JsonCase;
+    use axum::{extract::Query, http::Uri};
+    use serde_json::json;
+
+    fn date_of(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parse_date_accepts_plain_and_rfc3339() {
+        assert_eq!(parse_date("2025-12-01"), Some(date_of("2025-12-01")));
+        assert_eq!(parse_date(" 2025-12-01 "), Some(date_of("2025-12-01")));
+        assert_eq!(parse_date("2025-12-01T00:00:00Z"), Some(date_of("2025-12-01")));
+        // Tanggal di offset-nya sendiri, bukan setelah dikonversi ke UTC.
+        assert_eq!(parse_date("2025-12-01T23:30:00+07:00"), Some(date_of("2025-12-01")));
+        assert_eq!(parse_date("2025-12-01T01:00:00-05:00"), Some(date_of("2025-12-01")));
+        for bad in ["", "01-12-2025", "2025-12-01T00:00:00", "kemarin"] {
+            assert_eq!(parse_date(bad), None, "{bad}");
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Window {
+        #[serde(default, deserialize_with = "opt_date")]
+        from: Option<NaiveDate>,
+        #[serde(deserialize_with = "date")]
+        to: NaiveDate,
+    }
+
+    #[test]
+    fn date_fields_deserialize_from_query_and_json() {
+        let uri: Uri = "/loans?from=2025-12-01T08:00:00%2B07:00&to=2025-12-31".parse().unwrap();
+        let Query(window) = Query::<Window>::try_from_uri(&uri).unwrap();
+        assert_eq!(window.from, Some(date_of("2025-12-01")));
+        assert_eq!(window.to, date_of("2025-12-31"));
+
+        let uri: Uri = "/loans?to=2025-12-31T00:00:00Z".parse().unwrap();
+        let Query(window) = Query::<Window>::try_from_uri(&uri).unwrap();
+        assert_eq!((window.from, window.to), (None, date_of("2025-12-31")));
+
+        let window: Window = serde_json::from_value(json!({ "to": "2025-12-31" })).unwrap();
+        assert_eq!(window.to, date_of("2025-12-31"));
+        let err = serde_json::from_value::<Window>(json!({ "to": "31/12/2025" })).unwrap_err();
+        assert!(err.to_string().contains("RFC 3339"));
+    }
+
+    #[test]
+    fn row_style_converts_timestamps_before_renaming_keys() {
+        let row = json!({ "borrowed_at": "2025-12-01T01:00:00", "due_date": "2025-12-08" });
+        let style = RowStyle {
+            case: JsonCase::Camel,
+            timestamp_offset: UtcOffset::from_str("+07:00").map(|o| o.0),
+        };
+        assert_eq!(
+            style.apply(row),
+            json!({ "borrowedAt": "2025-12-01T08:00:00+07:00", "dueDate": "2025-12-08" })
+        );
+    }
+}
@@ -0,0 +1,107 @@
+// Identitas build untuk `GET /version` dan `GET /health/ready`: versi crate, commit git, dan
+// waktu build ditanam saat kompilasi lewat `build.rs`. `GET /health/schema` membandingkan
+// migrasi yang ditanam di binary dengan isi tabel `_sqlx_migrations`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Fitur opsional yang ikut dikompilasi. Build ini belum punya feature flag cargo, jadi
+/// daftarnya kosong; entri ditambah di sini bersama feature-nya.
+const FEATURES: &[&str] = &[];
+
+/// Blok versi yang sama di /version dan /health/ready.
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    /// Kosong kalau build dibuat tanpa git dan tanpa env GIT_COMMIT.
+    pub git_commit: &'static str,
+    pub built_at: Option<DateTime<Utc>>,
+    pub database: &'static str,
+    pub features: &'static [&'static str],
+}
+
+impl VersionInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: env!("BUILD_GIT_COMMIT"),
+            built_at: env!("BUILD_TIMESTAMP")
+                .parse()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+            database: "mysql",
+            features: FEATURES,
+        }
+    }
+}
+
+/// Respons `GET /health/ready`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    /// `ready`, atau `unavailable` kalau DB tidak bisa dihubungi.
+    pub status: &'static str,
+    pub database_ok: bool,
+    #[serde(flatten)]
+    pub build: VersionInfo,
+}
+
+/// Respons `GET /health/schema`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaStatus {
+    /// `ok`, `outdated` (ada migrasi yang belum diterapkan), `failed` (migrasi gagal di
+    /// tengah jalan), `ahead` (DB lebih baru dari binary), atau `unavailable`.
+    pub status: &'static str,
+    /// Versi migrasi tertinggi yang sukses di DB; None kalau belum ada / DB tidak terjangkau.
+    pub current_version: Option<i64>,
+    /// Versi migrasi tertinggi yang dibawa binary ini.
+    pub expected_version: Option<i64>,
+    pub up_to_date: bool,
+    /// Versi yang dibawa binary tapi belum diterapkan di DB.
+    pub pending: Vec<i64>,
+    /// Versi yang tercatat gagal (`success = 0`).
+    pub failed: Vec<i64>,
+}
+
+impl SchemaStatus {
+    /// Bandingkan versi yang dibawa binary (`expected`) dengan baris `(version, success)` di DB.
+    pub fn compare(expected: &[i64], applied: &[(i64, bool)]) -> Self {
+        let succeeded: Vec<i64> =
+            applied.iter().filter(|(_, ok)| *ok).map(|(version, _)| *version).collect();
+        let failed: Vec<i64> =
+            applied.iter().filter(|(_, ok)| !*ok).map(|(version, _)| *version).collect();
+        let pending: Vec<i64> =
+            expected.iter().copied().filter(|v| !succeeded.contains(v)).collect();
+        let current_version = succeeded.iter().copied().max();
+        let expected_version = expected.iter().copied().max();
+
+        let status = if !failed.is_empty() {
+            "failed"
+        } else if !pending.is_empty() {
+            "outdated"
+        } else if current_version > expected_version {
+            "ahead"
+        } else {
+            "ok"
+        };
+        Self {
+            status,
+            current_version,
+            expected_version,
+            up_to_date: status == "ok",
+            pending,
+            failed,
+        }
+    }
+
+    /// DB tidak bisa dibaca.
+    pub fn unavailable(expected: &[i64]) -> Self {
+        Self {
+            status: "unavailable",
+            current_version: None,
+            expected_version: expected.iter().copied().max(),
+            up_to_date: false,
+            pending: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}